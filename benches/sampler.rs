@@ -0,0 +1,74 @@
+//! Sampler throughput: a full pixel of many 2D dimensions for the
+//! **HaltonSampler** compared to the **SobolSampler** and the
+//! **StratifiedSampler**. Run with `cargo bench --no-default-features`.
+
+#[macro_use]
+extern crate criterion;
+extern crate pbrt;
+
+use criterion::Criterion;
+use pbrt::core::geometry::{Bounds2i, Point2f, Point2i};
+use pbrt::core::pbrt::Float;
+use pbrt::core::sampler::Sampler;
+use pbrt::samplers::halton::HaltonSampler;
+use pbrt::samplers::sobol::SobolSampler;
+use pbrt::samplers::stratified::StratifiedSampler;
+
+const SAMPLES_PER_PIXEL: i64 = 16;
+const DIMENSIONS: usize = 32;
+
+fn sample_bounds() -> Bounds2i {
+    Bounds2i {
+        p_min: Point2i { x: 0, y: 0 },
+        p_max: Point2i { x: 64, y: 64 },
+    }
+}
+
+/// Starts a pixel and fetches *DIMENSIONS* 2D samples for each of
+/// its pixel samples.
+fn sample_pixel(sampler: &mut Box<Sampler + Send + Sync>) -> Float {
+    let mut sum: Float = 0.0 as Float;
+    sampler.start_pixel(&Point2i { x: 17, y: 42 });
+    loop {
+        for _ in 0..DIMENSIONS {
+            let u: Point2f = sampler.get_2d();
+            sum += u.x + u.y;
+        }
+        if !sampler.start_next_sample() {
+            break;
+        }
+    }
+    sum
+}
+
+fn bench_samplers(c: &mut Criterion) {
+    let mut halton: Box<Sampler + Send + Sync> = Box::new(HaltonSampler::new(
+        SAMPLES_PER_PIXEL,
+        sample_bounds(),
+        false,
+        5_i64,
+    ));
+    c.bench_function("halton get_2d", move |b| {
+        b.iter(|| sample_pixel(&mut halton))
+    });
+    let mut sobol: Box<Sampler + Send + Sync> =
+        Box::new(SobolSampler::new(SAMPLES_PER_PIXEL, sample_bounds(), false));
+    c.bench_function("sobol get_2d", move |b| b.iter(|| sample_pixel(&mut sobol)));
+    let mut stratified: Box<Sampler + Send + Sync> =
+        Box::new(StratifiedSampler::new(4, 4, true, DIMENSIONS as i64));
+    c.bench_function("stratified get_2d", move |b| {
+        b.iter(|| sample_pixel(&mut stratified))
+    });
+    let mut fill: Box<Sampler + Send + Sync> = Box::new(HaltonSampler::new(
+        SAMPLES_PER_PIXEL,
+        sample_bounds(),
+        false,
+        5_i64,
+    ));
+    c.bench_function("halton benchmark_fill", move |b| {
+        b.iter(|| fill.benchmark_fill(1024))
+    });
+}
+
+criterion_group!(benches, bench_samplers);
+criterion_main!(benches);
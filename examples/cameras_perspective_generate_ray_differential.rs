@@ -6,6 +6,7 @@ use pbrt::core::film::Film;
 use pbrt::core::filter::Filter;
 use pbrt::core::geometry::{Bounds2f, Point2f, Point2i, Point3f, Ray, Vector2f, Vector3f};
 use pbrt::core::pbrt::Float;
+use pbrt::core::spectrum::ColorSpace;
 use pbrt::core::transform::{AnimatedTransform, Transform};
 use pbrt::filters::boxfilter::BoxFilter;
 use std::sync::Arc;
@@ -52,6 +53,9 @@ fn main() {
         filename,
         1.0,
         std::f32::INFINITY,
+        8_u8,
+        None,
+        ColorSpace::SRgb,
     ));
     let pos = Point3f {
         x: 2.0,
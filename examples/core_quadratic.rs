@@ -20,6 +20,7 @@ fn main() {
         },
         t_max: std::f32::INFINITY,
         time: 0.0,
+        ray_type: 0_u8,
         medium: None,
         differential: None,
     };
@@ -63,6 +64,7 @@ fn main() {
         },
         t_max: std::f32::INFINITY,
         time: 0.0,
+        ray_type: 0_u8,
         medium: None,
         differential: None,
     };
@@ -18,6 +18,7 @@ fn main() {
         d: direction,
         t_max: std::f32::INFINITY,
         time: 0.0,
+        ray_type: 0_u8,
         medium: None,
         differential: None,
     };
@@ -44,6 +44,7 @@ fn main() {
         &light_to_world,
         &MediumInterface::default(),
         &l_emit,
+        None,
         n_samples,
         shape,
         two_sided,
@@ -25,9 +25,11 @@ use pbrt::core::medium::MediumInterface;
 use pbrt::core::paramset::ParamSet;
 use pbrt::core::pbrt::{Float, Spectrum};
 use pbrt::core::primitive::{GeometricPrimitive, Primitive};
+use pbrt::core::reflection::REGULARIZE_MIN_ALPHA;
 use pbrt::core::sampler::Sampler;
 use pbrt::core::scene::Scene;
 use pbrt::core::shape::Shape;
+use pbrt::core::spectrum::ColorSpace;
 use pbrt::core::texture::Texture;
 use pbrt::core::transform::{AnimatedTransform, Transform};
 use pbrt::filters::gaussian::GaussianFilter;
@@ -880,6 +882,7 @@ fn main() {
                                                                     &cur_transform,
                                                                     &mi,
                                                                     &l_emit,
+                                                                    None,
                                                                     samples,
                                                                     shape,
                                                                     two_sided,
@@ -1284,6 +1287,9 @@ fn main() {
                 String::from(""),
                 scale,
                 max_sample_luminance,
+                8_u8,
+                None,
+                ColorSpace::SRgb,
             ));
             // MakeCamera
             let mut some_camera: Option<Arc<Camera + Sync + Send>> = None;
@@ -1312,7 +1318,11 @@ fn main() {
                 let mut some_sampler: Option<Box<Sampler + Sync + Send>>;
                 // use SobolSampler for now
                 let sample_bounds: Bounds2i = camera.get_film().get_sample_bounds();
-                let sampler = Box::new(SobolSampler::new(samples_per_pixel as i64, sample_bounds));
+                let sampler = Box::new(SobolSampler::new(
+                    samples_per_pixel as i64,
+                    sample_bounds,
+                    false,
+                ));
                 some_sampler = Some(sampler);
                 if let Some(mut sampler) = some_sampler {
                     // MakeIntegrator
@@ -1332,6 +1342,9 @@ fn main() {
                         pixel_bounds,
                         rr_threshold,
                         light_strategy,
+                        std::f32::INFINITY as Float,
+                        false,
+                        REGULARIZE_MIN_ALPHA,
                     ));
                     some_integrator = Some(integrator);
                     if let Some(mut integrator) = some_integrator {
@@ -38,9 +38,11 @@ use pbrt::core::medium::MediumInterface;
 use pbrt::core::pbrt::degrees;
 use pbrt::core::pbrt::{Float, Spectrum};
 use pbrt::core::primitive::{GeometricPrimitive, Primitive};
+use pbrt::core::reflection::REGULARIZE_MIN_ALPHA;
 use pbrt::core::sampler::Sampler;
 use pbrt::core::scene::Scene;
 use pbrt::core::shape::Shape;
+use pbrt::core::spectrum::ColorSpace;
 use pbrt::core::transform::{AnimatedTransform, Transform};
 use pbrt::filters::boxfilter::BoxFilter;
 use pbrt::integrators::ao::AOIntegrator;
@@ -244,6 +246,7 @@ impl RenderOptions {
                             &mesh.object_to_world,
                             &mi,
                             &l_emit,
+                            None,
                             n_samples,
                             shape.clone(),
                             two_sided,
@@ -2227,6 +2230,9 @@ fn main() -> std::io::Result<()> {
         filename,
         1.0,
         std::f32::INFINITY,
+        8_u8,
+        None,
+        ColorSpace::SRgb,
     ));
     let camera: Arc<Camera + Send + Sync> = Arc::new(PerspectiveCamera::new(
         animated_cam_to_world,
@@ -2288,6 +2294,9 @@ fn main() -> std::io::Result<()> {
                 pixel_bounds,
                 rr_threshold,
                 light_strategy,
+                std::f32::INFINITY as Float,
+                false,
+                REGULARIZE_MIN_ALPHA,
             ));
             // in the end we want to call render()
             render(
@@ -2357,6 +2366,9 @@ fn main() -> std::io::Result<()> {
                 pixel_bounds,
                 rr_threshold,
                 light_strategy,
+                std::f32::INFINITY as Float,
+                false,
+                REGULARIZE_MIN_ALPHA,
             ));
         } else {
             // AOIntegrator
@@ -17,6 +17,7 @@ use pbrt::core::pbrt::{Float, Spectrum};
 use pbrt::core::primitive::{GeometricPrimitive, Primitive};
 use pbrt::core::sampler::Sampler;
 use pbrt::core::scene::Scene;
+use pbrt::core::spectrum::ColorSpace;
 use pbrt::core::texture::{PlanarMapping2D, UVMapping2D};
 use pbrt::core::transform::{AnimatedTransform, Transform};
 use pbrt::filters::boxfilter::BoxFilter;
@@ -499,6 +500,7 @@ fn main() {
             wrap_mode,
             scale,
             gamma,
+            0.0 as Float,
             convert_to_spectrum,
         ));
         let sigma = Arc::new(ConstantTexture::new(0.0 as Float));
@@ -610,6 +612,9 @@ fn main() {
         filename,
         1.0,
         std::f32::INFINITY,
+        8_u8,
+        None,
+        ColorSpace::SRgb,
     ));
     let camera: Arc<Camera + Send + Sync> = Arc::new(PerspectiveCamera::new(
         animated_cam_to_world,
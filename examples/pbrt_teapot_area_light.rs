@@ -15,6 +15,7 @@ use pbrt::core::pbrt::{Float, Spectrum};
 use pbrt::core::primitive::{GeometricPrimitive, Primitive};
 use pbrt::core::sampler::Sampler;
 use pbrt::core::scene::Scene;
+use pbrt::core::spectrum::ColorSpace;
 use pbrt::core::transform::{AnimatedTransform, Transform};
 use pbrt::filters::boxfilter::BoxFilter;
 use pbrt::integrators::directlighting::{DirectLightingIntegrator, LightStrategy};
@@ -2032,6 +2033,9 @@ fn main() {
         filename,
         1.0,
         std::f32::INFINITY,
+        8_u8,
+        None,
+        ColorSpace::SRgb,
     ));
     let camera: Arc<Camera + Send + Sync> = Arc::new(PerspectiveCamera::new(
         animated_cam_to_world,
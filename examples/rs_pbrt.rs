@@ -843,6 +843,11 @@ fn main() {
     let program = args[0].clone();
     let mut opts = Options::new();
     opts.optflag("h", "help", "print this help menu");
+    opts.optflag(
+        "e",
+        "errormaterial",
+        "use magenta for missing or unknown materials and textures",
+    );
     opts.optopt("i", "", "parse an input file", "FILE");
     opts.optopt(
         "t",
@@ -886,6 +891,8 @@ fn main() {
                     "Rust code based on C++ code by Matt Pharr, Greg Humphreys, and Wenzel Jakob."
                 );
                 let (mut api_state, mut bsdf_state) = pbrt_init(number_of_threads);
+                api_state.error_material = matches.opt_present("e");
+                api_state.scene_name = x.clone();
                 parse_file(x, &mut api_state, &mut bsdf_state, "");
             }
             None => panic!("No input file name."),
@@ -37,6 +37,7 @@ fn main() {
         d: d,
         t_max: 17.7973537,
         time: 0.0,
+        ray_type: 0_u8,
         medium: None,
         differential: None,
     };
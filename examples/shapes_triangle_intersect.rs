@@ -90,6 +90,7 @@ fn main() {
         d: d,
         t_max: std::f32::INFINITY,
         time: 0.0,
+        ray_type: 0_u8,
         medium: None,
         differential: None,
     };
@@ -108,6 +109,7 @@ fn main() {
         d: d,
         t_max: std::f32::INFINITY,
         time: 0.0,
+        ray_type: 0_u8,
         medium: None,
         differential: None,
     };
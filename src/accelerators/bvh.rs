@@ -13,6 +13,7 @@ use core::material::Material;
 use core::paramset::ParamSet;
 use core::pbrt::Float;
 use core::primitive::Primitive;
+use core::profile::{Prof, ProfilePhase};
 
 // see bvh.h
 
@@ -411,6 +412,30 @@ impl BVHAccel {
         }
         my_offset
     }
+    /// Recomputes the node bounds bottom-up after the primitives
+    /// moved (e.g. vertex animation), keeping the tree topology.
+    /// Cheaper than a rebuild, but the tree quality degrades with
+    /// larger deformations.
+    pub fn refit(&mut self) {
+        // children are always stored after their parent (see
+        // flatten_bvh_tree()), so a reverse sweep visits them first
+        for i in (0..self.nodes.len()).rev() {
+            let node: LinearBVHNode = self.nodes[i];
+            let bounds: Bounds3f = if node.n_primitives > 0 {
+                // leaf
+                let mut bounds: Bounds3f = self.primitives[node.offset].world_bound();
+                for p in 1..node.n_primitives {
+                    bounds =
+                        bnd3_union_bnd3(&bounds, &self.primitives[node.offset + p].world_bound());
+                }
+                bounds
+            } else {
+                // interior
+                bnd3_union_bnd3(&self.nodes[i + 1].bounds, &self.nodes[node.offset].bounds)
+            };
+            self.nodes[i].bounds = bounds;
+        }
+    }
 }
 
 impl Primitive for BVHAccel {
@@ -425,7 +450,7 @@ impl Primitive for BVHAccel {
         if self.nodes.len() == 0 {
             return None;
         }
-        // TODO: ProfilePhase p(Prof::AccelIntersect);
+        let _p = ProfilePhase::new(Prof::AccelIntersect);
         let mut hit: bool = false;
         let inv_dir: Vector3f = Vector3f {
             x: 1.0 / ray.d.x,
@@ -493,7 +518,7 @@ impl Primitive for BVHAccel {
         if self.nodes.len() == 0 {
             return false;
         }
-        // TODO: ProfilePhase p(Prof::AccelIntersectP);
+        let _p = ProfilePhase::new(Prof::AccelIntersectP);
         let inv_dir: Vector3f = Vector3f {
             x: 1.0 / ray.d.x,
             y: 1.0 / ray.d.y,
@@ -551,3 +576,104 @@ impl Primitive for BVHAccel {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::primitive::GeometricPrimitive;
+    use core::shape::Shape;
+    use core::transform::Transform;
+    use shapes::sphere::Sphere;
+
+    fn sphere_at(center: &Vector3f) -> Arc<Primitive + Sync + Send> {
+        let object_to_world: Transform = Transform::translate(center);
+        let sphere: Arc<Shape + Send + Sync> = Arc::new(Sphere::new(
+            object_to_world,
+            Transform::inverse(&object_to_world),
+            false,
+            1.0 as Float,
+            -1.0 as Float,
+            1.0 as Float,
+            360.0 as Float,
+        ));
+        Arc::new(GeometricPrimitive::new(sphere, None, None, None))
+    }
+
+    fn center(primitive: &Arc<Primitive + Sync + Send>) -> Vector3f {
+        let bounds: Bounds3f = primitive.world_bound();
+        Vector3f::from((bounds.p_min + bounds.p_max) * 0.5 as Float)
+    }
+
+    fn assert_same_bounds(a: &Bounds3f, b: &Bounds3f) {
+        for i in 0..3 {
+            assert!((a.p_min[i] - b.p_min[i]).abs() < 1e-4 as Float);
+            assert!((a.p_max[i] - b.p_max[i]).abs() < 1e-4 as Float);
+        }
+    }
+
+    #[test]
+    fn refit_matches_a_rebuild_after_translation() {
+        let mut spheres: Vec<Arc<Primitive + Sync + Send>> = Vec::new();
+        for y in 0..4 {
+            for x in 0..4 {
+                spheres.push(sphere_at(&Vector3f {
+                    x: 3.0 * x as Float,
+                    y: 3.0 * y as Float,
+                    z: (x + y) as Float,
+                }));
+            }
+        }
+        let mut bvh: BVHAccel = BVHAccel::new(spheres, 2, SplitMethod::SAH);
+        let n_nodes: usize = bvh.nodes.len();
+        // move all primitives in place (keeping the BVH's order)
+        let offset: Vector3f = Vector3f {
+            x: 0.25,
+            y: -0.1,
+            z: 0.3,
+        };
+        for i in 0..bvh.primitives.len() {
+            let moved: Arc<Primitive + Sync + Send> =
+                sphere_at(&(center(&bvh.primitives[i]) + offset));
+            bvh.primitives[i] = moved;
+        }
+        bvh.refit();
+        assert_eq!(bvh.nodes.len(), n_nodes);
+        let rebuilt: BVHAccel = BVHAccel::new(bvh.primitives.clone(), 2, SplitMethod::SAH);
+        assert_same_bounds(&bvh.world_bound(), &rebuilt.world_bound());
+        // every node bounds its primitives (or its children)
+        for node in &bvh.nodes {
+            if node.n_primitives > 0 {
+                for p in 0..node.n_primitives {
+                    let b: Bounds3f = bvh.primitives[node.offset + p].world_bound();
+                    assert_same_bounds(&bnd3_union_bnd3(&node.bounds, &b), &node.bounds);
+                }
+            }
+        }
+        // both trees report the same hits
+        for y in -2..24 {
+            for x in -2..24 {
+                let mut ray: Ray = Ray {
+                    o: Point3f {
+                        x: 0.5 * x as Float,
+                        y: 0.5 * y as Float,
+                        z: -10.0,
+                    },
+                    d: Vector3f {
+                        x: 0.0,
+                        y: 0.0,
+                        z: 1.0,
+                    },
+                    t_max: std::f32::INFINITY,
+                    ..Ray::default()
+                };
+                let mut rebuilt_ray: Ray = ray.clone();
+                let hit: Option<SurfaceInteraction> = bvh.intersect(&mut ray);
+                let rebuilt_hit: Option<SurfaceInteraction> = rebuilt.intersect(&mut rebuilt_ray);
+                assert_eq!(hit.is_some(), rebuilt_hit.is_some());
+                if hit.is_some() {
+                    assert!((ray.t_max - rebuilt_ray.t_max).abs() < 1e-4 as Float);
+                }
+            }
+        }
+    }
+}
@@ -3,15 +3,13 @@ use std;
 use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
-use core::camera::{Camera, CameraSample};
+use core::camera::{Camera, CameraSample, ShutterCurve};
 use core::film::Film;
-use core::geometry::{Bounds2f, Point2f, Point3f, Ray, Vector3f};
-use core::interaction::InteractionCommon;
-use core::light::VisibilityTester;
+use core::geometry::{Bounds2f, Point2f, Point3f, Ray, RayDifferential, Vector3f, VisibilityFlags};
 use core::medium::Medium;
 use core::paramset::ParamSet;
 use core::pbrt::lerp;
-use core::pbrt::{Float, Spectrum};
+use core::pbrt::Float;
 use core::transform::AnimatedTransform;
 
 // see environment.h
@@ -21,6 +19,8 @@ pub struct EnvironmentCamera {
     pub camera_to_world: AnimatedTransform,
     pub shutter_open: Float,
     pub shutter_close: Float,
+    /// warps the time samples (see **ShutterCurve::warp_time()**)
+    pub shutter_curve: ShutterCurve,
     pub film: Arc<Film>,
     pub medium: Option<Arc<Medium + Send + Sync>>,
 }
@@ -37,6 +37,7 @@ impl EnvironmentCamera {
             camera_to_world: camera_to_world,
             shutter_open: shutter_open,
             shutter_close: shutter_close,
+            shutter_curve: ShutterCurve::Box,
             film: film,
             medium: medium,
         }
@@ -80,33 +81,58 @@ impl EnvironmentCamera {
                 panic!("\"screenwindow\" should have four values");
             }
         }
-        let camera = Arc::new(EnvironmentCamera::new(
+        let mut camera: EnvironmentCamera = EnvironmentCamera::new(
             cam2world,
             shutteropen,
             shutterclose,
             film,
             medium,
-        ));
-        camera
+        );
+        camera.shutter_curve = ShutterCurve::create(params);
+        Arc::new(camera)
+    }
+    /// Camera space direction for a point on the film (latitude and
+    /// longitude mapping).
+    fn direction(&self, p_film: &Point2f) -> Vector3f {
+        let theta: Float = PI * p_film.y / self.film.full_resolution.y as Float;
+        let phi: Float = 2.0 as Float * PI * p_film.x / self.film.full_resolution.x as Float;
+        Vector3f {
+            x: theta.sin() * phi.cos(),
+            y: theta.cos(),
+            z: theta.sin() * phi.sin(),
+        }
     }
 }
 
 impl Camera for EnvironmentCamera {
     fn generate_ray_differential(&self, sample: &CameraSample, ray: &mut Ray) -> Float {
-        let theta: Float = PI * sample.p_film.y / self.film.full_resolution.y as Float;
-        let phi: Float = 2.0 as Float * PI * sample.p_film.x / self.film.full_resolution.x as Float;
-        let dir: Vector3f = Vector3f {
-            x: theta.sin() * phi.cos(),
-            y: theta.cos(),
-            z: theta.sin() * phi.sin(),
+        let dir: Vector3f = self.direction(&sample.p_film);
+        // all rays start at the camera position, only the directions
+        // differ for the neighbouring pixels
+        let diff: RayDifferential = RayDifferential {
+            rx_origin: Point3f::default(),
+            ry_origin: Point3f::default(),
+            rx_direction: self.direction(&Point2f {
+                x: sample.p_film.x + 1.0 as Float,
+                y: sample.p_film.y,
+            }),
+            ry_direction: self.direction(&Point2f {
+                x: sample.p_film.x,
+                y: sample.p_film.y + 1.0 as Float,
+            }),
         };
         let mut in_ray: Ray = Ray {
             o: Point3f::default(),
             d: dir,
             t_max: std::f32::INFINITY,
-            time: lerp(sample.time, self.shutter_open, self.shutter_close),
+            time: lerp(
+                self.shutter_curve.warp_time(sample.time),
+                self.shutter_open,
+                self.shutter_close,
+            ),
+            ray_type: VisibilityFlags::Camera as u8,
             medium: None,
-            differential: None,
+            differential: Some(diff),
         };
         // ray->medium = medium;
         if let Some(ref medium_arc) = self.medium {
@@ -117,28 +143,6 @@ impl Camera for EnvironmentCamera {
         *ray = self.camera_to_world.transform_ray(&in_ray);
         1.0
     }
-    fn we(&self, _ray: &Ray, _p_raster2: Option<&mut Point2f>) -> Spectrum {
-        panic!("camera::we() is not implemented!");
-        // Spectrum::default()
-    }
-    fn pdf_we(&self, _ray: &Ray) -> (Float, Float) {
-        // let mut pdf_pos: Float = 0.0;
-        // let mut pdf_dir: Float = 0.0;
-        panic!("camera::pdf_we() is not implemented!");
-        // (pdf_pos, pdf_dir)
-    }
-    fn sample_wi(
-        &self,
-        _iref: &InteractionCommon,
-        _u: &Point2f,
-        _wi: &mut Vector3f,
-        _pdf: &mut Float,
-        _p_raster: &mut Point2f,
-        _vis: &mut VisibilityTester,
-    ) -> Spectrum {
-        panic!("camera::sample_wi() is not implemented!");
-        // Spectrum::default()
-    }
     fn get_shutter_open(&self) -> Float {
         self.shutter_open
     }
@@ -149,3 +153,92 @@ impl Camera for EnvironmentCamera {
         self.film.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::geometry::{vec3_dot_vec3, Point2i};
+    use core::interaction::InteractionCommon;
+    use core::light::VisibilityTester;
+    use core::pbrt::Spectrum;
+    use core::testutil::test_film;
+    use core::transform::Transform;
+
+    /// An **EnvironmentCamera** with a 16x8 lat-long film.
+    fn test_camera() -> EnvironmentCamera {
+        let film: Arc<Film> = Arc::new(test_film(
+            Point2i { x: 16, y: 8 },
+            0.5 as Float,
+            "environment.png",
+        ));
+        let t: Transform = Transform::default();
+        EnvironmentCamera::new(
+            AnimatedTransform::new(&t, 0.0 as Float, &t, 1.0 as Float),
+            0.0 as Float,
+            1.0 as Float,
+            film,
+            None,
+        )
+    }
+
+    #[test]
+    fn missing_importance_is_zero_instead_of_a_panic() {
+        let camera: EnvironmentCamera = test_camera();
+        let sample: CameraSample = CameraSample {
+            p_film: Point2f { x: 4.5, y: 3.5 },
+            p_lens: Point2f { x: 0.5, y: 0.5 },
+            time: 0.0 as Float,
+        };
+        let mut ray: Ray = Ray::default();
+        assert_eq!(
+            camera.generate_ray_differential(&sample, &mut ray),
+            1.0 as Float
+        );
+        let mut p_raster: Point2f = Point2f::default();
+        assert!(camera.we(&ray, Some(&mut p_raster)).is_black());
+        assert_eq!(camera.pdf_we(&ray), (0.0 as Float, 0.0 as Float));
+        let mut iref: InteractionCommon = InteractionCommon::default();
+        iref.p = ray.o + ray.d * 2.0 as Float;
+        let mut wi: Vector3f = Vector3f::default();
+        let mut pdf: Float = 1.0 as Float;
+        let mut vis: VisibilityTester = VisibilityTester::default();
+        let we: Spectrum = camera.sample_wi(
+            &iref,
+            &Point2f { x: 0.5, y: 0.5 },
+            &mut wi,
+            &mut pdf,
+            &mut p_raster,
+            &mut vis,
+        );
+        // BDPT skips connections with a zero pdf
+        assert!(we.is_black());
+        assert_eq!(pdf, 0.0 as Float);
+    }
+
+    #[test]
+    fn differentials_point_to_the_neighbouring_pixels() {
+        let camera: EnvironmentCamera = test_camera();
+        let sample: CameraSample = CameraSample {
+            p_film: Point2f { x: 4.5, y: 3.5 },
+            p_lens: Point2f { x: 0.5, y: 0.5 },
+            time: 0.0 as Float,
+        };
+        let mut ray: Ray = Ray::default();
+        camera.generate_ray_differential(&sample, &mut ray);
+        let diff: RayDifferential = ray.differential.expect("no differentials");
+        assert_eq!(diff.rx_origin.x, ray.o.x);
+        assert_eq!(diff.ry_origin.z, ray.o.z);
+        // one pixel is 2 pi / 16 in phi and pi / 8 in theta
+        let theta: Float = PI * 3.5 as Float / 8.0 as Float;
+        let dx: Float = (diff.rx_direction - ray.d).length();
+        let expected_dx: Float = 2.0 as Float * theta.sin() * (PI / 16.0 as Float).sin();
+        assert!(
+            (dx - expected_dx).abs() < 1e-5 as Float,
+            "{} vs {}",
+            dx,
+            expected_dx
+        );
+        let cos_dy: Float = vec3_dot_vec3(&diff.ry_direction, &ray.d);
+        assert!((cos_dy - (PI / 8.0 as Float).cos()).abs() < 1e-5 as Float);
+    }
+}
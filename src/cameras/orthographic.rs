@@ -1,26 +1,39 @@
 // std
 use std;
+use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
-use core::camera::{Camera, CameraSample};
+use core::camera::{get_screen_window, Camera, CameraSample, ShutterCurve};
 use core::film::Film;
-use core::geometry::{Bounds2f, Point2f, Point3f, Ray, RayDifferential, Vector3f};
+use core::geometry::{
+    nrm_abs_dot_vec3, Bounds2f, Bounds2i, Normal3f, Point2f, Point2i, Point3f, Ray,
+    RayDifferential, Vector2f, Vector3f, VisibilityFlags,
+};
 use core::interaction::InteractionCommon;
 use core::light::VisibilityTester;
-use core::medium::Medium;
+use core::medium::{Medium, MediumInterface};
 use core::paramset::ParamSet;
 use core::pbrt::lerp;
 use core::pbrt::{Float, Spectrum};
+use core::profile::{Prof, ProfilePhase};
 use core::sampling::concentric_sample_disk;
 use core::transform::{AnimatedTransform, Transform};
 
 // see orthographic.h
 
+/// Directions within this distance (in cosine) of the viewing
+/// direction count as parallel to it for a pinhole
+/// **OrthographicCamera** (whose camera rays form a delta
+/// distribution).
+const ORTHO_DIRECTION_EPSILON: Float = 1e-4 as Float;
+
 pub struct OrthographicCamera {
     // inherited from Camera (see camera.h)
     pub camera_to_world: AnimatedTransform,
     pub shutter_open: Float,
     pub shutter_close: Float,
+    /// warps the time samples (see **ShutterCurve::warp_time()**)
+    pub shutter_curve: ShutterCurve,
     pub film: Arc<Film>,
     pub medium: Option<Arc<Medium + Send + Sync>>,
     // inherited from ProjectiveCamera (see camera.h)
@@ -30,9 +43,14 @@ pub struct OrthographicCamera {
     pub raster_to_screen: Transform,
     pub lens_radius: Float,
     pub focal_distance: Float,
+    /// Weight camera rays by $\cos^4 \theta$ of the lens-to-film
+    /// angle (only for *lens_radius* > 0)
+    pub vignetting: bool,
     // private data (see orthographic.h)
     pub dx_camera: Vector3f,
     pub dy_camera: Vector3f,
+    /// area of the film in camera space
+    pub a: Float,
 }
 
 impl OrthographicCamera {
@@ -43,6 +61,7 @@ impl OrthographicCamera {
         shutter_close: Float,
         lens_radius: Float,
         focal_distance: Float,
+        vignetting: bool,
         film: Arc<Film>,
         medium: Option<Arc<Medium + Send + Sync>>,
     ) -> Self {
@@ -55,11 +74,8 @@ impl OrthographicCamera {
             film.full_resolution.y as Float,
             1.0,
         );
-        let scale2 = Transform::scale(
-            1.0 / (screen_window.p_max.x - screen_window.p_min.x),
-            1.0 / (screen_window.p_min.y - screen_window.p_max.y),
-            1.0,
-        );
+        let screen_extent: Vector2f = screen_window.diagonal();
+        let scale2 = Transform::scale(1.0 / screen_extent.x, -1.0 / screen_extent.y, 1.0);
         let translate = Transform::translate(&Vector3f {
             x: -screen_window.p_min.x,
             y: -screen_window.p_max.y,
@@ -80,10 +96,24 @@ impl OrthographicCamera {
             y: 1.0,
             z: 0.0,
         });
+        // compute film area in camera space
+        let res: Point2i = film.full_resolution;
+        let p_min: Point3f = raster_to_camera.transform_point(&Point3f {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        });
+        let p_max: Point3f = raster_to_camera.transform_point(&Point3f {
+            x: res.x as Float,
+            y: res.y as Float,
+            z: 0.0,
+        });
+        let a: Float = ((p_max.x - p_min.x) * (p_max.y - p_min.y)).abs();
         OrthographicCamera {
             camera_to_world: camera_to_world,
             shutter_open: shutter_open,
             shutter_close: shutter_close,
+            shutter_curve: ShutterCurve::Box,
             film: film,
             medium: medium,
             camera_to_screen: camera_to_screen,
@@ -92,10 +122,55 @@ impl OrthographicCamera {
             raster_to_screen: raster_to_screen,
             lens_radius: lens_radius,
             focal_distance: focal_distance,
+            vignetting: vignetting,
             dx_camera: dx_camera,
             dy_camera: dy_camera,
+            a: a,
+        }
+    }
+    /// Area of the lens (1 for a pinhole camera), used by the camera
+    /// importance functions.
+    fn lens_area(&self) -> Float {
+        if self.lens_radius != 0.0 as Float {
+            PI * self.lens_radius * self.lens_radius
+        } else {
+            1.0 as Float
+        }
+    }
+    /// Follows the world space *ray* (with normalized camera space
+    /// direction *d_camera*) back to the point on the film plane (in
+    /// camera space) it belongs to.
+    fn film_point(&self, w2c: &Transform, ray: &Ray, d_camera: &Vector3f) -> Point3f {
+        let mut p: Point3f = w2c.transform_point(&ray.o);
+        if self.lens_radius > 0.0 as Float {
+            // rays leaving the lens pass the plane of focus right in
+            // front of their film point
+            p = p + *d_camera * (self.focal_distance / d_camera.z);
+        }
+        Point3f {
+            x: p.x,
+            y: p.y,
+            z: 0.0 as Float,
+        }
+    }
+    /// Projects a (camera space) point on the film plane to raster
+    /// space.
+    fn film_to_raster(&self, p_film: &Point3f) -> Point2f {
+        let p_raster: Point3f = self
+            .screen_to_raster
+            .transform_point(&self.camera_to_screen.transform_point(p_film));
+        Point2f {
+            x: p_raster.x,
+            y: p_raster.y,
         }
     }
+    fn inside_sample_bounds(&self, p_raster: &Point2f) -> bool {
+        let sample_bounds: Bounds2i = self.film.get_sample_bounds();
+        p_raster.x >= sample_bounds.p_min.x as Float
+            && p_raster.x < sample_bounds.p_max.x as Float
+            && p_raster.y >= sample_bounds.p_min.y as Float
+            && p_raster.y < sample_bounds.p_max.y as Float
+    }
     pub fn create(
         params: &ParamSet,
         cam2world: AnimatedTransform,
@@ -108,50 +183,31 @@ impl OrthographicCamera {
         assert!(shutterclose >= shutteropen);
         let lensradius: Float = params.find_one_float("lensradius", 0.0);
         let focaldistance: Float = params.find_one_float("focaldistance", 1e6);
+        let vignetting: bool = params.find_one_bool("vignetting", false);
         let frame: Float = params.find_one_float(
             "frameaspectratio",
             (film.full_resolution.x as Float) / (film.full_resolution.y as Float),
         );
-        let mut screen: Bounds2f = Bounds2f::default();
-        if frame > 1.0 {
-            screen.p_min.x = -frame;
-            screen.p_max.x = frame;
-            screen.p_min.y = -1.0;
-            screen.p_max.y = 1.0;
-        } else {
-            screen.p_min.x = -1.0;
-            screen.p_max.x = 1.0;
-            screen.p_min.y = -1.0 / frame;
-            screen.p_max.y = 1.0 / frame;
-        }
-        let sw: Vec<Float> = params.find_float("screenwindow");
-        if sw.len() > 0_usize {
-            if sw.len() == 4 {
-                screen.p_min.x = sw[0];
-                screen.p_max.x = sw[1];
-                screen.p_min.y = sw[2];
-                screen.p_max.y = sw[3];
-            } else {
-                panic!("\"screenwindow\" should have four values");
-            }
-        }
-        let camera = Arc::new(OrthographicCamera::new(
+        let screen: Bounds2f = get_screen_window(params, frame);
+        let mut camera: OrthographicCamera = OrthographicCamera::new(
             cam2world,
             screen,
             shutteropen,
             shutterclose,
             lensradius,
             focaldistance,
+            vignetting,
             film,
             medium,
-        ));
-        camera
+        );
+        camera.shutter_curve = ShutterCurve::create(params);
+        Arc::new(camera)
     }
 }
 
 impl Camera for OrthographicCamera {
     fn generate_ray_differential(&self, sample: &CameraSample, ray: &mut Ray) -> Float {
-        // TODO: ProfilePhase prof(Prof::GenerateCameraRay);
+        let _p = ProfilePhase::new(Prof::GenerateCameraRay);
         // compute raster and camera sample positions
         let p_film: Point3f = Point3f {
             x: sample.p_film.x,
@@ -174,11 +230,17 @@ impl Camera for OrthographicCamera {
                 z: 1.0,
             },
             t_max: std::f32::INFINITY,
-            time: lerp(sample.time, self.shutter_open, self.shutter_close),
+            time: lerp(
+                self.shutter_curve.warp_time(sample.time),
+                self.shutter_open,
+                self.shutter_close,
+            ),
+            ray_type: VisibilityFlags::Camera as u8,
             medium: None,
             differential: Some(diff),
         };
         // modify ray for depth of field
+        let mut weight: Float = 1.0 as Float;
         if self.lens_radius > 0.0 as Float {
             // sample point on lens
             let p_lens: Point2f = concentric_sample_disk(&sample.p_lens) * self.lens_radius;
@@ -192,6 +254,13 @@ impl Camera for OrthographicCamera {
                 z: 0.0 as Float,
             };
             in_ray.d = (p_focus - in_ray.o).normalize();
+            if self.vignetting {
+                // cosine-fourth falloff (RealisticCamera's "simpleweighting"
+                // additionally scales by the exit pupil area)
+                let cos_theta: Float = in_ray.d.z;
+                let cos_2_theta: Float = cos_theta * cos_theta;
+                weight = cos_2_theta * cos_2_theta;
+            }
         }
         // compute offset rays for _OrthographicCamera_ ray differentials
         if self.lens_radius > 0.0 as Float {
@@ -236,29 +305,149 @@ impl Camera for OrthographicCamera {
             in_ray.medium = None;
         }
         *ray = self.camera_to_world.transform_ray(&in_ray);
-        1.0
+        weight
     }
-    fn we(&self, _ray: &Ray, _p_raster2: Option<&mut Point2f>) -> Spectrum {
-        panic!("camera::we() is not implemented!");
-        // Spectrum::default()
+    fn we(&self, ray: &Ray, p_raster2: Option<&mut Point2f>) -> Spectrum {
+        // interpolate camera matrix and check if $\w{}$ is forward-facing
+        let mut c2w: Transform = Transform::default();
+        self.camera_to_world.interpolate(ray.time, &mut c2w);
+        let w2c: Transform = Transform::inverse(&c2w);
+        let d_camera: Vector3f = w2c.transform_vector(&ray.d).normalize();
+        let cos_theta: Float = d_camera.z;
+        if cos_theta <= 0.0 as Float {
+            return Spectrum::default();
+        }
+        // a pinhole only emits importance along +z (delta distribution)
+        if self.lens_radius == 0.0 as Float && cos_theta < 1.0 as Float - ORTHO_DIRECTION_EPSILON {
+            return Spectrum::default();
+        }
+        // map ray $(\p{}, \w{})$ onto the raster grid
+        let p_raster: Point2f = self.film_to_raster(&self.film_point(&w2c, ray, &d_camera));
+        // return raster position if requested
+        if let Some(p_raster2) = p_raster2 {
+            *p_raster2 = p_raster;
+        }
+        // return zero importance for out of bounds points
+        if !self.inside_sample_bounds(&p_raster) {
+            return Spectrum::default();
+        }
+        if self.lens_radius == 0.0 as Float {
+            // constant importance over the parallel ray bundle
+            Spectrum::new(1.0 as Float / self.a)
+        } else {
+            // the film (area _a_) maps 1:1 onto the plane of focus
+            let cos_2_theta: Float = cos_theta * cos_theta;
+            Spectrum::new(
+                self.focal_distance * self.focal_distance
+                    / (self.a * self.lens_area() * cos_2_theta * cos_2_theta),
+            )
+        }
     }
-    fn pdf_we(&self, _ray: &Ray) -> (Float, Float) {
-        // let mut pdf_pos: Float = 0.0;
-        // let mut pdf_dir: Float = 0.0;
-        panic!("camera::pdf_we() is not implemented!");
-        // (pdf_pos, pdf_dir)
+    fn pdf_we(&self, ray: &Ray) -> (Float, Float) {
+        // interpolate camera matrix and fail if $\w{}$ is not forward-facing
+        let mut c2w: Transform = Transform::default();
+        self.camera_to_world.interpolate(ray.time, &mut c2w);
+        let w2c: Transform = Transform::inverse(&c2w);
+        let d_camera: Vector3f = w2c.transform_vector(&ray.d).normalize();
+        let cos_theta: Float = d_camera.z;
+        if cos_theta <= 0.0 as Float {
+            return (0.0 as Float, 0.0 as Float);
+        }
+        if self.lens_radius == 0.0 as Float && cos_theta < 1.0 as Float - ORTHO_DIRECTION_EPSILON {
+            return (0.0 as Float, 0.0 as Float);
+        }
+        // return zero probability for out of bounds points
+        let p_raster: Point2f = self.film_to_raster(&self.film_point(&w2c, ray, &d_camera));
+        if !self.inside_sample_bounds(&p_raster) {
+            return (0.0 as Float, 0.0 as Float);
+        }
+        if self.lens_radius == 0.0 as Float {
+            // uniform positions on the film, delta directions
+            (1.0 as Float / self.a, 1.0 as Float)
+        } else {
+            // uniform positions on the lens, uniform points on the
+            // (film sized) area of the plane of focus
+            (
+                1.0 as Float / self.lens_area(),
+                self.focal_distance * self.focal_distance
+                    / (self.a * cos_theta * cos_theta * cos_theta),
+            )
+        }
     }
     fn sample_wi(
         &self,
-        _iref: &InteractionCommon,
-        _u: &Point2f,
-        _wi: &mut Vector3f,
-        _pdf: &mut Float,
-        _p_raster: &mut Point2f,
-        _vis: &mut VisibilityTester,
+        iref: &InteractionCommon,
+        u: &Point2f,
+        wi: &mut Vector3f,
+        pdf: &mut Float,
+        p_raster: &mut Point2f,
+        vis: &mut VisibilityTester,
     ) -> Spectrum {
-        panic!("camera::sample_wi() is not implemented!");
-        // Spectrum::default()
+        let mut c2w: Transform = Transform::default();
+        self.camera_to_world.interpolate(iref.time, &mut c2w);
+        let p_origin: Point3f;
+        if self.lens_radius > 0.0 as Float {
+            // uniformly sample a point on the lens
+            let p_lens: Point2f = concentric_sample_disk(u) * self.lens_radius;
+            p_origin = Point3f {
+                x: p_lens.x,
+                y: p_lens.y,
+                z: 0.0 as Float,
+            };
+        } else {
+            // camera rays are parallel, so (for a pinhole) the only
+            // camera ray through _iref_ starts at its projection onto
+            // the film plane
+            let p_camera: Point3f = Transform::inverse(&c2w).transform_point(&iref.p);
+            // return zero importance for points behind the camera
+            if p_camera.z <= 0.0 as Float {
+                *pdf = 0.0 as Float;
+                return Spectrum::default();
+            }
+            p_origin = Point3f {
+                x: p_camera.x,
+                y: p_camera.y,
+                z: 0.0 as Float,
+            };
+        }
+        // Interaction lens_intr(p_origin_world, iref.time, medium);
+        let mut lens_intr: InteractionCommon = InteractionCommon::default();
+        lens_intr.p = c2w.transform_point(&p_origin);
+        lens_intr.time = iref.time;
+        lens_intr.n = Normal3f::from(c2w.transform_vector(&Vector3f {
+            x: 0.0 as Float,
+            y: 0.0 as Float,
+            z: 1.0 as Float,
+        }));
+        if let Some(ref medium_arc) = self.medium {
+            lens_intr.medium_interface = Some(Arc::new(MediumInterface::new(
+                Some(medium_arc.clone()),
+                Some(medium_arc.clone()),
+            )));
+        } else {
+            lens_intr.medium_interface = None;
+        }
+        // populate arguments and compute the importance value
+        vis.p0 = iref.clone();
+        vis.p1 = lens_intr.clone();
+        *wi = lens_intr.p - iref.p;
+        let dist: Float = wi.length();
+        *wi /= dist;
+        if self.lens_radius > 0.0 as Float {
+            // compute PDF for importance arriving at _iref_
+            *pdf = (dist * dist) / (nrm_abs_dot_vec3(&lens_intr.n, wi) * self.lens_area());
+            self.we(&lens_intr.spawn_ray(&-*wi), Some(p_raster))
+        } else {
+            *p_raster = self.film_to_raster(&p_origin);
+            // return zero importance for out of bounds points
+            if !self.inside_sample_bounds(p_raster) {
+                *pdf = 0.0 as Float;
+                return Spectrum::default();
+            }
+            // the direction is a delta distribution, no change of measure
+            *pdf = 1.0 as Float;
+            Spectrum::new(1.0 as Float / self.a)
+        }
     }
     fn get_shutter_open(&self) -> Float {
         self.shutter_open
@@ -270,3 +459,143 @@ impl Camera for OrthographicCamera {
         self.film.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::testutil::test_film;
+
+    fn test_camera(lens_radius: Float, vignetting: bool) -> OrthographicCamera {
+        let film: Arc<Film> = Arc::new(test_film(
+            Point2i { x: 16, y: 16 },
+            0.5 as Float,
+            "vignetting.png",
+        ));
+        let t: Transform = Transform::default();
+        OrthographicCamera::new(
+            AnimatedTransform::new(&t, 0.0 as Float, &t, 1.0 as Float),
+            Bounds2f {
+                p_min: Point2f { x: -1.0, y: -1.0 },
+                p_max: Point2f { x: 1.0, y: 1.0 },
+            },
+            0.0 as Float,
+            1.0 as Float,
+            lens_radius,
+            1.0 as Float,
+            vignetting,
+            film,
+            None,
+        )
+    }
+
+    fn weight(camera: &OrthographicCamera, p_film: Point2f) -> Float {
+        let sample: CameraSample = CameraSample {
+            p_film: p_film,
+            // center of the lens
+            p_lens: Point2f { x: 0.5, y: 0.5 },
+            time: 0.0 as Float,
+        };
+        let mut ray: Ray = Ray::default();
+        camera.generate_ray_differential(&sample, &mut ray)
+    }
+
+    #[test]
+    fn vignetting_darkens_off_axis_film_points() {
+        let camera: OrthographicCamera = test_camera(0.5 as Float, true);
+        let center: Float = weight(&camera, Point2f { x: 8.0, y: 8.0 });
+        assert!((center - 1.0 as Float).abs() < 1e-4 as Float, "{}", center);
+        let mut previous: Float = center;
+        for x in &[10.0 as Float, 13.0 as Float, 16.0 as Float] {
+            let w: Float = weight(&camera, Point2f { x: *x, y: 8.0 });
+            assert!(w < previous, "weight {} at x = {}", w, x);
+            previous = w;
+        }
+        let corner: Float = weight(&camera, Point2f { x: 16.0, y: 16.0 });
+        assert!(corner < 1.0 as Float && corner > 0.0 as Float);
+        // off by default
+        let plain: OrthographicCamera = test_camera(0.5 as Float, false);
+        assert_eq!(weight(&plain, Point2f { x: 16.0, y: 16.0 }), 1.0 as Float);
+    }
+
+    #[test]
+    fn pinhole_sample_wi_round_trips_the_raster_position() {
+        let camera: OrthographicCamera = test_camera(0.0 as Float, false);
+        let p_film: Point2f = Point2f { x: 5.5, y: 9.25 };
+        let sample: CameraSample = CameraSample {
+            p_film: p_film,
+            p_lens: Point2f { x: 0.5, y: 0.5 },
+            time: 0.0 as Float,
+        };
+        let mut ray: Ray = Ray::default();
+        camera.generate_ray_differential(&sample, &mut ray);
+        // a point in front of the film, on the camera ray through _p_film_
+        let mut iref: InteractionCommon = InteractionCommon::default();
+        iref.p = ray.o + ray.d * 2.0 as Float;
+        iref.time = 0.0 as Float;
+        let mut wi: Vector3f = Vector3f::default();
+        let mut pdf: Float = 0.0 as Float;
+        let mut p_raster: Point2f = Point2f::default();
+        let mut vis: VisibilityTester = VisibilityTester::default();
+        let we: Spectrum = camera.sample_wi(
+            &iref,
+            &Point2f { x: 0.5, y: 0.5 },
+            &mut wi,
+            &mut pdf,
+            &mut p_raster,
+            &mut vis,
+        );
+        assert!(!we.is_black());
+        assert!(pdf > 0.0 as Float);
+        assert!((p_raster.x - p_film.x).abs() < 1e-4 as Float, "{:?}", p_raster);
+        assert!((p_raster.y - p_film.y).abs() < 1e-4 as Float, "{:?}", p_raster);
+        // importance arrives along the (reversed) camera ray
+        assert!((wi.x - -ray.d.x).abs() < 1e-4 as Float);
+        assert!((wi.y - -ray.d.y).abs() < 1e-4 as Float);
+        assert!((wi.z - -ray.d.z).abs() < 1e-4 as Float);
+    }
+
+    #[test]
+    fn no_importance_behind_the_camera() {
+        let camera: OrthographicCamera = test_camera(0.0 as Float, false);
+        // a point behind the film plane (camera space z < 0)
+        let mut iref: InteractionCommon = InteractionCommon::default();
+        iref.p = Point3f {
+            x: 0.25,
+            y: -0.25,
+            z: -2.0,
+        };
+        let mut wi: Vector3f = Vector3f::default();
+        let mut pdf: Float = 1.0 as Float;
+        let mut p_raster: Point2f = Point2f::default();
+        let mut vis: VisibilityTester = VisibilityTester::default();
+        let we: Spectrum = camera.sample_wi(
+            &iref,
+            &Point2f { x: 0.5, y: 0.5 },
+            &mut wi,
+            &mut pdf,
+            &mut p_raster,
+            &mut vis,
+        );
+        assert!(we.is_black());
+        assert_eq!(pdf, 0.0 as Float);
+        // rays leaving the film backwards carry no importance either
+        for lens_radius in &[0.0 as Float, 0.5 as Float] {
+            let camera: OrthographicCamera = test_camera(*lens_radius, false);
+            let ray: Ray = Ray {
+                o: Point3f::default(),
+                d: Vector3f {
+                    x: 0.0,
+                    y: 0.0,
+                    z: -1.0,
+                },
+                t_max: std::f32::INFINITY,
+                time: 0.0 as Float,
+                medium: None,
+                differential: None,
+                ray_type: VisibilityFlags::Untyped as u8,
+            };
+            assert!(camera.we(&ray, None).is_black());
+            assert_eq!(camera.pdf_we(&ray), (0.0 as Float, 0.0 as Float));
+        }
+    }
+}
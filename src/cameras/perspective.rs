@@ -3,11 +3,12 @@ use std;
 use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
-use core::camera::{Camera, CameraSample};
+use core::camera::{get_screen_window, Camera, CameraSample, ShutterCurve};
 use core::film::Film;
 use core::geometry::{nrm_abs_dot_vec3, vec3_dot_vec3};
 use core::geometry::{
-    Bounds2f, Bounds2i, Normal3f, Point2f, Point2i, Point3f, Ray, RayDifferential, Vector3f,
+    Bounds2f, Bounds2i, Normal3f, Point2f, Point2i, Point3f, Ray, RayDifferential, Vector2f,
+    Vector3f, VisibilityFlags,
 };
 use core::interaction::InteractionCommon;
 use core::light::VisibilityTester;
@@ -15,6 +16,7 @@ use core::medium::{Medium, MediumInterface};
 use core::paramset::ParamSet;
 use core::pbrt::lerp;
 use core::pbrt::{Float, Spectrum};
+use core::profile::{Prof, ProfilePhase};
 use core::sampling::concentric_sample_disk;
 use core::transform::{AnimatedTransform, Transform};
 
@@ -25,6 +27,8 @@ pub struct PerspectiveCamera {
     pub camera_to_world: AnimatedTransform,
     pub shutter_open: Float,
     pub shutter_close: Float,
+    /// warps the time samples (see **ShutterCurve::warp_time()**)
+    pub shutter_curve: ShutterCurve,
     pub film: Arc<Film>,
     pub medium: Option<Arc<Medium + Send + Sync>>,
     // inherited from ProjectiveCamera (see camera.h)
@@ -37,6 +41,8 @@ pub struct PerspectiveCamera {
     // private data (see perspective.h)
     pub dx_camera: Vector3f,
     pub dy_camera: Vector3f,
+    /// area of the image plane (at z = 1) covered by the film's
+    /// sample bounds, precomputed for the camera importance functions
     pub a: Float,
 }
 
@@ -61,11 +67,8 @@ impl PerspectiveCamera {
             film.full_resolution.y as Float,
             1.0,
         );
-        let scale2 = Transform::scale(
-            1.0 / (screen_window.p_max.x - screen_window.p_min.x),
-            1.0 / (screen_window.p_min.y - screen_window.p_max.y),
-            1.0,
-        );
+        let screen_extent: Vector2f = screen_window.diagonal();
+        let scale2 = Transform::scale(1.0 / screen_extent.x, -1.0 / screen_extent.y, 1.0);
         let translate = Transform::translate(&Vector3f {
             x: -screen_window.p_min.x,
             y: -screen_window.p_max.y,
@@ -115,6 +118,7 @@ impl PerspectiveCamera {
             camera_to_world: camera_to_world,
             shutter_open: shutter_open,
             shutter_close: shutter_close,
+            shutter_curve: ShutterCurve::Box,
             film: film,
             medium: medium,
             // camera_to_screen: camera_to_screen,
@@ -144,25 +148,13 @@ impl PerspectiveCamera {
             "frameaspectratio",
             (film.full_resolution.x as Float) / (film.full_resolution.y as Float),
         );
-        let mut screen: Bounds2f = Bounds2f::default();
-        if frame > 1.0 {
-            screen.p_min.x = -frame;
-            screen.p_max.x = frame;
-            screen.p_min.y = -1.0;
-            screen.p_max.y = 1.0;
-        } else {
-            screen.p_min.x = -1.0;
-            screen.p_max.x = 1.0;
-            screen.p_min.y = -1.0 / frame;
-            screen.p_max.y = 1.0 / frame;
-        }
-        // TODO: const Float *sw = params.FindFloat("screenwindow", &swi);
+        let screen: Bounds2f = get_screen_window(params, frame);
         let fov: Float = params.find_one_float("fov", 90.0);
         // let halffov: Float =
         //     params.find_one_float(String::from("halffov"), -1.0);
         // TODO: if (halffov > 0.f)
         // TODO: let perspective_camera: Arc<Camera + Sync + Send> =
-        let camera = Arc::new(PerspectiveCamera::new(
+        let mut camera: PerspectiveCamera = PerspectiveCamera::new(
             cam2world,
             screen,
             shutteropen,
@@ -172,14 +164,24 @@ impl PerspectiveCamera {
             fov,
             film,
             medium,
-        ));
-        camera
+        );
+        camera.shutter_curve = ShutterCurve::create(params);
+        Arc::new(camera)
+    }
+    /// Area of the lens (1 for a pinhole camera), used by the camera
+    /// importance functions.
+    fn lens_area(&self) -> Float {
+        if self.lens_radius != 0.0 as Float {
+            PI * self.lens_radius * self.lens_radius
+        } else {
+            1.0 as Float
+        }
     }
 }
 
 impl Camera for PerspectiveCamera {
     fn generate_ray_differential(&self, sample: &CameraSample, ray: &mut Ray) -> Float {
-        // TODO: ProfilePhase prof(Prof::GenerateCameraRay);
+        let _p = ProfilePhase::new(Prof::GenerateCameraRay);
         // compute raster and camera sample positions
         let p_film: Point3f = Point3f {
             x: sample.p_film.x,
@@ -214,7 +216,12 @@ impl Camera for PerspectiveCamera {
             o: Point3f::default(),
             d: dir,
             t_max: std::f32::INFINITY,
-            time: lerp(sample.time, self.shutter_open, self.shutter_close),
+            time: lerp(
+                self.shutter_curve.warp_time(sample.time),
+                self.shutter_open,
+                self.shutter_close,
+            ),
+            ray_type: VisibilityFlags::Camera as u8,
             medium: None,
             differential: Some(diff),
         };
@@ -309,16 +316,9 @@ impl Camera for PerspectiveCamera {
         {
             return Spectrum::default();
         }
-        // compute lens area of perspective camera
-        let lens_area: Float;
-        if self.lens_radius != 0.0 as Float {
-            lens_area = PI * self.lens_radius * self.lens_radius;
-        } else {
-            lens_area = 1.0 as Float;
-        }
         // return importance for point on image plane
         let cos_2_theta: Float = cos_theta * cos_theta;
-        Spectrum::new(1.0 as Float / (self.a * lens_area * cos_2_theta * cos_2_theta))
+        Spectrum::new(1.0 as Float / (self.a * self.lens_area() * cos_2_theta * cos_2_theta))
     }
     fn pdf_we(&self, ray: &Ray) -> (Float, Float) {
         let mut pdf_pos: Float = 0.0;
@@ -359,15 +359,7 @@ impl Camera for PerspectiveCamera {
             // *pdf_pos = *pdf_dir = 0;
             return (pdf_pos, pdf_dir);
         }
-        // compute lens area of perspective camera
-        // Float lens_area = self.lens_radius != 0 ? (Pi * self.lens_radius * self.lens_radius) : 1;
-        let lens_area: Float;
-        if self.lens_radius != 0.0 as Float {
-            lens_area = PI * self.lens_radius * self.lens_radius;
-        } else {
-            lens_area = 1.0 as Float;
-        }
-        pdf_pos = 1.0 as Float / lens_area;
+        pdf_pos = 1.0 as Float / self.lens_area();
         pdf_dir = 1.0 as Float / (self.a * cos_theta * cos_theta * cos_theta);
         (pdf_pos, pdf_dir)
     }
@@ -419,12 +411,7 @@ impl Camera for PerspectiveCamera {
 
         // compute PDF for importance arriving at _iref_
 
-        // compute lens area of perspective camera
-        let mut lens_area: Float = 1.0 as Float;
-        if self.lens_radius != 0.0 as Float {
-            lens_area = PI * self.lens_radius * self.lens_radius;
-        }
-        *pdf = (dist * dist) / (nrm_abs_dot_vec3(&lens_intr.n, wi) * lens_area);
+        *pdf = (dist * dist) / (nrm_abs_dot_vec3(&lens_intr.n, wi) * self.lens_area());
         self.we(&lens_intr.spawn_ray(&-*wi), Some(p_raster))
     }
     fn get_shutter_open(&self) -> Float {
@@ -437,3 +424,97 @@ impl Camera for PerspectiveCamera {
         self.film.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::testutil::test_film;
+
+    fn test_camera(lens_radius: Float) -> PerspectiveCamera {
+        let film: Arc<Film> = Arc::new(test_film(
+            Point2i { x: 16, y: 16 },
+            0.5 as Float,
+            "importance.png",
+        ));
+        let t: Transform = Transform::default();
+        PerspectiveCamera::new(
+            AnimatedTransform::new(&t, 0.0 as Float, &t, 1.0 as Float),
+            Bounds2f {
+                p_min: Point2f { x: -1.0, y: -1.0 },
+                p_max: Point2f { x: 1.0, y: 1.0 },
+            },
+            0.0 as Float,
+            1.0 as Float,
+            lens_radius,
+            1.0 as Float,
+            60.0 as Float,
+            film,
+            None,
+        )
+    }
+
+    fn camera_ray(camera: &PerspectiveCamera, p_film: Point2f) -> Ray {
+        let sample: CameraSample = CameraSample {
+            p_film: p_film,
+            // center of the lens
+            p_lens: Point2f { x: 0.5, y: 0.5 },
+            time: 0.0 as Float,
+        };
+        let mut ray: Ray = Ray::default();
+        camera.generate_ray_differential(&sample, &mut ray);
+        ray
+    }
+
+    #[test]
+    fn importance_integrates_to_the_flux_through_the_film() {
+        let camera: PerspectiveCamera = test_camera(0.0 as Float);
+        let n_pixels: Float = 16.0 as Float * 16.0 as Float;
+        // the ray through the center of the film
+        let ray: Ray = camera_ray(&camera, Point2f { x: 8.0, y: 8.0 });
+        let mut p_raster: Point2f = Point2f::default();
+        let we: Float = camera.we(&ray, Some(&mut p_raster))[0];
+        assert!((p_raster.x - 8.0).abs() < 1e-3 && (p_raster.y - 8.0).abs() < 1e-3);
+        // one pixel covers a / n_pixels of the image plane at z = 1
+        let flux: Float = we * camera.a / n_pixels;
+        assert!((flux * n_pixels - 1.0).abs() < 1e-4, "{}", flux);
+        let (pdf_pos, pdf_dir) = camera.pdf_we(&ray);
+        assert_eq!(pdf_pos, 1.0 as Float);
+        assert!((pdf_dir * camera.a - 1.0).abs() < 1e-4, "{}", pdf_dir);
+        // We(w) cos(theta) dw summed over all pixels, where a pixel
+        // at angle theta subtends a / n_pixels * cos^3(theta)
+        let mut total: Float = 0.0 as Float;
+        for y in 0..16 {
+            for x in 0..16 {
+                let p_film: Point2f = Point2f {
+                    x: x as Float + 0.5,
+                    y: y as Float + 0.5,
+                };
+                let ray: Ray = camera_ray(&camera, p_film);
+                let cos_theta: Float = ray.d.normalize().z;
+                let mut p_raster: Point2f = Point2f::default();
+                let we: Float = camera.we(&ray, Some(&mut p_raster))[0];
+                assert!((p_raster.x - p_film.x).abs() < 1e-3, "{:?}", p_raster);
+                assert!((p_raster.y - p_film.y).abs() < 1e-3, "{:?}", p_raster);
+                total += we * cos_theta.powi(4) * camera.a / n_pixels;
+            }
+        }
+        assert!((total - 1.0).abs() < 1e-3, "{}", total);
+        // no importance behind the camera
+        let mut backwards: Ray = ray.clone();
+        backwards.d = -ray.d;
+        assert!(camera.we(&backwards, None).is_black());
+    }
+
+    #[test]
+    fn thin_lens_importance_is_divided_by_the_lens_area() {
+        let pinhole: PerspectiveCamera = test_camera(0.0 as Float);
+        let thin_lens: PerspectiveCamera = test_camera(0.1 as Float);
+        let ray: Ray = camera_ray(&pinhole, Point2f { x: 5.5, y: 9.5 });
+        let lens_area: Float = PI * 0.1 as Float * 0.1 as Float;
+        let we_pinhole: Float = pinhole.we(&ray, None)[0];
+        let we_lens: Float = thin_lens.we(&ray, None)[0];
+        assert!((we_lens * lens_area / we_pinhole - 1.0).abs() < 1e-4);
+        let (pdf_pos, _pdf_dir) = thin_lens.pdf_we(&ray);
+        assert!((pdf_pos * lens_area - 1.0).abs() < 1e-4);
+    }
+}
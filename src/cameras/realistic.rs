@@ -7,18 +7,19 @@ use std::path::PathBuf;
 use std::sync::mpsc;
 use std::sync::Arc;
 // pbrt
-use core::camera::{Camera, CameraSample};
+use core::camera::{Camera, CameraSample, ShutterCurve};
 use core::film::Film;
 use core::floatfile::read_float_file;
 use core::geometry::{bnd2_expand, bnd2_union_pnt2, nrm_faceforward_vec3, pnt2_inside_bnd2};
-use core::geometry::{Bounds2f, Normal3f, Point2f, Point3f, Ray, RayDifferential, Vector3f};
-use core::interaction::InteractionCommon;
-use core::light::VisibilityTester;
+use core::geometry::{
+    Bounds2f, Normal3f, Point2f, Point3f, Ray, RayDifferential, Vector3f, VisibilityFlags,
+};
 use core::lowdiscrepancy::radical_inverse;
 use core::medium::Medium;
 use core::paramset::ParamSet;
 use core::pbrt::{lerp, quadratic};
-use core::pbrt::{Float, Spectrum};
+use core::pbrt::Float;
+use core::profile::{Prof, ProfilePhase};
 use core::reflection::refract;
 use core::transform::{AnimatedTransform, Transform};
 
@@ -38,6 +39,8 @@ pub struct RealisticCamera {
     pub camera_to_world: AnimatedTransform,
     pub shutter_open: Float,
     pub shutter_close: Float,
+    /// warps the time samples (see **ShutterCurve::warp_time()**)
+    pub shutter_curve: ShutterCurve,
     pub film: Arc<Film>,
     pub medium: Option<Arc<Medium + Send + Sync>>,
     // private data (see realistic.h)
@@ -82,6 +85,7 @@ impl RealisticCamera {
             camera_to_world: camera_to_world,
             shutter_open: shutter_open,
             shutter_close: shutter_close,
+            shutter_curve: ShutterCurve::Box,
             film: film.clone(),
             medium: medium,
             simple_weighting: simple_weighting,
@@ -180,7 +184,7 @@ impl RealisticCamera {
                      lens_file, lens_data.len());
         }
         // println!("lens_data = {:?}", lens_data);
-        let camera = Arc::new(RealisticCamera::new(
+        let mut camera: RealisticCamera = RealisticCamera::new(
             cam2world,
             shutteropen,
             shutterclose,
@@ -190,11 +194,12 @@ impl RealisticCamera {
             &lens_data,
             film,
             medium,
-        ));
-        camera
+        );
+        camera.shutter_curve = ShutterCurve::create(params);
+        Arc::new(camera)
     }
     pub fn generate_ray(&self, sample: &CameraSample, ray: &mut Ray) -> Float {
-        // TODO: ProfilePhase prof(Prof::GenerateCameraRay);
+        let _p = ProfilePhase::new(Prof::GenerateCameraRay);
         // ++totalRays;
         // find point on film, _p_film_, corresponding to _sample.p_film_
         let s: Point2f = Point2f {
@@ -221,7 +226,12 @@ impl RealisticCamera {
         r_film.o = p_film;
         r_film.d = p_rear - p_film;
         r_film.t_max = std::f32::INFINITY;
-        r_film.time = lerp(sample.time, self.shutter_open, self.shutter_close);
+        r_film.time = lerp(
+            self.shutter_curve.warp_time(sample.time),
+            self.shutter_open,
+            self.shutter_close,
+        );
+        r_film.ray_type = VisibilityFlags::Camera as u8;
         if !self.trace_lenses_from_film(&r_film, Some(ray)) {
             // ++vignettedRays;
             return 0.0 as Float;
@@ -459,6 +469,7 @@ impl RealisticCamera {
             },
             t_max: std::f32::INFINITY,
             time: 0.0 as Float,
+            ray_type: VisibilityFlags::Untyped as u8,
             medium: None,
             differential: None,
         };
@@ -481,6 +492,11 @@ impl RealisticCamera {
                 "Unable to trace ray from film to scene for thick lens approximation. Is aperture stop extremely small?");
         self.compute_cardinal_points(&r_film, &r_scene, 1, pz, fz);
     }
+    /// Uses the thick lens approximation (cardinal points found by
+    /// tracing paraxial rays) to compute the distance between the
+    /// rear lens element and the film which focuses the lens system
+    /// at *focus_distance*. **new()** stores the result as the
+    /// thickness of the last element interface.
     pub fn focus_thick_lens(&self, focus_distance: Float) -> Float {
         let mut pz: [Float; 2] = [0.0 as Float; 2];
         let mut fz: [Float; 2] = [0.0 as Float; 2];
@@ -498,6 +514,9 @@ impl RealisticCamera {
         let delta: Float = 0.5 as Float * (pz[1] - z + pz[0] - c.sqrt());
         self.element_interfaces.last().unwrap().thickness + delta
     }
+    /// Finds the film distance focusing at *focus_distance* by a
+    /// binary search around the thick lens approximation (see
+    /// **focus_thick_lens()**).
     pub fn focus_binary_search(&self, focus_distance: Float) -> Float {
         // find _film_distance_lower_, _film_distance_upper_ that bound focus distance
         let mut film_distance_upper: Float = self.focus_thick_lens(focus_distance);
@@ -548,6 +567,7 @@ impl RealisticCamera {
                     },
                     t_max: std::f32::INFINITY,
                     time: 0.0 as Float,
+                    ray_type: VisibilityFlags::Untyped as u8,
                     medium: None,
                     differential: None,
                 },
@@ -621,6 +641,7 @@ impl RealisticCamera {
                     d: p_rear - p_film,
                     t_max: std::f32::INFINITY,
                     time: 0.0 as Float,
+                    ray_type: VisibilityFlags::Untyped as u8,
                     medium: None,
                     differential: None,
                 },
@@ -686,10 +707,6 @@ impl RealisticCamera {
             z: self.lens_rear_z(),
         }
     }
-    pub fn test_exit_pupil_bounds(&self) {
-        // WORK
-        println!("TODO: RealisticCamera::test_exit_pupil_bounds()");
-    }
 }
 
 impl Camera for RealisticCamera {
@@ -736,28 +753,6 @@ impl Camera for RealisticCamera {
         ray.differential = Some(rd);
         wt
     }
-    fn we(&self, _ray: &Ray, _p_raster2: Option<&mut Point2f>) -> Spectrum {
-        panic!("camera::we() is not implemented!");
-        // Spectrum::default()
-    }
-    fn pdf_we(&self, _ray: &Ray) -> (Float, Float) {
-        // let mut pdf_pos: Float = 0.0;
-        // let mut pdf_dir: Float = 0.0;
-        panic!("camera::pdf_we() is not implemented!");
-        // (pdf_pos, pdf_dir)
-    }
-    fn sample_wi(
-        &self,
-        _iref: &InteractionCommon,
-        _u: &Point2f,
-        _wi: &mut Vector3f,
-        _pdf: &mut Float,
-        _p_raster: &mut Point2f,
-        _vis: &mut VisibilityTester,
-    ) -> Spectrum {
-        panic!("camera::sample_wi() is not implemented!");
-        // Spectrum::default()
-    }
     fn get_shutter_open(&self) -> Float {
         self.shutter_open
     }
@@ -768,3 +763,150 @@ impl Camera for RealisticCamera {
         self.film.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::geometry::Point2i;
+    use core::rng::Rng;
+    use core::sampling::concentric_sample_disk;
+    use core::testutil::test_film;
+    use std::io::Write;
+
+    lazy_static! {
+        // bounding the exit pupil traces millions of rays, build it once
+        static ref TWO_ELEMENT_CAMERA: RealisticCamera = two_element_camera();
+    }
+
+    /// A biconvex singlet (radius, thickness, eta, and aperture
+    /// diameter per interface, in mm) focused at 10 m.
+    fn two_element_camera() -> RealisticCamera {
+        let path = std::env::temp_dir().join("rs_pbrt_two_element.dat");
+        {
+            let mut file = std::fs::File::create(&path).unwrap();
+            file.write_all(b"# biconvex lens\n50.0 5.0 1.5 20.0\n-50.0 45.0 1.0 20.0\n")
+                .unwrap();
+        }
+        let mut lens_data: Vec<Float> = Vec::new();
+        assert!(read_float_file(
+            &String::from(path.to_str().unwrap()),
+            &mut lens_data
+        ));
+        assert_eq!(lens_data.len(), 8_usize);
+        let film: Arc<Film> = Arc::new(test_film(
+            Point2i { x: 16, y: 16 },
+            0.5 as Float,
+            "realistic.png",
+        ));
+        let t: Transform = Transform::default();
+        RealisticCamera::new(
+            AnimatedTransform::new(&t, 0.0 as Float, &t, 1.0 as Float),
+            0.0 as Float,
+            1.0 as Float,
+            20.0 as Float,
+            10.0 as Float,
+            false,
+            &lens_data,
+            film,
+            None,
+        )
+    }
+
+    #[test]
+    fn on_axis_rays_pass_through_the_lens() {
+        let camera: &RealisticCamera = &TWO_ELEMENT_CAMERA;
+        assert_eq!(camera.element_interfaces.len(), 2_usize);
+        for p_lens in &[
+            Point2f { x: 0.5, y: 0.5 },
+            Point2f { x: 0.25, y: 0.5 },
+            Point2f { x: 0.5, y: 0.8 },
+        ] {
+            let sample: CameraSample = CameraSample {
+                p_film: Point2f { x: 8.0, y: 8.0 },
+                p_lens: *p_lens,
+                time: 0.5 as Float,
+            };
+            let mut ray: Ray = Ray::default();
+            let weight: Float = camera.generate_ray(&sample, &mut ray);
+            assert!(weight > 0.0 as Float, "vignetted for {:?}", p_lens);
+            // leaves the lens towards the scene, close to the optical axis
+            assert!(ray.d.z > 0.99 as Float, "{:?}", ray.d);
+        }
+    }
+
+    /// See TestExitPupilBounds() in realistic.cpp: all rays making it
+    /// through the lens system have to start inside the precomputed
+    /// exit pupil bounds.
+    #[test]
+    fn exit_pupil_bounds_contain_all_rays() {
+        let camera: &RealisticCamera = &TWO_ELEMENT_CAMERA;
+        let mut rng: Rng = Rng::default();
+        let film_diagonal: Float = camera.film.diagonal;
+        let n_bounds: usize = camera.exit_pupil_bounds.len();
+        let mut n_traced: usize = 0;
+        for _ in 0..16 {
+            // random film position on the $x$ axis
+            let u: Float = rng.uniform_float();
+            let p_film: Point3f = Point3f {
+                x: u * film_diagonal / 2.0 as Float,
+                y: 0.0 as Float,
+                z: 0.0 as Float,
+            };
+            let r: Float = p_film.x / (film_diagonal / 2.0 as Float);
+            let pupil_index: usize =
+                (n_bounds - 1).min((r * (n_bounds - 1) as Float).floor() as usize);
+            let mut pupil_bounds: Bounds2f = camera.exit_pupil_bounds[pupil_index];
+            if pupil_index + 1 < n_bounds {
+                // union with the bounds of the next film segment
+                let next: Bounds2f = camera.exit_pupil_bounds[pupil_index + 1];
+                pupil_bounds = bnd2_union_pnt2(&pupil_bounds, &next.p_min);
+                pupil_bounds = bnd2_union_pnt2(&pupil_bounds, &next.p_max);
+            }
+            // randomly pick points on the aperture
+            for _ in 0..256 {
+                let u2: Point2f = Point2f {
+                    x: rng.uniform_float(),
+                    y: rng.uniform_float(),
+                };
+                let pd: Point2f = concentric_sample_disk(&u2) * camera.rear_element_radius();
+                let mut test_ray: Ray = Ray::default();
+                test_ray.o = p_film;
+                test_ray.d = Point3f {
+                    x: pd.x,
+                    y: pd.y,
+                    z: camera.lens_rear_z(),
+                } - p_film;
+                test_ray.t_max = std::f32::INFINITY;
+                if !camera.trace_lenses_from_film(&test_ray, None) {
+                    continue;
+                }
+                n_traced += 1;
+                assert!(
+                    pnt2_inside_bnd2(&pd, &pupil_bounds),
+                    "{:?} went through, but outside bounds {:?}",
+                    pd,
+                    pupil_bounds
+                );
+            }
+        }
+        assert!(n_traced > 0);
+    }
+
+    #[test]
+    fn focusing_closer_moves_the_film_away_from_the_lens() {
+        let camera: &RealisticCamera = &TWO_ELEMENT_CAMERA;
+        let near: Float = camera.focus_thick_lens(1.0 as Float);
+        let far: Float = camera.focus_thick_lens(1.0e6 as Float);
+        assert!(near > far, "near: {} far: {}", near, far);
+        // new() stores the film distance for its focus distance
+        let thickness: Float = camera.element_interfaces.last().unwrap().thickness;
+        assert!((thickness - camera.focus_thick_lens(10.0 as Float)).abs() < 1e-5 as Float);
+        // the thick lens approximation is close to the traced focus
+        let focus: Float = camera.focus_distance(near);
+        assert!(
+            (focus - 1.0 as Float).abs() < 0.05 as Float,
+            "focus: {}",
+            focus
+        );
+    }
+}
@@ -14,11 +14,12 @@ use cameras::orthographic::OrthographicCamera;
 use cameras::perspective::PerspectiveCamera;
 use cameras::realistic::RealisticCamera;
 use core::camera::Camera;
-use core::film::Film;
+use core::film::{png_bit_depth, Bloom, Film, RenderMetadata};
 use core::filter::Filter;
 use core::geometry::{vec3_coordinate_system, vec3_cross_vec3};
 use core::geometry::{Bounds2f, Bounds2i, Normal3f, Point2f, Point2i, Point3f, Vector3f};
-use core::integrator::SamplerIntegrator;
+use core::geometry::VisibilityFlags;
+use core::integrator::{AdaptiveSampling, SamplerIntegrator};
 use core::light::Light;
 use core::material::Material;
 use core::medium::get_medium_scattering_properties;
@@ -28,10 +29,11 @@ use core::paramset::{ParamSet, TextureParams};
 use core::pbrt::{clamp_t, lerp};
 use core::pbrt::{Float, Spectrum};
 use core::primitive::{GeometricPrimitive, Primitive, TransformedPrimitive};
-use core::reflection::FourierBSDFTable;
-use core::sampler::Sampler;
+use core::reflection::{FourierBSDFTable, REGULARIZE_MIN_ALPHA};
+use core::sampler::{set_max_sampler_dimensions, Sampler, ARRAY_START_DIM, MAX_SAMPLER_DIMENSIONS};
 use core::scene::Scene;
 use core::shape::Shape;
+use core::spectrum::{ColorSpace, SpectrumType};
 use core::texture::{
     IdentityMapping3D, PlanarMapping2D, Texture, TextureMapping2D, TextureMapping3D, UVMapping2D,
 };
@@ -47,12 +49,13 @@ use integrators::directlighting::{DirectLightingIntegrator, LightStrategy};
 use integrators::mlt::render_mlt;
 use integrators::mlt::MLTIntegrator;
 use integrators::path::PathIntegrator;
-use integrators::render;
+use integrators::{render, render_adaptive};
 use integrators::sppm::render_sppm;
 use integrators::sppm::SPPMIntegrator;
 use integrators::volpath::VolPathIntegrator;
 use lights::diffuse::DiffuseAreaLight;
 use lights::distant::DistantLight;
+use lights::goniometric::GonioPhotometricLight;
 use lights::infinite::InfiniteAreaLight;
 use lights::point::PointLight;
 use lights::spot::SpotLight;
@@ -71,20 +74,24 @@ use materials::translucent::TranslucentMaterial;
 use materials::uber::UberMaterial;
 use media::grid::GridDensityMedium;
 use media::homogeneous::HomogeneousMedium;
+use samplers::cmj::CmjSampler;
 use samplers::halton::HaltonSampler;
+use samplers::maxmindist::MaxMinDistSampler;
 use samplers::random::RandomSampler;
 use samplers::sobol::SobolSampler;
+use samplers::stratified::StratifiedSampler;
 use samplers::zerotwosequence::ZeroTwoSequenceSampler;
 use shapes::curve::create_curve_shape;
 use shapes::cylinder::Cylinder;
 use shapes::disk::Disk;
+use shapes::heightfield::create_heightfield;
 use shapes::loopsubdiv::loop_subdivide;
 use shapes::nurbs::nurbs_evaluate_surface;
 use shapes::nurbs::Homogeneous3;
 use shapes::plymesh::create_ply_mesh;
 use shapes::sphere::Sphere;
 use shapes::triangle::{Triangle, TriangleMesh};
-use textures::checkerboard::Checkerboard2DTexture;
+use textures::checkerboard::{Checkerboard2DTexture, Checkerboard3DTexture};
 use textures::constant::ConstantTexture;
 use textures::fbm::FBmTexture;
 use textures::imagemap::ImageTexture;
@@ -109,6 +116,12 @@ impl Default for BsdfState {
 pub struct ApiState {
     number_of_threads: u8,
     pub search_directory: Option<Box<PathBuf>>,
+    /// Use a bright magenta material (instead of the default matte)
+    /// wherever a material is missing or unknown, and bright magenta
+    /// for missing spectrum textures (see **TextureParams**)
+    pub error_material: bool,
+    /// Scene file being rendered (stored in the **RenderMetadata**)
+    pub scene_name: String,
     cur_transform: TransformSet,
     active_transform_bits: u8,
     named_coordinate_systems: HashMap<&'static str, TransformSet>,
@@ -125,6 +138,8 @@ impl Default for ApiState {
         ApiState {
             number_of_threads: 0_u8,
             search_directory: None,
+            error_material: false,
+            scene_name: String::new(),
             cur_transform: TransformSet {
                 t: [Transform {
                     m: Matrix4x4 {
@@ -143,10 +158,10 @@ impl Default for ApiState {
                             [0.0, 0.0, 0.0, 1.0],
                         ],
                     },
-                }; 2],
+                }; MAX_TRANSFORMS],
             },
             named_coordinate_systems: HashMap::new(),
-            active_transform_bits: 3_u8, // 0x11 for MaxTransforms = 2
+            active_transform_bits: ALL_TRANSFORMS_BITS,
             render_options: RenderOptions::default(),
             graphics_state: GraphicsState::new(),
             pushed_graphics_states: Vec::new(),
@@ -157,26 +172,39 @@ impl Default for ApiState {
     }
 }
 
+/// Number of transforms kept per **TransformSet** (start and end time).
+pub const MAX_TRANSFORMS: usize = 2;
+/// Transform operations apply to the transform at the start time.
+pub const START_TRANSFORM_BITS: u8 = 1_u8 << 0;
+/// Transform operations apply to the transform at the end time.
+pub const END_TRANSFORM_BITS: u8 = 1_u8 << 1;
+/// Transform operations apply to all transforms.
+pub const ALL_TRANSFORMS_BITS: u8 = (1_u8 << MAX_TRANSFORMS) - 1;
+
 #[derive(Debug, Default, Copy, Clone)]
 pub struct TransformSet {
-    pub t: [Transform; 2],
+    pub t: [Transform; MAX_TRANSFORMS],
 }
 
 impl TransformSet {
     pub fn is_animated(&self) -> bool {
-        // for (int i = 0; i < MaxTransforms - 1; ++i)
-        //     if (t[i] != t[i + 1]) return true;
-        // return false;
-
-        // we have only 2 transforms
-        if self.t[0] != self.t[1] {
-            true
-        } else {
-            false
+        for i in 0..MAX_TRANSFORMS - 1 {
+            if self.t[i] != self.t[i + 1] {
+                return true;
+            }
         }
+        false
     }
 }
 
+/// A camera rendered in addition to the main one (see
+/// **pbrt_add_camera()**).
+pub struct AdditionalCamera {
+    pub name: String,
+    pub params: ParamSet,
+    pub camera_to_world: TransformSet,
+}
+
 pub struct RenderOptions {
     pub transform_start_time: Float,
     pub transform_end_time: Float,
@@ -193,6 +221,9 @@ pub struct RenderOptions {
     pub camera_name: String, // "perspective";
     pub camera_params: ParamSet,
     pub camera_to_world: TransformSet,
+    /// Cameras added with **pbrt_add_camera()** (e.g. for multi-view
+    /// rendering), each rendered into its own images
+    pub additional_cameras: Vec<AdditionalCamera>,
     pub named_media: HashMap<String, Arc<Medium + Sync + Send>>,
     pub lights: Vec<Arc<Light + Sync + Send>>,
     pub primitives: Vec<Arc<Primitive + Sync + Send>>,
@@ -236,7 +267,7 @@ impl Default for RenderOptions {
                             [0.0, 0.0, 0.0, 1.0],
                         ],
                     },
-                }; 2],
+                }; MAX_TRANSFORMS],
             },
             named_media: HashMap::new(),
             lights: Vec::new(),
@@ -244,6 +275,7 @@ impl Default for RenderOptions {
             instances: HashMap::new(),
             current_instance: String::from(""),
             have_scattering_media: false,
+            additional_cameras: Vec::new(),
         }
     }
 }
@@ -308,6 +340,7 @@ fn create_material(
         spectrum_textures: api_state.graphics_state.spectrum_textures.clone(),
         geom_params: ParamSet::default(),
         material_params: material_params,
+        error_material: api_state.error_material,
     };
     if api_state.graphics_state.current_material != String::new() {
         match api_state
@@ -323,6 +356,7 @@ fn create_material(
                     "WARNING: Named material \"{}\" not defined. Using \"matte\".",
                     api_state.graphics_state.current_material
                 );
+                return Some(fallback_material(api_state));
             }
         }
     } else {
@@ -347,13 +381,15 @@ fn create_material(
             let mat1 = match api_state.graphics_state.named_materials.get(&m1) {
                 Some(named_material) => named_material,
                 None => {
-                    panic!("Material \"{}\" unknown.", m1);
+                    println!("WARNING: Material \"{}\" unknown. Using \"matte\".", m1);
+                    return Some(fallback_material(api_state));
                 }
             };
             let mat2 = match api_state.graphics_state.named_materials.get(&m2) {
                 Some(named_material) => named_material,
                 None => {
-                    panic!("Material \"{}\" unknown.", m2);
+                    println!("WARNING: Material \"{}\" unknown. Using \"matte\".", m2);
+                    return Some(fallback_material(api_state));
                 }
             };
             let scale: Arc<Texture<Spectrum> + Send + Sync> =
@@ -376,19 +412,54 @@ fn create_material(
         } else if api_state.graphics_state.material == "kdsubsurface" {
             println!("TODO: CreateKdsubsurfaceMaterial");
         } else if api_state.graphics_state.material == "fourier" {
-            return Some(FourierMaterial::create(&mut mp, bsdf_state));
+            if let Some(material) = FourierMaterial::create(&mut mp, bsdf_state) {
+                return Some(material);
+            }
+            println!("WARNING: Unable to create \"fourier\" material. Using fallback material.");
         } else if api_state.graphics_state.material == "disney" {
             return Some(DisneyMaterial::create(&mut mp));
         } else {
-            panic!(
-                "Material \"{}\" unknown.",
+            println!(
+                "WARNING: Material \"{}\" unknown. Using \"matte\".",
                 api_state.graphics_state.material
             );
         }
     }
-    let kd = Arc::new(ConstantTexture::new(Spectrum::new(0.5)));
+    Some(fallback_material(api_state))
+}
+
+/// Material used if the requested one is missing or unknown: a grey
+/// matte material, or a bright magenta one if
+/// **ApiState::error_material** is set (to spot the problem easily).
+fn fallback_material(api_state: &ApiState) -> Arc<Material + Send + Sync> {
+    let kd = if api_state.error_material {
+        Arc::new(ConstantTexture::new(Spectrum::rgb(1.0, 0.0, 1.0)))
+    } else {
+        Arc::new(ConstantTexture::new(Spectrum::new(0.5)))
+    };
     let sigma = Arc::new(ConstantTexture::new(0.0 as Float));
-    Some(Arc::new(MatteMaterial::new(kd, sigma, None)))
+    Arc::new(MatteMaterial::new(kd, sigma, None))
+}
+
+/// Reads the (boolean) shape parameters "visiblecamera",
+/// "visibleshadow", "visiblediffuse", "visibleglossy", and
+/// "visiblereflection" (all default to true) into a bitwise
+/// combination of **VisibilityFlags**.
+fn get_visibility_flags(params: &ParamSet) -> u8 {
+    let mut visibility: u8 = 0_u8;
+    let flags: [(&str, u8); 5] = [
+        ("visiblecamera", VisibilityFlags::Camera as u8),
+        ("visibleshadow", VisibilityFlags::Shadow as u8),
+        ("visiblediffuse", VisibilityFlags::Diffuse as u8),
+        ("visibleglossy", VisibilityFlags::Glossy as u8),
+        ("visiblereflection", VisibilityFlags::Reflection as u8),
+    ];
+    for &(name, flag) in flags.iter() {
+        if params.find_one_bool(name, true) {
+            visibility |= flag;
+        }
+    }
+    visibility
 }
 
 fn create_medium_interface(api_state: &ApiState) -> MediumInterface {
@@ -501,7 +572,32 @@ fn make_light(api_state: &mut ApiState, medium_interface: &MediumInterface) {
         ));
         api_state.render_options.lights.push(spot_light);
     } else if api_state.param_set.name == "goniometric" {
-        println!("TODO: CreateGoniometricLight");
+        // CreateGoniometricLight
+        let i: Spectrum = api_state
+            .param_set
+            .find_one_spectrum("I", Spectrum::new(1.0 as Float));
+        let sc: Spectrum = api_state
+            .param_set
+            .find_one_spectrum("scale", Spectrum::new(1.0 as Float));
+        let mut texname: String = api_state
+            .param_set
+            .find_one_filename("mapname", String::from(""));
+        if texname != String::from("") {
+            if let Some(ref search_directory) = api_state.search_directory {
+                // texname = AbsolutePath(ResolveFilename(texname));
+                let mut path_buf: PathBuf = PathBuf::from("/");
+                path_buf.push(search_directory.as_ref());
+                path_buf.push(texname);
+                texname = String::from(path_buf.to_str().unwrap());
+            }
+        }
+        let gonio_light = Arc::new(GonioPhotometricLight::new(
+            &api_state.cur_transform.t[0],
+            medium_interface,
+            &(i * sc),
+            texname,
+        ));
+        api_state.render_options.lights.push(gonio_light);
     } else if api_state.param_set.name == "projection" {
         println!("TODO: CreateProjectionLight");
     } else if api_state.param_set.name == "distant" {
@@ -557,11 +653,16 @@ fn make_light(api_state: &mut ApiState, medium_interface: &MediumInterface) {
         }
         let n_samples: i32 = api_state.param_set.find_one_int("nsamples", 1 as i32);
         // TODO: if (PbrtOptions.quickRender) nSamples = std::max(1, nSamples / 4);
+        // exposure in stops, on top of the linear _scale_
+        let exposure: Float = api_state
+            .param_set
+            .find_one_float("exposure", 0.0 as Float);
+        let exposure_scale: Float = (2.0 as Float).powf(exposure);
 
         // return std::make_shared<InfiniteAreaLight>(light2world, L * sc, nSamples, texmap);
         let infinte_light = Arc::new(InfiniteAreaLight::new(
             &api_state.cur_transform.t[0],
-            &(l * sc),
+            &(l * sc * exposure_scale),
             n_samples,
             texmap,
         ));
@@ -579,8 +680,8 @@ fn make_medium(api_state: &mut ApiState) {
     // MakeMedium (api.cpp:685)
     let sig_a_rgb: [Float; 3] = [0.0011, 0.0024, 0.014];
     let sig_s_rgb: [Float; 3] = [2.55, 3.21, 3.77];
-    let mut sig_a: Spectrum = Spectrum::from_rgb(&sig_a_rgb);
-    let mut sig_s: Spectrum = Spectrum::from_rgb(&sig_s_rgb);
+    let mut sig_a: Spectrum = Spectrum::from_rgb(&sig_a_rgb, SpectrumType::Illuminant);
+    let mut sig_s: Spectrum = Spectrum::from_rgb(&sig_s_rgb, SpectrumType::Illuminant);
     let preset: String = api_state.param_set.find_one_string("preset", String::new());
     let found: bool = get_medium_scattering_properties(&preset, &mut sig_a, &mut sig_s);
     if preset != String::from("") && !found {
@@ -666,6 +767,7 @@ fn make_texture(api_state: &mut ApiState) {
         spectrum_textures: api_state.graphics_state.spectrum_textures.clone(),
         geom_params: geom_params,
         material_params: material_params,
+        error_material: api_state.error_material,
     };
     if api_state.param_set.tex_type == "float" {
         match api_state
@@ -746,7 +848,9 @@ fn make_texture(api_state: &mut ApiState) {
             } else if wrap == "clamp" {
                 wrap_mode = ImageWrap::Clamp;
             }
-            let scale: Float = tp.find_float("scale", 1.0);
+            // linear _scale_ and _exposure_ (in stops) compose multiplicatively
+            let exposure: Float = tp.find_float("exposure", 0.0);
+            let scale: Float = tp.find_float("scale", 1.0) * (2.0 as Float).powf(exposure);
             let mut filename: String = tp.find_filename("filename", String::new());
             if let Some(ref search_directory) = api_state.search_directory {
                 // filename = AbsolutePath(ResolveFilename(filename));
@@ -761,6 +865,9 @@ fn make_texture(api_state: &mut ApiState) {
             // HasExtension(filename,
             // ".png"));
             let gamma: bool = tp.find_bool("gamma", true);
+            // negative values sharpen, positive values blur ("mipbias" is an alias)
+            let mip_bias: Float = tp.find_float("mipbias", 0.0);
+            let lod_bias: Float = tp.find_float("lodbias", mip_bias);
 
             if let Some(mapping) = map {
                 let ft = Arc::new(ImageTexture::new(
@@ -771,6 +878,7 @@ fn make_texture(api_state: &mut ApiState) {
                     wrap_mode,
                     scale,
                     gamma,
+                    lod_bias,
                     convert_to_float,
                 ));
                 Arc::make_mut(&mut api_state.graphics_state.float_textures)
@@ -805,7 +913,17 @@ fn make_texture(api_state: &mut ApiState) {
             };
             let map: Box<TextureMapping3D + Send + Sync> =
                 Box::new(IdentityMapping3D::new(tex_2_world));
-            let ft = Arc::new(WindyTexture::new(map));
+            let offset: Point3f = tp.find_point3f("offset", Point3f::default());
+            let min_lod: Float = tp.find_float("minlod", 0.0 as Float);
+            let ft = Arc::new(WindyTexture::new(
+                map,
+                Vector3f {
+                    x: offset.x,
+                    y: offset.y,
+                    z: offset.z,
+                },
+                min_lod,
+            ));
             Arc::make_mut(&mut api_state.graphics_state.float_textures)
                 .insert(api_state.param_set.name.clone(), ft);
         } else if api_state.param_set.tex_name == "ptex" {
@@ -901,7 +1019,9 @@ fn make_texture(api_state: &mut ApiState) {
             } else if wrap == "clamp" {
                 wrap_mode = ImageWrap::Clamp;
             }
-            let scale: Float = tp.find_float("scale", 1.0);
+            // linear _scale_ and _exposure_ (in stops) compose multiplicatively
+            let exposure: Float = tp.find_float("exposure", 0.0);
+            let scale: Float = tp.find_float("scale", 1.0) * (2.0 as Float).powf(exposure);
             let mut filename: String = tp.find_filename("filename", String::new());
             if let Some(ref search_directory) = api_state.search_directory {
                 // filename = AbsolutePath(ResolveFilename(filename));
@@ -916,6 +1036,9 @@ fn make_texture(api_state: &mut ApiState) {
             // HasExtension(filename,
             // ".png"));
             let gamma: bool = tp.find_bool("gamma", true);
+            // negative values sharpen, positive values blur ("mipbias" is an alias)
+            let mip_bias: Float = tp.find_float("mipbias", 0.0);
+            let lod_bias: Float = tp.find_float("lodbias", mip_bias);
 
             if let Some(mapping) = map {
                 let st = Arc::new(ImageTexture::new(
@@ -926,6 +1049,7 @@ fn make_texture(api_state: &mut ApiState) {
                     wrap_mode,
                     scale,
                     gamma,
+                    lod_bias,
                     convert_to_spectrum,
                 ));
                 Arc::make_mut(&mut api_state.graphics_state.spectrum_textures)
@@ -993,7 +1117,15 @@ fn make_texture(api_state: &mut ApiState) {
                 }
             } else {
                 // dim == 3
-                println!("TODO: TextureMapping3D");
+                let tex_2_world: Transform = Transform {
+                    m: api_state.cur_transform.t[0].m,
+                    m_inv: api_state.cur_transform.t[0].m_inv,
+                };
+                let map: Box<TextureMapping3D + Send + Sync> =
+                    Box::new(IdentityMapping3D::new(tex_2_world));
+                let st = Arc::new(Checkerboard3DTexture::new(map, tex1, tex2));
+                Arc::make_mut(&mut api_state.graphics_state.spectrum_textures)
+                    .insert(api_state.param_set.name.clone(), st);
             }
         } else if api_state.param_set.tex_name == "dots" {
             println!("TODO: CreateDotsSpectrumTexture");
@@ -1237,7 +1369,18 @@ fn get_shapes_and_materials(
             panic!("No search directory for plymesh.");
         }
     } else if api_state.param_set.name == "heightfield" {
-        println!("TODO: CreateHeightfield");
+        let mtl: Option<Arc<Material + Send + Sync>> = create_material(&api_state, bsdf_state);
+        let heightfield_shapes: Vec<Arc<Shape + Send + Sync>> = create_heightfield(
+            &obj_to_world,
+            &world_to_obj,
+            api_state.graphics_state.reverse_orientation,
+            &api_state.param_set,
+            api_state.graphics_state.float_textures.clone(),
+        );
+        for shape in heightfield_shapes {
+            shapes.push(shape.clone());
+            materials.push(mtl.clone());
+        }
     } else if api_state.param_set.name == "loopsubdiv" {
         // CreateLoopSubdiv
         let n_levels: i32 = api_state
@@ -1574,6 +1717,177 @@ pub fn pbrt_cleanup(api_state: &ApiState) {
         api_state.pushed_transforms.len() == 0_usize,
         "Missing end to pbrtTransformBegin()"
     );
+    // render the main camera, then all cameras added with pbrt_add_camera()
+    render_all_cameras(api_state, |scene, name, params, camera_to_world, output_suffix| {
+        render_with_camera(api_state, scene, name, params, camera_to_world, output_suffix)
+    });
+    // don't keep the MipMaps of this scene alive for the next one
+    ImageTexture::<Float>::clear_cache();
+    ImageTexture::<Spectrum>::clear_cache();
+}
+
+/// Builds the scene once and calls *render_camera* for the main
+/// camera and then for each camera added with **pbrt_add_camera()**,
+/// passing the output suffix for its images ("", "_1", "_2", ...).
+fn render_all_cameras<F>(api_state: &ApiState, mut render_camera: F)
+where
+    F: FnMut(&Scene, &str, &ParamSet, &TransformSet, &str),
+{
+    let scene: Scene = make_scene(api_state);
+    render_camera(
+        &scene,
+        &api_state.render_options.camera_name,
+        &api_state.render_options.camera_params,
+        &api_state.render_options.camera_to_world,
+        "",
+    );
+    for (i, camera) in api_state.render_options.additional_cameras.iter().enumerate() {
+        render_camera(
+            &scene,
+            &camera.name,
+            &camera.params,
+            &camera.camera_to_world,
+            &format!("_{}", i + 1),
+        );
+    }
+}
+
+/// Creates the accelerator for all primitives (see MakeAccelerator()
+/// in api.cpp) and the scene.
+fn make_scene(api_state: &ApiState) -> Scene {
+    // MakeAccelerator
+    let accelerator: Arc<BVHAccel>;
+    if api_state.render_options.accelerator_name == "bvh" {
+        //  CreateBVHAccelerator
+        let split_method_name: String = api_state
+            .render_options
+            .accelerator_params
+            .find_one_string("splitmethod", String::from("sah"));
+        let split_method;
+        if split_method_name == "sah" {
+            split_method = SplitMethod::SAH;
+        } else if split_method_name == "hlbvh" {
+            split_method = SplitMethod::HLBVH;
+        } else if split_method_name == "middle" {
+            split_method = SplitMethod::Middle;
+        } else if split_method_name == "equal" {
+            split_method = SplitMethod::EqualCounts;
+        } else {
+            println!(
+                "WARNING: BVH split method \"{}\" unknown.  Using \"sah\".",
+                split_method_name
+            );
+            split_method = SplitMethod::SAH;
+        }
+        let max_prims_in_node: i32 = api_state
+            .render_options
+            .accelerator_params
+            .find_one_int("maxnodeprims", 4);
+        accelerator = Arc::new(BVHAccel::new(
+            api_state.render_options.primitives.clone(),
+            max_prims_in_node as usize,
+            split_method,
+        ));
+    } else if api_state.render_options.accelerator_name == "kdtree" {
+        // println!("TODO: CreateKdTreeAccelerator");
+        // WARNING: Use BVHAccel for now !!!
+        accelerator = Arc::new(BVHAccel::new(
+            api_state.render_options.primitives.clone(),
+            4,
+            SplitMethod::SAH,
+        ));
+    } else {
+        panic!(
+            "Accelerator \"{}\" unknown.",
+            api_state.render_options.accelerator_name
+        );
+    }
+    // MakeScene
+    // TODO: primitives.erase(primitives.begin(), primitives.end());
+    // TODO: lights.erase(lights.begin(), lights.end());
+    Scene::new(accelerator, api_state.render_options.lights.clone())
+}
+
+/// Creates the sampler called *name* from the **Sampler** parameters
+/// of the scene description (see MakeSampler() in api.cpp).
+/// *sample_bounds* are the film's sample bounds, needed by the global
+/// samplers. Unknown samplers fall back to **HaltonSampler** with a
+/// warning.
+pub fn create_sampler(
+    name: &str,
+    params: &ParamSet,
+    sample_bounds: Bounds2i,
+) -> Box<Sampler + Sync + Send> {
+    // TODO: if (PbrtOptions.quickRender) nsamp = 1;
+    if name == "lowdiscrepancy" || name == "02sequence" {
+        ZeroTwoSequenceSampler::create(params)
+    } else if name == "sobol" {
+        SobolSampler::create(params, sample_bounds)
+    } else if name == "random" {
+        RandomSampler::create(params)
+    } else if name == "stratified" {
+        StratifiedSampler::create(params)
+    } else if name == "maxmindist" {
+        MaxMinDistSampler::create(params)
+    } else if name == "cmj" {
+        let nsamp: i32 = params.find_one_int("pixelsamples", 16);
+        Box::new(CmjSampler::new(nsamp as i64))
+    } else {
+        if name != "halton" {
+            println!("WARNING: Sampler \"{}\" unknown. Using \"halton\".", name);
+        }
+        let nsamp: i32 = params.find_one_int("pixelsamples", 16);
+        let sample_at_center: bool = params.find_one_bool("samplepixelcenter", false);
+        let mut array_start_dim: i32 = params.find_one_int("arraystartdim", ARRAY_START_DIM as i32);
+        if (array_start_dim as i64) < ARRAY_START_DIM {
+            println!(
+                "WARNING: \"arraystartdim\" {} would overlap the camera sample. Using {}.",
+                array_start_dim, ARRAY_START_DIM
+            );
+            array_start_dim = ARRAY_START_DIM as i32;
+        }
+        // optional explicit seed for the digit permutations; integer
+        // parameters are 32 bit signed, so negative values select
+        // the seeds from 2^31 on (the bits are taken as unsigned)
+        let permutation_seed: Vec<i32> = params.find_int("permutationseed");
+        let mut sampler: HaltonSampler = if let Some(seed) = permutation_seed.first() {
+            HaltonSampler::with_permutations(
+                *seed as u32 as u64,
+                nsamp as i64,
+                sample_bounds,
+                sample_at_center,
+                array_start_dim as i64,
+            )
+        } else {
+            HaltonSampler::new(
+                nsamp as i64,
+                sample_bounds,
+                sample_at_center,
+                array_start_dim as i64,
+            )
+        };
+        // opt-in: let the per tile reseeds select their own digit permutations
+        sampler.reseed_permutations = params.find_one_bool("reseedpermutations", false);
+        // opt-in: skip sample arrays for single sample previews
+        if params.find_one_bool("preview", false) {
+            sampler.set_preview(true);
+        }
+        Box::new(sampler)
+    }
+}
+
+/// Creates film, camera, sampler, and integrator for the given camera
+/// and renders the (shared) *scene*. The output files get
+/// *output_suffix* appended to their base name (see
+/// **Film::set_output_suffix()**).
+fn render_with_camera(
+    api_state: &ApiState,
+    scene: &Scene,
+    camera_name: &str,
+    camera_params: &ParamSet,
+    camera_to_world: &TransformSet,
+    output_suffix: &str,
+) {
     // MakeFilter
     let mut some_filter: Option<Arc<Filter + Sync + Send>> = None;
     if api_state.render_options.filter_name == "box" {
@@ -1646,8 +1960,45 @@ pub fn pbrt_cleanup(api_state: &ApiState) {
             .render_options
             .film_params
             .find_one_float("maxsampleluminance", std::f32::INFINITY);
+        let bit_depth: u8 = png_bit_depth(
+            api_state
+                .render_options
+                .film_params
+                .find_one_int("bitdepth", 8),
+        );
+        let mut bloom: Option<Bloom> = None;
+        if api_state.render_options.film_params.find_one_bool("bloom", false) {
+            bloom = Some(Bloom {
+                threshold: api_state
+                    .render_options
+                    .film_params
+                    .find_one_float("bloomthreshold", 1.0),
+                radius: api_state
+                    .render_options
+                    .film_params
+                    .find_one_float("bloomradius", 8.0),
+                intensity: api_state
+                    .render_options
+                    .film_params
+                    .find_one_float("bloomintensity", 0.1),
+            });
+        }
+        let color_space_name: String = api_state
+            .render_options
+            .film_params
+            .find_one_string("colorspace", String::from("srgb"));
+        let color_space: ColorSpace = match ColorSpace::from_name(&color_space_name) {
+            Some(color_space) => color_space,
+            None => {
+                println!(
+                    "WARNING: Unknown \"colorspace\" \"{}\". Using \"srgb\".",
+                    color_space_name
+                );
+                ColorSpace::SRgb
+            }
+        };
         if let Some(filter) = some_filter {
-            let film: Arc<Film> = Arc::new(Film::new(
+            let mut film: Film = Film::new(
                 Point2i { x: xres, y: yres },
                 crop,
                 filter,
@@ -1655,37 +2006,42 @@ pub fn pbrt_cleanup(api_state: &ApiState) {
                 filename,
                 scale,
                 max_sample_luminance,
-            ));
+                bit_depth,
+                bloom,
+                color_space,
+            );
+            film.set_output_suffix(output_suffix);
+            let film: Arc<Film> = Arc::new(film);
             // MakeCamera
             // TODO: let mut some_camera: Option<Arc<Camera + Sync + Send>> = None;
             let mut some_camera: Option<Arc<Camera + Sync + Send>>;
             let medium_interface: MediumInterface = create_medium_interface(&api_state);
             let animated_cam_to_world: AnimatedTransform = AnimatedTransform::new(
-                &api_state.render_options.camera_to_world.t[0],
+                &camera_to_world.t[0],
                 api_state.render_options.transform_start_time,
-                &api_state.render_options.camera_to_world.t[1],
+                &camera_to_world.t[1],
                 api_state.render_options.transform_end_time,
             );
-            if api_state.render_options.camera_name == "perspective" {
+            if camera_name == "perspective" {
                 let camera: Arc<Camera + Send + Sync> = PerspectiveCamera::create(
-                    &api_state.render_options.camera_params,
+                    camera_params,
                     animated_cam_to_world,
                     film,
                     medium_interface.outside,
                 );
                 some_camera = Some(camera);
-            } else if api_state.render_options.camera_name == "orthographic" {
+            } else if camera_name == "orthographic" {
                 let camera: Arc<Camera + Send + Sync> = OrthographicCamera::create(
-                    &api_state.render_options.camera_params,
+                    camera_params,
                     animated_cam_to_world,
                     film,
                     medium_interface.outside,
                 );
                 some_camera = Some(camera);
-            } else if api_state.render_options.camera_name == "realistic" {
+            } else if camera_name == "realistic" {
                 if let Some(ref search_directory) = api_state.search_directory {
                     let camera: Arc<Camera + Send + Sync> = RealisticCamera::create(
-                        &api_state.render_options.camera_params,
+                        camera_params,
                         animated_cam_to_world,
                         film,
                         medium_interface.outside,
@@ -1695,7 +2051,7 @@ pub fn pbrt_cleanup(api_state: &ApiState) {
                     some_camera = Some(camera);
                 } else {
                     let camera: Arc<Camera + Send + Sync> = RealisticCamera::create(
-                        &api_state.render_options.camera_params,
+                        camera_params,
                         animated_cam_to_world,
                         film,
                         medium_interface.outside,
@@ -1704,80 +2060,37 @@ pub fn pbrt_cleanup(api_state: &ApiState) {
                     );
                     some_camera = Some(camera);
                 }
-            } else if api_state.render_options.camera_name == "environment" {
+            } else if camera_name == "environment" {
                 let camera: Arc<Camera + Send + Sync> = EnvironmentCamera::create(
-                    &api_state.render_options.camera_params,
+                    camera_params,
                     animated_cam_to_world,
                     film,
                     medium_interface.outside,
                 );
                 some_camera = Some(camera);
             } else {
-                panic!(
-                    "Camera \"{}\" unknown.",
-                    api_state.render_options.camera_name
-                );
+                panic!("Camera \"{}\" unknown.", camera_name);
             }
             if let Some(camera) = some_camera {
                 // MakeSampler
-                let mut some_sampler: Option<Box<Sampler + Sync + Send>> = None;
-                if api_state.render_options.sampler_name == "lowdiscrepancy"
-                    || api_state.render_options.sampler_name == "02sequence"
-                {
-                    let nsamp: i32 = api_state
-                        .render_options
-                        .sampler_params
-                        .find_one_int("pixelsamples", 16);
-                    let sd: i32 = api_state
-                        .render_options
-                        .sampler_params
-                        .find_one_int("dimensions", 4);
-                    // TODO: if (PbrtOptions.quickRender) nsamp = 1;
-                    let sampler = Box::new(ZeroTwoSequenceSampler::new(nsamp as i64, sd as i64));
-                    some_sampler = Some(sampler);
-                } else if api_state.render_options.sampler_name == "maxmindist" {
-                    println!("TODO: CreateMaxMinDistSampler");
-                } else if api_state.render_options.sampler_name == "halton" {
-                    let nsamp: i32 = api_state
-                        .render_options
-                        .sampler_params
-                        .find_one_int("pixelsamples", 16);
-                    // TODO: if (PbrtOptions.quickRender) nsamp = 1;
-                    let sample_at_center: bool = api_state
-                        .render_options
-                        .integrator_params
-                        .find_one_bool("samplepixelcenter", false);
-                    let sample_bounds: Bounds2i = camera.get_film().get_sample_bounds();
-                    let sampler = Box::new(HaltonSampler::new(
-                        nsamp as i64,
-                        sample_bounds,
-                        sample_at_center,
-                    ));
-                    some_sampler = Some(sampler);
-                } else if api_state.render_options.sampler_name == "sobol" {
-                    let nsamp: i32 = api_state
-                        .render_options
-                        .sampler_params
-                        .find_one_int("pixelsamples", 16);
-                    let sample_bounds: Bounds2i = camera.get_film().get_sample_bounds();
-                    let sampler = Box::new(SobolSampler::new(nsamp as i64, sample_bounds));
-                    some_sampler = Some(sampler);
-                } else if api_state.render_options.sampler_name == "random" {
-                    let nsamp: i32 = api_state
-                        .render_options
-                        .sampler_params
-                        .find_one_int("pixelsamples", 4);
-                    let sampler = Box::new(RandomSampler::new(nsamp as i64));
-                    some_sampler = Some(sampler);
-                } else if api_state.render_options.sampler_name == "stratified" {
-                    println!("TODO: CreateStratifiedSampler");
-                } else {
-                    panic!(
-                        "Sampler \"{}\" unknown.",
-                        api_state.render_options.sampler_name
-                    );
-                }
+                let sample_bounds: Bounds2i = camera.get_film().get_sample_bounds();
+                let some_sampler: Option<Box<Sampler + Sync + Send>> = Some(create_sampler(
+                    &api_state.render_options.sampler_name,
+                    &api_state.render_options.sampler_params,
+                    sample_bounds,
+                ));
                 if let Some(mut sampler) = some_sampler {
+                    let mut metadata: RenderMetadata = RenderMetadata::new(
+                        api_state.scene_name.clone(),
+                        sampler.get_samples_per_pixel(),
+                        String::from(camera_name),
+                    );
+                    for p in &camera_params.floats {
+                        if p.n_values == 1 {
+                            metadata.camera_params.push((p.name.clone(), p.values[0]));
+                        }
+                    }
+                    camera.get_film().set_metadata(metadata);
                     // MakeIntegrator
                     // if let Some(mut sampler) = some_sampler {
                     let mut some_integrator: Option<Box<SamplerIntegrator + Sync + Send>> = None;
@@ -1849,11 +2162,26 @@ pub fn pbrt_cleanup(api_state: &ApiState) {
                             .render_options
                             .integrator_params
                             .find_one_string("lightsamplestrategy", String::from("spatial"));
+                        let intensity_clamp: Float = api_state
+                            .render_options
+                            .integrator_params
+                            .find_one_float("clampindirect", std::f32::INFINITY as Float);
+                        let regularize: bool = api_state
+                            .render_options
+                            .integrator_params
+                            .find_one_bool("regularize", false);
+                        let regularize_min_alpha: Float = api_state
+                            .render_options
+                            .integrator_params
+                            .find_one_float("regularizealpha", REGULARIZE_MIN_ALPHA);
                         let integrator = Box::new(PathIntegrator::new(
                             max_depth as u32,
                             pixel_bounds,
                             rr_threshold,
                             light_strategy,
+                            intensity_clamp,
+                            regularize,
+                            regularize_min_alpha,
                         ));
                         some_integrator = Some(integrator);
                     } else if api_state.render_options.integrator_name == "volpath" {
@@ -1890,11 +2218,21 @@ pub fn pbrt_cleanup(api_state: &ApiState) {
                             .render_options
                             .integrator_params
                             .find_one_string("lightsamplestrategy", String::from("spatial"));
+                        let regularize: bool = api_state
+                            .render_options
+                            .integrator_params
+                            .find_one_bool("regularize", false);
+                        let regularize_min_alpha: Float = api_state
+                            .render_options
+                            .integrator_params
+                            .find_one_float("regularizealpha", REGULARIZE_MIN_ALPHA);
                         let integrator = Box::new(VolPathIntegrator::new(
                             max_depth as u32,
                             pixel_bounds,
                             rr_threshold,
                             light_strategy,
+                            regularize,
+                            regularize_min_alpha,
                         ));
                         some_integrator = Some(integrator);
                     } else if api_state.render_options.integrator_name == "bdpt" {
@@ -2056,75 +2394,59 @@ pub fn pbrt_cleanup(api_state: &ApiState) {
                             // warn if no light sources are defined
                             println!("WARNING: No light sources defined in scene; rendering a black image.",);
                         }
-                        // MakeAccelerator
-                        if api_state.render_options.accelerator_name == "bvh" {
-                            //  CreateBVHAccelerator
-                            let split_method_name: String = api_state
+                        // adaptive sampling (only if an error threshold is given)
+                        let error_threshold: Float = api_state
+                            .render_options
+                            .integrator_params
+                            .find_one_float("errorthreshold", 0.0 as Float);
+                        let mut adaptive: Option<AdaptiveSampling> = None;
+                        if error_threshold > 0.0 as Float {
+                            let spp: i64 = sampler.get_samples_per_pixel();
+                            let mut max_samples: i64 = api_state
                                 .render_options
-                                .accelerator_params
-                                .find_one_string("splitmethod", String::from("sah"));
-                            let split_method;
-                            if split_method_name == "sah" {
-                                split_method = SplitMethod::SAH;
-                            } else if split_method_name == "hlbvh" {
-                                split_method = SplitMethod::HLBVH;
-                            } else if split_method_name == "middle" {
-                                split_method = SplitMethod::Middle;
-                            } else if split_method_name == "equal" {
-                                split_method = SplitMethod::EqualCounts;
-                            } else {
+                                .integrator_params
+                                .find_one_int("maxsamples", spp as i32)
+                                as i64;
+                            if max_samples > spp {
                                 println!(
-                                    "WARNING: BVH split method \"{}\" unknown.  Using \"sah\".",
-                                    split_method_name
+                                    "WARNING: \"maxsamples\" ({}) is limited by the sampler's \
+                                     \"pixelsamples\" ({}).",
+                                    max_samples, spp
                                 );
-                                split_method = SplitMethod::SAH;
+                                max_samples = spp;
                             }
-                            let max_prims_in_node: i32 = api_state
+                            let min_samples: i64 = (api_state
                                 .render_options
-                                .accelerator_params
-                                .find_one_int("maxnodeprims", 4);
-                            let accelerator = Arc::new(BVHAccel::new(
-                                api_state.render_options.primitives.clone(),
-                                max_prims_in_node as usize,
-                                split_method,
+                                .integrator_params
+                                .find_one_int("minsamples", 4)
+                                as i64)
+                                .max(1_i64)
+                                .min(max_samples);
+                            adaptive = Some(AdaptiveSampling::new(
+                                min_samples,
+                                max_samples,
+                                error_threshold,
                             ));
-                            // MakeScene
-                            let scene: Scene = Scene::new(
-                                accelerator.clone(),
-                                api_state.render_options.lights.clone(),
-                            );
-                            // TODO: primitives.erase(primitives.begin(), primitives.end());
-                            // TODO: lights.erase(lights.begin(), lights.end());
-                            let num_threads: u8 = api_state.number_of_threads;
-                            render(
-                                &scene,
-                                &camera.clone(),
+                        }
+                        set_max_sampler_dimensions(
+                            api_state
+                                .render_options
+                                .integrator_params
+                                .find_one_int("maxsamplerdimensions", MAX_SAMPLER_DIMENSIONS as i32)
+                                as i64,
+                        );
+                        let num_threads: u8 = api_state.number_of_threads;
+                        if let Some(ref adaptive) = adaptive {
+                            render_adaptive(
+                                scene,
+                                &camera,
                                 &mut sampler,
                                 &mut integrator,
+                                adaptive,
                                 num_threads,
                             );
-                        } else if api_state.render_options.accelerator_name == "kdtree" {
-                            // println!("TODO: CreateKdTreeAccelerator");
-                            // WARNING: Use BVHAccel for now !!!
-                            let accelerator = Arc::new(BVHAccel::new(
-                                api_state.render_options.primitives.clone(),
-                                4,
-                                SplitMethod::SAH,
-                            ));
-                            // MakeScene
-                            let scene: Scene = Scene::new(
-                                accelerator.clone(),
-                                api_state.render_options.lights.clone(),
-                            );
-                            // TODO: primitives.erase(primitives.begin(), primitives.end());
-                            // TODO: lights.erase(lights.begin(), lights.end());
-                            let num_threads: u8 = api_state.number_of_threads;
-                            render(&scene, &camera, &mut sampler, &mut integrator, num_threads);
                         } else {
-                            panic!(
-                                "Accelerator \"{}\" unknown.",
-                                api_state.render_options.accelerator_name
-                            );
+                            render(scene, &camera, &mut sampler, &mut integrator, num_threads);
                         }
                     } else if let Some(mut integrator) = some_bdpt_integrator {
                         // because we can't call
@@ -2139,82 +2461,8 @@ pub fn pbrt_cleanup(api_state: &ApiState) {
                             // warn if no light sources are defined
                             println!("WARNING: No light sources defined in scene; rendering a black image.",);
                         }
-                        // MakeAccelerator
-                        if api_state.render_options.accelerator_name == "bvh" {
-                            //  CreateBVHAccelerator
-                            let split_method_name: String = api_state
-                                .render_options
-                                .accelerator_params
-                                .find_one_string("splitmethod", String::from("sah"));
-                            let split_method;
-                            if split_method_name == "sah" {
-                                split_method = SplitMethod::SAH;
-                            } else if split_method_name == "hlbvh" {
-                                split_method = SplitMethod::HLBVH;
-                            } else if split_method_name == "middle" {
-                                split_method = SplitMethod::Middle;
-                            } else if split_method_name == "equal" {
-                                split_method = SplitMethod::EqualCounts;
-                            } else {
-                                println!(
-                                    "WARNING: BVH split method \"{}\" unknown.  Using \"sah\".",
-                                    split_method_name
-                                );
-                                split_method = SplitMethod::SAH;
-                            }
-                            let max_prims_in_node: i32 = api_state
-                                .render_options
-                                .accelerator_params
-                                .find_one_int("maxnodeprims", 4);
-                            let accelerator = Arc::new(BVHAccel::new(
-                                api_state.render_options.primitives.clone(),
-                                max_prims_in_node as usize,
-                                split_method,
-                            ));
-                            // MakeScene
-                            let scene: Scene = Scene::new(
-                                accelerator.clone(),
-                                api_state.render_options.lights.clone(),
-                            );
-                            // TODO: primitives.erase(primitives.begin(), primitives.end());
-                            // TODO: lights.erase(lights.begin(), lights.end());
-                            let num_threads: u8 = api_state.number_of_threads;
-                            render_bdpt(
-                                &scene,
-                                &camera,
-                                &mut sampler,
-                                &mut integrator,
-                                num_threads,
-                            );
-                        } else if api_state.render_options.accelerator_name == "kdtree" {
-                            // println!("TODO: CreateKdTreeAccelerator");
-                            // WARNING: Use BVHAccel for now !!!
-                            let accelerator = Arc::new(BVHAccel::new(
-                                api_state.render_options.primitives.clone(),
-                                4,
-                                SplitMethod::SAH,
-                            ));
-                            // MakeScene
-                            let scene: Scene = Scene::new(
-                                accelerator.clone(),
-                                api_state.render_options.lights.clone(),
-                            );
-                            // TODO: primitives.erase(primitives.begin(), primitives.end());
-                            // TODO: lights.erase(lights.begin(), lights.end());
-                            let num_threads: u8 = api_state.number_of_threads;
-                            render_bdpt(
-                                &scene,
-                                &camera,
-                                &mut sampler,
-                                &mut integrator,
-                                num_threads,
-                            );
-                        } else {
-                            panic!(
-                                "Accelerator \"{}\" unknown.",
-                                api_state.render_options.accelerator_name
-                            );
-                        }
+                        let num_threads: u8 = api_state.number_of_threads;
+                        render_bdpt(scene, &camera, &mut sampler, &mut integrator, num_threads);
                     } else if let Some(mut integrator) = some_mlt_integrator {
                         // because we can't call
                         // integrator.render() yet,
@@ -2228,70 +2476,8 @@ pub fn pbrt_cleanup(api_state: &ApiState) {
                             // warn if no light sources are defined
                             println!("WARNING: No light sources defined in scene; rendering a black image.",);
                         }
-                        // MakeAccelerator
-                        if api_state.render_options.accelerator_name == "bvh" {
-                            //  CreateBVHAccelerator
-                            let split_method_name: String = api_state
-                                .render_options
-                                .accelerator_params
-                                .find_one_string("splitmethod", String::from("sah"));
-                            let split_method;
-                            if split_method_name == "sah" {
-                                split_method = SplitMethod::SAH;
-                            } else if split_method_name == "hlbvh" {
-                                split_method = SplitMethod::HLBVH;
-                            } else if split_method_name == "middle" {
-                                split_method = SplitMethod::Middle;
-                            } else if split_method_name == "equal" {
-                                split_method = SplitMethod::EqualCounts;
-                            } else {
-                                println!(
-                                    "WARNING: BVH split method \"{}\" unknown.  Using \"sah\".",
-                                    split_method_name
-                                );
-                                split_method = SplitMethod::SAH;
-                            }
-                            let max_prims_in_node: i32 = api_state
-                                .render_options
-                                .accelerator_params
-                                .find_one_int("maxnodeprims", 4);
-                            let accelerator = Arc::new(BVHAccel::new(
-                                api_state.render_options.primitives.clone(),
-                                max_prims_in_node as usize,
-                                split_method,
-                            ));
-                            // MakeScene
-                            let scene: Scene = Scene::new(
-                                accelerator.clone(),
-                                api_state.render_options.lights.clone(),
-                            );
-                            // TODO: primitives.erase(primitives.begin(), primitives.end());
-                            // TODO: lights.erase(lights.begin(), lights.end());
-                            let num_threads: u8 = api_state.number_of_threads;
-                            render_mlt(&scene, &camera, &mut sampler, &mut integrator, num_threads);
-                        } else if api_state.render_options.accelerator_name == "kdtree" {
-                            // println!("TODO: CreateKdTreeAccelerator");
-                            // WARNING: Use BVHAccel for now !!!
-                            let accelerator = Arc::new(BVHAccel::new(
-                                api_state.render_options.primitives.clone(),
-                                4,
-                                SplitMethod::SAH,
-                            ));
-                            // MakeScene
-                            let scene: Scene = Scene::new(
-                                accelerator.clone(),
-                                api_state.render_options.lights.clone(),
-                            );
-                            // TODO: primitives.erase(primitives.begin(), primitives.end());
-                            // TODO: lights.erase(lights.begin(), lights.end());
-                            let num_threads: u8 = api_state.number_of_threads;
-                            render_mlt(&scene, &camera, &mut sampler, &mut integrator, num_threads);
-                        } else {
-                            panic!(
-                                "Accelerator \"{}\" unknown.",
-                                api_state.render_options.accelerator_name
-                            );
-                        }
+                        let num_threads: u8 = api_state.number_of_threads;
+                        render_mlt(scene, &camera, &mut sampler, &mut integrator, num_threads);
                     } else if let Some(mut integrator) = some_sppm_integrator {
                         // because we can't call
                         // integrator.render() yet,
@@ -2305,82 +2491,8 @@ pub fn pbrt_cleanup(api_state: &ApiState) {
                             // warn if no light sources are defined
                             println!("WARNING: No light sources defined in scene; rendering a black image.",);
                         }
-                        // MakeAccelerator
-                        if api_state.render_options.accelerator_name == "bvh" {
-                            //  CreateBVHAccelerator
-                            let split_method_name: String = api_state
-                                .render_options
-                                .accelerator_params
-                                .find_one_string("splitmethod", String::from("sah"));
-                            let split_method;
-                            if split_method_name == "sah" {
-                                split_method = SplitMethod::SAH;
-                            } else if split_method_name == "hlbvh" {
-                                split_method = SplitMethod::HLBVH;
-                            } else if split_method_name == "middle" {
-                                split_method = SplitMethod::Middle;
-                            } else if split_method_name == "equal" {
-                                split_method = SplitMethod::EqualCounts;
-                            } else {
-                                println!(
-                                    "WARNING: BVH split method \"{}\" unknown.  Using \"sah\".",
-                                    split_method_name
-                                );
-                                split_method = SplitMethod::SAH;
-                            }
-                            let max_prims_in_node: i32 = api_state
-                                .render_options
-                                .accelerator_params
-                                .find_one_int("maxnodeprims", 4);
-                            let accelerator = Arc::new(BVHAccel::new(
-                                api_state.render_options.primitives.clone(),
-                                max_prims_in_node as usize,
-                                split_method,
-                            ));
-                            // MakeScene
-                            let scene: Scene = Scene::new(
-                                accelerator.clone(),
-                                api_state.render_options.lights.clone(),
-                            );
-                            // TODO: primitives.erase(primitives.begin(), primitives.end());
-                            // TODO: lights.erase(lights.begin(), lights.end());
-                            let num_threads: u8 = api_state.number_of_threads;
-                            render_sppm(
-                                &scene,
-                                &camera,
-                                &mut sampler,
-                                &mut integrator,
-                                num_threads,
-                            );
-                        } else if api_state.render_options.accelerator_name == "kdtree" {
-                            // println!("TODO: CreateKdTreeAccelerator");
-                            // WARNING: Use BVHAccel for now !!!
-                            let accelerator = Arc::new(BVHAccel::new(
-                                api_state.render_options.primitives.clone(),
-                                4,
-                                SplitMethod::SAH,
-                            ));
-                            // MakeScene
-                            let scene: Scene = Scene::new(
-                                accelerator.clone(),
-                                api_state.render_options.lights.clone(),
-                            );
-                            // TODO: primitives.erase(primitives.begin(), primitives.end());
-                            // TODO: lights.erase(lights.begin(), lights.end());
-                            let num_threads: u8 = api_state.number_of_threads;
-                            render_sppm(
-                                &scene,
-                                &camera,
-                                &mut sampler,
-                                &mut integrator,
-                                num_threads,
-                            );
-                        } else {
-                            panic!(
-                                "Accelerator \"{}\" unknown.",
-                                api_state.render_options.accelerator_name
-                            );
-                        }
+                        let num_threads: u8 = api_state.number_of_threads;
+                        render_sppm(scene, &camera, &mut sampler, &mut integrator, num_threads);
                     } else {
                         panic!("Unable to create integrator.");
                     }
@@ -2405,36 +2517,30 @@ pub fn pbrt_translate(api_state: &mut ApiState, dx: Float, dy: Float, dz: Float)
         y: dy,
         z: dz,
     });
-    if api_state.active_transform_bits & 1_u8 > 0_u8 {
-        // 0x?1
+    if api_state.active_transform_bits & START_TRANSFORM_BITS > 0_u8 {
         api_state.cur_transform.t[0] = api_state.cur_transform.t[0] * translate;
     }
-    if api_state.active_transform_bits & 2_u8 > 0_u8 {
-        // 0x1?
+    if api_state.active_transform_bits & END_TRANSFORM_BITS > 0_u8 {
         api_state.cur_transform.t[1] = api_state.cur_transform.t[1] * translate;
     }
 }
 
 pub fn pbrt_transform(api_state: &mut ApiState, tr: &Transform) {
     // println!("{:?}", tr);
-    if api_state.active_transform_bits & 1_u8 > 0_u8 {
-        // 0x?1
+    if api_state.active_transform_bits & START_TRANSFORM_BITS > 0_u8 {
         api_state.cur_transform.t[0] = *tr;
     }
-    if api_state.active_transform_bits & 2_u8 > 0_u8 {
-        // 0x1?
+    if api_state.active_transform_bits & END_TRANSFORM_BITS > 0_u8 {
         api_state.cur_transform.t[1] = *tr;
     }
 }
 
 pub fn pbrt_concat_transform(api_state: &mut ApiState, tr: &Transform) {
     // println!("Concat{:?}", tr);
-    if api_state.active_transform_bits & 1_u8 > 0_u8 {
-        // 0x?1
+    if api_state.active_transform_bits & START_TRANSFORM_BITS > 0_u8 {
         api_state.cur_transform.t[0] = api_state.cur_transform.t[0] * *tr;
     }
-    if api_state.active_transform_bits & 2_u8 > 0_u8 {
-        // 0x1?
+    if api_state.active_transform_bits & END_TRANSFORM_BITS > 0_u8 {
         api_state.cur_transform.t[1] = api_state.cur_transform.t[1] * *tr;
     }
 }
@@ -2449,12 +2555,10 @@ pub fn pbrt_rotate(api_state: &mut ApiState, angle: Float, dx: Float, dy: Float,
             z: dz,
         },
     );
-    if api_state.active_transform_bits & 1_u8 > 0_u8 {
-        // 0x?1
+    if api_state.active_transform_bits & START_TRANSFORM_BITS > 0_u8 {
         api_state.cur_transform.t[0] = api_state.cur_transform.t[0] * rotate;
     }
-    if api_state.active_transform_bits & 2_u8 > 0_u8 {
-        // 0x1?
+    if api_state.active_transform_bits & END_TRANSFORM_BITS > 0_u8 {
         api_state.cur_transform.t[1] = api_state.cur_transform.t[1] * rotate;
     }
 }
@@ -2462,12 +2566,10 @@ pub fn pbrt_rotate(api_state: &mut ApiState, angle: Float, dx: Float, dy: Float,
 pub fn pbrt_scale(api_state: &mut ApiState, sx: Float, sy: Float, sz: Float) {
     // println!("Scale {} {} {}", sx, sy, sz);
     let scale: Transform = Transform::scale(sx, sy, sz);
-    if api_state.active_transform_bits & 1_u8 > 0_u8 {
-        // 0x?1
+    if api_state.active_transform_bits & START_TRANSFORM_BITS > 0_u8 {
         api_state.cur_transform.t[0] = api_state.cur_transform.t[0] * scale;
     }
-    if api_state.active_transform_bits & 2_u8 > 0_u8 {
-        // 0x1?
+    if api_state.active_transform_bits & END_TRANSFORM_BITS > 0_u8 {
         api_state.cur_transform.t[1] = api_state.cur_transform.t[1] * scale;
     }
 }
@@ -2504,12 +2606,10 @@ pub fn pbrt_look_at(
         z: uz,
     };
     let look_at: Transform = Transform::look_at(&pos, &look, &up);
-    if api_state.active_transform_bits & 1_u8 > 0_u8 {
-        // 0x?1
+    if api_state.active_transform_bits & START_TRANSFORM_BITS > 0_u8 {
         api_state.cur_transform.t[0] = api_state.cur_transform.t[0] * look_at;
     }
-    if api_state.active_transform_bits & 2_u8 > 0_u8 {
-        // 0x1?
+    if api_state.active_transform_bits & END_TRANSFORM_BITS > 0_u8 {
         api_state.cur_transform.t[1] = api_state.cur_transform.t[1] * look_at;
     }
 }
@@ -2536,17 +2636,17 @@ pub fn pbrt_coord_sys_transform(api_state: &mut ApiState, params: ParamSet) {
 
 pub fn pbrt_active_transform_all(api_state: &mut ApiState) {
     // println!("ActiveTransform All");
-    api_state.active_transform_bits = 3_u8 // 0x11
+    api_state.active_transform_bits = ALL_TRANSFORMS_BITS;
 }
 
 pub fn pbrt_active_transform_end_time(api_state: &mut ApiState) {
     // println!("ActiveTransform EndTime");
-    api_state.active_transform_bits = 2_u8 // 0x10
+    api_state.active_transform_bits = END_TRANSFORM_BITS;
 }
 
 pub fn pbrt_active_transform_start_time(api_state: &mut ApiState) {
     // println!("ActiveTransform StartTime");
-    api_state.active_transform_bits = 1_u8 // 0x01
+    api_state.active_transform_bits = START_TRANSFORM_BITS;
 }
 
 pub fn pbrt_transform_times(api_state: &mut ApiState, start: Float, end: Float) {
@@ -2623,6 +2723,28 @@ pub fn pbrt_camera(api_state: &mut ApiState, params: ParamSet) {
         .copy_from(&api_state.param_set);
 }
 
+/// Like **pbrt_camera()**, but keeps the main camera and registers
+/// another one (using the current transform), which gets rendered
+/// with the same scene and settings into separate images.
+pub fn pbrt_add_camera(api_state: &mut ApiState, params: ParamSet) {
+    let mut camera_params: ParamSet = ParamSet::default();
+    camera_params.copy_from(&params);
+    let camera_to_world: TransformSet = TransformSet {
+        t: [
+            Transform::inverse(&api_state.cur_transform.t[0]),
+            Transform::inverse(&api_state.cur_transform.t[1]),
+        ],
+    };
+    api_state
+        .render_options
+        .additional_cameras
+        .push(AdditionalCamera {
+            name: params.name.clone(),
+            params: camera_params,
+            camera_to_world: camera_to_world,
+        });
+}
+
 pub fn pbrt_make_named_medium(api_state: &mut ApiState, params: ParamSet) {
     // println!("MakeNamedMedium \"{}\"", params.name);
     // print_params(&api_state.param_set);
@@ -2645,7 +2767,7 @@ pub fn pbrt_world_begin(api_state: &mut ApiState) {
     // println!("WorldBegin");
     api_state.cur_transform.t[0] = Transform::default();
     api_state.cur_transform.t[1] = Transform::default();
-    api_state.active_transform_bits = 3_u8; // 0x11
+    api_state.active_transform_bits = ALL_TRANSFORMS_BITS;
     api_state.named_coordinate_systems.insert(
         "world",
         TransformSet {
@@ -2778,7 +2900,10 @@ pub fn pbrt_make_named_material(
         .get(api_state.param_set.name.as_str())
     {
         Some(_named_material) => {
-            println!("Named material \"{}\" redefined", mat_type);
+            println!(
+                "WARNING: Named material \"{}\" redefined.",
+                api_state.param_set.name
+            );
         }
         None => {}
     }
@@ -2815,6 +2940,7 @@ pub fn pbrt_shape(api_state: &mut ApiState, bsdf_state: &mut BsdfState, params:
     // println!("Shape \"{}\"", params.name);
     // print_params(&params);
     api_state.param_set = params;
+    let visibility: u8 = get_visibility_flags(&api_state.param_set);
     // collect area lights
     let mut prims: Vec<Arc<Primitive + Send + Sync>> = Vec::new();
     let mut area_lights: Vec<Arc<Light + Send + Sync>> = Vec::new();
@@ -2854,22 +2980,47 @@ pub fn pbrt_shape(api_state: &mut ApiState, bsdf_state: &mut BsdfState, params:
                     .find_one_bool("twosided", false);
                 // TODO: if (PbrtOptions.quickRender) nSamples = std::max(1, nSamples / 4);
                 let l_emit: Spectrum = l * sc;
+                // optional texture modulating the emission
+                let mut l_emit_texture: Option<Arc<Texture<Spectrum> + Send + Sync>> = None;
+                let l_tex_name: String = api_state
+                    .graphics_state
+                    .area_light_params
+                    .find_texture("Ltexture");
+                if l_tex_name != String::new() {
+                    match api_state
+                        .graphics_state
+                        .spectrum_textures
+                        .get(l_tex_name.as_str())
+                    {
+                        Some(spectrum_texture) => {
+                            l_emit_texture = Some(spectrum_texture.clone());
+                        }
+                        None => {
+                            println!(
+                                "WARNING: Couldn't find spectrum texture \"{}\" for \"Ltexture\"",
+                                l_tex_name
+                            );
+                        }
+                    }
+                }
                 let area_light: Arc<DiffuseAreaLight> = Arc::new(DiffuseAreaLight::new(
                     &light_to_world,
                     &mi,
                     &l_emit,
+                    l_emit_texture,
                     n_samples,
                     shape.clone(),
                     two_sided,
                 ));
                 area_lights.push(area_light.clone());
-                let geo_prim = Arc::new(GeometricPrimitive::new(
+                let mut geo_prim: GeometricPrimitive = GeometricPrimitive::new(
                     shape.clone(),
                     material.clone(),
                     Some(area_light.clone()),
                     Some(Arc::new(mi.clone())),
-                ));
-                prims.push(geo_prim.clone());
+                );
+                geo_prim.visibility = visibility;
+                prims.push(Arc::new(geo_prim));
             }
         }
     } else {
@@ -2881,13 +3032,14 @@ pub fn pbrt_shape(api_state: &mut ApiState, bsdf_state: &mut BsdfState, params:
         for i in 0..shapes.len() {
             let shape = &shapes[i];
             let material = &materials[i];
-            let geo_prim = Arc::new(GeometricPrimitive::new(
+            let mut geo_prim: GeometricPrimitive = GeometricPrimitive::new(
                 shape.clone(),
                 material.clone(),
                 None,
                 Some(Arc::new(mi.clone())),
-            ));
-            prims.push(geo_prim.clone());
+            );
+            geo_prim.visibility = visibility;
+            prims.push(Arc::new(geo_prim));
         }
         // animated?
         if api_state.cur_transform.is_animated() {
@@ -3052,3 +3204,281 @@ pub fn pbrt_object_instance(api_state: &mut ApiState, params: ParamSet) {
         return;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::geometry::Ray;
+    use core::interaction::SurfaceInteraction;
+    use core::material::TransportMode;
+    use core::reflection::{Bsdf, BxdfType};
+    use std::f32::consts::PI;
+
+    #[test]
+    fn cameras_share_one_scene() {
+        let mut api_state: ApiState = ApiState::default();
+        let sphere: Arc<Shape + Send + Sync> = Arc::new(Sphere::new(
+            Transform::default(),
+            Transform::default(),
+            false,
+            1.0 as Float,
+            -1.0 as Float,
+            1.0 as Float,
+            360.0 as Float,
+        ));
+        api_state
+            .render_options
+            .primitives
+            .push(Arc::new(GeometricPrimitive::new(sphere, None, None, None)));
+        // a second camera, moved back along z
+        pbrt_translate(&mut api_state, 0.0, 0.0, -5.0);
+        let mut params: ParamSet = ParamSet::default();
+        params.name = String::from("orthographic");
+        pbrt_add_camera(&mut api_state, params);
+        let mut calls: Vec<(Arc<BVHAccel>, String, String, Float)> = Vec::new();
+        render_all_cameras(&api_state, |scene, name, _params, camera_to_world, suffix| {
+            calls.push((
+                scene.aggregate.clone(),
+                String::from(name),
+                String::from(suffix),
+                camera_to_world.t[0].m.m[2][3],
+            ));
+        });
+        assert_eq!(calls.len(), 2_usize);
+        // the BVH was built once and shared
+        assert!(Arc::ptr_eq(&calls[0].0, &calls[1].0));
+        assert_eq!((calls[0].1.as_str(), calls[0].2.as_str()), ("perspective", ""));
+        assert_eq!((calls[1].1.as_str(), calls[1].2.as_str()), ("orthographic", "_1"));
+        assert_eq!(calls[0].3, 0.0 as Float);
+        assert_eq!(calls[1].3, 5.0 as Float);
+    }
+
+    #[test]
+    fn start_and_end_time_transforms_are_interpolated() {
+        let mut api_state: ApiState = ApiState::default();
+        pbrt_transform_times(&mut api_state, 0.0 as Float, 1.0 as Float);
+        pbrt_active_transform_start_time(&mut api_state);
+        pbrt_translate(&mut api_state, 1.0, 0.0, 0.0);
+        pbrt_active_transform_end_time(&mut api_state);
+        pbrt_translate(&mut api_state, 3.0, 0.0, 0.0);
+        pbrt_active_transform_all(&mut api_state);
+        // applies to both transforms again
+        pbrt_translate(&mut api_state, 0.0, 2.0, 0.0);
+        let transforms: TransformSet = api_state.cur_transform;
+        assert!(transforms.is_animated());
+        let animated: AnimatedTransform = AnimatedTransform::new(
+            &transforms.t[0],
+            api_state.render_options.transform_start_time,
+            &transforms.t[1],
+            api_state.render_options.transform_end_time,
+        );
+        let origin: Point3f = Point3f::default();
+        for &(time, x) in &[(0.0, 1.0), (0.5, 2.0), (1.0, 3.0)] {
+            let p: Point3f = animated.transform_point(time as Float, &origin);
+            assert!((p.x - x as Float).abs() < 1e-5 as Float, "{} at {}", p.x, time);
+            assert!((p.y - 2.0 as Float).abs() < 1e-5 as Float, "{} at {}", p.y, time);
+        }
+    }
+
+    #[test]
+    fn create_sampler_falls_back_to_halton() {
+        let bounds: Bounds2i = Bounds2i {
+            p_min: Point2i { x: 0, y: 0 },
+            p_max: Point2i { x: 16, y: 16 },
+        };
+        let mut params: ParamSet = ParamSet::default();
+        params.add_int(String::from("pixelsamples"), 8);
+        let mut halton: Box<Sampler + Sync + Send> = create_sampler("halton", &params, bounds);
+        let mut unknown: Box<Sampler + Sync + Send> = create_sampler("unknown", &params, bounds);
+        assert_eq!(halton.get_samples_per_pixel(), 8_i64);
+        assert_eq!(unknown.get_samples_per_pixel(), 8_i64);
+        let pixel: Point2i = Point2i { x: 3, y: 5 };
+        let samples: Vec<Point2f> = halton.fill_pixel_2d(&pixel);
+        assert_eq!(samples.len(), 8_usize);
+        for p in &samples {
+            assert!(p.x >= 0.0 as Float && p.x < 1.0 as Float);
+            assert!(p.y >= 0.0 as Float && p.y < 1.0 as Float);
+        }
+        assert_eq!(unknown.fill_pixel_2d(&pixel), samples);
+    }
+
+    #[test]
+    fn create_sampler_reads_any_permutation_seed() {
+        let bounds: Bounds2i = Bounds2i {
+            p_min: Point2i { x: 0, y: 0 },
+            p_max: Point2i { x: 16, y: 16 },
+        };
+        let pixel: Point2i = Point2i { x: 3, y: 5 };
+        let samples = |sampler: &mut Sampler| -> Vec<Float> {
+            sampler.start_pixel(&pixel);
+            (0..8).map(|_| sampler.get_1d()).collect()
+        };
+        for seed in &[0_i32, 7_i32, -1_i32] {
+            let mut params: ParamSet = ParamSet::default();
+            params.add_int(String::from("pixelsamples"), 4);
+            params.add_int(String::from("permutationseed"), *seed);
+            let mut created: Box<Sampler + Sync + Send> = create_sampler("halton", &params, bounds);
+            let mut expected: HaltonSampler = HaltonSampler::with_permutations(
+                *seed as u32 as u64,
+                4_i64,
+                bounds,
+                false,
+                ARRAY_START_DIM,
+            );
+            assert_eq!(samples(&mut *created), samples(&mut expected));
+        }
+    }
+
+    #[test]
+    fn shapes_share_their_named_material() {
+        let (mut api_state, mut bsdf_state) = pbrt_init(1_u8);
+        let mut params: ParamSet = ParamSet::default();
+        params.name = String::from("shiny");
+        params.add_string(String::from("type"), String::from("uber"));
+        pbrt_make_named_material(&mut api_state, &mut bsdf_state, params);
+        let mut params: ParamSet = ParamSet::default();
+        params.name = String::from("shiny");
+        pbrt_named_material(&mut api_state, params);
+        for _ in 0..2 {
+            let mut params: ParamSet = ParamSet::default();
+            params.name = String::from("sphere");
+            pbrt_shape(&mut api_state, &mut bsdf_state, params);
+        }
+        // an anonymous material is created per shape
+        let mut params: ParamSet = ParamSet::default();
+        params.name = String::from("uber");
+        pbrt_material(&mut api_state, params);
+        let mut params: ParamSet = ParamSet::default();
+        params.name = String::from("sphere");
+        pbrt_shape(&mut api_state, &mut bsdf_state, params);
+        let materials: Vec<Arc<Material + Send + Sync>> = api_state
+            .render_options
+            .primitives
+            .iter()
+            .map(|prim| prim.get_material().unwrap())
+            .collect();
+        assert_eq!(materials.len(), 3_usize);
+        assert!(Arc::ptr_eq(&materials[0], &materials[1]));
+        assert!(!Arc::ptr_eq(&materials[0], &materials[2]));
+    }
+
+    /// Creates a unit sphere with the current material and returns the
+    /// reflectance (BSDF times pi) of the point facing -z.
+    fn reflectance(api_state: &mut ApiState, bsdf_state: &mut BsdfState) -> Spectrum {
+        let mut params: ParamSet = ParamSet::default();
+        params.name = String::from("sphere");
+        pbrt_shape(api_state, bsdf_state, params);
+        let prim: Arc<Primitive + Send + Sync> = api_state.render_options.primitives.pop().unwrap();
+        assert!(prim.get_material().is_some());
+        let mut ray: Ray = Ray {
+            o: Point3f {
+                x: 0.0,
+                y: 0.0,
+                z: -5.0,
+            },
+            d: Vector3f {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+            t_max: std::f32::INFINITY,
+            ..Ray::default()
+        };
+        let mut si: SurfaceInteraction = prim.intersect(&mut ray).unwrap();
+        si.compute_scattering_functions(&ray, false, TransportMode::Radiance);
+        let bsdf: Arc<Bsdf> = si.bsdf.clone().expect("no BSDF");
+        let wi: Vector3f = Vector3f {
+            x: 0.3,
+            y: 0.0,
+            z: -1.0,
+        }
+        .normalize();
+        bsdf.f(&-ray.d, &wi, BxdfType::BsdfAll as u8) * PI
+    }
+
+    fn assert_rgb(s: Spectrum, rgb: [Float; 3]) {
+        for c in 0..3 {
+            assert!(
+                (s[c] - rgb[c]).abs() < 1e-4 as Float,
+                "{:?} != {:?}",
+                s,
+                rgb
+            );
+        }
+    }
+
+    #[test]
+    fn missing_materials_fall_back_to_the_default_matte() {
+        let (mut api_state, mut bsdf_state) = pbrt_init(1_u8);
+        let gray: [Float; 3] = [0.5, 0.5, 0.5];
+        let magenta: [Float; 3] = [1.0, 0.0, 1.0];
+        // no Material statement at all
+        assert_rgb(reflectance(&mut api_state, &mut bsdf_state), gray);
+        // unknown materials and textures don't panic
+        let mut params: ParamSet = ParamSet::default();
+        params.name = String::from("unobtainium");
+        pbrt_material(&mut api_state, params);
+        assert_rgb(reflectance(&mut api_state, &mut bsdf_state), gray);
+        let mut params: ParamSet = ParamSet::default();
+        params.name = String::from("matte");
+        params.add_texture(String::from("Kd"), String::from("missing"));
+        pbrt_material(&mut api_state, params);
+        assert_rgb(reflectance(&mut api_state, &mut bsdf_state), gray);
+        // both stand out with the error material
+        api_state.error_material = true;
+        assert_rgb(reflectance(&mut api_state, &mut bsdf_state), magenta);
+        let mut params: ParamSet = ParamSet::default();
+        params.name = String::from("unobtainium");
+        pbrt_material(&mut api_state, params);
+        assert_rgb(reflectance(&mut api_state, &mut bsdf_state), magenta);
+    }
+
+    /// Radiance of an infinite light (without an environment map)
+    /// created with the given *exposure* and *scale*.
+    fn infinite_light_le(exposure: Float, scale: Float) -> Float {
+        let (mut api_state, _bsdf_state) = pbrt_init(1_u8);
+        let mut params: ParamSet = ParamSet::default();
+        params.name = String::from("infinite");
+        params.add_float(String::from("exposure"), exposure);
+        params.add_rgb_spectrum(String::from("scale"), Spectrum::new(scale));
+        pbrt_light_source(&mut api_state, params);
+        let mut ray: Ray = Ray::default();
+        ray.d.z = 1.0 as Float;
+        api_state.render_options.lights[0].le(&mut ray)[0]
+    }
+
+    /// Value of a white image texture created with the given
+    /// *exposure* and *scale*.
+    fn image_texture_value(exposure: Float, scale: Float) -> Float {
+        let path = std::env::temp_dir().join("rs_pbrt_exposure.png");
+        image::save_buffer(&path, &[255_u8, 255, 255], 1, 1, image::RGB(8)).unwrap();
+        let (mut api_state, _bsdf_state) = pbrt_init(1_u8);
+        let mut params: ParamSet = ParamSet::default();
+        params.name = String::from("white");
+        params.tex_type = String::from("spectrum");
+        params.tex_name = String::from("imagemap");
+        params.add_string(
+            String::from("filename"),
+            String::from(path.to_str().unwrap()),
+        );
+        params.add_float(String::from("exposure"), exposure);
+        params.add_float(String::from("scale"), scale);
+        pbrt_texture(&mut api_state, params);
+        std::fs::remove_file(&path).unwrap();
+        let mut si: SurfaceInteraction = SurfaceInteraction::default();
+        si.uv = Point2f { x: 0.5, y: 0.5 };
+        api_state.graphics_state.spectrum_textures["white"].evaluate(&si)[0]
+    }
+
+    #[test]
+    fn exposure_and_scale_multiply() {
+        for value in &[infinite_light_le, image_texture_value] {
+            let base: Float = value(0.0 as Float, 1.0 as Float);
+            assert!((base - 1.0 as Float).abs() < 1e-4 as Float, "{}", base);
+            // one stop doubles the radiance, minus one halves it
+            assert!((value(1.0 as Float, 1.0 as Float) - 2.0 as Float).abs() < 1e-4 as Float);
+            assert!((value(-1.0 as Float, 1.0 as Float) - 0.5 as Float).abs() < 1e-4 as Float);
+            assert!((value(1.0 as Float, 3.0 as Float) - 6.0 as Float).abs() < 1e-4 as Float);
+        }
+    }
+}
@@ -19,6 +19,7 @@ use core::pbrt::clamp_t;
 use core::pbrt::INV_4_PI;
 use core::pbrt::{Float, Spectrum};
 use core::primitive::Primitive;
+use core::profile::{Prof, ProfilePhase};
 use core::reflection::{cos_theta, fr_dielectric};
 use core::reflection::{Bsdf, Bxdf, BxdfType};
 use core::scene::Scene;
@@ -42,7 +43,12 @@ pub trait Bssrdf {
 pub trait SeparableBssrdf {
     fn sw(&self, w: &Vector3f) -> Spectrum;
     fn sp(&self, pi: &SurfaceInteraction) -> Spectrum;
+    /// Combined PDF of all three projection axes and color channels
+    /// (one-sample MIS) for the exit point *pi*.
     fn pdf_sp(&self, pi: &SurfaceInteraction) -> Float;
+    /// Samples an exit point *pi* by tracing a probe ray segment along
+    /// a randomly chosen axis and picking one of the hits with the same
+    /// material.
     fn sample_sp(
         &self,
         scene: &Scene,
@@ -118,7 +124,7 @@ impl TabulatedBssrdf {
 
 impl Bssrdf for TabulatedBssrdf {
     fn s(&self, pi: &SurfaceInteraction, wi: &Vector3f) -> Spectrum {
-        // ProfilePhase pp(Prof::BSSRDFEvaluation);
+        let _p = ProfilePhase::new(Prof::BSSRDFEvaluation);
         let ft: Float = fr_dielectric(cos_theta(&self.po_wo), 1.0 as Float, self.eta);
         self.sp(pi) * self.sw(wi) * (1.0 as Float - ft)
     }
@@ -134,7 +140,7 @@ impl Bssrdf for TabulatedBssrdf {
         u2: &Point2f,
         pdf: &mut Float,
     ) -> (Spectrum, Option<SurfaceInteraction>) {
-        // ProfilePhase pp(Prof::BSSRDFSampling);
+        let _p = ProfilePhase::new(Prof::BSSRDFSampling);
         let mut si: SurfaceInteraction = SurfaceInteraction::default();
         let sp: Spectrum = self.sample_sp(scene, u1, u2, &mut si, pdf);
         if !sp.is_black() {
@@ -201,7 +207,7 @@ impl SeparableBssrdf for TabulatedBssrdf {
         pi: &mut SurfaceInteraction,
         pdf: &mut Float,
     ) -> Spectrum {
-        // ProfilePhase pp(Prof::BSSRDFEvaluation);
+        let _p = ProfilePhase::new(Prof::BSSRDFEvaluation);
         let mut u1: Float = u1; // shadowing input parameter
 
         // choose projection axis for BSSRDF sampling
@@ -259,7 +265,6 @@ impl SeparableBssrdf for TabulatedBssrdf {
         // accumulate chain of intersections along ray
         // IntersectionChain *ptr = chain;
         let mut chain: Vec<SurfaceInteraction> = Vec::new();
-        let mut n_found: usize = 0;
         loop {
             let mut r: Ray = base.spawn_ray_to_pnt(&p_target);
             if r.d == Vector3f::default() {
@@ -283,7 +288,6 @@ impl SeparableBssrdf for TabulatedBssrdf {
                             //         ptr->next = next;
                             //         ptr = next;
                             chain.push(si.clone());
-                            n_found += 1;
                         }
                     }
                 }
@@ -293,6 +297,7 @@ impl SeparableBssrdf for TabulatedBssrdf {
         }
 
         // randomly choose one of several intersections during BSSRDF sampling
+        let n_found: usize = chain.len();
         if n_found == 0_usize {
             return Spectrum::default();
         }
@@ -675,3 +680,166 @@ pub fn compute_beam_diffusion_bssrdf(g: Float, eta: Float, t: &mut BssrdfTable)
     }
     // }, t.n_rho_samples);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use accelerators::bvh::{BVHAccel, SplitMethod};
+    use core::primitive::GeometricPrimitive;
+    use core::shape::Shape;
+    use core::transform::Transform;
+    use materials::matte::MatteMaterial;
+    use shapes::disk::Disk;
+    use textures::constant::ConstantTexture;
+
+    fn matte() -> Arc<Material + Send + Sync> {
+        Arc::new(MatteMaterial::new(
+            Arc::new(ConstantTexture {
+                value: Spectrum::new(0.5 as Float),
+            }),
+            Arc::new(ConstantTexture {
+                value: 0.0 as Float,
+            }),
+            None,
+        ))
+    }
+
+    /// A slab between z = -0.5 and z = 0 made of *material*, with a
+    /// sheet of another material in the middle (at z = -0.25).
+    fn slab(material: &Arc<Material + Send + Sync>) -> Scene {
+        let mut primitives: Vec<Arc<Primitive + Sync + Send>> = Vec::new();
+        for &(height, ref material) in [
+            (0.0 as Float, material.clone()),
+            (-0.25 as Float, matte()),
+            (-0.5 as Float, material.clone()),
+        ]
+        .iter()
+        {
+            let disk: Arc<Shape + Send + Sync> = Arc::new(Disk::new(
+                Transform::default(),
+                Transform::default(),
+                height < 0.0 as Float,
+                height,
+                100.0 as Float,
+                0.0 as Float,
+                360.0 as Float,
+            ));
+            primitives.push(Arc::new(GeometricPrimitive::new(
+                disk,
+                Some(material.clone()),
+                None,
+                None,
+            )));
+        }
+        let bvh: Arc<BVHAccel> = Arc::new(BVHAccel::new(primitives, 4, SplitMethod::SAH));
+        Scene::new(bvh, Vec::new())
+    }
+
+    /// Enters the top of the slab (off the disk center) and returns a
+    /// **TabulatedBssrdf** for that entry point.
+    fn entry_bssrdf(scene: &Scene, material: &Arc<Material + Send + Sync>) -> TabulatedBssrdf {
+        let mut ray: Ray = Ray {
+            o: Point3f {
+                x: 0.5,
+                y: 0.0,
+                z: 1.0,
+            },
+            d: Vector3f {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            t_max: std::f32::INFINITY,
+            ..Ray::default()
+        };
+        let po: SurfaceInteraction = scene.intersect(&mut ray).unwrap();
+        let mut table: BssrdfTable = BssrdfTable::new(100, 64);
+        compute_beam_diffusion_bssrdf(0.0 as Float, 1.33 as Float, &mut table);
+        TabulatedBssrdf::new(
+            &po,
+            Some(material.clone()),
+            TransportMode::Radiance,
+            1.33 as Float,
+            &Spectrum::new(0.1 as Float),
+            &Spectrum::new(1.0 as Float),
+            Arc::new(table),
+        )
+    }
+
+    #[test]
+    fn probe_ray_chains_hits_with_the_same_material() {
+        let material: Arc<Material + Send + Sync> = matte();
+        let scene: Scene = slab(&material);
+        let bssrdf: TabulatedBssrdf = entry_bssrdf(&scene, &material);
+        let u2: Point2f = Point2f { x: 0.3, y: 0.0 };
+        // the probe along the normal has to reach the bottom of the slab
+        let r: Float = bssrdf.sample_sr(0, u2.x);
+        let r_max: Float = bssrdf.sample_sr(0, 0.999 as Float);
+        assert!(r_max * r_max - r * r > 0.25 as Float, "{} {}", r, r_max);
+        // axis along the normal, first channel, then pick one of the
+        // two hits (bottom first, the sheet in between is skipped)
+        for &(u1, z) in [(0.05 as Float, -0.5 as Float), (0.1, 0.0)].iter() {
+            let mut pi: SurfaceInteraction = SurfaceInteraction::default();
+            let mut pdf: Float = 0.0 as Float;
+            let sp: Spectrum = bssrdf.sample_sp(&scene, u1, &u2, &mut pi, &mut pdf);
+            assert!(!sp.is_black());
+            assert!((pi.p.z - z).abs() < 1e-4 as Float, "{:?} for {}", pi.p, u1);
+            assert!((pnt3_distance(&pi.p, &bssrdf.po_p) - (r * r + z * z).sqrt()).abs() < 1e-3);
+            // both hits were found and share the probability
+            let expected: Float = bssrdf.pdf_sp(&pi) / 2.0 as Float;
+            assert!(
+                (pdf - expected).abs() <= 1e-4 as Float * expected,
+                "{} {}",
+                pdf,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn exit_points_cluster_around_the_entry_point() {
+        let material: Arc<Material + Send + Sync> = matte();
+        let scene: Scene = slab(&material);
+        let bssrdf: TabulatedBssrdf = entry_bssrdf(&scene, &material);
+        let r_max: Float = bssrdf.sample_sr(0, 0.999 as Float);
+        // exit points on top of the slab per ring [0, 1/8), [1/8, 1/4),
+        // and [1/4, 1/2) of *r_max*
+        let mut counts: [usize; 3] = [0_usize; 3];
+        let n: usize = 64;
+        for i in 0..n {
+            for j in 0..n {
+                // only probes along the normal (u1 < 0.5)
+                let u1: Float = (i as Float + 0.5 as Float) / (2 * n) as Float;
+                let u2: Point2f = Point2f {
+                    x: (j as Float + 0.5 as Float) / n as Float,
+                    y: ((i * n + j) as Float * 0.618034 as Float).fract(),
+                };
+                let mut pi: SurfaceInteraction = SurfaceInteraction::default();
+                let mut pdf: Float = 0.0 as Float;
+                let sp: Spectrum = bssrdf.sample_sp(&scene, u1, &u2, &mut pi, &mut pdf);
+                if sp.is_black() || pi.p.z < -0.25 as Float {
+                    continue;
+                }
+                let r: Float = pnt3_distance(&pi.p, &bssrdf.po_p) / r_max;
+                if r < 0.125 as Float {
+                    counts[0] += 1;
+                } else if r < 0.25 as Float {
+                    counts[1] += 1;
+                } else if r < 0.5 as Float {
+                    counts[2] += 1;
+                }
+            }
+        }
+        // exit points per area fall off with the distance
+        let density: Vec<Float> = [(0.0, 0.125), (0.125, 0.25), (0.25, 0.5)]
+            .iter()
+            .zip(counts.iter())
+            .map(|(&(r0, r1), &count)| count as Float / (r1 * r1 - r0 * r0))
+            .collect();
+        assert!(
+            density[0] > density[1] && density[1] > density[2],
+            "{:?}",
+            counts
+        );
+    }
+}
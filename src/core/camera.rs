@@ -3,29 +3,61 @@
 //! provide.
 
 // std
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 // pbrt
 use core::film::Film;
-use core::geometry::{Point2f, Ray, Vector3f};
+use core::geometry::{Bounds2f, Point2f, Ray, Vector3f};
 use core::interaction::InteractionCommon;
 use core::light::VisibilityTester;
+use core::paramset::ParamSet;
 use core::pbrt::{Float, Spectrum};
+use core::sampling::Distribution1D;
 
 // see camera.h
 
+/// Set once the missing importance functions of a camera were
+/// reported (see **Camera::we()**).
+static IMPORTANCE_WARNING: AtomicBool = AtomicBool::new(false);
+
+fn warn_no_importance() {
+    if !IMPORTANCE_WARNING.swap(true, Ordering::Relaxed) {
+        println!("WARNING: Camera has no importance function, camera connections are skipped.");
+    }
+}
+
 pub trait Camera {
     fn generate_ray_differential(&self, sample: &CameraSample, ray: &mut Ray) -> Float;
-    fn we(&self, ray: &Ray, p_raster2: Option<&mut Point2f>) -> Spectrum;
-    fn pdf_we(&self, ray: &Ray) -> (Float, Float);
+    /// Importance emitted along *ray* (and the raster position it
+    /// hits). Cameras without a well-defined importance function
+    /// keep the default, which warns once and returns zero
+    /// importance, so bidirectional integrators skip the connection.
+    fn we(&self, _ray: &Ray, _p_raster2: Option<&mut Point2f>) -> Spectrum {
+        warn_no_importance();
+        Spectrum::default()
+    }
+    /// Positional and directional densities for *ray* (both zero by
+    /// default, see **we()**).
+    fn pdf_we(&self, _ray: &Ray) -> (Float, Float) {
+        warn_no_importance();
+        (0.0 as Float, 0.0 as Float)
+    }
+    /// Samples a point on the camera for the reference point *iref*.
+    /// By default no sample is generated (*pdf* is zero, see
+    /// **we()**).
     fn sample_wi(
         &self,
-        iref: &InteractionCommon,
-        u: &Point2f,
-        wi: &mut Vector3f,
+        _iref: &InteractionCommon,
+        _u: &Point2f,
+        _wi: &mut Vector3f,
         pdf: &mut Float,
-        p_raster: &mut Point2f,
-        vis: &mut VisibilityTester,
-    ) -> Spectrum;
+        _p_raster: &mut Point2f,
+        _vis: &mut VisibilityTester,
+    ) -> Spectrum {
+        warn_no_importance();
+        *pdf = 0.0 as Float;
+        Spectrum::default()
+    }
     fn get_shutter_open(&self) -> Float;
     fn get_shutter_close(&self) -> Float;
     fn get_film(&self) -> Arc<Film>;
@@ -37,3 +69,160 @@ pub struct CameraSample {
     pub p_lens: Point2f,
     pub time: Float,
 }
+
+/// Response of the shutter over the exposure time. Cameras warp the
+/// time sample with **warp_time()** before mapping it to [shutter
+/// open, shutter close], so a non-box curve concentrates the motion
+/// blur samples where the shutter is (more) open.
+#[derive(Debug, Clone)]
+pub enum ShutterCurve {
+    /// shutter opens and closes instantly (pbrt's default)
+    Box,
+    /// shutter opens linearly until mid-exposure, then closes again
+    Triangle,
+    /// user supplied response values, evenly spaced over the exposure
+    Piecewise(Distribution1D),
+}
+
+impl ShutterCurve {
+    /// Reads the "shuttercurve" parameter ("box", "triangle", or
+    /// "piecewise" with the response values in "shutterresponse").
+    pub fn create(params: &ParamSet) -> Self {
+        let curve: String = params.find_one_string("shuttercurve", String::from("box"));
+        if curve == "box" {
+            ShutterCurve::Box
+        } else if curve == "triangle" {
+            ShutterCurve::Triangle
+        } else if curve == "piecewise" {
+            let response: Vec<Float> = params.find_float("shutterresponse");
+            if response.is_empty() || response.iter().any(|v| *v < 0.0 as Float) {
+                println!(
+                    "WARNING: \"shutterresponse\" needs non-negative values. Using a box shutter."
+                );
+                ShutterCurve::Box
+            } else {
+                ShutterCurve::Piecewise(Distribution1D::new(response))
+            }
+        } else {
+            println!("WARNING: Shutter curve \"{}\" unknown. Using \"box\".", curve);
+            ShutterCurve::Box
+        }
+    }
+    /// Maps the uniform sample *u* in [0,1) to a (relative) time in
+    /// [0,1) distributed proportional to the shutter response. The
+    /// box curve returns *u* unchanged.
+    pub fn warp_time(&self, u: Float) -> Float {
+        match *self {
+            ShutterCurve::Box => u,
+            ShutterCurve::Triangle => {
+                // invert the CDF of the tent function on [0,1]
+                if u < 0.5 as Float {
+                    (u / 2.0 as Float).sqrt()
+                } else {
+                    1.0 as Float - ((1.0 as Float - u) / 2.0 as Float).sqrt()
+                }
+            }
+            ShutterCurve::Piecewise(ref distribution) => {
+                distribution.sample_continuous(u, None, None)
+            }
+        }
+    }
+}
+
+/// Screen window of a projective camera for the frame aspect ratio
+/// *frame*, unless overridden by the "screenwindow" parameter.
+/// Malformed or degenerate (zero width or height) windows are
+/// reported and replaced by the default window.
+pub fn get_screen_window(params: &ParamSet, frame: Float) -> Bounds2f {
+    let mut screen: Bounds2f = Bounds2f::default();
+    if frame > 1.0 {
+        screen.p_min.x = -frame;
+        screen.p_max.x = frame;
+        screen.p_min.y = -1.0;
+        screen.p_max.y = 1.0;
+    } else {
+        screen.p_min.x = -1.0;
+        screen.p_max.x = 1.0;
+        screen.p_min.y = -1.0 / frame;
+        screen.p_max.y = 1.0 / frame;
+    }
+    let sw: Vec<Float> = params.find_float("screenwindow");
+    if sw.len() > 0_usize {
+        if sw.len() != 4 {
+            println!("ERROR: \"screenwindow\" should have four values. Using the default.");
+        } else if sw[0] == sw[1] || sw[2] == sw[3] {
+            // would divide by zero for the screen to raster transform
+            println!(
+                "ERROR: Degenerate \"screenwindow\" [{}, {}, {}, {}] with zero width or height. \
+                 Using the default.",
+                sw[0], sw[1], sw[2], sw[3]
+            );
+        } else {
+            screen.p_min.x = sw[0];
+            screen.p_max.x = sw[1];
+            screen.p_min.y = sw[2];
+            screen.p_max.y = sw[3];
+        }
+    }
+    screen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn degenerate_screen_window_falls_back_to_default() {
+        let default: Bounds2f = get_screen_window(&ParamSet::default(), 1.5 as Float);
+        let mut params: ParamSet = ParamSet::default();
+        params.add_floats(String::from("screenwindow"), vec![0.0, 0.0, 0.0, 0.0]);
+        let screen: Bounds2f = get_screen_window(&params, 1.5 as Float);
+        assert_eq!(screen.p_min, default.p_min);
+        assert_eq!(screen.p_max, default.p_max);
+        assert!(screen.p_max.x - screen.p_min.x > 0.0 as Float);
+        assert!(screen.p_max.y - screen.p_min.y > 0.0 as Float);
+        // a valid window is used as given
+        let mut params: ParamSet = ParamSet::default();
+        params.add_floats(String::from("screenwindow"), vec![-0.5, 0.5, -0.25, 0.25]);
+        let screen: Bounds2f = get_screen_window(&params, 1.5 as Float);
+        assert_eq!(screen.p_min.x, -0.5 as Float);
+        assert_eq!(screen.p_max.y, 0.25 as Float);
+    }
+
+    #[test]
+    fn triangle_shutter_concentrates_samples_at_mid_exposure() {
+        // the box default leaves the time samples unchanged
+        let default: ShutterCurve = ShutterCurve::create(&ParamSet::default());
+        for i in 0..16 {
+            let u: Float = i as Float / 16.0 as Float;
+            assert_eq!(default.warp_time(u), u);
+        }
+        let mut params: ParamSet = ParamSet::default();
+        params.add_string(String::from("shuttercurve"), String::from("triangle"));
+        let triangle: ShutterCurve = ShutterCurve::create(&params);
+        // the midpoint stays, everything else moves towards it
+        assert!((triangle.warp_time(0.5 as Float) - 0.5 as Float).abs() < 1e-6 as Float);
+        assert!(triangle.warp_time(0.25 as Float) > 0.25 as Float);
+        assert!(triangle.warp_time(0.75 as Float) < 0.75 as Float);
+        // 3/4 of the samples fall into the middle half of the exposure
+        let n: usize = 1000;
+        let mut box_count: usize = 0;
+        let mut triangle_count: usize = 0;
+        let mut previous: Float = 0.0 as Float;
+        for i in 0..n {
+            let u: Float = (i as Float + 0.5 as Float) / n as Float;
+            let t: Float = triangle.warp_time(u);
+            assert!(t >= previous && t < 1.0 as Float);
+            previous = t;
+            if t >= 0.25 as Float && t < 0.75 as Float {
+                triangle_count += 1;
+            }
+            let t: Float = default.warp_time(u);
+            if t >= 0.25 as Float && t < 0.75 as Float {
+                box_count += 1;
+            }
+        }
+        assert_eq!(box_count, n / 2);
+        assert!((triangle_count as i32 - 750).abs() <= 2, "{}", triangle_count);
+    }
+}
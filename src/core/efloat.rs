@@ -7,23 +7,48 @@
 use std;
 use std::ops::{Add, Div, Mul, Sub};
 // pbrt
-use core::pbrt::MACHINE_EPSILON;
 use core::pbrt::{next_float_down, next_float_up};
 
 // see efloat.h
 
 /// Find solution(s) of the quadratic equation at<sup>2</sup> + bt + c = 0 using
 /// *EFloat* instead of *Float* for error bounds.
+///
+/// The discriminant is evaluated in double precision over the
+/// intervals of *a*, *b* and *c*, so the intervals of *t0* and *t1*
+/// conservatively bound the exact roots,
+/// which lets the quadrics (**Sphere**, **Cylinder**) reject hits via
+/// `lower_bound()`/`upper_bound()` instead of an ad-hoc epsilon.
 pub fn quadratic_efloat(a: EFloat, b: EFloat, c: EFloat, t0: &mut EFloat, t1: &mut EFloat) -> bool {
     let discrim: f64 = b.v as f64 * b.v as f64 - 4.0f64 * a.v as f64 * c.v as f64;
     if discrim < 0.0 {
         false
     } else {
         let root_discrim: f64 = discrim.sqrt();
-        let float_root_discrim: EFloat = EFloat::new(
-            root_discrim as f32,
-            MACHINE_EPSILON as f32 * root_discrim as f32,
-        );
+        // bound the discriminant over the intervals of the
+        // coefficients (products of two f32 values are exact in f64)
+        let b2: [f64; 2] = [
+            b.lower_bound() as f64 * b.lower_bound() as f64,
+            b.upper_bound() as f64 * b.upper_bound() as f64,
+        ];
+        let b2_low: f64 = if b.lower_bound() <= 0.0 && b.upper_bound() >= 0.0 {
+            0.0
+        } else {
+            b2[0].min(b2[1])
+        };
+        let ac: [f64; 4] = [
+            a.lower_bound() as f64 * c.lower_bound() as f64,
+            a.upper_bound() as f64 * c.lower_bound() as f64,
+            a.lower_bound() as f64 * c.upper_bound() as f64,
+            a.upper_bound() as f64 * c.upper_bound() as f64,
+        ];
+        let ac_low: f64 = 4.0f64 * ac[0].min(ac[1]).min(ac[2].min(ac[3]));
+        let ac_high: f64 = 4.0f64 * ac[0].max(ac[1]).max(ac[2].max(ac[3]));
+        let float_root_discrim: EFloat = EFloat {
+            v: root_discrim as f32,
+            low: next_float_down((b2_low - ac_high).max(0.0).sqrt() as f32),
+            high: next_float_up((b2[0].max(b2[1]) - ac_low).max(0.0).sqrt() as f32),
+        };
         // compute quadratic _t_ values
         let q: EFloat;
         if b.v < 0.0f32 {
@@ -157,3 +182,98 @@ impl Div for EFloat {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Roots of at<sup>2</sup> + bt + c = 0 in double precision.
+    fn exact_roots(a: f64, b: f64, c: f64) -> (f64, f64) {
+        let root_discrim: f64 = (b * b - 4.0 * a * c).sqrt();
+        let q: f64 = if b < 0.0 {
+            -0.5 * (b - root_discrim)
+        } else {
+            -0.5 * (b + root_discrim)
+        };
+        let (t0, t1) = (q / a, c / q);
+        if t0 > t1 {
+            (t1, t0)
+        } else {
+            (t0, t1)
+        }
+    }
+
+    fn assert_brackets(t: &EFloat, exact: f64) {
+        assert!(
+            t.lower_bound() as f64 <= exact && exact <= t.upper_bound() as f64,
+            "[{}, {}] does not contain {}",
+            t.lower_bound(),
+            t.upper_bound(),
+            exact
+        );
+        // the interval stays tight
+        assert!(t.upper_bound() - t.lower_bound() <= 1e-5 * t.v.abs().max(1.0));
+    }
+
+    #[test]
+    fn quadratic_roots_bracket_the_exact_roots() {
+        let coefficients: [(f32, f32, f32); 4] = [
+            (1.0, -3.0, 2.0),
+            (2.0, 0.1, -0.02),
+            // catastrophic cancellation in the naive formula
+            (1.0, -1e4, 1.0),
+            (0.3, 0.7, 0.1),
+        ];
+        for &(a, b, c) in coefficients.iter() {
+            let mut t0: EFloat = EFloat::default();
+            let mut t1: EFloat = EFloat::default();
+            assert!(quadratic_efloat(
+                EFloat::new(a, 0.0),
+                EFloat::new(b, 0.0),
+                EFloat::new(c, 0.0),
+                &mut t0,
+                &mut t1,
+            ));
+            assert!(t0.v <= t1.v);
+            let (e0, e1) = exact_roots(a as f64, b as f64, c as f64);
+            assert_brackets(&t0, e0);
+            assert_brackets(&t1, e1);
+        }
+        // no real roots
+        let mut t0: EFloat = EFloat::default();
+        let mut t1: EFloat = EFloat::default();
+        assert!(!quadratic_efloat(
+            EFloat::new(1.0, 0.0),
+            EFloat::new(0.0, 0.0),
+            EFloat::new(1.0, 0.0),
+            &mut t0,
+            &mut t1,
+        ));
+    }
+
+    #[test]
+    fn sphere_hits_bracket_the_exact_distances() {
+        // ray against a unit sphere, with the rounding errors of the
+        // coefficients tracked like in Sphere::intersect()
+        let o: [f32; 3] = [0.1, 0.2, -5.0];
+        let d: [f32; 3] = [0.013, 0.021, 0.97];
+        let ox: EFloat = EFloat::new(o[0], 0.0);
+        let oy: EFloat = EFloat::new(o[1], 0.0);
+        let oz: EFloat = EFloat::new(o[2], 0.0);
+        let dx: EFloat = EFloat::new(d[0], 0.0);
+        let dy: EFloat = EFloat::new(d[1], 0.0);
+        let dz: EFloat = EFloat::new(d[2], 0.0);
+        let a: EFloat = dx * dx + dy * dy + dz * dz;
+        let b: EFloat = (dx * ox + dy * oy + dz * oz) * 2.0;
+        let c: EFloat = ox * ox + oy * oy + oz * oz - EFloat::new(1.0, 0.0);
+        let mut t0: EFloat = EFloat::default();
+        let mut t1: EFloat = EFloat::default();
+        assert!(quadratic_efloat(a, b, c, &mut t0, &mut t1));
+        let dot = |u: [f32; 3], v: [f32; 3]| -> f64 {
+            u[0] as f64 * v[0] as f64 + u[1] as f64 * v[1] as f64 + u[2] as f64 * v[2] as f64
+        };
+        let (e0, e1) = exact_roots(dot(d, d), 2.0 * dot(d, o), dot(o, o) - 1.0);
+        assert_brackets(&t0, e0);
+        assert_brackets(&t1, e1);
+    }
+}
@@ -10,12 +10,17 @@
 //!
 
 // std
-#[cfg(feature = "openexr")]
 use std;
-use std::ops::{DerefMut, Index};
+use std::fs::File;
+use std::io::Write;
+use std::ops::DerefMut;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock, RwLockWriteGuard};
+use std::time::Duration;
 // others
+#[cfg(feature = "openexr")]
+use byteorder::{ByteOrder, LittleEndian};
 use image;
 #[cfg(feature = "openexr")]
 use openexr::{FrameBuffer, Header, PixelType, ScanlineOutputFile};
@@ -24,16 +29,34 @@ use core::filter::Filter;
 use core::geometry::{
     bnd2_intersect_bnd2, pnt2_ceil, pnt2_floor, pnt2_inside_exclusive, pnt2_max_pnt2, pnt2_min_pnt2,
 };
-use core::geometry::{Bounds2f, Bounds2i, Point2f, Point2i, Vector2f};
+use core::geometry::{Bounds2f, Bounds2i, Point2f, Point2i, Vector2f, Vector2i};
 use core::parallel::AtomicFloat;
-use core::pbrt::{clamp_t, gamma_correct};
+use core::pbrt::{clamp_t, gamma_correct, is_power_of_2};
 use core::pbrt::{Float, Spectrum};
-use core::spectrum::xyz_to_rgb;
+use core::profile::{Prof, ProfilePhase};
+use core::spectrum::{convert_color_space, ColorSpace, SpectrumType};
 
 // see film.h
 
 const FILTER_TABLE_WIDTH: usize = 16;
 
+/// Validates the "bitdepth" film parameter. PNG output supports 8
+/// and 16 bits per channel, anything else falls back to 8.
+pub fn png_bit_depth(bit_depth: i32) -> u8 {
+    if bit_depth != 8 && bit_depth != 16 {
+        println!(
+            "WARNING: Unsupported \"bitdepth\" {} for PNG output. Using 8.",
+            bit_depth
+        );
+        return 8_u8;
+    }
+    bit_depth as u8
+}
+/// Range of the base-2 logarithm of the luminance covered by
+/// **Film::luminance_histogram()**.
+pub const HISTOGRAM_MIN_LOG_LUMINANCE: Float = -10.0 as Float;
+pub const HISTOGRAM_MAX_LOG_LUMINANCE: Float = 10.0 as Float;
+
 #[derive(Debug, Clone)]
 pub struct Pixel {
     xyz: [Float; 3],
@@ -96,7 +119,7 @@ impl<'a> FilmTile<'a> {
         }
     }
     pub fn add_sample(&mut self, p_film: &Point2f, l: &mut Spectrum, sample_weight: Float) {
-        // TODO: ProfilePhase _(Prof::AddFilmSample);
+        let _p = ProfilePhase::new(Prof::AddFilmSample);
         if l.y() > self.max_sample_luminance {
             *l *= Spectrum::new(self.max_sample_luminance / l.y());
         }
@@ -156,6 +179,89 @@ impl<'a> FilmTile<'a> {
     }
 }
 
+/// Settings for **Film::apply_bloom()**.
+#[derive(Debug, Copy, Clone)]
+pub struct Bloom {
+    /// Only the luminance above this value blooms
+    pub threshold: Float,
+    /// Radius of the Gaussian blur (in pixels)
+    pub radius: Float,
+    /// Scale of the blurred highlights added back to the image
+    pub intensity: Float,
+}
+
+/// Provenance information written next to the image (see
+/// **Film::write_metadata()**).
+#[derive(Debug, Default, Clone)]
+pub struct RenderMetadata {
+    /// The scene file which was rendered
+    pub scene_name: String,
+    pub samples_per_pixel: i64,
+    /// Wall clock time of the rendering (in seconds)
+    pub render_time: f64,
+    /// Camera type, e.g. "perspective"
+    pub camera_name: String,
+    /// All single valued float parameters of the camera (e.g. "fov")
+    pub camera_params: Vec<(String, Float)>,
+    /// Version of the crate which rendered the image
+    pub version: String,
+}
+
+impl RenderMetadata {
+    pub fn new(scene_name: String, samples_per_pixel: i64, camera_name: String) -> Self {
+        RenderMetadata {
+            scene_name: scene_name,
+            samples_per_pixel: samples_per_pixel,
+            render_time: 0.0_f64,
+            camera_name: camera_name,
+            camera_params: Vec::new(),
+            version: String::from(env!("CARGO_PKG_VERSION")),
+        }
+    }
+    /// Serializes the metadata as a JSON object. Non-finite numbers
+    /// (which JSON can't represent) are written as *null*.
+    pub fn to_json(&self) -> String {
+        let camera_params: Vec<String> = self
+            .camera_params
+            .iter()
+            .map(|&(ref name, value)| {
+                format!("\"{}\": {}", json_escape(name), json_number(value as f64))
+            }).collect();
+        format!(
+            "{{\n  \"scene\": \"{}\",\n  \"samples_per_pixel\": {},\n  \"render_time\": {},\n  \
+             \"camera\": \"{}\",\n  \"camera_params\": {{{}}},\n  \"version\": \"{}\"\n}}\n",
+            json_escape(&self.scene_name),
+            self.samples_per_pixel,
+            json_number(self.render_time),
+            json_escape(&self.camera_name),
+            camera_params.join(", "),
+            json_escape(&self.version)
+        )
+    }
+}
+
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        format!("{}", value)
+    } else {
+        String::from("null")
+    }
+}
+
+fn json_escape(s: &str) -> String {
+    let mut escaped: String = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 pub struct Film {
     // Film Public Data
     /// The overall resolution of the image in pixels
@@ -174,6 +280,19 @@ pub struct Film {
     filter_table: [Float; FILTER_TABLE_WIDTH * FILTER_TABLE_WIDTH],
     scale: Float,
     max_sample_luminance: Float,
+    /// Bits per channel of the PNG output (8 or 16)
+    bit_depth: u8,
+    /// Optional bloom/glare post-process (see **apply_bloom()**)
+    bloom: Option<Bloom>,
+    /// Gamut of the written image (converted from linear sRGB)
+    color_space: ColorSpace,
+    /// Written as "pbrt{suffix}.json" next to the image, if set
+    metadata: RwLock<Option<RenderMetadata>>,
+    /// Appended to the base name of all written files
+    output_suffix: String,
+    /// Number of samples (and splats) dropped because of NaN,
+    /// negative, or infinite radiance values
+    dropped_samples: AtomicUsize,
 }
 
 impl Film {
@@ -185,6 +304,9 @@ impl Film {
         filename: String,
         scale: Float,
         max_sample_luminance: Float,
+        bit_depth: u8,
+        bloom: Option<Bloom>,
+        color_space: ColorSpace,
     ) -> Self {
         let cropped_pixel_bounds: Bounds2i = Bounds2i {
             p_min: Point2i {
@@ -223,6 +345,12 @@ impl Film {
             filter_table: filter_table,
             scale: scale,
             max_sample_luminance: max_sample_luminance,
+            bit_depth: bit_depth,
+            bloom: bloom,
+            color_space: color_space,
+            metadata: RwLock::new(None),
+            output_suffix: String::new(),
+            dropped_samples: AtomicUsize::new(0_usize),
         }
     }
     pub fn get_cropped_pixel_bounds(&self) -> Bounds2i {
@@ -255,6 +383,54 @@ impl Film {
             },
         }
     }
+    /// Bounds of the tile with (scanline order) index *tile_index*
+    /// when the cropped pixel bounds are split into tiles of
+    /// *tile_size* x *tile_size* pixels (a power of two). Tiles on
+    /// the right and bottom edge are clamped to the cropped pixel
+    /// bounds.
+    pub fn tile_bounds(&self, tile_index: usize, tile_size: i32) -> Bounds2i {
+        let n_tiles: Point2i = self.tile_count(tile_size);
+        assert!((tile_index as i32) < n_tiles.x * n_tiles.y);
+        let tile: Point2i = Point2i {
+            x: tile_index as i32 % n_tiles.x,
+            y: tile_index as i32 / n_tiles.x,
+        };
+        let x0: i32 = self.cropped_pixel_bounds.p_min.x + tile.x * tile_size;
+        let x1: i32 = std::cmp::min(x0 + tile_size, self.cropped_pixel_bounds.p_max.x);
+        let y0: i32 = self.cropped_pixel_bounds.p_min.y + tile.y * tile_size;
+        let y1: i32 = std::cmp::min(y0 + tile_size, self.cropped_pixel_bounds.p_max.y);
+        Bounds2i::new(Point2i { x: x0, y: y0 }, Point2i { x: x1, y: y1 })
+    }
+    /// Number of tiles in x and y used by **tile_bounds()**.
+    pub fn tile_count(&self, tile_size: i32) -> Point2i {
+        assert!(tile_size > 0_i32 && is_power_of_2(tile_size));
+        let extent: Vector2i = self.cropped_pixel_bounds.diagonal();
+        Point2i {
+            x: (extent.x + tile_size - 1) / tile_size,
+            y: (extent.y + tile_size - 1) / tile_size,
+        }
+    }
+    /// Like **tile_bounds()**, but the tiles along the edges of the
+    /// cropped pixel bounds extend to the sample bounds (see
+    /// **get_sample_bounds()**), so the tiles cover every pixel the
+    /// filter needs samples from exactly once.
+    pub fn sample_tile_bounds(&self, tile_index: usize, tile_size: i32) -> Bounds2i {
+        let mut bounds: Bounds2i = self.tile_bounds(tile_index, tile_size);
+        let sample_bounds: Bounds2i = self.get_sample_bounds();
+        if bounds.p_min.x == self.cropped_pixel_bounds.p_min.x {
+            bounds.p_min.x = sample_bounds.p_min.x;
+        }
+        if bounds.p_min.y == self.cropped_pixel_bounds.p_min.y {
+            bounds.p_min.y = sample_bounds.p_min.y;
+        }
+        if bounds.p_max.x == self.cropped_pixel_bounds.p_max.x {
+            bounds.p_max.x = sample_bounds.p_max.x;
+        }
+        if bounds.p_max.y == self.cropped_pixel_bounds.p_max.y {
+            bounds.p_max.y = sample_bounds.p_max.y;
+        }
+        bounds
+    }
     pub fn get_physical_extent(&self) -> Bounds2f {
         let aspect: Float = self.full_resolution.y as Float / self.full_resolution.x as Float;
         let x: Float = (self.diagonal * self.diagonal / (1.0 as Float + aspect * aspect)).sqrt();
@@ -309,7 +485,7 @@ impl Film {
         )
     }
     pub fn merge_film_tile(&self, tile: &FilmTile) {
-        // TODO: ProfilePhase p(Prof::MergeFilmTile);
+        let _p = ProfilePhase::new(Prof::MergeFilmTile);
         // println!("Merging film tile {:?}", tile.pixel_bounds);
         // TODO: std::lock_guard<std::mutex> lock(mutex);
         for pixel in &tile.pixel_bounds {
@@ -352,12 +528,13 @@ impl Film {
     }
     pub fn add_splat(&self, p: &Point2f, v: &Spectrum) {
         let mut v: Spectrum = *v;
-        // TODO: ProfilePhase pp(Prof::SplatFilm);
+        let _p = ProfilePhase::new(Prof::SplatFilm);
         if v.has_nans() {
             println!(
                 "ERROR: Ignoring splatted spectrum with NaN values at ({:?}, {:?})",
                 p.x, p.y
             );
+            self.add_dropped_sample();
             return;
         } else if v.y() < 0.0 as Float {
             println!(
@@ -366,12 +543,14 @@ impl Film {
                 p.x,
                 p.y
             );
+            self.add_dropped_sample();
             return;
         } else if v.y().is_infinite() {
             println!(
                 "ERROR: Ignoring splatted spectrum with infinite luminance at ({:?}, {:?})",
                 p.x, p.y
             );
+            self.add_dropped_sample();
             return;
         }
 
@@ -399,6 +578,62 @@ impl Film {
         splat_xyz[1].add(xyz[1]);
         splat_xyz[2].add(xyz[2]);
     }
+    /// Returns the current (reconstructed) values of the pixels within
+    /// *bounds* in scanline order, e.g. to display finished tiles
+    /// while rendering.
+    pub fn get_pixel_values(&self, bounds: &Bounds2i) -> Vec<Spectrum> {
+        let pixels = self.pixels.read().unwrap();
+        let width: i32 = self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x;
+        let mut values: Vec<Spectrum> = Vec::with_capacity(bounds.area() as usize);
+        for y in bounds.p_min.y..bounds.p_max.y {
+            for x in bounds.p_min.x..bounds.p_max.x {
+                let p: Point2i = Point2i { x: x, y: y };
+                assert!(pnt2_inside_exclusive(&p, &self.cropped_pixel_bounds));
+                let offset: i32 = (x - self.cropped_pixel_bounds.p_min.x)
+                    + (y - self.cropped_pixel_bounds.p_min.y) * width;
+                let pixel: &Pixel = &pixels[offset as usize];
+                values.push(Spectrum::from_rgb_array(self.pixel_rgb(pixel, 1.0 as Float)));
+            }
+        }
+        values
+    }
+    /// Final RGB value of a pixel as written by the image writers:
+    /// the filtered XYZ value, plus the splats (scaled by
+    /// *splat_scale*), scaled by the film's *scale*. The values are
+    /// linear sRGB (see **convert_to_output()**).
+    fn pixel_rgb(&self, pixel: &Pixel, splat_scale: Float) -> [Float; 3] {
+        let mut l: Spectrum = Spectrum::from_xyz(&pixel.xyz, SpectrumType::Illuminant);
+        // normalize pixel with weight sum
+        let filter_weight_sum: Float = pixel.filter_weight_sum;
+        if filter_weight_sum != 0.0 as Float {
+            let inv_wt: Float = 1.0 as Float / filter_weight_sum;
+            l = (l * inv_wt).clamp(0.0 as Float, std::f32::INFINITY as Float);
+        }
+        // add splat value at pixel
+        let splat_xyz: [Float; 3] = [
+            Float::from(&pixel.splat_xyz[0]),
+            Float::from(&pixel.splat_xyz[1]),
+            Float::from(&pixel.splat_xyz[2]),
+        ];
+        l += Spectrum::from_xyz(&splat_xyz, SpectrumType::Illuminant) * splat_scale;
+        // scale pixel value by _scale_
+        (l * self.scale).to_rgb_array()
+    }
+    /// Converts the final linear sRGB *rgb* buffer into the film's
+    /// *color_space* (after post-processing, before the transfer
+    /// function).
+    fn convert_to_output(&self, rgb: &mut [Float]) {
+        if self.color_space == ColorSpace::SRgb {
+            return;
+        }
+        for c in rgb.chunks_mut(3) {
+            let converted: [Float; 3] = convert_color_space(&[c[0], c[1], c[2]], self.color_space);
+            c.copy_from_slice(&converted);
+        }
+    }
+    /// Writes the final image. The splat buffer (filled by
+    /// **add_splat()**) is multiplied by *splat_scale* before being
+    /// added to the filtered pixel values, e.g. `1/spp` for BDPT.
     #[cfg(not(feature = "openexr"))]
     pub fn write_image(&self, splat_scale: Float) {
         let mut rgb: Vec<Float> =
@@ -413,89 +648,32 @@ impl Film {
             let pixel: &Pixel = &self.pixels.read().unwrap()[offset];
 
             let start: usize = 3 * offset;
-            let mut rgb_array: [Float; 3] = [0.0 as Float; 3];
-            xyz_to_rgb(&pixel.xyz, &mut rgb_array); // TODO: Use 'rgb' directly.
+            let rgb_array: [Float; 3] = self.pixel_rgb(pixel, splat_scale);
             rgb[start + 0] = rgb_array[0];
             rgb[start + 1] = rgb_array[1];
             rgb[start + 2] = rgb_array[2];
-            // normalize pixel with weight sum
-            let filter_weight_sum: Float = pixel.filter_weight_sum;
-            if filter_weight_sum != 0.0 as Float {
-                let inv_wt: Float = 1.0 as Float / filter_weight_sum;
-                rgb[start + 0] = (rgb[start + 0] * inv_wt).max(0.0 as Float);
-                rgb[start + 1] = (rgb[start + 1] * inv_wt).max(0.0 as Float);
-                rgb[start + 2] = (rgb[start + 2] * inv_wt).max(0.0 as Float);
-            }
-            // add splat value at pixel
-            let mut splat_rgb: [Float; 3] = [0.0 as Float; 3];
-            let pixel_splat_xyz: &[AtomicFloat; 3] = &pixel.splat_xyz;
-            let splat_xyz: [Float; 3] = [
-                Float::from(pixel_splat_xyz.index(0)),
-                Float::from(pixel_splat_xyz.index(1)),
-                Float::from(pixel_splat_xyz.index(2)),
-            ];
-            xyz_to_rgb(&splat_xyz, &mut splat_rgb);
-            rgb[start + 0] += splat_scale * splat_rgb[0];
-            rgb[start + 1] += splat_scale * splat_rgb[1];
-            rgb[start + 2] += splat_scale * splat_rgb[2];
-            // scale pixel value by _scale_
-            rgb[start + 0] *= self.scale;
-            rgb[start + 1] *= self.scale;
-            rgb[start + 2] *= self.scale;
-        }
-        let filename = "pbrt.png";
+        }
+        if let Some(bloom) = self.bloom {
+            self.apply_bloom(&mut rgb, bloom.threshold, bloom.radius, bloom.intensity);
+        }
+        self.convert_to_output(&mut rgb);
+        let filename: String = format!("pbrt{}.png", self.output_suffix);
         println!(
             "Writing image {:?} with bounds {:?}",
             filename, // TODO: self.filename,
             self.cropped_pixel_bounds
         );
         // TODO: pbrt::WriteImage(filename, &rgb[0], croppedPixelBounds, fullResolution);
-        let mut buffer: Vec<u8> = vec![0.0 as u8; (3 * self.cropped_pixel_bounds.area()) as usize];
-        // 8-bit format; apply gamma (see WriteImage(...) in imageio.cpp)
-        let width: u32 =
-            (self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x) as u32;
-        let height: u32 =
-            (self.cropped_pixel_bounds.p_max.y - self.cropped_pixel_bounds.p_min.y) as u32;
-        for y in 0..height {
-            for x in 0..width {
-                // red
-                let index: usize = (3 * (y * width + x) + 0) as usize;
-                buffer[index] = clamp_t(
-                    255.0 as Float * gamma_correct(rgb[index]) + 0.5,
-                    0.0 as Float,
-                    255.0 as Float,
-                ) as u8;
-                // green
-                let index: usize = (3 * (y * width + x) + 1) as usize;
-                buffer[index] = clamp_t(
-                    255.0 as Float * gamma_correct(rgb[index]) + 0.5,
-                    0.0 as Float,
-                    255.0 as Float,
-                ) as u8;
-                // blue
-                let index: usize = (3 * (y * width + x) + 2) as usize;
-                buffer[index] = clamp_t(
-                    255.0 as Float * gamma_correct(rgb[index]) + 0.5,
-                    0.0 as Float,
-                    255.0 as Float,
-                ) as u8;
-            }
-        }
-        // write "pbrt.png" to disk
-        image::save_buffer(
-            &Path::new("pbrt.png"),
-            &buffer,
-            width,
-            height,
-            image::RGB(8),
-        ).unwrap();
+        self.write_png(&rgb, &Path::new(&filename));
+        self.write_metadata();
     }
+    /// Writes the final image. The splat buffer (filled by
+    /// **add_splat()**) is multiplied by *splat_scale* before being
+    /// added to the filtered pixel values, e.g. `1/spp` for BDPT.
     #[cfg(feature = "openexr")]
     pub fn write_image(&self, splat_scale: Float) {
         let mut rgb: Vec<Float> =
             vec![0.0 as Float; (3 * self.cropped_pixel_bounds.area()) as usize];
-        let mut exr: Vec<(Float, Float, Float)> = // copy data for OpenEXR image
-            vec![(0.0_f32, 0.0_f32, 0.0_f32); self.cropped_pixel_bounds.area() as usize];
         let mut offset;
         for p in &self.cropped_pixel_bounds {
             // convert pixel XYZ color to RGB
@@ -505,107 +683,315 @@ impl Film {
                 + (p.y - self.cropped_pixel_bounds.p_min.y) * width) as usize;
             let pixel: &Pixel = &self.pixels.read().unwrap()[offset];
             let start = 3 * offset;
-            let mut rgb_array: [Float; 3] = [0.0 as Float; 3];
-            xyz_to_rgb(&pixel.xyz, &mut rgb_array); // TODO: Use 'rgb' directly.
+            let rgb_array: [Float; 3] = self.pixel_rgb(pixel, splat_scale);
             rgb[start + 0] = rgb_array[0];
             rgb[start + 1] = rgb_array[1];
             rgb[start + 2] = rgb_array[2];
-            // normalize pixel with weight sum
-            let filter_weight_sum: Float = pixel.filter_weight_sum;
-            if filter_weight_sum != 0.0 as Float {
-                let inv_wt: Float = 1.0 as Float / filter_weight_sum;
-                rgb[start + 0] = (rgb[start + 0] * inv_wt).max(0.0 as Float);
-                rgb[start + 1] = (rgb[start + 1] * inv_wt).max(0.0 as Float);
-                rgb[start + 2] = (rgb[start + 2] * inv_wt).max(0.0 as Float);
-            }
-            // add splat value at pixel
-            let mut splat_rgb: [Float; 3] = [0.0 as Float; 3];
-            let pixel_splat_xyz: &[AtomicFloat; 3] = &pixel.splat_xyz;
-            let splat_xyz: [Float; 3] = [
-                Float::from(pixel_splat_xyz.index(0)),
-                Float::from(pixel_splat_xyz.index(1)),
-                Float::from(pixel_splat_xyz.index(2)),
-            ];
-            xyz_to_rgb(&splat_xyz, &mut splat_rgb);
-            rgb[start + 0] += splat_scale * splat_rgb[0];
-            rgb[start + 1] += splat_scale * splat_rgb[1];
-            rgb[start + 2] += splat_scale * splat_rgb[2];
-            // scale pixel value by _scale_
-            rgb[start + 0] *= self.scale;
-            rgb[start + 1] *= self.scale;
-            rgb[start + 2] *= self.scale;
-            // copy data for OpenEXR image
-            exr[offset].0 = rgb[start + 0];
-            exr[offset].1 = rgb[start + 1];
-            exr[offset].2 = rgb[start + 2];
-        }
-        let filename = "pbrt.png";
+        }
+        if let Some(bloom) = self.bloom {
+            self.apply_bloom(&mut rgb, bloom.threshold, bloom.radius, bloom.intensity);
+        }
+        self.convert_to_output(&mut rgb);
+        // copy data for OpenEXR image
+        let exr: Vec<(Float, Float, Float)> = rgb.chunks(3).map(|c| (c[0], c[1], c[2])).collect();
+        let filename: String = format!("pbrt{}.png", self.output_suffix);
         println!(
             "Writing image {:?} with bounds {:?}",
             filename, // TODO: self.filename,
             self.cropped_pixel_bounds
         );
         // TODO: pbrt::WriteImage(filename, &rgb[0], croppedPixelBounds, fullResolution);
-        let mut buffer: Vec<u8> = vec![0.0 as u8; (3 * self.cropped_pixel_bounds.area()) as usize];
-        // 8-bit format; apply gamma (see WriteImage(...) in imageio.cpp)
         let width: u32 =
             (self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x) as u32;
         let height: u32 =
             (self.cropped_pixel_bounds.p_max.y - self.cropped_pixel_bounds.p_min.y) as u32;
         // OpenEXR
-        let filename = "pbrt_rust.exr";
+        let filename: String = format!("pbrt_rust{}.exr", self.output_suffix);
         println!(
             "Writing image {:?} with bounds {:?}",
             filename, // TODO: self.filename,
             self.cropped_pixel_bounds
         );
-        let mut file = std::fs::File::create("pbrt_rust.exr").unwrap();
-        let mut output_file = ScanlineOutputFile::new(
-            &mut file,
-            Header::new()
-                .set_resolution(width, height)
-                .add_channel("R", PixelType::FLOAT)
-                .add_channel("G", PixelType::FLOAT)
-                .add_channel("B", PixelType::FLOAT),
-        ).unwrap();
-        let mut fb = FrameBuffer::new(width as u32, height as u32);
-        fb.insert_channels(&["R", "G", "B"], &exr);
-        output_file.write_pixels(&fb).unwrap();
-
-        // OpenEXR
+        let mut data: std::io::Cursor<Vec<u8>> = std::io::Cursor::new(Vec::new());
+        {
+            let mut output_file = ScanlineOutputFile::new(
+                &mut data,
+                Header::new()
+                    .set_resolution(width, height)
+                    .add_channel("R", PixelType::FLOAT)
+                    .add_channel("G", PixelType::FLOAT)
+                    .add_channel("B", PixelType::FLOAT),
+            ).unwrap();
+            let mut fb = FrameBuffer::new(width as u32, height as u32);
+            fb.insert_channels(&["R", "G", "B"], &exr);
+            output_file.write_pixels(&fb).unwrap();
+        }
+        let mut data: Vec<u8> = data.into_inner();
+        add_exr_chromaticities(&mut data, &self.color_space.chromaticities());
+        let mut file = std::fs::File::create(&filename).unwrap();
+        file.write_all(&data).unwrap();
+        // PNG
+        let filename: String = format!("pbrt{}.png", self.output_suffix);
+        self.write_png(&rgb, &Path::new(&filename));
+        self.write_metadata();
+    }
+    /// Appends *suffix* to the base name of the written files, e.g.
+    /// "pbrt_1.png" instead of "pbrt.png" for a suffix of "_1".
+    pub fn set_output_suffix(&mut self, suffix: &str) {
+        self.output_suffix = String::from(suffix);
+    }
+    pub fn set_metadata(&self, metadata: RenderMetadata) {
+        *self.metadata.write().unwrap() = Some(metadata);
+    }
+    /// Counts a sample (or splat) which was set to black or ignored
+    /// because of an unexpected radiance value.
+    pub fn add_dropped_sample(&self) {
+        self.dropped_samples.fetch_add(1_usize, Ordering::Relaxed);
+    }
+    pub fn get_dropped_samples(&self) -> usize {
+        self.dropped_samples.load(Ordering::Relaxed)
+    }
+    /// Prints the number of dropped samples (if any) at the end of
+    /// the rendering.
+    pub fn report_dropped_samples(&self) {
+        let dropped: usize = self.get_dropped_samples();
+        if dropped > 0_usize {
+            println!(
+                "WARNING: {} sample(s) with NaN, negative, or infinite radiance were dropped",
+                dropped
+            );
+        }
+    }
+    /// Records the wall clock time of the rendering in the metadata
+    /// (if any).
+    pub fn set_render_time(&self, elapsed: Duration) {
+        if let Some(ref mut metadata) = *self.metadata.write().unwrap() {
+            metadata.render_time =
+                elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9_f64;
+        }
+    }
+    /// Writes the **RenderMetadata** (if any) as a JSON sidecar file
+    /// "pbrt{suffix}.json". The OpenEXR bindings don't support custom header
+    /// attributes, so the sidecar is used for both image formats.
+    fn write_metadata(&self) {
+        if let Some(ref metadata) = *self.metadata.read().unwrap() {
+            let filename: String = format!("pbrt{}.json", self.output_suffix);
+            println!("Writing render metadata {:?}", filename);
+            match File::create(&filename) {
+                Ok(mut file) => {
+                    if let Err(e) = file.write_all(metadata.to_json().as_bytes()) {
+                        println!("WARNING: Could not write {:?}: {}", filename, e);
+                    }
+                }
+                Err(e) => println!("WARNING: Could not create {:?}: {}", filename, e),
+            }
+        }
+    }
+    /// Adds a glow around bright highlights to the reconstructed *rgb*
+    /// buffer (of the cropped pixel bounds, before tone mapping): the
+    /// luminance above *threshold* is blurred with a separable
+    /// Gaussian of the given *radius* (in pixels) and added back,
+    /// scaled by *intensity*.
+    pub fn apply_bloom(
+        &self,
+        rgb: &mut [Float],
+        threshold: Float,
+        radius: Float,
+        intensity: Float,
+    ) {
+        let width: usize =
+            (self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x) as usize;
+        let height: usize =
+            (self.cropped_pixel_bounds.p_max.y - self.cropped_pixel_bounds.p_min.y) as usize;
+        assert_eq!(rgb.len(), 3 * width * height);
+        if radius <= 0.0 as Float || intensity == 0.0 as Float {
+            return;
+        }
+        // extract the part of each pixel above the threshold
+        let mut bright: Vec<Float> = vec![0.0 as Float; rgb.len()];
+        let mut any_bright: bool = false;
+        for i in 0..(width * height) {
+            let y: Float = Spectrum::rgb(rgb[3 * i], rgb[3 * i + 1], rgb[3 * i + 2]).y();
+            if y > threshold {
+                let s: Float = (y - threshold) / y;
+                for c in 0..3 {
+                    bright[3 * i + c] = rgb[3 * i + c] * s;
+                }
+                any_bright = true;
+            }
+        }
+        if !any_bright {
+            return;
+        }
+        // normalized Gaussian kernel, covering three standard deviations
+        let sigma: Float = radius / 3.0 as Float;
+        let half_width: i32 = radius.ceil() as i32;
+        let mut kernel: Vec<Float> = Vec::with_capacity((2 * half_width + 1) as usize);
+        for i in -half_width..(half_width + 1) {
+            let x: Float = i as Float;
+            kernel.push((-x * x / (2.0 as Float * sigma * sigma)).exp());
+        }
+        let kernel_sum: Float = kernel.iter().sum();
+        for k in kernel.iter_mut() {
+            *k /= kernel_sum;
+        }
+        // blur horizontally, then vertically
+        let mut tmp: Vec<Float> = vec![0.0 as Float; rgb.len()];
         for y in 0..height {
             for x in 0..width {
-                // red
-                let index: usize = (3 * (y * width + x) + 0) as usize;
-                buffer[index] = clamp_t(
-                    255.0 as Float * gamma_correct(rgb[index]) + 0.5,
+                for (k, weight) in kernel.iter().enumerate() {
+                    let sx: i32 = x as i32 + k as i32 - half_width;
+                    if sx < 0 || sx >= width as i32 {
+                        continue;
+                    }
+                    let src: usize = 3 * (y * width + sx as usize);
+                    let dst: usize = 3 * (y * width + x);
+                    for c in 0..3 {
+                        tmp[dst + c] += weight * bright[src + c];
+                    }
+                }
+            }
+        }
+        for y in 0..height {
+            for x in 0..width {
+                let dst: usize = 3 * (y * width + x);
+                for (k, weight) in kernel.iter().enumerate() {
+                    let sy: i32 = y as i32 + k as i32 - half_width;
+                    if sy < 0 || sy >= height as i32 {
+                        continue;
+                    }
+                    let src: usize = 3 * (sy as usize * width + x);
+                    for c in 0..3 {
+                        rgb[dst + c] += intensity * weight * tmp[src + c];
+                    }
+                }
+            }
+        }
+    }
+    /// Writes *rgb* to the PNG file *path* with 8 or 16 bits per
+    /// channel (see **bit_depth**); apply gamma (see WriteImage(...)
+    /// in imageio.cpp).
+    fn write_png(&self, rgb: &[Float], path: &Path) {
+        let width: u32 =
+            (self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x) as u32;
+        let height: u32 =
+            (self.cropped_pixel_bounds.p_max.y - self.cropped_pixel_bounds.p_min.y) as u32;
+        let n_values: usize = (3 * width * height) as usize;
+        if self.bit_depth == 16_u8 {
+            // 16-bit format; PNG stores samples big-endian
+            let mut buffer: Vec<u8> = vec![0_u8; 2 * n_values];
+            for index in 0..n_values {
+                let v: u16 = clamp_t(
+                    65535.0 as Float * gamma_correct(rgb[index]) + 0.5,
                     0.0 as Float,
-                    255.0 as Float,
-                ) as u8;
-                // green
-                let index: usize = (3 * (y * width + x) + 1) as usize;
+                    65535.0 as Float,
+                ) as u16;
+                buffer[2 * index] = (v >> 8) as u8;
+                buffer[2 * index + 1] = (v & 0xff) as u8;
+            }
+            image::save_buffer(
+                path,
+                &buffer,
+                width,
+                height,
+                image::RGB(16),
+            ).unwrap();
+        } else {
+            // 8-bit format
+            let mut buffer: Vec<u8> = vec![0_u8; n_values];
+            for index in 0..n_values {
                 buffer[index] = clamp_t(
                     255.0 as Float * gamma_correct(rgb[index]) + 0.5,
                     0.0 as Float,
                     255.0 as Float,
                 ) as u8;
-                // blue
-                let index: usize = (3 * (y * width + x) + 2) as usize;
+            }
+            image::save_buffer(
+                path,
+                &buffer,
+                width,
+                height,
+                image::RGB(8),
+            ).unwrap();
+        }
+    }
+    /// Computes a histogram with *bins* bins of the base-2 logarithm
+    /// of the luminance of the reconstructed pixels (splats are not
+    /// included), covering [HISTOGRAM_MIN_LOG_LUMINANCE,
+    /// HISTOGRAM_MAX_LOG_LUMINANCE]. Values outside that range are
+    /// counted in the first or last bin.
+    pub fn luminance_histogram(&self, bins: usize) -> Vec<u32> {
+        assert!(bins > 0);
+        let mut histogram: Vec<u32> = vec![0_u32; bins];
+        let pixels = self.pixels.read().unwrap();
+        let range: Float = HISTOGRAM_MAX_LOG_LUMINANCE - HISTOGRAM_MIN_LOG_LUMINANCE;
+        for pixel in pixels.iter() {
+            let rgb: [Float; 3] = self.pixel_rgb(pixel, 0.0 as Float);
+            let y: Float = Spectrum::from_rgb_array(rgb).y();
+            let bin: usize = if y > 0.0 as Float {
+                let t: Float = (y.log2() - HISTOGRAM_MIN_LOG_LUMINANCE) / range;
+                clamp_t((t * bins as Float) as i64, 0_i64, bins as i64 - 1_i64) as usize
+            } else {
+                0_usize
+            };
+            histogram[bin] += 1_u32;
+        }
+        histogram
+    }
+    /// Suggests an exposure scale which maps the median luminance of
+    /// the reconstructed image (see **luminance_histogram()**) to 1.
+    pub fn auto_exposure(&self) -> Float {
+        // odd number of bins, so log2(1) falls onto a bin center
+        let bins: usize = 101_usize;
+        let histogram: Vec<u32> = self.luminance_histogram(bins);
+        let total: u64 = histogram.iter().map(|c| *c as u64).sum();
+        if total == 0_u64 {
+            return 1.0 as Float;
+        }
+        let mut count: u64 = 0_u64;
+        let mut median_bin: usize = bins - 1;
+        for (i, c) in histogram.iter().enumerate() {
+            count += *c as u64;
+            if 2_u64 * count >= total {
+                median_bin = i;
+                break;
+            }
+        }
+        let range: Float = HISTOGRAM_MAX_LOG_LUMINANCE - HISTOGRAM_MIN_LOG_LUMINANCE;
+        let log_median: Float = HISTOGRAM_MIN_LOG_LUMINANCE
+            + (median_bin as Float + 0.5 as Float) * range / bins as Float;
+        1.0 as Float / log_median.exp2()
+    }
+    /// Debug output: writes a grayscale image to *path* where each
+    /// pixel's brightness is proportional to the accumulated
+    /// **filter_weight_sum**, e.g. to visualize sampler coverage. No
+    /// gamma is applied, so brightness ratios match weight ratios.
+    pub fn write_sample_density(&self, path: &str) {
+        let pixels = self.pixels.read().unwrap();
+        let mut max_weight_sum: Float = 0.0 as Float;
+        for pixel in pixels.iter() {
+            max_weight_sum = max_weight_sum.max(pixel.filter_weight_sum);
+        }
+        let width: u32 =
+            (self.cropped_pixel_bounds.p_max.x - self.cropped_pixel_bounds.p_min.x) as u32;
+        let height: u32 =
+            (self.cropped_pixel_bounds.p_max.y - self.cropped_pixel_bounds.p_min.y) as u32;
+        let mut buffer: Vec<u8> = vec![0_u8; (width * height) as usize];
+        if max_weight_sum > 0.0 as Float {
+            for (index, pixel) in pixels.iter().enumerate() {
                 buffer[index] = clamp_t(
-                    255.0 as Float * gamma_correct(rgb[index]) + 0.5,
+                    255.0 as Float * pixel.filter_weight_sum.max(0.0 as Float) / max_weight_sum
+                        + 0.5,
                     0.0 as Float,
                     255.0 as Float,
                 ) as u8;
             }
         }
-        // write "pbrt.png" to disk
-        image::save_buffer(
-            &Path::new("pbrt.png"),
-            &buffer,
-            width,
-            height,
-            image::RGB(8),
-        ).unwrap();
+        println!(
+            "Writing sample density {:?} with bounds {:?}",
+            path, self.cropped_pixel_bounds
+        );
+        image::save_buffer(&Path::new(path), &buffer, width, height, image::Gray(8)).unwrap();
     }
     // pub fn get_pixel<'a>(&self, p: &Point2i) -> &'a Pixel {
     //     assert!(pnt2_inside_exclusive(p, &self.cropped_pixel_bounds));
@@ -615,3 +1001,370 @@ impl Film {
     //     &self.pixels.read().unwrap()[offset as usize]
     // }
 }
+
+/// Inserts a "chromaticities" attribute (red, green, blue and white
+/// xy) into the header of the OpenEXR file in *data*, which the
+/// OpenEXR bindings can't set, and moves the chunk offsets following
+/// the header accordingly.
+#[cfg(feature = "openexr")]
+fn add_exr_chromaticities(data: &mut Vec<u8>, chromaticities: &[Float; 8]) {
+    // skip magic number and version field
+    let mut pos: usize = 8_usize;
+    // each attribute: name and type (null-terminated), size, value
+    while data[pos] != 0_u8 {
+        for _i in 0..2 {
+            while data[pos] != 0_u8 {
+                pos += 1;
+            }
+            pos += 1;
+        }
+        let size: usize = LittleEndian::read_i32(&data[pos..pos + 4]) as usize;
+        pos += 4 + size;
+    }
+    let header_end: usize = pos;
+    let mut attribute: Vec<u8> = b"chromaticities\0chromaticities\0".to_vec();
+    let mut value: [u8; 36] = [0_u8; 36];
+    LittleEndian::write_i32(&mut value[0..4], 32_i32);
+    for (i, c) in chromaticities.iter().enumerate() {
+        LittleEndian::write_f32(&mut value[4 + 4 * i..8 + 4 * i], *c as f32);
+    }
+    attribute.extend_from_slice(&value);
+    // the offset table follows the header's null byte, its first
+    // entry points right behind the table
+    let table_start: usize = header_end + 1;
+    let first_chunk: usize = LittleEndian::read_u64(&data[table_start..table_start + 8]) as usize;
+    let n_chunks: usize = (first_chunk - table_start) / 8;
+    for i in 0..n_chunks {
+        let entry: &mut [u8] = &mut data[table_start + 8 * i..table_start + 8 * (i + 1)];
+        let offset: u64 = LittleEndian::read_u64(entry) + attribute.len() as u64;
+        LittleEndian::write_u64(entry, offset);
+    }
+    let tail: Vec<u8> = data.split_off(header_end);
+    data.extend_from_slice(&attribute);
+    data.extend_from_slice(&tail);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::testutil::test_film;
+    use filters::boxfilter::BoxFilter;
+    use png;
+    use png::HasParameters;
+
+    /// Counts how often each pixel of *bounds* is covered by *tiles*.
+    fn coverage(bounds: &Bounds2i, tiles: &[Bounds2i]) -> Vec<u32> {
+        let extent: Vector2i = bounds.diagonal();
+        let mut covered: Vec<u32> = vec![0_u32; (extent.x * extent.y) as usize];
+        for tile in tiles {
+            for p in tile {
+                assert!(pnt2_inside_exclusive(&p, bounds));
+                let offset: i32 = (p.y - bounds.p_min.y) * extent.x + (p.x - bounds.p_min.x);
+                covered[offset as usize] += 1_u32;
+            }
+        }
+        covered
+    }
+
+    #[test]
+    fn tiles_cover_the_crop_window_once() {
+        // a ragged crop window (37 x 21 pixels) and a wide filter
+        let filter: Arc<Filter + Sync + Send> = Arc::new(BoxFilter {
+            radius: Vector2f { x: 2.0, y: 2.0 },
+            inv_radius: Vector2f { x: 0.5, y: 0.5 },
+        });
+        let film: Film = Film::new(
+            Point2i { x: 64, y: 32 },
+            Bounds2f {
+                p_min: Point2f { x: 0.1, y: 0.25 },
+                p_max: Point2f { x: 0.678, y: 0.906 },
+            },
+            filter,
+            35.0 as Float,
+            String::from("test.png"),
+            1.0 as Float,
+            std::f32::INFINITY,
+            8_u8,
+            None,
+            ColorSpace::SRgb,
+        );
+        let crop: Bounds2i = film.get_cropped_pixel_bounds();
+        assert_eq!((crop.diagonal().x, crop.diagonal().y), (37, 21));
+        let tile_size: i32 = 16_i32;
+        let n_tiles: Point2i = film.tile_count(tile_size);
+        assert_eq!(n_tiles, Point2i { x: 3, y: 2 });
+        let n: usize = (n_tiles.x * n_tiles.y) as usize;
+        let tiles: Vec<Bounds2i> = (0..n).map(|i| film.tile_bounds(i, tile_size)).collect();
+        assert!(coverage(&crop, &tiles).iter().all(|c| *c == 1_u32));
+        // ragged last tile
+        let last: Vector2i = tiles[n - 1].diagonal();
+        assert_eq!((last.x, last.y), (5, 5));
+        // the render loop's tiles also cover the filter's border
+        let sample_tiles: Vec<Bounds2i> =
+            (0..n).map(|i| film.sample_tile_bounds(i, tile_size)).collect();
+        let sample_bounds: Bounds2i = film.get_sample_bounds();
+        assert!(sample_bounds.diagonal().x > crop.diagonal().x);
+        assert!(coverage(&sample_bounds, &sample_tiles).iter().all(|c| *c == 1_u32));
+    }
+
+    #[test]
+    fn nan_splat_is_dropped_and_counted() {
+        let film: Film = test_film(Point2i { x: 4, y: 4 }, 0.5 as Float, "test.png");
+        let p: Point2f = Point2f { x: 1.5, y: 2.5 };
+        let pixel: Bounds2i = Bounds2i {
+            p_min: Point2i { x: 1, y: 2 },
+            p_max: Point2i { x: 2, y: 3 },
+        };
+        let before: Vec<Spectrum> = film.get_pixel_values(&pixel);
+        film.add_splat(&p, &Spectrum::new(std::f32::NAN));
+        let after: Vec<Spectrum> = film.get_pixel_values(&pixel);
+        for c in 0..3 {
+            assert_eq!(before[0][c], after[0][c]);
+        }
+        assert_eq!(film.get_dropped_samples(), 1_usize);
+        // a valid splat still gets through (and isn't counted)
+        film.add_splat(&p, &Spectrum::new(1.0 as Float));
+        assert!(film.get_pixel_values(&pixel)[0][0] > 0.0 as Float);
+        assert_eq!(film.get_dropped_samples(), 1_usize);
+    }
+
+    #[test]
+    fn sample_density_is_relative_to_the_densest_pixel() {
+        let film: Film = test_film(Point2i { x: 2, y: 1 }, 0.5 as Float, "test.png");
+        let mut tile: FilmTile = film.get_film_tile(&film.get_sample_bounds());
+        // four samples in the left pixel, one in the right one
+        for p in &[(0.5, 0.5), (0.5, 0.5), (0.5, 0.5), (0.5, 0.5), (1.5, 0.5)] {
+            let mut l: Spectrum = Spectrum::new(1.0 as Float);
+            tile.add_sample(&Point2f { x: p.0, y: p.1 }, &mut l, 1.0 as Float);
+        }
+        film.merge_film_tile(&tile);
+        let path = std::env::temp_dir().join("pbrt_sample_density.png");
+        film.write_sample_density(path.to_str().unwrap());
+        let density: Vec<u8> = image::open(&path).unwrap().to_luma().into_raw();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(density, vec![255_u8, 64_u8]);
+    }
+
+    fn uniform_film(l: Float) -> Film {
+        let film: Film = test_film(Point2i { x: 4, y: 4 }, 0.5 as Float, "test.png");
+        let mut tile: FilmTile = film.get_film_tile(&film.get_sample_bounds());
+        for y in 0..4 {
+            for x in 0..4 {
+                let p: Point2f = Point2f {
+                    x: x as Float + 0.5 as Float,
+                    y: y as Float + 0.5 as Float,
+                };
+                let mut radiance: Spectrum = Spectrum::new(l);
+                tile.add_sample(&p, &mut radiance, 1.0 as Float);
+            }
+        }
+        film.merge_film_tile(&tile);
+        film
+    }
+
+    #[test]
+    fn uniform_luminance_fills_one_bin_and_needs_no_exposure() {
+        let film: Film = uniform_film(1.0 as Float);
+        // one bin per stop, log2(1) is in the middle
+        let histogram: Vec<u32> = film.luminance_histogram(20);
+        assert_eq!(histogram[10], 16_u32);
+        assert_eq!(histogram.iter().sum::<u32>(), 16_u32);
+        assert!((film.auto_exposure() - 1.0 as Float).abs() < 0.1 as Float);
+        // two stops brighter, two stops less exposure
+        let bright: Film = uniform_film(4.0 as Float);
+        assert_eq!(bright.luminance_histogram(20)[12], 16_u32);
+        assert!((bright.auto_exposure() - 0.25 as Float).abs() < 0.025 as Float);
+    }
+
+    #[test]
+    fn splats_are_scaled_by_the_splat_scale() {
+        let film: Film = test_film(Point2i { x: 4, y: 4 }, 0.5 as Float, "test.png");
+        // four splats adding up to 8 in pixel (1, 2), nothing else
+        let n: usize = 4;
+        for _ in 0..n {
+            film.add_splat(&Point2f { x: 1.5, y: 2.5 }, &Spectrum::new(2.0 as Float));
+        }
+        let pixels = film.pixels.read().unwrap();
+        let rgb: [Float; 3] = film.pixel_rgb(&pixels[2 * 4 + 1], 1.0 as Float / n as Float);
+        for c in 0..3 {
+            assert!((rgb[c] - 2.0 as Float).abs() < 1e-4 as Float, "{:?}", rgb);
+        }
+        assert_eq!(
+            film.pixel_rgb(&pixels[0], 1.0 as Float / n as Float),
+            [0.0; 3]
+        );
+        // without scaling the splats add up
+        let rgb: [Float; 3] = film.pixel_rgb(&pixels[2 * 4 + 1], 1.0 as Float);
+        assert!((rgb[1] - 8.0 as Float).abs() < 1e-3 as Float, "{:?}", rgb);
+    }
+
+    #[test]
+    fn bloom_spreads_bright_pixels_only() {
+        let film: Film = test_film(Point2i { x: 9, y: 9 }, 0.5 as Float, "test.png");
+        // a single bright pixel in the center of a black image
+        let mut rgb: Vec<Float> = vec![0.0 as Float; 3 * 81];
+        let center: usize = 4 * 9 + 4;
+        for c in 0..3 {
+            rgb[3 * center + c] = 10.0 as Float;
+        }
+        film.apply_bloom(&mut rgb, 1.0 as Float, 2.0 as Float, 1.0 as Float);
+        assert!(rgb[3 * center] > 10.0 as Float);
+        for neighbor in &[center - 1, center + 1, center - 9, center + 9, center + 10] {
+            assert!(rgb[3 * neighbor] > 0.0 as Float, "pixel {}", neighbor);
+            assert!(rgb[3 * neighbor] < rgb[3 * center]);
+        }
+        // outside the radius
+        assert_eq!(rgb[0], 0.0 as Float);
+        // the part above the threshold is spread, not amplified
+        let total: Float = rgb.iter().step_by(3).sum();
+        assert!((total - 19.0 as Float).abs() < 1e-3 as Float, "{}", total);
+        // an image below the threshold is left alone
+        let mut dim: Vec<Float> = (0..3 * 81).map(|i| (i % 7) as Float * 0.1).collect();
+        let expected: Vec<Float> = dim.clone();
+        film.apply_bloom(&mut dim, 1.0 as Float, 2.0 as Float, 1.0 as Float);
+        assert_eq!(dim, expected);
+    }
+
+    /// Writes a dark gray ramp of *width* pixels as a PNG with
+    /// *bit_depth* bits and returns the decoded red channel.
+    fn png_ramp(width: i32, bit_depth: u8) -> Vec<u16> {
+        let filter: Arc<Filter + Sync + Send> = Arc::new(BoxFilter {
+            radius: Vector2f { x: 0.5, y: 0.5 },
+            inv_radius: Vector2f { x: 2.0, y: 2.0 },
+        });
+        let film: Film = Film::new(
+            Point2i { x: width, y: 1 },
+            Bounds2f {
+                p_min: Point2f { x: 0.0, y: 0.0 },
+                p_max: Point2f { x: 1.0, y: 1.0 },
+            },
+            filter,
+            35.0 as Float,
+            String::from("test.png"),
+            1.0 as Float,
+            std::f32::INFINITY,
+            bit_depth,
+            None,
+            ColorSpace::SRgb,
+        );
+        let mut rgb: Vec<Float> = Vec::new();
+        for x in 0..width {
+            let v: Float = 0.01 as Float * x as Float / (width - 1) as Float;
+            rgb.extend_from_slice(&[v, v, v]);
+        }
+        let path = std::env::temp_dir().join(format!("pbrt_ramp_{}.png", bit_depth));
+        film.write_png(&rgb, &path);
+        // the image crate strips 16-bit samples, read them raw instead
+        let mut decoder = png::Decoder::new(File::open(&path).unwrap());
+        decoder.set(png::Transformations::IDENTITY);
+        let (info, mut reader) = decoder.read_info().unwrap();
+        assert_eq!(info.color_type, png::ColorType::RGB);
+        assert_eq!(info.bit_depth as u8, bit_depth);
+        let mut data: Vec<u8> = vec![0_u8; info.buffer_size()];
+        reader.next_frame(&mut data).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        if bit_depth == 16_u8 {
+            // samples are stored big-endian
+            data.chunks(6)
+                .map(|c| (c[0] as u16) << 8 | c[1] as u16)
+                .collect()
+        } else {
+            data.chunks(3).map(|c| c[0] as u16).collect()
+        }
+    }
+
+    #[test]
+    fn png_16_bit_round_trip_has_finer_steps() {
+        let width: i32 = 64_i32;
+        let ramp_16: Vec<u16> = png_ramp(width, 16_u8);
+        let ramp_8: Vec<u16> = png_ramp(width, 8_u8);
+        for x in 0..width as usize {
+            let v: Float = 0.01 as Float * x as Float / (width - 1) as Float;
+            let expected: Float = 65535.0 as Float * gamma_correct(v) + 0.5;
+            assert_eq!(ramp_16[x], expected as u16);
+        }
+        // every pixel of the 16-bit ramp is distinct, the 8-bit one bands
+        assert!(ramp_16.windows(2).all(|w| w[0] < w[1]));
+        assert!(ramp_8.windows(2).any(|w| w[0] == w[1]));
+    }
+
+    #[test]
+    fn unsupported_bit_depths_fall_back_to_8() {
+        assert_eq!(png_bit_depth(8), 8_u8);
+        assert_eq!(png_bit_depth(16), 16_u8);
+        assert_eq!(png_bit_depth(12), 8_u8);
+        assert_eq!(png_bit_depth(32), 8_u8);
+    }
+
+    #[cfg(feature = "openexr")]
+    #[test]
+    fn exr_chromaticities_are_inserted_into_the_header() {
+        // magic, version, one int attribute, end of header
+        let mut data: Vec<u8> = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+        data.extend_from_slice(b"foo\0int\0");
+        data.extend_from_slice(&[4, 0, 0, 0, 7, 0, 0, 0, 0]);
+        // offset table (two chunks) and the chunks
+        let table_start: usize = data.len();
+        let chunks: Vec<u8> = vec![1, 2, 3, 4, 5, 6];
+        let mut table: [u8; 16] = [0_u8; 16];
+        LittleEndian::write_u64(&mut table[0..8], (table_start + 16) as u64);
+        LittleEndian::write_u64(&mut table[8..16], (table_start + 19) as u64);
+        data.extend_from_slice(&table);
+        data.extend_from_slice(&chunks);
+        let chromaticities: [Float; 8] = ColorSpace::Rec2020.chromaticities();
+        let mut patched: Vec<u8> = data.clone();
+        add_exr_chromaticities(&mut patched, &chromaticities);
+        // the new attribute follows the existing one
+        let name: &[u8] = b"chromaticities\0chromaticities\0";
+        let start: usize = table_start - 1;
+        assert_eq!(&patched[..start], &data[..start]);
+        assert_eq!(&patched[start..start + name.len()], name);
+        let value: usize = start + name.len();
+        assert_eq!(LittleEndian::read_i32(&patched[value..value + 4]), 32_i32);
+        for i in 0..8 {
+            let c: f32 = LittleEndian::read_f32(&patched[value + 4 + 4 * i..value + 8 + 4 * i]);
+            assert_eq!(c, chromaticities[i] as f32);
+        }
+        // the chunks moved, and the offsets still point at them
+        let new_table: usize = value + 36 + 1;
+        assert_eq!(patched[new_table - 1], 0_u8);
+        let first: usize = LittleEndian::read_u64(&patched[new_table..new_table + 8]) as usize;
+        let second: usize =
+            LittleEndian::read_u64(&patched[new_table + 8..new_table + 16]) as usize;
+        assert_eq!(first, new_table + 16);
+        assert_eq!(&patched[first..], &chunks[..]);
+        assert_eq!(&patched[second..], &chunks[3..]);
+    }
+
+    /// Raw JSON value of the (unique) *key* in *json*.
+    fn json_value<'a>(json: &'a str, key: &str) -> &'a str {
+        let pattern: String = format!("\"{}\": ", key);
+        let start: usize = json.find(&pattern).expect("missing key") + pattern.len();
+        let end: usize = json[start..]
+            .find(|c: char| c == ',' || c == '\n' || c == '}')
+            .unwrap_or(json.len() - start);
+        &json[start..start + end]
+    }
+
+    #[test]
+    fn metadata_json_round_trip() {
+        let mut metadata: RenderMetadata =
+            RenderMetadata::new(String::from("water.pbrt"), 64_i64, String::from("perspective"));
+        metadata.render_time = 12.5_f64;
+        metadata.camera_params = vec![
+            (String::from("fov"), 0.1 as Float),
+            (String::from("focaldistance"), std::f32::INFINITY),
+            (String::from("lensradius"), std::f32::NAN),
+        ];
+        let json: String = metadata.to_json();
+        assert_eq!(json_value(&json, "scene"), "\"water.pbrt\"");
+        assert_eq!(json_value(&json, "samples_per_pixel").parse::<i64>(), Ok(64_i64));
+        assert_eq!(json_value(&json, "render_time").parse::<f64>(), Ok(12.5_f64));
+        assert_eq!(json_value(&json, "fov").parse::<Float>(), Ok(0.1 as Float));
+        // JSON has no representation for infinities and NaNs
+        assert_eq!(json_value(&json, "focaldistance"), "null");
+        assert_eq!(json_value(&json, "lensradius"), "null");
+        metadata.render_time = std::f64::NAN;
+        assert_eq!(json_value(&metadata.to_json(), "render_time"), "null");
+    }
+}
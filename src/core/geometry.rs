@@ -97,7 +97,7 @@
 //! ```rust
 //! extern crate pbrt;
 //!
-//! use pbrt::core::geometry::{Ray, Point3f, Vector3f};
+//! use pbrt::core::geometry::{Ray, Point3f, Vector3f, VisibilityFlags};
 //!
 //! fn main() {
 //!     let origin = Point3f {
@@ -115,6 +115,7 @@
 //!         d: direction,
 //!         t_max: std::f32::INFINITY,
 //!         time: 0.0,
+//!         ray_type: VisibilityFlags::Untyped as u8,
 //!         medium: None,
 //!         differential: None,
 //!     };
@@ -1164,10 +1165,8 @@ pub fn pnt3_offset_ray_origin(
     for i in 0..3 {
         if offset[i] > 0.0 as Float {
             po[i] = next_float_up(po[i]);
-        } else {
-            if offset[i] < 0.0 as Float {
-                po[i] = next_float_down(po[i]);
-            }
+        } else if offset[i] < 0.0 as Float {
+            po[i] = next_float_down(po[i]);
         }
     }
     po
@@ -1687,6 +1686,9 @@ impl<T> Bounds3<T> {
         }
         o
     }
+    /// Computes the center and radius of a sphere which bounds the
+    /// box, e.g. the world bounds of a scene for infinite and distant
+    /// lights. For degenerate (empty) boxes the radius is zero.
     pub fn bounding_sphere(b: &Bounds3f, center: &mut Point3f, radius: &mut Float) {
         let p_min: Point3f = b.p_min as Point3f;
         let p_max: Point3f = b.p_max as Point3f;
@@ -1703,6 +1705,9 @@ impl<T> Bounds3<T> {
 }
 
 impl Bounds3<Float> {
+    /// Linearly interpolates between the corners of the box by the
+    /// given amount in each dimension, e.g. (0.5, 0.5, 0.5) returns
+    /// the center.
     pub fn lerp(&self, t: &Point3f) -> Point3f {
         Point3f {
             x: lerp(t.x, self.p_min.x as Float, self.p_max.x as Float),
@@ -1893,6 +1898,22 @@ pub fn bnd3_expand(b: &Bounds3f, delta: Float) -> Bounds3f {
     )
 }
 
+/// Categories of rays. A primitive stores a bitwise combination of
+/// them to hide itself from specific kinds of rays, e.g. to be
+/// visible to the camera but not cast shadows.
+#[repr(u8)]
+pub enum VisibilityFlags {
+    /// rays without a category (e.g. light or connection rays) see
+    /// every primitive
+    Untyped = 0,
+    Camera = 1,
+    Shadow = 2,
+    Diffuse = 4,
+    Glossy = 8,
+    Reflection = 16,
+    All = 31,
+}
+
 #[derive(Default, Clone)]
 pub struct Ray {
     /// origin
@@ -1903,6 +1924,8 @@ pub struct Ray {
     pub t_max: Float,
     /// used for animations
     pub time: Float,
+    /// category of the ray (one of the **VisibilityFlags**)
+    pub ray_type: u8,
     pub medium: Option<Arc<Medium + Send + Sync>>,
     /// in C++: 'class RayDifferential : public Ray'
     pub differential: Option<RayDifferential>,
@@ -1914,8 +1937,13 @@ impl Ray {
         self.o + self.d * t
     }
     // from class RayDifferential
+
+    /// Scales the offsets of the auxiliary rays by *s*. The cameras
+    /// generate differentials for a spacing of one pixel, the
+    /// integrators scale them by 1/sqrt(spp) to account for the
+    /// smaller footprint of each sample.
     pub fn scale_differentials(&mut self, s: Float) {
-        if let Some(d) = self.differential.iter_mut().next() {
+        if let Some(ref mut d) = self.differential {
             d.rx_origin = self.o + (d.rx_origin - self.o) * s;
             d.ry_origin = self.o + (d.ry_origin - self.o) * s;
             d.rx_direction = self.d + (d.rx_direction - self.d) * s;
@@ -1931,3 +1959,198 @@ pub struct RayDifferential {
     pub rx_direction: Vector3f,
     pub ry_direction: Vector3f,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_ray_origin_moves_past_the_error_bound_towards_w() {
+        let p: Point3f = Point3f {
+            x: 0.3 as Float,
+            y: -1.7 as Float,
+            z: 2.9 as Float,
+        };
+        let p_error: Vector3f = Vector3f {
+            x: 1e-5 as Float,
+            y: 2e-5 as Float,
+            z: 4e-6 as Float,
+        };
+        let n: Normal3f = Normal3f {
+            x: 0.48 as Float,
+            y: -0.6 as Float,
+            z: 0.64 as Float,
+        };
+        let d: Float = nrm_dot_vec3(&nrm_abs(&n), &p_error);
+        for w in &[Vector3f::from(n), -Vector3f::from(n)] {
+            let w: Vector3f = *w
+                + Vector3f {
+                    x: 0.1 as Float,
+                    y: 0.2 as Float,
+                    z: 0.0 as Float,
+                };
+            let po: Point3f = pnt3_offset_ray_origin(&p, &p_error, &n, &w);
+            let moved: Vector3f = po - p;
+            // same side as w
+            assert!(vec3_dot_nrm(&moved, &n) * vec3_dot_nrm(&w, &n) > 0.0 as Float);
+            // each coordinate moved at least as far as the error bound
+            let side: Float = vec3_dot_nrm(&w, &n).signum();
+            for i in 0..3 {
+                let offset: Float = Vector3f::from(n)[i] * d * side;
+                assert!(moved[i] * offset > 0.0 as Float);
+                assert!(moved[i].abs() >= offset.abs(), "{} < {}", moved[i], offset);
+            }
+        }
+    }
+
+    fn unit_cube() -> Bounds3f {
+        Bounds3f::new(
+            Point3f {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Point3f {
+                x: 1.0,
+                y: 1.0,
+                z: 1.0,
+            },
+        )
+    }
+
+    #[test]
+    fn unit_cube_bounding_sphere_and_center() {
+        let b: Bounds3f = unit_cube();
+        let mut center: Point3f = Point3f::default();
+        let mut radius: Float = 0.0 as Float;
+        Bounds3f::bounding_sphere(&b, &mut center, &mut radius);
+        assert_eq!((center.x, center.y, center.z), (0.5, 0.5, 0.5));
+        assert!((radius - (3.0 as Float).sqrt() / 2.0 as Float).abs() < 1e-6 as Float);
+        let mid: Point3f = b.lerp(&Point3f {
+            x: 0.5,
+            y: 0.5,
+            z: 0.5,
+        });
+        assert_eq!((mid.x, mid.y, mid.z), (center.x, center.y, center.z));
+    }
+
+    #[test]
+    fn expand_and_union_point_grow_the_box() {
+        let b: Bounds3f = bnd3_expand(&unit_cube(), 0.25 as Float);
+        assert_eq!((b.p_min.x, b.p_min.y, b.p_min.z), (-0.25, -0.25, -0.25));
+        assert_eq!((b.p_max.x, b.p_max.y, b.p_max.z), (1.25, 1.25, 1.25));
+        let p: Point3f = Point3f {
+            x: 2.0,
+            y: 0.5,
+            z: -1.0,
+        };
+        let u: Bounds3f = bnd3_union_pnt3(&unit_cube(), &p);
+        assert_eq!((u.p_min.x, u.p_min.y, u.p_min.z), (0.0, 0.0, -1.0));
+        assert_eq!((u.p_max.x, u.p_max.y, u.p_max.z), (2.0, 1.0, 1.0));
+        // points inside don't change the box
+        let inside: Point3f = Point3f {
+            x: 0.25,
+            y: 0.5,
+            z: 0.75,
+        };
+        let same: Bounds3f = bnd3_union_pnt3(&unit_cube(), &inside);
+        assert_eq!((same.p_min.x, same.p_min.y, same.p_min.z), (0.0, 0.0, 0.0));
+        assert_eq!((same.p_max.x, same.p_max.y, same.p_max.z), (1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn scale_differentials_scales_the_offsets() {
+        let o: Point3f = Point3f {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        };
+        let d: Vector3f = Vector3f {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        };
+        let mut ray: Ray = Ray {
+            o: o,
+            d: d,
+            t_max: std::f32::INFINITY,
+            differential: Some(RayDifferential {
+                rx_origin: o + Vector3f {
+                    x: 0.5,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                ry_origin: o + Vector3f {
+                    x: 0.0,
+                    y: -0.25,
+                    z: 0.0,
+                },
+                rx_direction: d + Vector3f {
+                    x: 0.125,
+                    y: 0.0,
+                    z: 0.0,
+                },
+                ry_direction: d,
+            }),
+            ..Ray::default()
+        };
+        ray.scale_differentials(0.5 as Float);
+        let diff: RayDifferential = ray.differential.unwrap();
+        let rx: Vector3f = diff.rx_origin - o;
+        let ry: Vector3f = diff.ry_origin - o;
+        assert_eq!((rx.x, rx.y, rx.z), (0.25, 0.0, 0.0));
+        assert_eq!((ry.x, ry.y, ry.z), (0.0, -0.125, 0.0));
+        let dx: Vector3f = diff.rx_direction - d;
+        assert_eq!((dx.x, dx.y, dx.z), (0.0625, 0.0, 0.0));
+        assert_eq!(diff.ry_direction.z, 1.0);
+        // rays without differentials are left alone
+        let mut plain: Ray = Ray::default();
+        plain.scale_differentials(0.5 as Float);
+        assert!(plain.differential.is_none());
+    }
+
+    #[test]
+    fn bounds2_helpers() {
+        let tile: Bounds2i = Bounds2i {
+            p_min: Point2i { x: 0, y: 0 },
+            p_max: Point2i { x: 16, y: 8 },
+        };
+        let d: Vector2i = tile.diagonal();
+        assert_eq!((d.x, d.y), (16, 8));
+        assert_eq!(tile.area(), 128);
+        // the upper bounds are excluded
+        assert!(pnt2_inside_exclusive(&Point2i { x: 0, y: 0 }, &tile));
+        assert!(pnt2_inside_exclusive(&Point2i { x: 15, y: 7 }, &tile));
+        assert!(!pnt2_inside_exclusive(&Point2i { x: 16, y: 7 }, &tile));
+        assert!(!pnt2_inside_exclusive(&Point2i { x: 15, y: 8 }, &tile));
+        // overlapping and disjoint tiles
+        let shifted: Bounds2i = Bounds2i {
+            p_min: Point2i { x: 8, y: 4 },
+            p_max: Point2i { x: 24, y: 12 },
+        };
+        let overlap: Bounds2i = bnd2_intersect_bnd2(&tile, &shifted);
+        assert_eq!((overlap.p_min.x, overlap.p_min.y), (8, 4));
+        assert_eq!((overlap.p_max.x, overlap.p_max.y), (16, 8));
+        let disjoint: Bounds2i = Bounds2i {
+            p_min: Point2i { x: 20, y: 0 },
+            p_max: Point2i { x: 30, y: 8 },
+        };
+        let empty: Bounds2i = bnd2_intersect_bnd2(&tile, &disjoint);
+        assert!(empty.p_min.x >= empty.p_max.x);
+        for p in &[Point2i { x: 16, y: 4 }, Point2i { x: 20, y: 4 }] {
+            assert!(!pnt2_inside_exclusive(p, &empty));
+        }
+        // film crop windows
+        let window: Bounds2f = Bounds2f {
+            p_min: Point2f { x: -1.0, y: -0.5 },
+            p_max: Point2f { x: 3.0, y: 0.5 },
+        };
+        let center: Point2f = window.lerp(&Point2f { x: 0.5, y: 0.5 });
+        assert_eq!((center.x, center.y), (1.0, 0.0));
+        let corner: Point2f = window.lerp(&Point2f { x: 1.0, y: 0.0 });
+        assert_eq!((corner.x, corner.y), (3.0, -0.5));
+        let extent: Vector2f = window.diagonal();
+        assert_eq!((extent.x, extent.y), (4.0, 1.0));
+        assert_eq!(window.area(), 4.0 as Float);
+    }
+}
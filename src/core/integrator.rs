@@ -12,6 +12,7 @@ use core::light::is_delta_light;
 use core::light::{Light, VisibilityTester};
 use core::pbrt::{Float, Spectrum};
 use core::primitive::Primitive;
+use core::profile::{Prof, ProfilePhase};
 use core::reflection::BxdfType;
 use core::sampler::Sampler;
 use core::sampling::power_heuristic;
@@ -20,6 +21,11 @@ use core::scene::Scene;
 
 // see integrator.h
 
+/// Hard upper limit for the path depth of all integrators. It is
+/// applied independently of the user's **maxdepth** to avoid endless
+/// recursion (e.g. between two parallel mirrors).
+pub const MAX_RAY_DEPTH: u32 = 64_u32;
+
 pub trait SamplerIntegrator {
     // TODO: use Sampler trait
     fn preprocess(&mut self, scene: &Scene, sampler: &mut Box<Sampler + Send + Sync>);
@@ -37,6 +43,77 @@ pub trait SamplerIntegrator {
     fn get_pixel_bounds(&self) -> Bounds2i;
 }
 
+/// Settings for per pixel adaptive sampling: every pixel gets at
+/// least *min_samples*, after that sampling stops as soon as the
+/// relative standard error of the pixel's luminance drops below
+/// *error_threshold* (or *max_samples* were taken). Pixels whose
+/// samples all agree so far need at least 3 / *error_threshold*
+/// samples (see **is_converged()**).
+#[derive(Debug, Copy, Clone)]
+pub struct AdaptiveSampling {
+    pub min_samples: i64,
+    pub max_samples: i64,
+    pub error_threshold: Float,
+}
+
+impl AdaptiveSampling {
+    pub fn new(min_samples: i64, max_samples: i64, error_threshold: Float) -> Self {
+        assert!(min_samples > 0_i64);
+        assert!(max_samples >= min_samples);
+        AdaptiveSampling {
+            min_samples: min_samples,
+            max_samples: max_samples,
+            error_threshold: error_threshold,
+        }
+    }
+    pub fn is_converged(&self, estimate: &PixelEstimate) -> bool {
+        if estimate.n >= self.max_samples {
+            return true;
+        }
+        if estimate.n < self.min_samples {
+            return false;
+        }
+        if estimate.variance() == 0.0 as Float {
+            // identical samples don't prove a flat pixel: rare bright
+            // paths (e.g. caustics) might just not have been found
+            // yet. After _n_ misses their probability can still be up
+            // to 3 / _n_ ("rule of three", 95% confidence).
+            return estimate.n as Float * self.error_threshold >= 3.0 as Float;
+        }
+        estimate.relative_error() < self.error_threshold
+    }
+}
+
+/// Running mean and variance (Welford's algorithm) of the luminance
+/// of the samples taken for a single pixel.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PixelEstimate {
+    pub n: i64,
+    pub mean: Float,
+    pub m2: Float,
+}
+
+impl PixelEstimate {
+    pub fn add(&mut self, y: Float) {
+        self.n += 1_i64;
+        let delta: Float = y - self.mean;
+        self.mean += delta / self.n as Float;
+        self.m2 += delta * (y - self.mean);
+    }
+    pub fn variance(&self) -> Float {
+        if self.n < 2_i64 {
+            return 0.0 as Float;
+        }
+        self.m2 / (self.n - 1_i64) as Float
+    }
+    /// Standard error of the mean, relative to the mean (black
+    /// pixels are compared against a small absolute value instead).
+    pub fn relative_error(&self) -> Float {
+        let std_error: Float = (self.variance() / self.n as Float).sqrt();
+        std_error / self.mean.abs().max(1e-3 as Float)
+    }
+}
+
 // see integrator.cpp
 
 /// Most basic direct lighting strategy.
@@ -47,14 +124,18 @@ pub fn uniform_sample_all_lights(
     n_light_samples: &Vec<i32>,
     handle_media: bool,
 ) -> Spectrum {
-    // TODO: ProfilePhase p(Prof::DirectLighting);
+    let _p = ProfilePhase::new(Prof::DirectLighting);
     let mut l: Spectrum = Spectrum::new(0.0);
     for j in 0..scene.lights.len() {
         // accumulate contribution of _j_th light to _L_
         let ref light = scene.lights[j];
         let n_samples = n_light_samples[j];
-        let u_light_array: Vec<Point2f> = sampler.get_2d_array(n_samples);
-        let u_scattering_array: Vec<Point2f> = sampler.get_2d_array(n_samples);
+        let mut u_light_array: Vec<Point2f> = Vec::new();
+        let mut u_scattering_array: Vec<Point2f> = Vec::new();
+        if sampler.supports_arrays() {
+            u_light_array = sampler.get_2d_array(n_samples);
+            u_scattering_array = sampler.get_2d_array(n_samples);
+        }
         if u_light_array.is_empty() || u_scattering_array.is_empty() {
             // use a single sample for illumination from _light_
             let u_light: Point2f = sampler.get_2d();
@@ -99,7 +180,7 @@ pub fn uniform_sample_one_light(
     handle_media: bool,
     light_distrib: Option<&Distribution1D>,
 ) -> Spectrum {
-    // TODO: ProfilePhase p(Prof::DirectLighting);
+    let _p = ProfilePhase::new(Prof::DirectLighting);
 
     // randomly choose a single light to sample, _light_
     let n_lights: usize = scene.lights.len();
@@ -168,6 +249,7 @@ pub fn estimate_direct(
         wo: it.get_wo(),
         n: it.get_n(),
         medium_interface: it.get_medium_interface(),
+        uv: Point2f::default(),
     };
     let mut li: Spectrum = light.sample_li(
         &it_common,
@@ -277,26 +359,22 @@ pub fn estimate_direct(
                 tr = tr_spectrum; // copy return value
                 if let Some(light_isect) = light_isect_opt {
                     found_surface_interaction = true;
-                    if let Some(primitive) = light_isect.primitive {
-                        if let Some(area_light) = primitive.get_area_light() {
-                            let pa = &*area_light as *const _ as *const usize;
-                            let pl = &*light as *const _ as *const usize;
-                            if pa == pl {
-                                li = light_isect.le(&-wi);
-                            }
+                    if let Some(area_light) = light_isect.get_area_light() {
+                        let pa = &*area_light as *const _ as *const usize;
+                        let pl = &*light as *const _ as *const usize;
+                        if pa == pl {
+                            li = light_isect.le(&-wi);
                         }
                     }
                 }
             } else {
                 if let Some(light_isect) = scene.intersect(&mut ray) {
                     found_surface_interaction = true;
-                    if let Some(primitive) = light_isect.primitive {
-                        if let Some(area_light) = primitive.get_area_light() {
-                            let pa = &*area_light as *const _ as *const usize;
-                            let pl = &*light as *const _ as *const usize;
-                            if pa == pl {
-                                li = light_isect.le(&-wi);
-                            }
+                    if let Some(area_light) = light_isect.get_area_light() {
+                        let pa = &*area_light as *const _ as *const usize;
+                        let pl = &*light as *const _ as *const usize;
+                        if pa == pl {
+                            li = light_isect.le(&-wi);
                         }
                     }
                 }
@@ -312,6 +390,15 @@ pub fn estimate_direct(
     ld
 }
 
+/// Sum of **Light::le()** over all infinite lights for a *ray* which escaped the scene.
+pub fn escaped_radiance(scene: &Scene, ray: &mut Ray) -> Spectrum {
+    let mut l: Spectrum = Spectrum::default();
+    for light in &scene.infinite_lights {
+        l += light.le(ray);
+    }
+    l
+}
+
 /// The light to start each photon path from is chosen according to a
 /// PDF defined by the lights' respective powers.
 pub fn compute_light_power_distribution(scene: &Scene) -> Option<Arc<Distribution1D>> {
@@ -325,3 +412,67 @@ pub fn compute_light_power_distribution(scene: &Scene) -> Option<Arc<Distributio
     }
     Some(Arc::new(Distribution1D::new(light_power)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use accelerators::bvh::{BVHAccel, SplitMethod};
+    use core::medium::MediumInterface;
+    use core::primitive::GeometricPrimitive;
+    use core::shape::Shape;
+    use core::transform::Transform;
+    use lights::infinite::InfiniteAreaLight;
+    use lights::point::PointLight;
+    use shapes::sphere::Sphere;
+
+    fn scene(lights: Vec<Arc<Light + Sync + Send>>) -> Scene {
+        let t: Transform = Transform::default();
+        let sphere: Arc<Shape + Send + Sync> = Arc::new(Sphere::new(
+            t,
+            t,
+            false,
+            1.0 as Float,
+            -1.0 as Float,
+            1.0 as Float,
+            360.0 as Float,
+        ));
+        let primitives: Vec<Arc<Primitive + Sync + Send>> =
+            vec![Arc::new(GeometricPrimitive::new(sphere, None, None, None))];
+        let bvh: Arc<BVHAccel> = Arc::new(BVHAccel::new(primitives, 4, SplitMethod::SAH));
+        Scene::new(bvh, lights)
+    }
+
+    fn constant_sky(l: Float) -> Arc<Light + Sync + Send> {
+        Arc::new(InfiniteAreaLight::new(
+            &Transform::default(),
+            &Spectrum::new(l),
+            1_i32,
+            String::new(),
+        ))
+    }
+
+    #[test]
+    fn escaped_rays_sum_all_infinite_lights() {
+        let point: Arc<Light + Sync + Send> = Arc::new(PointLight::new(
+            &Transform::default(),
+            &MediumInterface::default(),
+            &Spectrum::new(10.0 as Float),
+        ));
+        let mut ray: Ray = Ray::default();
+        ray.d.z = 1.0 as Float;
+        let one: Scene = scene(vec![constant_sky(0.25 as Float), point.clone()]);
+        assert_eq!(one.infinite_lights.len(), 1_usize);
+        let l: Float = escaped_radiance(&one, &mut ray)[0];
+        assert!((l - 0.25 as Float).abs() < 1e-5 as Float, "{}", l);
+        // a sky dome and a constant fill
+        let two: Scene = scene(vec![
+            constant_sky(0.25 as Float),
+            point,
+            constant_sky(0.5 as Float),
+        ]);
+        assert_eq!(two.infinite_lights.len(), 2_usize);
+        let l: Float = escaped_radiance(&two, &mut ray)[0];
+        assert!((l - 0.75 as Float).abs() < 1e-5 as Float, "{}", l);
+        assert!(escaped_radiance(&scene(Vec::new()), &mut ray).is_black());
+    }
+}
@@ -15,7 +15,8 @@ use core::bssrdf::TabulatedBssrdf;
 use core::geometry::{
     nrm_faceforward_nrm, pnt3_offset_ray_origin, vec3_cross_vec3, vec3_dot_nrm, vec3_dot_vec3,
 };
-use core::geometry::{Normal3f, Point2f, Point3f, Ray, Vector3f};
+use core::geometry::{Normal3f, Point2f, Point3f, Ray, Vector3f, VisibilityFlags};
+use core::light::AreaLight;
 use core::material::TransportMode;
 use core::medium::{Medium, MediumInterface, PhaseFunction};
 use core::pbrt::SHADOW_EPSILON;
@@ -51,6 +52,9 @@ pub struct InteractionCommon {
     pub wo: Vector3f,
     pub n: Normal3f,
     pub medium_interface: Option<Arc<MediumInterface>>,
+    /// Surface parameterization of the point, if known (e.g. for
+    /// points sampled on a shape), used for textured emission
+    pub uv: Point2f,
 }
 
 impl InteractionCommon {
@@ -61,6 +65,7 @@ impl InteractionCommon {
             d: *d,
             t_max: std::f32::INFINITY,
             time: self.time,
+            ray_type: VisibilityFlags::Reflection as u8,
             differential: None,
             medium: self.get_medium(d),
         }
@@ -73,6 +78,7 @@ impl InteractionCommon {
             d: d,
             t_max: 1.0 - SHADOW_EPSILON,
             time: self.time,
+            ray_type: VisibilityFlags::Shadow as u8,
             differential: None,
             medium: self.get_medium(&d),
         }
@@ -87,6 +93,7 @@ impl InteractionCommon {
             d: d,
             t_max: 1.0 - SHADOW_EPSILON,
             time: self.time,
+            ray_type: VisibilityFlags::Shadow as u8,
             differential: None,
             medium: self.get_medium(&d),
         }
@@ -115,6 +122,9 @@ pub struct Shading {
     pub dpdv: Vector3f,
     pub dndu: Normal3f,
     pub dndv: Normal3f,
+    /// Set by **Material::bump()**, the shading normal may then be
+    /// tilted far away from the geometric normal
+    pub bumped: bool,
 }
 
 #[derive(Default, Clone)]
@@ -215,6 +225,7 @@ impl Interaction for MediumInteraction {
             d: *d,
             t_max: std::f32::INFINITY,
             time: self.time,
+            ray_type: VisibilityFlags::Reflection as u8,
             differential: None,
             medium: self.get_medium(d),
         }
@@ -310,6 +321,7 @@ impl<'p, 's> SurfaceInteraction<'p, 's> {
             dpdv: Vector3f::from(*dpdv),
             dndu: *dndu,
             dndv: *dndv,
+            bumped: false,
         };
         if let Some(shape) = sh {
             // adjust normal based on orientation and handedness
@@ -488,20 +500,28 @@ impl<'p, 's> SurfaceInteraction<'p, 's> {
             self.dpdy = Vector3f::default();
         }
     }
-    pub fn le(&self, w: &Vector3f) -> Spectrum {
+    /// Returns the area light of the hit primitive (if it's
+    /// emissive), e.g. to evaluate **pdf_li()** for MIS.
+    pub fn get_area_light(&self) -> Option<Arc<AreaLight + Send + Sync>> {
         if let Some(primitive) = self.primitive {
-            if let Some(area_light) = primitive.get_area_light() {
-                // create InteractionCommon from self
-                let interaction: InteractionCommon = InteractionCommon {
-                    p: self.p,
-                    time: self.time,
-                    p_error: self.p_error,
-                    wo: self.wo,
-                    n: self.n,
-                    medium_interface: None,
-                };
-                return area_light.l(&interaction, w);
-            }
+            primitive.get_area_light()
+        } else {
+            None
+        }
+    }
+    pub fn le(&self, w: &Vector3f) -> Spectrum {
+        if let Some(area_light) = self.get_area_light() {
+            // create InteractionCommon from self
+            let interaction: InteractionCommon = InteractionCommon {
+                p: self.p,
+                time: self.time,
+                p_error: self.p_error,
+                wo: self.wo,
+                n: self.n,
+                medium_interface: None,
+                uv: self.uv,
+            };
+            return area_light.l(&interaction, w);
         }
         Spectrum::default()
     }
@@ -521,6 +541,7 @@ impl<'p, 's> Interaction for SurfaceInteraction<'p, 's> {
             d: *d,
             t_max: std::f32::INFINITY,
             time: self.time,
+            ray_type: VisibilityFlags::Reflection as u8,
             differential: None,
             medium: self.get_medium(d),
         }
@@ -39,6 +39,9 @@ pub trait Light {
     fn preprocess(&self, scene: &Scene);
     fn le(&self, _ray: &mut Ray) -> Spectrum;
     fn pdf_li(&self, iref: &Interaction, wi: Vector3f) -> Float;
+    /// Samples a ray leaving the light (used by particle tracing
+    /// methods like BDPT and SPPM). Returns the emitted radiance and
+    /// the positional and directional densities separately.
     fn sample_le(
         &self,
         u1: &Point2f,
@@ -49,6 +52,9 @@ pub trait Light {
         pdf_pos: &mut Float,
         pdf_dir: &mut Float,
     ) -> Spectrum;
+    /// Returns the positional and directional densities for a ray
+    /// leaving the light. Delta lights return zero for the delta
+    /// component, since it can't be hit by chance.
     fn pdf_le(&self, ray: &Ray, n_light: &Normal3f, pdf_pos: &mut Float, pdf_dir: &mut Float);
     fn get_flags(&self) -> u8;
     fn get_n_samples(&self) -> i32;
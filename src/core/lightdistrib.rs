@@ -13,6 +13,7 @@ use core::lowdiscrepancy::radical_inverse;
 use core::medium::MediumInterface;
 use core::pbrt::clamp_t;
 use core::pbrt::{Float, Spectrum};
+use core::profile::{Prof, ProfilePhase};
 use core::sampling::Distribution1D;
 use core::scene::Scene;
 
@@ -190,6 +191,7 @@ impl SpatialLightDistribution {
                 },
                 n: Normal3f::default(),
                 medium_interface: Some(Arc::new(MediumInterface::default())),
+                uv: Point2f::default(),
             };
             // Use the next two Halton dimensions to sample a point on the
             // light source.
@@ -241,7 +243,7 @@ impl SpatialLightDistribution {
 
 impl LightDistribution for SpatialLightDistribution {
     fn lookup(&self, p: &Point3f) -> Arc<Distribution1D> {
-        // TODO: ProfilePhase _(Prof::LightDistribLookup);
+        let _p = ProfilePhase::new(Prof::LightDistribLookup);
         // TODO: ++nLookups;
 
         // first, compute integer voxel coordinates for the given
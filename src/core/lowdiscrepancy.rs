@@ -171,6 +171,136 @@ pub const PRIME_SUMS: [u32; PRIME_TABLE_SIZE as usize] = [
     3643424, 3651303, 3659186, 3667087, 3674994,
 ];
 
+/// Generator matrices for the first dimension of the
+/// **MaxMinDistSampler**, one row per power of two samples per pixel
+/// (row *m* holds the *m* columns used for 2^m samples, the rest are
+/// zero). Each matrix is a product of unit lower and upper triangular
+/// matrices, so together with *i / 2^m* as the other coordinate the
+/// points form a (0,m,2)-net; the entries were found by a local search
+/// maximizing the toroidal minimum distance between the points, after
+/// Grünschloß et al., "(t, m, s)-Nets and Maximized Minimum Distance".
+pub const C_MAX_MIN_DIST: [[u32; 32]; 17] = [
+    [
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0x80000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0x80000000, 0xc0000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0xa0000000, 0x40000000, 0x80000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0xc0000000, 0x50000000, 0x20000000, 0x30000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0x88000000, 0xd8000000, 0x20000000, 0x40000000, 0x80000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0x80000000, 0xe0000000, 0x20000000, 0xf0000000, 0xf8000000, 0x0c000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0xc0000000, 0x98000000, 0xe8000000, 0x88000000, 0x7a000000, 0x04000000, 0x06000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0x80000000, 0xc2000000, 0x32000000, 0x14000000, 0x3a000000, 0x3c000000, 0xc1000000,
+        0x01000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0x81000000, 0xc0800000, 0xa9800000, 0x10000000, 0x99800000, 0x3e000000, 0xc4000000,
+        0xfb800000, 0x80000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0x80400000, 0xf2000000, 0xb0000000, 0xe2400000, 0xfb000000, 0xff000000, 0x0e400000,
+        0x82800000, 0xff800000, 0xcf000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0x98600000, 0x5d200000, 0x7d000000, 0x35200000, 0x38200000, 0x4cc00000, 0x72200000,
+        0x83e00000, 0x81400000, 0x9ec00000, 0x1ae00000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0x80100000, 0xdc300000, 0xed000000, 0x40300000, 0x21200000, 0x86100000, 0x32300000,
+        0xa0200000, 0x50e00000, 0x9d700000, 0x70400000, 0xad100000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0xb7200000, 0xc6780000, 0x20e80000, 0xde880000, 0x9f000000, 0x7f000000, 0x72e00000,
+        0x9ee80000, 0x26880000, 0x20580000, 0x83e00000, 0x4a580000, 0xd9080000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0xc22c0000, 0x80080000, 0x7e080000, 0x6ca00000, 0x62b80000, 0x07000000, 0x1fa00000,
+        0x8c240000, 0x2a540000, 0x3b4c0000, 0xfa9c0000, 0xb98c0000, 0x06140000, 0x565c0000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0x8b060000, 0xf9600000, 0x5bea0000, 0x68740000, 0xc3220000, 0xe6520000, 0xc43e0000,
+        0x2dec0000, 0x389e0000, 0xd10c0000, 0xb9fe0000, 0x2b080000, 0xb0f20000, 0x390a0000,
+        0xf3c60000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+    [
+        0xc7830000, 0x96be0000, 0xb7be0000, 0xf8150000, 0x5e650000, 0xd14d0000, 0xd2060000,
+        0x93e40000, 0xda8f0000, 0xa55c0000, 0x6c170000, 0xc1890000, 0xf6640000, 0xa0d10000,
+        0x59940000, 0x80510000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000, 0x00000000,
+        0x00000000, 0x00000000, 0x00000000, 0x00000000,
+    ],
+];
+
 /// The bits of an integer quantity can be efficiently reversed with a
 /// series of logical bit operations.
 pub fn reverse_bits_32(n: u32) -> u32 {
@@ -202,6 +332,23 @@ pub fn inverse_radical_inverse(base: u8, inverse: u64, n_digits: u64) -> u64 {
     index
 }
 
+/// Computes the sample value for index *a* from the generator matrix
+/// *c* (one 32 bit column per bit of *a*), XORed with *scramble*.
+pub fn sample_generator_matrix(c: &[u32; 32], a: u32, scramble: u32) -> Float {
+    let mut v: u32 = 0_u32;
+    let mut a: u32 = a;
+    let mut i: usize = 0_usize;
+    while a != 0 {
+        if a & 1 != 0 {
+            v ^= c[i];
+        }
+        a >>= 1;
+        i += 1;
+    }
+    v ^= scramble;
+    (v as Float * hexf32!("0x1.0p-32") as Float).min(FLOAT_ONE_MINUS_EPSILON)
+}
+
 /// Takes a generator matrix *c*, a number of 1D samples to generate
 /// *n*, and stores the corresponding samples in memory at the
 /// location pointed to by *p*.
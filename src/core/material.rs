@@ -88,5 +88,6 @@ pub trait Material {
         let dndu = si.shading.dndu;
         let dndv = si.shading.dndv;
         si.set_shading_geometry(&dpdu, &dpdv, &dndu, &dndv, false);
+        si.shading.bumped = true;
     }
 }
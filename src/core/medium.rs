@@ -10,7 +10,9 @@ use core::geometry::{Point2f, Ray, Vector3f};
 use core::interaction::MediumInteraction;
 use core::pbrt::INV_4_PI;
 use core::pbrt::{Float, Spectrum};
+use core::profile::{Prof, ProfilePhase};
 use core::sampler::Sampler;
+use core::spectrum::SpectrumType;
 
 pub const SUBSURFACE_PARAMETER_TABLE: [MeasuredSS; 47] = [
     // From "A Practical Model for Subsurface Light Transport"
@@ -283,11 +285,11 @@ pub struct HenyeyGreenstein {
 
 impl PhaseFunction for HenyeyGreenstein {
     fn p(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
-        // TODO: ProfilePhase _(Prof::PhaseFuncEvaluation);
+        let _p = ProfilePhase::new(Prof::PhaseFuncEvaluation);
         phase_hg(vec3_dot_vec3(wo, wi), self.g)
     }
     fn sample_p(&self, wo: &Vector3f, wi: &mut Vector3f, u: &Point2f) -> Float {
-        // TODO: ProfilePhase _(Prof::PhaseFuncSampling);
+        let _p = ProfilePhase::new(Prof::PhaseFuncSampling);
         // compute $\cos \theta$ for Henyey--Greenstein sample
         let cos_theta: Float;
         if self.g.abs() < 1e-3 as Float {
@@ -376,8 +378,8 @@ pub fn get_medium_scattering_properties(
     }
     for mss in SUBSURFACE_PARAMETER_TABLE.iter() {
         if name == mss.name {
-            *sigma_a = Spectrum::from_rgb(&mss.sigma_a);
-            *sigma_prime_s = Spectrum::from_rgb(&mss.sigma_prime_s);
+            *sigma_a = Spectrum::from_rgb(&mss.sigma_a, SpectrumType::Illuminant);
+            *sigma_prime_s = Spectrum::from_rgb(&mss.sigma_prime_s, SpectrumType::Illuminant);
             return true;
         }
     }
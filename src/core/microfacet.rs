@@ -5,6 +5,7 @@
 
 // std
 use std::f32::consts::PI;
+use std::sync::Arc;
 // pbrt
 use core::geometry::{spherical_direction, vec3_abs_dot_vec3};
 use core::geometry::{Point2f, Vector3f};
@@ -34,6 +35,12 @@ pub trait MicrofacetDistribution {
     }
     fn sample_wh(&self, wo: &Vector3f, u: &Point2f) -> Vector3f;
     fn get_sample_visible_area(&self) -> bool;
+    /// Returns a rougher copy of the distribution (used for path
+    /// regularization), or None if both alphas are already at least
+    /// *min_alpha*.
+    fn regularize(&self, _min_alpha: Float) -> Option<Arc<MicrofacetDistribution + Send + Sync>> {
+        None
+    }
 }
 
 pub struct TrowbridgeReitzDistribution {
@@ -144,6 +151,16 @@ impl MicrofacetDistribution for TrowbridgeReitzDistribution {
     fn get_sample_visible_area(&self) -> bool {
         self.sample_visible_area
     }
+    fn regularize(&self, min_alpha: Float) -> Option<Arc<MicrofacetDistribution + Send + Sync>> {
+        if self.alpha_x >= min_alpha && self.alpha_y >= min_alpha {
+            return None;
+        }
+        Some(Arc::new(TrowbridgeReitzDistribution::new(
+            self.alpha_x.max(min_alpha),
+            self.alpha_y.max(min_alpha),
+            self.sample_visible_area,
+        )))
+    }
 }
 
 fn trowbridge_reitz_sample_11(
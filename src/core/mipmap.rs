@@ -13,13 +13,14 @@ use core::geometry::{Point2f, Point2i, Vector2f};
 use core::memory::BlockedArray;
 use core::pbrt::{clamp_t, is_power_of_2, lerp, mod_t, round_up_pow2_32};
 use core::pbrt::{Float, Spectrum};
+use core::profile::{Prof, ProfilePhase};
 use core::texture::lanczos;
 
 // see mipmap.h
 
 const WEIGHT_LUT_SIZE: usize = 128;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ImageWrap {
     Repeat,
     Black,
@@ -210,10 +211,9 @@ where
         let l = &self.pyramid[level];
         let (u_size, v_size) = (l.u_size() as isize, l.v_size() as isize);
         let (ss, tt): (usize, usize) = match self.wrap_mode {
-            ImageWrap::Repeat => (
-                mod_t(s as usize, u_size as usize),
-                mod_t(t as usize, v_size as usize),
-            ),
+            // wrap toroidally in signed arithmetic, filter footprints
+            // crossing the seam (s < 0 or t < 0) read from the far end
+            ImageWrap::Repeat => (mod_t(s, u_size) as usize, mod_t(t, v_size) as usize),
             ImageWrap::Clamp => (
                 clamp_t(s, 0, u_size - 1) as usize,
                 clamp_t(t, 0, v_size - 1) as usize,
@@ -235,7 +235,7 @@ where
     }
     pub fn lookup_pnt_flt(&self, st: &Point2f, width: Float) -> T {
         // TODO: ++nTrilerpLookups;
-        // TODO: ProfilePhase p(Prof::TexFiltTrilerp);
+        let _p = ProfilePhase::new(Prof::TexFiltTrilerp);
         // compute MIPMap level for trilinear filtering
         let level: Float = self.levels() as Float - 1.0 as Float + width.max(1e-8 as Float).log2();
         // perform trilinear interpolation at appropriate MIPMap level
@@ -263,7 +263,7 @@ where
             return self.lookup_pnt_flt(st, width);
         }
         // TODO: ++nEWALookups;
-        // TODO: ProfilePhase p(Prof::TexFiltEWA);
+        let _p = ProfilePhase::new(Prof::TexFiltEWA);
         // compute ellipse minor and major axes
         if dst0.length_squared() < dst1.length_squared() {
             // std::swap(dst0, dst1);
@@ -415,3 +415,63 @@ impl Clampable for Spectrum {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 8x8 texture with a horizontal gradient (0 to 7 in *s*).
+    fn gradient(do_trilinear: bool, wrap_mode: ImageWrap) -> MipMap<Float> {
+        let mut texels: Vec<Float> = Vec::with_capacity(64);
+        for _t in 0..8 {
+            for s in 0..8 {
+                texels.push(s as Float);
+            }
+        }
+        MipMap::new(
+            &Point2i { x: 8, y: 8 },
+            &texels,
+            do_trilinear,
+            8.0 as Float,
+            wrap_mode,
+        )
+    }
+
+    #[test]
+    fn repeat_wraps_signed_texel_coordinates() {
+        // also for sizes which aren't a power of two
+        assert_eq!(mod_t(-1_isize, 6_isize), 5_isize);
+        assert_eq!(mod_t(-7_isize, 6_isize), 5_isize);
+        assert_eq!(mod_t(6_isize, 6_isize), 0_isize);
+        let mipmap: MipMap<Float> = gradient(true, ImageWrap::Repeat);
+        assert_eq!(*mipmap.texel(0, -1, 0), 7.0 as Float);
+        assert_eq!(*mipmap.texel(0, -9, -1), 7.0 as Float);
+        assert_eq!(*mipmap.texel(0, 8, 0), 0.0 as Float);
+        assert_eq!(*mipmap.texel(1, -1, 0), 6.5 as Float);
+    }
+
+    #[test]
+    fn footprint_at_the_seam_reads_both_ends() {
+        let seam: Point2f = Point2f { x: 0.0, y: 0.5 };
+        let wrapped: Point2f = Point2f { x: 1.0, y: 0.5 };
+        // bilinear lookup halfway between the last and the first texel
+        let trilinear: MipMap<Float> = gradient(true, ImageWrap::Repeat);
+        let value: Float = trilinear.lookup_pnt_flt(&seam, 1e-3 as Float);
+        assert!((value - 3.5 as Float).abs() < 1e-4 as Float, "{}", value);
+        assert_eq!(value, trilinear.lookup_pnt_flt(&wrapped, 1e-3 as Float));
+        // continuous across the seam
+        let before: Float = trilinear.lookup_pnt_flt(&Point2f { x: 0.999, y: 0.5 }, 1e-3);
+        let after: Float = trilinear.lookup_pnt_flt(&Point2f { x: 0.001, y: 0.5 }, 1e-3);
+        assert!((before - value).abs() < 0.1 as Float);
+        assert!((after - value).abs() < 0.1 as Float);
+        // the EWA footprint covers texels on both sides symmetrically
+        let ewa: MipMap<Float> = gradient(false, ImageWrap::Repeat);
+        let mut dst0: Vector2f = Vector2f { x: 0.125, y: 0.0 };
+        let mut dst1: Vector2f = Vector2f { x: 0.0, y: 0.125 };
+        let value: Float = ewa.lookup_pnt_vec_vec(&seam, &mut dst0, &mut dst1);
+        assert!((value - 3.5 as Float).abs() < 1e-3 as Float, "{}", value);
+        // clamping doesn't wrap
+        let clamped: MipMap<Float> = gradient(true, ImageWrap::Clamp);
+        assert_eq!(clamped.lookup_pnt_flt(&seam, 1e-3 as Float), 0.0 as Float);
+    }
+}
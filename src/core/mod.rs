@@ -23,6 +23,7 @@ pub mod parallel;
 pub mod paramset;
 pub mod pbrt;
 pub mod primitive;
+pub mod profile;
 pub mod quaternion;
 pub mod reflection;
 pub mod rng;
@@ -32,5 +33,7 @@ pub mod scene;
 pub mod shape;
 pub mod sobolmatrices;
 pub mod spectrum;
+#[cfg(test)]
+pub mod testutil;
 pub mod texture;
 pub mod transform;
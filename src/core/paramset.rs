@@ -407,6 +407,29 @@ impl ParamSet {
             });
         }
     }
+    /// Returns a new parameter set where the parameters of
+    /// **overrides** take precedence (per name and type) over the
+    /// ones in **self**, e.g. to derive a material from a base
+    /// material and only change a few parameters.
+    pub fn merge(&self, overrides: &ParamSet) -> ParamSet {
+        ParamSet {
+            key_word: self.key_word.clone(),
+            name: self.name.clone(),
+            tex_type: self.tex_type.clone(),
+            tex_name: self.tex_name.clone(),
+            bools: merge_items(&self.bools, &overrides.bools),
+            ints: merge_items(&self.ints, &overrides.ints),
+            floats: merge_items(&self.floats, &overrides.floats),
+            point2fs: merge_items(&self.point2fs, &overrides.point2fs),
+            vector2fs: merge_items(&self.vector2fs, &overrides.vector2fs),
+            point3fs: merge_items(&self.point3fs, &overrides.point3fs),
+            vector3fs: merge_items(&self.vector3fs, &overrides.vector3fs),
+            normals: merge_items(&self.normals, &overrides.normals),
+            spectra: merge_items(&self.spectra, &overrides.spectra),
+            strings: merge_items(&self.strings, &overrides.strings),
+            textures: merge_items(&self.textures, &overrides.textures),
+        }
+    }
     pub fn erase_spectrum(&mut self, name: String) -> bool {
         for i in 0..self.spectra.len() {
             if self.spectra[i].name == name {
@@ -603,6 +626,10 @@ pub struct TextureParams {
     pub spectrum_textures: Arc<HashMap<String, Arc<Texture<Spectrum> + Send + Sync>>>,
     pub geom_params: ParamSet,
     pub material_params: ParamSet,
+    /// Replace missing spectrum textures by bright magenta (see
+    /// **ApiState::error_material**). Missing float textures always
+    /// fall back to their default value, there is no color to spot.
+    pub error_material: bool,
 }
 
 impl TextureParams {
@@ -617,6 +644,18 @@ impl TextureParams {
             spectrum_textures: s_tex,
             geom_params: geom_params,
             material_params: material_params,
+            error_material: false,
+        }
+    }
+    /// Constant texture used for a missing spectrum texture, if
+    /// **error_material** is set.
+    fn error_texture(&self) -> Option<Arc<Texture<Spectrum> + Send + Sync>> {
+        if self.error_material {
+            Some(Arc::new(ConstantTexture {
+                value: Spectrum::rgb(1.0, 0.0, 1.0),
+            }))
+        } else {
+            None
         }
     }
     pub fn get_spectrum_texture(
@@ -634,10 +673,13 @@ impl TextureParams {
                     return spectrum_texture.clone();
                 }
                 None => {
-                    panic!(
-                        "Couldn't find spectrum texture named \"{}\" for parameter \"{}\"",
+                    println!(
+                        "WARNING: Couldn't find spectrum texture named \"{}\" for parameter \"{}\"",
                         name, n
                     );
+                    if let Some(error_texture) = self.error_texture() {
+                        return error_texture;
+                    }
                 }
             }
         }
@@ -658,10 +700,10 @@ impl TextureParams {
                 Some(spectrum_texture) => return Some(spectrum_texture.clone()),
                 None => {
                     println!(
-                        "Couldn't find spectrum texture named \"{}\" for parameter \"{}\"",
+                        "WARNING: Couldn't find spectrum texture named \"{}\" for parameter \"{}\"",
                         name, n
                     );
-                    return None;
+                    return self.error_texture();
                 }
             }
         }
@@ -685,6 +727,35 @@ impl TextureParams {
             Arc::new(ConstantTexture { value: val })
         }
     }
+    /// Returns the float texture for the material's **bumpmap**
+    /// parameter (if any). A spectrum texture can't be used as a bump
+    /// map, which is reported naming the *material*.
+    pub fn get_bump_map(&mut self, material: &str) -> Option<Arc<Texture<Float> + Send + Sync>> {
+        if let Some(error) = self.bump_map_error(material) {
+            println!("ERROR: {}", error);
+            return None;
+        }
+        self.get_float_texture_or_null("bumpmap")
+    }
+    /// Describes the problem if the **bumpmap** parameter refers to a
+    /// spectrum texture (see **get_bump_map()**).
+    pub fn bump_map_error(&mut self, material: &str) -> Option<String> {
+        let mut name: String = self.geom_params.find_texture("bumpmap");
+        if name == "" {
+            name = self.material_params.find_texture("bumpmap");
+        }
+        if name != ""
+            && !self.float_textures.contains_key(name.as_str())
+            && self.spectrum_textures.contains_key(name.as_str())
+        {
+            return Some(format!(
+                "\"bumpmap\" of \"{}\" material refers to spectrum texture \"{}\", \
+                 but bump maps need a float texture. Rendering without bump mapping.",
+                material, name
+            ));
+        }
+        None
+    }
     pub fn get_float_texture_or_null(
         &mut self,
         n: &str,
@@ -708,10 +779,18 @@ impl TextureParams {
                     return Some(float_texture.clone());
                 }
                 None => {
-                    println!(
-                        "Couldn't find float texture named \"{}\" for parameter \"{}\"",
-                        name, n
-                    );
+                    if self.spectrum_textures.contains_key(name.as_str()) {
+                        println!(
+                            "ERROR: Texture \"{}\" for parameter \"{}\" is a spectrum texture, \
+                             but a float texture is expected. Ignoring it.",
+                            name, n
+                        );
+                    } else {
+                        println!(
+                            "Couldn't find float texture named \"{}\" for parameter \"{}\"",
+                            name, n
+                        );
+                    }
                     return None;
                 }
             }
@@ -746,6 +825,10 @@ impl TextureParams {
         self.geom_params
             .find_one_bool(name, self.material_params.find_one_bool(name, d))
     }
+    pub fn find_point3f(&mut self, name: &str, d: Point3f) -> Point3f {
+        self.geom_params
+            .find_one_point3f(name, self.material_params.find_one_point3f(name, d))
+    }
     pub fn find_vector3f(&mut self, name: &str, d: Vector3f) -> Vector3f {
         self.geom_params
             .find_one_vector3f(name, self.material_params.find_one_vector3f(name, d))
@@ -756,6 +839,30 @@ impl TextureParams {
     }
 }
 
+/// Copies all items of **base** which aren't found (by name) in
+/// **overrides**, followed by all items of **overrides**.
+fn merge_items<T>(
+    base: &Vec<ParamSetItem<T>>,
+    overrides: &Vec<ParamSetItem<T>>,
+) -> Vec<ParamSetItem<T>>
+where
+    T: Clone,
+{
+    let mut items: Vec<ParamSetItem<T>> = Vec::new();
+    let kept = base
+        .iter()
+        .filter(|b| !overrides.iter().any(|o| o.name == b.name));
+    for item in kept.chain(overrides.iter()) {
+        items.push(ParamSetItem::<T> {
+            name: item.name.clone(),
+            values: item.values.clone(),
+            n_values: item.n_values,
+            looked_up: false,
+        });
+    }
+    items
+}
+
 /// Replaces a macro on the C++ side.
 pub fn lookup_one<T>(vec: &Vec<ParamSetItem<T>>, name: &str, d: T) -> T
 where
@@ -769,3 +876,91 @@ where
     }
     d
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use materials::uber::UberMaterial;
+
+    #[test]
+    fn merge_prefers_overrides() {
+        let mut base: ParamSet = ParamSet::default();
+        base.add_float(String::from("Kd"), 0.5 as Float);
+        base.add_float(String::from("roughness"), 0.1 as Float);
+        base.add_string(String::from("type"), String::from("plastic"));
+        let mut overrides: ParamSet = ParamSet::default();
+        overrides.add_float(String::from("roughness"), 0.3 as Float);
+        let merged: ParamSet = base.merge(&overrides);
+        assert_eq!(merged.find_one_float("Kd", 0.0 as Float), 0.5 as Float);
+        assert_eq!(merged.find_one_float("roughness", 0.0 as Float), 0.3 as Float);
+        assert_eq!(merged.find_float("roughness").len(), 1_usize);
+        assert_eq!(
+            merged.find_one_string("type", String::new()),
+            String::from("plastic")
+        );
+        // the base set is left alone
+        assert_eq!(base.find_one_float("roughness", 0.0 as Float), 0.1 as Float);
+    }
+
+    #[test]
+    fn texture_params_find_point3f_prefers_geometry_params() {
+        let p = |x: Float| -> Point3f {
+            Point3f {
+                x: x,
+                y: 2.0,
+                z: 3.0,
+            }
+        };
+        let mut tp: TextureParams = TextureParams::default();
+        assert_eq!(tp.find_point3f("offset", p(0.0)).x, 0.0 as Float);
+        tp.material_params
+            .add_point3f(String::from("offset"), p(1.0));
+        assert_eq!(tp.find_point3f("offset", p(0.0)).x, 1.0 as Float);
+        // shape parameters override the material's
+        tp.geom_params.add_point3f(String::from("offset"), p(-1.0));
+        let offset: Point3f = tp.find_point3f("offset", p(0.0));
+        assert_eq!((offset.x, offset.y, offset.z), (-1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn spectrum_bump_maps_are_reported() {
+        let mut float_textures: HashMap<String, Arc<Texture<Float> + Send + Sync>> = HashMap::new();
+        float_textures.insert(
+            String::from("height"),
+            Arc::new(ConstantTexture {
+                value: 0.1 as Float,
+            }),
+        );
+        let mut spectrum_textures: HashMap<String, Arc<Texture<Spectrum> + Send + Sync>> =
+            HashMap::new();
+        spectrum_textures.insert(
+            String::from("color"),
+            Arc::new(ConstantTexture {
+                value: Spectrum::new(0.1 as Float),
+            }),
+        );
+        let params = |bumpmap: &str| -> TextureParams {
+            let mut material_params: ParamSet = ParamSet::default();
+            material_params.add_texture(String::from("bumpmap"), String::from(bumpmap));
+            TextureParams::new(
+                ParamSet::default(),
+                material_params,
+                Arc::new(float_textures.clone()),
+                Arc::new(spectrum_textures.clone()),
+            )
+        };
+        let mut spectrum: TextureParams = params("color");
+        let error: String = spectrum.bump_map_error("uber").expect("not reported");
+        assert!(
+            error.contains("\"uber\"") && error.contains("\"color\""),
+            "{}",
+            error
+        );
+        assert!(spectrum.get_bump_map("uber").is_none());
+        // the material is still created, without bump mapping
+        UberMaterial::create(&mut spectrum);
+        let mut float: TextureParams = params("height");
+        assert!(float.bump_map_error("uber").is_none());
+        assert!(float.get_bump_map("uber").is_some());
+    }
+}
@@ -3,7 +3,6 @@
 
 // std
 use std;
-use std::f32::consts::PI;
 use std::ops::{Add, BitAnd, Div, Mul, Sub};
 // others
 use num;
@@ -18,6 +17,7 @@ pub type Float = f32;
 
 pub const MACHINE_EPSILON: Float = std::f32::EPSILON * 0.5;
 pub const SHADOW_EPSILON: Float = 0.0001;
+pub const PI: Float = std::f32::consts::PI;
 pub const INV_PI: Float = 0.31830988618379067154;
 pub const INV_2_PI: Float = 0.15915494309189533577;
 pub const INV_4_PI: Float = 0.07957747154594766788;
@@ -212,11 +212,29 @@ pub fn round_up_pow2_64(v: i64) -> i64 {
     ret + 1
 }
 
+/// Round an integer up to the next higher (or equal) perfect square,
+/// so *v* samples can be stratified as sqrt(v) x sqrt(v).
+pub fn round_up_square_32(v: i32) -> i32 {
+    let mut root: i32 = (v as f64).sqrt() as i32;
+    while root * root < v {
+        root += 1_i32;
+    }
+    root * root
+}
+
 /// Helper function which emulates the behavior of std::upper_bound().
+///
+/// Returns the index *i* of the interval [node(i), node(i + 1)] for a
+/// monotonic predicate like `|i| nodes[i] <= x`, clamped to [0,
+/// *size* - 2]. Ties are broken consistently towards the upper
+/// interval: a value exactly on an interior node *i* returns *i*
+/// (not *i* - 1), a value equal to the first node returns 0, and a
+/// value equal to (or beyond) the last node returns *size* - 2.
 pub fn find_interval<P>(size: i32, pred: P) -> i32
 where
     P: Fn(i32) -> bool,
 {
+    assert!(size >= 2, "find_interval() needs at least two nodes");
     let mut first: i32 = 0;
     let mut len: i32 = size;
     while len > 0 {
@@ -302,3 +320,48 @@ pub fn erf_inv(x: Float) -> Float {
     }
     p * clamped_x
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_up_square() {
+        assert_eq!(round_up_square_32(1), 1);
+        assert_eq!(round_up_square_32(4), 4);
+        assert_eq!(round_up_square_32(5), 9);
+        assert_eq!(round_up_square_32(7), 9);
+        assert_eq!(round_up_square_32(9), 9);
+        assert_eq!(round_up_square_32(10), 16);
+        assert_eq!(round_up_square_32(1000), 1024);
+    }
+
+    #[test]
+    fn find_interval_on_and_between_nodes() {
+        let nodes: [Float; 5] = [0.0, 1.0, 2.0, 4.0, 8.0];
+        let size: i32 = nodes.len() as i32;
+        let find = |x: Float| find_interval(size, |i| nodes[i as usize] <= x);
+        // first and last node
+        assert_eq!(find(0.0 as Float), 0);
+        assert_eq!(find(8.0 as Float), size - 2);
+        // interior nodes belong to the interval they start
+        for i in 1..(size - 1) {
+            assert_eq!(find(nodes[i as usize]), i);
+        }
+        // values outside of the nodes are clamped
+        assert_eq!(find(-1.0 as Float), 0);
+        assert_eq!(find(100.0 as Float), size - 2);
+        // results never decrease and stay within [0, size - 2]
+        let mut previous: i32 = 0;
+        for step in 0..100 {
+            let x: Float = -1.0 as Float + step as Float * 0.1 as Float;
+            let i: i32 = find(x);
+            assert!(i >= 0 && i <= size - 2, "{} for {}", i, x);
+            assert!(i >= previous, "{} < {} for {}", i, previous, x);
+            assert_eq!(i, find(x));
+            previous = i;
+        }
+        // two nodes form a single interval
+        assert_eq!(find_interval(2, |i| [0.0 as Float, 1.0][i as usize] <= 1.0), 0);
+    }
+}
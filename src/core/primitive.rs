@@ -5,7 +5,7 @@
 use std::sync::Arc;
 // pbrt
 use core::geometry::nrm_dot_nrm;
-use core::geometry::{Bounds3f, Ray};
+use core::geometry::{Bounds3f, Ray, VisibilityFlags};
 use core::interaction::SurfaceInteraction;
 use core::light::AreaLight;
 use core::material::{Material, TransportMode};
@@ -51,6 +51,9 @@ pub struct GeometricPrimitive {
     pub material: Option<Arc<Material + Send + Sync>>,
     pub area_light: Option<Arc<AreaLight + Send + Sync>>,
     pub medium_interface: Option<Arc<MediumInterface>>,
+    /// bitwise combination of **VisibilityFlags** for the kinds of
+    /// rays which can hit this primitive
+    pub visibility: u8,
 }
 
 impl GeometricPrimitive {
@@ -67,6 +70,7 @@ impl GeometricPrimitive {
                     material: material,
                     area_light: Some(area_light),
                     medium_interface: Some(medium_interface),
+                    visibility: VisibilityFlags::All as u8,
                 }
             } else {
                 GeometricPrimitive {
@@ -74,6 +78,7 @@ impl GeometricPrimitive {
                     material: material,
                     area_light: Some(area_light),
                     medium_interface: None,
+                    visibility: VisibilityFlags::All as u8,
                 }
             }
         } else {
@@ -83,6 +88,7 @@ impl GeometricPrimitive {
                     material: material,
                     area_light: None,
                     medium_interface: Some(medium_interface),
+                    visibility: VisibilityFlags::All as u8,
                 }
             } else {
                 GeometricPrimitive {
@@ -90,10 +96,16 @@ impl GeometricPrimitive {
                     material: material,
                     area_light: None,
                     medium_interface: None,
+                    visibility: VisibilityFlags::All as u8,
                 }
             }
         }
     }
+    /// Rays without a category (**VisibilityFlags::Untyped**) see
+    /// every primitive.
+    pub fn is_visible_to(&self, ray: &Ray) -> bool {
+        ray.ray_type == VisibilityFlags::Untyped as u8 || (self.visibility & ray.ray_type) != 0_u8
+    }
 }
 
 impl Primitive for GeometricPrimitive {
@@ -101,6 +113,9 @@ impl Primitive for GeometricPrimitive {
         self.shape.world_bound()
     }
     fn intersect(&self, ray: &mut Ray) -> Option<SurfaceInteraction> {
+        if !self.is_visible_to(ray) || !self.shape.object_bound_may_intersect(ray) {
+            return None;
+        }
         if let Some((mut isect, t_hit)) = self.shape.intersect(ray) {
             isect.primitive = Some(self.clone());
             ray.t_max = t_hit;
@@ -136,6 +151,9 @@ impl Primitive for GeometricPrimitive {
         }
     }
     fn intersect_p(&self, r: &Ray) -> bool {
+        if !self.is_visible_to(r) || !self.shape.object_bound_may_intersect(r) {
+            return false;
+        }
         self.shape.intersect_p(r)
     }
     fn get_material(&self) -> Option<Arc<Material + Send + Sync>> {
@@ -226,3 +244,48 @@ impl Primitive for TransformedPrimitive {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::geometry::{Point3f, Vector3f};
+    use std;
+    use shapes::sphere::Sphere;
+
+    fn ray_towards_sphere(ray_type: u8) -> Ray {
+        Ray {
+            o: Point3f {
+                x: 0.0 as Float,
+                y: 0.0 as Float,
+                z: -5.0 as Float,
+            },
+            d: Vector3f {
+                x: 0.0 as Float,
+                y: 0.0 as Float,
+                z: 1.0 as Float,
+            },
+            t_max: std::f32::INFINITY,
+            ray_type: ray_type,
+            ..Ray::default()
+        }
+    }
+
+    #[test]
+    fn shadow_invisible_primitive_is_skipped_by_shadow_rays() {
+        let sphere: Arc<Shape + Send + Sync> = Arc::new(Sphere::new(
+            Transform::default(),
+            Transform::default(),
+            false,
+            1.0 as Float,
+            -1.0 as Float,
+            1.0 as Float,
+            360.0 as Float,
+        ));
+        let mut prim: GeometricPrimitive = GeometricPrimitive::new(sphere, None, None, None);
+        prim.visibility = VisibilityFlags::All as u8 & !(VisibilityFlags::Shadow as u8);
+        let shadow_ray: Ray = ray_towards_sphere(VisibilityFlags::Shadow as u8);
+        assert!(!prim.intersect_p(&shadow_ray));
+        let mut camera_ray: Ray = ray_towards_sphere(VisibilityFlags::Camera as u8);
+        assert!(prim.intersect(&mut camera_ray).is_some());
+    }
+}
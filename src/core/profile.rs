@@ -0,0 +1,286 @@
+//! A lightweight replacement for pbrt's **ProfilePhase**. Each
+//! thread accumulates the time spent inside a phase in thread-local
+//! counters, which get merged into a global table when the thread
+//! exits (or when **report_profile()** is called). Only active with
+//! the `profile` feature, otherwise the guard compiles to nothing.
+
+// std
+#[cfg(feature = "profile")]
+use std::cell::RefCell;
+#[cfg(feature = "profile")]
+use std::sync::Mutex;
+#[cfg(feature = "profile")]
+use std::time::Instant;
+
+// see stats.h
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Prof {
+    SceneConstruction,
+    AccelConstruction,
+    TextureLoading,
+    MIPMapCreation,
+    IntegratorRender,
+    SamplerIntegratorLi,
+    SPPMCameraPass,
+    SPPMGridConstruction,
+    SPPMPhotonPass,
+    SPPMStatsUpdate,
+    BDPTGenerateSubpath,
+    BDPTConnectSubpaths,
+    LightDistribLookup,
+    LightDistribSpinWait,
+    LightDistribCreation,
+    DirectLighting,
+    BSDFEvaluation,
+    BSDFSampling,
+    BSDFPdf,
+    BSSRDFEvaluation,
+    BSSRDFSampling,
+    PhaseFuncEvaluation,
+    PhaseFuncSampling,
+    AccelIntersect,
+    AccelIntersectP,
+    LightSample,
+    LightPdf,
+    MediumSample,
+    MediumTr,
+    TriIntersect,
+    TriIntersectP,
+    CurveIntersect,
+    CurveIntersectP,
+    ShapeIntersect,
+    ShapeIntersectP,
+    ComputeScatteringFuncs,
+    GenerateCameraRay,
+    MergeFilmTile,
+    SplatFilm,
+    AddFilmSample,
+    StartPixel,
+    GetSample,
+    TexFiltTrilerp,
+    TexFiltEWA,
+    NumProfCategories,
+}
+
+pub const NUM_PROF_CATEGORIES: usize = Prof::NumProfCategories as usize;
+
+pub const PROF_NAMES: [&str; NUM_PROF_CATEGORIES] = [
+    "Scene parsing and creation",
+    "Acceleration structure creation",
+    "Texture loading",
+    "MIP map generation",
+    "Integrator::Render()",
+    "SamplerIntegrator::Li()",
+    "SPPM camera pass",
+    "SPPM grid construction",
+    "SPPM photon pass",
+    "SPPM statistics update",
+    "BDPT subpath generation",
+    "BDPT subpath connections",
+    "SpatialLightDistribution lookup",
+    "SpatialLightDistribution spin wait",
+    "SpatialLightDistribution creation",
+    "Direct lighting",
+    "BSDF::f()",
+    "BSDF::Sample_f()",
+    "BSDF::PDF()",
+    "BSSRDF::f()",
+    "BSSRDF::Sample_f()",
+    "PhaseFunction::p()",
+    "PhaseFunction::Sample_p()",
+    "Accelerator::Intersect()",
+    "Accelerator::IntersectP()",
+    "Light::Sample_*()",
+    "Light::Pdf()",
+    "Medium::Sample()",
+    "Medium::Tr()",
+    "Triangle::Intersect()",
+    "Triangle::IntersectP()",
+    "Curve::Intersect()",
+    "Curve::IntersectP()",
+    "Other Shape::Intersect()",
+    "Other Shape::IntersectP()",
+    "Material::ComputeScatteringFunctions()",
+    "Camera::GenerateRay[Differential]()",
+    "Film::MergeTile()",
+    "Film::AddSplat()",
+    "Film::AddSample()",
+    "Sampler::StartPixelSample()",
+    "Sampler::GetSample[12]D()",
+    "MIPMap::Lookup() (trilinear)",
+    "MIPMap::Lookup() (EWA)",
+];
+
+#[cfg(feature = "profile")]
+lazy_static! {
+    static ref PROFILE_TOTALS: Mutex<Vec<u64>> = Mutex::new(vec![0_u64; NUM_PROF_CATEGORIES]);
+}
+
+/// Per thread nanoseconds spent in each phase. Merged into
+/// **PROFILE_TOTALS** when the thread goes away.
+#[cfg(feature = "profile")]
+struct ThreadProfile {
+    nanos: Vec<u64>,
+    /// One entry per active **ProfilePhase** (innermost last): the
+    /// nanoseconds spent in phases nested inside of it so far
+    nested_nanos: Vec<u64>,
+}
+
+#[cfg(feature = "profile")]
+impl ThreadProfile {
+    fn flush(&mut self) {
+        let mut totals = PROFILE_TOTALS.lock().unwrap();
+        for (total, nanos) in totals.iter_mut().zip(self.nanos.iter_mut()) {
+            *total += *nanos;
+            *nanos = 0_u64;
+        }
+    }
+}
+
+#[cfg(feature = "profile")]
+impl Drop for ThreadProfile {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[cfg(feature = "profile")]
+thread_local! {
+    static THREAD_PROFILE: RefCell<ThreadProfile> = RefCell::new(ThreadProfile {
+        nanos: vec![0_u64; NUM_PROF_CATEGORIES],
+        nested_nanos: Vec::new(),
+    });
+}
+
+/// RAII guard: the time between **new()** and the guard being
+/// dropped is added to the given phase. Time spent in nested phases
+/// is only counted for the innermost phase (exclusive time), so the
+/// phases add up to the profiled wall clock time.
+#[cfg(feature = "profile")]
+pub struct ProfilePhase {
+    category: Prof,
+    start: Instant,
+}
+
+#[cfg(feature = "profile")]
+impl ProfilePhase {
+    pub fn new(category: Prof) -> Self {
+        let _ = THREAD_PROFILE.try_with(|p| {
+            if let Ok(mut p) = p.try_borrow_mut() {
+                p.nested_nanos.push(0_u64);
+            }
+        });
+        ProfilePhase {
+            category: category,
+            start: Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "profile")]
+impl Drop for ProfilePhase {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        let nanos: u64 = elapsed.as_secs() * 1_000_000_000_u64 + elapsed.subsec_nanos() as u64;
+        let category: usize = self.category as usize;
+        // ignore phases ending while the thread is shutting down
+        let _ = THREAD_PROFILE.try_with(|p| {
+            if let Ok(mut p) = p.try_borrow_mut() {
+                let nested: u64 = p.nested_nanos.pop().unwrap_or(0_u64);
+                p.nanos[category] += nanos.saturating_sub(nested);
+                // the enclosing phase (if any) doesn't count this time
+                if let Some(outer) = p.nested_nanos.last_mut() {
+                    *outer += nanos;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(not(feature = "profile"))]
+pub struct ProfilePhase {}
+
+#[cfg(not(feature = "profile"))]
+impl ProfilePhase {
+    #[inline(always)]
+    pub fn new(_category: Prof) -> Self {
+        ProfilePhase {}
+    }
+}
+
+/// Returns the accumulated nanoseconds per phase (including the
+/// calling thread's counters). All zero without the `profile`
+/// feature.
+#[cfg(feature = "profile")]
+pub fn profile_totals() -> Vec<u64> {
+    let _ = THREAD_PROFILE.try_with(|p| {
+        if let Ok(mut p) = p.try_borrow_mut() {
+            p.flush();
+        }
+    });
+    PROFILE_TOTALS.lock().unwrap().clone()
+}
+
+#[cfg(not(feature = "profile"))]
+pub fn profile_totals() -> Vec<u64> {
+    vec![0_u64; NUM_PROF_CATEGORIES]
+}
+
+/// Print a breakdown of the time spent in each phase (sorted by
+/// time, phases which never ran are skipped).
+pub fn report_profile() {
+    if !cfg!(feature = "profile") {
+        return;
+    }
+    let totals: Vec<u64> = profile_totals();
+    let overall: u64 = totals.iter().sum();
+    if overall == 0_u64 {
+        return;
+    }
+    let mut order: Vec<usize> = (0..NUM_PROF_CATEGORIES).collect();
+    order.sort_by(|a, b| totals[*b].cmp(&totals[*a]));
+    println!("  Profile");
+    for i in order {
+        if totals[i] == 0_u64 {
+            continue;
+        }
+        let seconds: f64 = totals[i] as f64 * 1e-9;
+        let percent: f64 = 100.0 * totals[i] as f64 / overall as f64;
+        println!(
+            "    {:<42} {:>10.3}s {:>6.2} %",
+            PROF_NAMES[i], seconds, percent
+        );
+    }
+}
+
+#[cfg(all(test, feature = "profile"))]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn nested_phases_count_exclusive_time() {
+        // categories no other test touches
+        let outer: usize = Prof::SPPMGridConstruction as usize;
+        let inner: usize = Prof::SPPMStatsUpdate as usize;
+        let before: Vec<u64> = profile_totals();
+        {
+            let _p = ProfilePhase::new(Prof::SPPMGridConstruction);
+            thread::sleep(Duration::from_millis(10));
+            {
+                let _p = ProfilePhase::new(Prof::SPPMStatsUpdate);
+                thread::sleep(Duration::from_millis(30));
+            }
+        }
+        let after: Vec<u64> = profile_totals();
+        let outer_nanos: u64 = after[outer] - before[outer];
+        let inner_nanos: u64 = after[inner] - before[inner];
+        assert!(inner_nanos >= 30_000_000_u64);
+        assert!(outer_nanos >= 10_000_000_u64);
+        // the nested 30ms are not counted for the outer phase again
+        assert!(outer_nanos < inner_nanos);
+    }
+}
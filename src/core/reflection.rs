@@ -19,7 +19,7 @@ use core::geometry::{
     nrm_cross_vec3, nrm_dot_vec3, nrm_faceforward_vec3, vec3_abs_dot_vec3, vec3_dot_nrm,
     vec3_dot_vec3,
 };
-use core::geometry::{Normal3f, Point2f, Vector3f};
+use core::geometry::{Normal3f, Point2f, Vector3f, VisibilityFlags};
 use core::interaction::SurfaceInteraction;
 use core::interpolation::{catmull_rom_weights, fourier, sample_catmull_rom_2d, sample_fourier};
 use core::material::TransportMode;
@@ -27,8 +27,10 @@ use core::microfacet::{MicrofacetDistribution, TrowbridgeReitzDistribution};
 use core::pbrt::INV_PI;
 use core::pbrt::{clamp_t, radians};
 use core::pbrt::{Float, Spectrum};
+use core::profile::{Prof, ProfilePhase};
 use core::rng::FLOAT_ONE_MINUS_EPSILON;
 use core::sampling::cosine_sample_hemisphere;
+use core::spectrum::SpectrumType;
 
 // see reflection.h
 
@@ -144,6 +146,10 @@ impl FourierBSDFTable {
                             for i in 0..self.m_max as usize {
                                 self.recip.push(1.0 as Float / i as Float);
                             }
+                            // grayscale BSDFs stored as RGB only need one channel
+                            if self.n_channels == 3_i32 && self.is_monochrome() {
+                                self.reduce_to_monochrome();
+                            }
                         } else {
                             panic!(
                                 "ERROR: Tabulated BSDF file {:?} has an incompatible file format or version."
@@ -164,9 +170,50 @@ impl FourierBSDFTable {
                     "ERROR: Tabulated BSDF file {:?} has an incompatible file format or version."
                 );
             }
+        } else {
+            println!("ERROR: Unable to read header of tabulated BSDF file {:?}", filename);
+            return false;
+        }
+        true
+    }
+    /// Returns true if the table has a single channel or if the red
+    /// and blue Fourier coefficients of all (mu_i, mu_o) pairs match
+    /// the luminance coefficients (i.e. the BSDF is grayscale).
+    pub fn is_monochrome(&self) -> bool {
+        if self.n_channels == 1_i32 {
+            return true;
+        }
+        for i in 0..self.a_offset.len() {
+            let offset: usize = self.a_offset[i] as usize;
+            let m: usize = self.m[i] as usize;
+            for k in 0..m {
+                let y: Float = self.a[offset + k];
+                let tolerance: Float = 1e-6 as Float * y.abs().max(1.0 as Float);
+                for c in 1..self.n_channels as usize {
+                    if (self.a[offset + c * m + k] - y).abs() > tolerance {
+                        return false;
+                    }
+                }
+            }
         }
         true
     }
+    /// Drops the red and blue channels of a grayscale table (see
+    /// **is_monochrome()**), keeping only the luminance coefficients.
+    /// **FourierBSDF** then returns the luminance for all channels.
+    fn reduce_to_monochrome(&mut self) {
+        let mut a: Vec<Float> = Vec::with_capacity(self.a.len() / self.n_channels as usize);
+        for i in 0..self.a_offset.len() {
+            let offset: usize = self.a_offset[i] as usize;
+            let m: usize = self.m[i] as usize;
+            self.a_offset[i] = a.len() as i32;
+            if m > 0 {
+                a.extend_from_slice(&self.a[offset..offset + m]);
+            }
+        }
+        self.a = a;
+        self.n_channels = 1_i32;
+    }
     pub fn get_ak(&self, offset_i: i32, offset_o: i32, mptr: &mut i32) -> i32 {
         let idx: i32 = offset_o * self.n_mu + offset_i;
         assert!(
@@ -189,6 +236,12 @@ impl FourierBSDFTable {
     }
 }
 
+/// Default lower bound for the microfacet roughness of lobes hit
+/// after a non-specular bounce (see **Bsdf::regularize()** and the
+/// "regularizealpha" integrator parameter).
+pub const REGULARIZE_MIN_ALPHA: Float = 0.3 as Float;
+
+#[derive(Clone)]
 pub struct Bsdf {
     pub eta: Float,
     /// shading normal
@@ -198,6 +251,10 @@ pub struct Bsdf {
     pub ss: Vector3f,
     pub ts: Vector3f,
     pub bxdfs: Vec<Arc<Bxdf + Sync + Send>>,
+    /// Reject directions on different sides of the geometric and the
+    /// shading normal (only for bump mapped surfaces, see
+    /// **consistent_sides()**)
+    pub check_sides: bool,
 }
 
 impl Bsdf {
@@ -210,6 +267,7 @@ impl Bsdf {
             ss: ss,
             ts: nrm_cross_vec3(&si.shading.n, &ss),
             bxdfs: bxdfs,
+            check_sides: si.shading.bumped,
         }
     }
     pub fn num_components(&self, flags: u8) -> u8 {
@@ -222,6 +280,22 @@ impl Bsdf {
         }
         num
     }
+    /// Path regularization: roughens near-specular microfacet lobes
+    /// to at least *min_alpha* and replaces perfectly specular lobes
+    /// (mirrors, glass) by microfacet lobes of that roughness,
+    /// trading a bit of bias for less noise from
+    /// specular-diffuse-specular paths.
+    pub fn regularize(&mut self, min_alpha: Float) {
+        let mut bxdfs: Vec<Arc<Bxdf + Sync + Send>> = Vec::with_capacity(self.bxdfs.len());
+        for bxdf in &self.bxdfs {
+            if let Some(mut regularized) = bxdf.regularize(min_alpha) {
+                bxdfs.append(&mut regularized);
+            } else {
+                bxdfs.push(bxdf.clone());
+            }
+        }
+        self.bxdfs = bxdfs;
+    }
     pub fn world_to_local(&self, v: &Vector3f) -> Vector3f {
         Vector3f {
             x: vec3_dot_vec3(v, &self.ss),
@@ -236,13 +310,30 @@ impl Bsdf {
             z: self.ss.z * v.x + self.ts.z * v.y + self.ns.z * v.z,
         }
     }
+    /// Returns false if *w_world* (with local representation
+    /// *w_local*) lies on different sides of the geometric and the
+    /// shading normal. This happens when bump mapping tilts the
+    /// shading normal too far; such directions would otherwise leak
+    /// light through the (geometric) surface. Surfaces without bump
+    /// mapping (e.g. smooth shaded meshes) aren't checked.
+    fn consistent_sides(&self, w_world: &Vector3f, w_local: &Vector3f) -> bool {
+        !self.check_sides || vec3_dot_nrm(w_world, &self.ng) * w_local.z > 0.0 as Float
+    }
+    /// Sums the contributions of all BxDFs matching *flags* (see
+    /// **BxdfType**), using the geometric normal to decide between
+    /// reflection and transmission lobes. On bump mapped surfaces,
+    /// directions which are on different sides of the geometric and
+    /// the shading normal don't contribute.
     pub fn f(&self, wo_w: &Vector3f, wi_w: &Vector3f, flags: u8) -> Spectrum {
-        // TODO: ProfilePhase pp(Prof::BSDFEvaluation);
+        let _p = ProfilePhase::new(Prof::BSDFEvaluation);
         let wi: Vector3f = self.world_to_local(wi_w);
         let wo: Vector3f = self.world_to_local(wo_w);
         if wo.z == 0.0 as Float {
             return Spectrum::new(0.0 as Float);
         }
+        if !self.consistent_sides(wo_w, &wo) || !self.consistent_sides(wi_w, &wi) {
+            return Spectrum::new(0.0 as Float);
+        }
         let reflect: bool = (vec3_dot_vec3(wi_w, &Vector3f::from(self.ng))
             * vec3_dot_vec3(wo_w, &Vector3f::from(self.ng)))
             > 0.0 as Float;
@@ -260,6 +351,10 @@ impl Bsdf {
         f
     }
     /// Calls the individual Bxdf::sample_f() methods to generate samples.
+    /// Only BxDFs matching *bsdf_flags* are chosen from. Like in
+    /// **f()**, sampled directions on different sides of the geometric
+    /// and the shading normal (of a bump mapped surface) return a
+    /// black spectrum and a zero *pdf*.
     pub fn sample_f(
         &self,
         wo_world: &Vector3f,
@@ -269,7 +364,7 @@ impl Bsdf {
         bsdf_flags: u8,
         sampled_type: &mut u8,
     ) -> Spectrum {
-        // TODO: ProfilePhase pp(Prof::BSDFSampling);
+        let _p = ProfilePhase::new(Prof::BSDFSampling);
         // choose which _BxDF_ to sample
         let matching_comps: u8 = self.num_components(bsdf_flags);
         if matching_comps == 0 {
@@ -341,6 +436,10 @@ impl Bsdf {
                 return Spectrum::default();
             }
             *wi_world = self.local_to_world(&wi);
+            if !self.consistent_sides(wo_world, &wo) || !self.consistent_sides(&*wi_world, &wi) {
+                *pdf = 0.0 as Float;
+                return Spectrum::default();
+            }
             // compute overall PDF with all matching _BxDF_s
             if (bxdf.get_type() & BxdfType::BsdfSpecular as u8 == 0_u8) && matching_comps > 1_u8 {
                 for i in 0..n_bxdfs {
@@ -383,7 +482,7 @@ impl Bsdf {
         }
     }
     pub fn pdf(&self, wo_world: &Vector3f, wi_world: &Vector3f, bsdf_flags: u8) -> Float {
-        // TODO: ProfilePhase pp(Prof::BSDFPdf);
+        let _p = ProfilePhase::new(Prof::BSDFPdf);
         let n_bxdfs: usize = self.bxdfs.len();
         if n_bxdfs == 0 {
             return 0.0 as Float;
@@ -393,6 +492,10 @@ impl Bsdf {
         if wo.z == 0.0 as Float {
             return 0.0 as Float;
         }
+        // consistent with f() and sample_f()
+        if !self.consistent_sides(wo_world, &wo) || !self.consistent_sides(wi_world, &wi) {
+            return 0.0 as Float;
+        }
         let mut pdf: Float = 0.0 as Float;
         let mut matching_comps: u8 = 0;
         for i in 0..n_bxdfs {
@@ -409,6 +512,11 @@ impl Bsdf {
     }
 }
 
+/// Bit flags describing a **Bxdf** lobe (see **Bxdf::get_type()**).
+/// Combined with `|` they form the *flags* used by **Bsdf::f()**,
+/// **Bsdf::sample_f()** and **Bsdf::pdf()** to restrict evaluation
+/// to matching lobes, e.g. `BsdfAll & !BsdfSpecular` for light
+/// sampling.
 #[repr(u8)]
 pub enum BxdfType {
     BsdfReflection = 1,
@@ -419,6 +527,18 @@ pub enum BxdfType {
     BsdfAll = 31,
 }
 
+/// Returns the ray category (see **VisibilityFlags**) of a ray
+/// continuing a path after sampling a **Bxdf** of *sampled_type*.
+pub fn bxdf_type_to_ray_type(sampled_type: u8) -> u8 {
+    if sampled_type & BxdfType::BsdfSpecular as u8 != 0_u8 {
+        VisibilityFlags::Reflection as u8
+    } else if sampled_type & BxdfType::BsdfGlossy as u8 != 0_u8 {
+        VisibilityFlags::Glossy as u8
+    } else {
+        VisibilityFlags::Diffuse as u8
+    }
+}
+
 pub trait Bxdf {
     fn matches_flags(&self, t: u8) -> bool {
         self.get_type() & t == self.get_type()
@@ -457,6 +577,13 @@ pub trait Bxdf {
     }
 
     fn get_type(&self) -> u8;
+
+    /// Returns the lobe(s) replacing the BxDF with its roughness
+    /// raised to at least *min_alpha*, or None if there is nothing to
+    /// roughen.
+    fn regularize(&self, _min_alpha: Float) -> Option<Vec<Arc<Bxdf + Send + Sync>>> {
+        None
+    }
 }
 
 pub struct ScaledBxDF {
@@ -494,6 +621,19 @@ impl Bxdf for ScaledBxDF {
     fn get_type(&self) -> u8 {
         self.bxdf.get_type()
     }
+    fn regularize(&self, min_alpha: Float) -> Option<Vec<Arc<Bxdf + Send + Sync>>> {
+        if let Some(bxdfs) = self.bxdf.regularize(min_alpha) {
+            Some(
+                bxdfs
+                    .into_iter()
+                    .map(|bxdf| {
+                        Arc::new(ScaledBxDF::new(bxdf, self.scale)) as Arc<Bxdf + Send + Sync>
+                    }).collect(),
+            )
+        } else {
+            None
+        }
+    }
 }
 
 pub trait Fresnel {
@@ -577,6 +717,18 @@ impl Bxdf for SpecularReflection {
     fn get_type(&self) -> u8 {
         BxdfType::BsdfReflection as u8 | BxdfType::BsdfSpecular as u8
     }
+    fn regularize(&self, min_alpha: Float) -> Option<Vec<Arc<Bxdf + Send + Sync>>> {
+        if min_alpha <= 0.0 as Float {
+            return None;
+        }
+        let distribution = Arc::new(TrowbridgeReitzDistribution::new(min_alpha, min_alpha, true));
+        let bxdf: Arc<Bxdf + Send + Sync> = Arc::new(MicrofacetReflection::new(
+            self.r,
+            distribution,
+            self.fresnel.clone(),
+        ));
+        Some(vec![bxdf])
+    }
 }
 
 pub struct SpecularTransmission {
@@ -659,6 +811,20 @@ impl Bxdf for SpecularTransmission {
     fn get_type(&self) -> u8 {
         BxdfType::BsdfTransmission as u8 | BxdfType::BsdfSpecular as u8
     }
+    fn regularize(&self, min_alpha: Float) -> Option<Vec<Arc<Bxdf + Send + Sync>>> {
+        if min_alpha <= 0.0 as Float {
+            return None;
+        }
+        let distribution = Arc::new(TrowbridgeReitzDistribution::new(min_alpha, min_alpha, true));
+        let bxdf: Arc<Bxdf + Send + Sync> = Arc::new(MicrofacetTransmission::new(
+            self.t,
+            distribution,
+            self.eta_a,
+            self.eta_b,
+            self.mode,
+        ));
+        Some(vec![bxdf])
+    }
 }
 
 pub struct FresnelSpecular {
@@ -766,6 +932,31 @@ impl Bxdf for FresnelSpecular {
             | BxdfType::BsdfTransmission as u8
             | BxdfType::BsdfSpecular as u8
     }
+    /// Splits into the rough reflection and transmission lobes
+    /// **GlassMaterial** uses for non-zero roughness.
+    fn regularize(&self, min_alpha: Float) -> Option<Vec<Arc<Bxdf + Send + Sync>>> {
+        if min_alpha <= 0.0 as Float {
+            return None;
+        }
+        let distribution = Arc::new(TrowbridgeReitzDistribution::new(min_alpha, min_alpha, true));
+        let fresnel = Arc::new(FresnelDielectric {
+            eta_i: self.eta_a,
+            eta_t: self.eta_b,
+        });
+        let reflection: Arc<Bxdf + Send + Sync> = Arc::new(MicrofacetReflection::new(
+            self.r,
+            distribution.clone(),
+            fresnel,
+        ));
+        let transmission: Arc<Bxdf + Send + Sync> = Arc::new(MicrofacetTransmission::new(
+            self.t,
+            distribution,
+            self.eta_a,
+            self.eta_b,
+            self.mode,
+        ));
+        Some(vec![reflection, transmission])
+    }
 }
 
 #[derive(Debug, Default, Copy, Clone)]
@@ -852,6 +1043,10 @@ impl Bxdf for LambertianTransmission {
     }
 }
 
+/// Microfacet based diffuse reflection (Oren-Nayar). The roughness
+/// *sigma* (the standard deviation of the microfacet orientation
+/// angle) is given in degrees; *sigma* = 0 reduces to Lambertian
+/// reflection.
 pub struct OrenNayar {
     pub r: Spectrum,
     pub a: Float,
@@ -995,6 +1190,19 @@ impl Bxdf for MicrofacetReflection {
     fn get_type(&self) -> u8 {
         BxdfType::BsdfReflection as u8 | BxdfType::BsdfGlossy as u8
     }
+
+    fn regularize(&self, min_alpha: Float) -> Option<Vec<Arc<Bxdf + Send + Sync>>> {
+        if let Some(distribution) = self.distribution.regularize(min_alpha) {
+            let bxdf: Arc<Bxdf + Send + Sync> = Arc::new(MicrofacetReflection::new(
+                self.r,
+                distribution,
+                self.fresnel.clone(),
+            ));
+            Some(vec![bxdf])
+        } else {
+            None
+        }
+    }
 }
 
 // MicrofacetTransmission
@@ -1081,6 +1289,21 @@ impl Bxdf for MicrofacetTransmission {
         BxdfType::BsdfTransmission as u8 | BxdfType::BsdfGlossy as u8
     }
 
+    fn regularize(&self, min_alpha: Float) -> Option<Vec<Arc<Bxdf + Send + Sync>>> {
+        if let Some(distribution) = self.distribution.regularize(min_alpha) {
+            let bxdf: Arc<Bxdf + Send + Sync> = Arc::new(MicrofacetTransmission::new(
+                self.t,
+                distribution,
+                self.eta_a,
+                self.eta_b,
+                self.mode,
+            ));
+            Some(vec![bxdf])
+        } else {
+            None
+        }
+    }
+
     /// Override sample_f() to use a better importance sampling method than weighted cosine based
     /// on the microface distribution
     fn sample_f(
@@ -1237,6 +1460,40 @@ impl FourierBSDF {
             mode: mode,
         }
     }
+    /// Converts the (already scaled) luminance *y* and, for tables
+    /// with three channels, the red and blue Fourier expansions in
+    /// *ak* into a **Spectrum**. All colors of the table go through
+    /// **Spectrum::from_rgb()**, so a spectral **Spectrum** type only
+    /// has to upsample there.
+    fn to_spectrum(
+        &self,
+        ak: &Vec<Float>,
+        m_max: i32,
+        cos_phi: Float,
+        y: Float,
+        scale: Float,
+    ) -> Spectrum {
+        if self.bsdf_table.n_channels == 1_i32 {
+            return Spectrum::new(y);
+        }
+        // compute and return RGB colors for tabulated BSDF
+        let r: Float = fourier(
+            ak,
+            (1_i32 * self.bsdf_table.m_max) as usize,
+            m_max,
+            cos_phi as f64,
+        ) * scale;
+        let b: Float = fourier(
+            ak,
+            (2_i32 * self.bsdf_table.m_max) as usize,
+            m_max,
+            cos_phi as f64,
+        ) * scale;
+        let g: Float = 1.39829 as Float * y - 0.100913 as Float * b - 0.297375 as Float * r;
+        let rgb: [Float; 3] = [r, g, b];
+        Spectrum::from_rgb(&rgb, SpectrumType::Illuminant)
+            .clamp(0.0 as Float, std::f32::INFINITY as Float)
+    }
 }
 
 impl Bxdf for FourierBSDF {
@@ -1304,26 +1561,7 @@ impl Bxdf for FourierBSDF {
             }
             scale *= eta * eta;
         }
-        if self.bsdf_table.n_channels == 1_i32 {
-            Spectrum::new(y * scale)
-        } else {
-            // compute and return RGB colors for tabulated BSDF
-            let r: Float = fourier(
-                &ak,
-                (1_i32 * self.bsdf_table.m_max) as usize,
-                m_max,
-                cos_phi as f64,
-            );
-            let b: Float = fourier(
-                &ak,
-                (2_i32 * self.bsdf_table.m_max) as usize,
-                m_max,
-                cos_phi as f64,
-            );
-            let g: Float = 1.39829 as Float * y - 0.100913 as Float * b - 0.297375 as Float * r;
-            let rgb: [Float; 3] = [r * scale, g * scale, b * scale];
-            Spectrum::from_rgb(&rgb).clamp(0.0 as Float, std::f32::INFINITY as Float)
-        }
+        self.to_spectrum(&ak, m_max, cos_phi, y * scale, scale)
     }
     fn sample_f(
         &self,
@@ -1440,26 +1678,7 @@ impl Bxdf for FourierBSDF {
             }
             scale *= eta * eta;
         }
-        if self.bsdf_table.n_channels == 1_i32 {
-            Spectrum::new(y * scale)
-        } else {
-            // compute and return RGB colors for tabulated BSDF
-            let r: Float = fourier(
-                &ak,
-                (1_i32 * self.bsdf_table.m_max) as usize,
-                m_max,
-                cos_phi as f64,
-            );
-            let b: Float = fourier(
-                &ak,
-                (2_i32 * self.bsdf_table.m_max) as usize,
-                m_max,
-                cos_phi as f64,
-            );
-            let g: Float = 1.39829 as Float * y - 0.100913 as Float * b - 0.297375 as Float * r;
-            let rgb: [Float; 3] = [r * scale, g * scale, b * scale];
-            Spectrum::from_rgb(&rgb).clamp(0.0 as Float, std::f32::INFINITY as Float)
-        }
+        self.to_spectrum(&ak, m_max, cos_phi, y * scale, scale)
     }
     fn pdf(&self, wo: &Vector3f, wi: &Vector3f) -> Float {
         // find the zenith angle cosines and azimuth difference angle
@@ -1698,3 +1917,399 @@ pub fn fr_conductor(cos_theta_i: Float, eta_i: Spectrum, eta_t: Spectrum, k: Spe
 fn pow5(v: Float) -> Float {
     (v * v) * (v * v) * v
 }
+
+/// Numerical checks of the BxDFs, used by the material tests.
+#[cfg(any(test, debug_assertions))]
+pub mod test_util {
+    use super::*;
+
+    /// White furnace test for a rough dielectric interface (as used
+    /// by **GlassMaterial**) with index of refraction *eta* and
+    /// roughness *alpha*: estimates the fraction of the energy
+    /// arriving from a few directions which a **MicrofacetReflection**
+    /// lobe reflects plus a **MicrofacetTransmission** lobe transmits,
+    /// and asserts that no energy is created. Both lobes are
+    /// importance sampled, transmission without radiance scaling.
+    /// Returns the largest fraction found.
+    pub fn check_dielectric_energy(eta: Float, alpha: Float) -> Float {
+        let distrib = Arc::new(TrowbridgeReitzDistribution::new(alpha, alpha, true));
+        let fresnel = Arc::new(FresnelDielectric {
+            eta_i: 1.0 as Float,
+            eta_t: eta,
+        });
+        let reflection: MicrofacetReflection =
+            MicrofacetReflection::new(Spectrum::new(1.0 as Float), distrib.clone(), fresnel);
+        let transmission: MicrofacetTransmission = MicrofacetTransmission::new(
+            Spectrum::new(1.0 as Float),
+            distrib,
+            1.0 as Float,
+            eta,
+            TransportMode::Importance,
+        );
+        let lobes: [&Bxdf; 2] = [&reflection, &transmission];
+        // stratified (midpoint) samples
+        let n: usize = 128_usize;
+        let mut max_energy: Float = 0.0 as Float;
+        for cos_theta_o in [0.2 as Float, 0.5 as Float, 0.9 as Float].iter() {
+            let wo: Vector3f = Vector3f {
+                x: (1.0 as Float - cos_theta_o * cos_theta_o).sqrt(),
+                y: 0.0 as Float,
+                z: *cos_theta_o,
+            };
+            let mut sum: Float = 0.0 as Float;
+            for i in 0..n {
+                for j in 0..n {
+                    let u: Point2f = Point2f {
+                        x: (i as Float + 0.5 as Float) / n as Float,
+                        y: (j as Float + 0.5 as Float) / n as Float,
+                    };
+                    for bxdf in lobes.iter() {
+                        let mut wi: Vector3f = Vector3f::default();
+                        let mut pdf: Float = 0.0 as Float;
+                        let mut sampled_type: u8 = 0_u8;
+                        let f: Spectrum =
+                            bxdf.sample_f(&wo, &mut wi, &u, &mut pdf, &mut sampled_type);
+                        if pdf > 0.0 as Float && !f.is_black() {
+                            sum += f.y() * abs_cos_theta(&wi) / pdf;
+                        }
+                    }
+                }
+            }
+            let energy: Float = sum / (n * n) as Float;
+            assert!(
+                energy <= 1.0 as Float + 1e-3 as Float,
+                "eta {}, alpha {}, cos(theta_o) {}: energy {} > 1",
+                eta,
+                alpha,
+                cos_theta_o,
+                energy
+            );
+            max_energy = max_energy.max(energy);
+        }
+        max_energy
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+    use core::geometry::spherical_direction;
+    use core::material::Material;
+    use core::texture::Texture;
+    use materials::matte::MatteMaterial;
+    use std::io::Write;
+
+    fn local_bsdf(bxdfs: Vec<Arc<Bxdf + Sync + Send>>) -> Bsdf {
+        Bsdf {
+            eta: 1.0 as Float,
+            ns: Normal3f {
+                x: 0.0 as Float,
+                y: 0.0 as Float,
+                z: 1.0 as Float,
+            },
+            ng: Normal3f {
+                x: 0.0 as Float,
+                y: 0.0 as Float,
+                z: 1.0 as Float,
+            },
+            ss: Vector3f {
+                x: 1.0 as Float,
+                y: 0.0 as Float,
+                z: 0.0 as Float,
+            },
+            ts: Vector3f {
+                x: 0.0 as Float,
+                y: 1.0 as Float,
+                z: 0.0 as Float,
+            },
+            bxdfs: bxdfs,
+            check_sides: false,
+        }
+    }
+
+    /// Sum of the lobe pdfs for a direction 20 degrees off the mirror
+    /// direction of *wo* (30 degrees from the normal).
+    fn off_specular_pdf(bsdf: &Bsdf) -> Float {
+        let wo: Vector3f = spherical_direction(
+            radians(30.0 as Float).sin(),
+            radians(30.0 as Float).cos(),
+            0.0 as Float,
+        );
+        let wi: Vector3f = spherical_direction(
+            radians(50.0 as Float).sin(),
+            radians(50.0 as Float).cos(),
+            PI,
+        );
+        bsdf.bxdfs.iter().map(|bxdf| bxdf.pdf(&wo, &wi)).sum()
+    }
+
+    #[test]
+    fn regularize_roughens_near_specular_lobes() {
+        let distrib = Arc::new(TrowbridgeReitzDistribution::new(
+            0.01 as Float,
+            0.01 as Float,
+            true,
+        ));
+        let mut bsdf: Bsdf = local_bsdf(vec![Arc::new(MicrofacetReflection::new(
+            Spectrum::new(1.0 as Float),
+            distrib,
+            Arc::new(FresnelNoOp {}),
+        ))]);
+        let before: Float = off_specular_pdf(&bsdf);
+        bsdf.regularize(0.3 as Float);
+        let after: Float = off_specular_pdf(&bsdf);
+        assert!(after > before, "pdf {} <= {}", after, before);
+        // already rough enough, nothing changes
+        bsdf.regularize(0.1 as Float);
+        assert_eq!(off_specular_pdf(&bsdf), after);
+    }
+
+    #[test]
+    fn regularize_replaces_specular_lobes() {
+        let specular: u8 = BxdfType::BsdfSpecular as u8
+            | BxdfType::BsdfReflection as u8
+            | BxdfType::BsdfTransmission as u8;
+        let glossy: u8 = BxdfType::BsdfGlossy as u8
+            | BxdfType::BsdfReflection as u8
+            | BxdfType::BsdfTransmission as u8;
+        // mirror
+        let mut mirror: Bsdf = local_bsdf(vec![Arc::new(SpecularReflection::new(
+            Spectrum::new(1.0 as Float),
+            Arc::new(FresnelNoOp {}),
+        ))]);
+        assert_eq!(off_specular_pdf(&mirror), 0.0 as Float);
+        mirror.regularize(0.3 as Float);
+        assert_eq!(mirror.num_components(specular), 0_u8);
+        assert_eq!(mirror.num_components(glossy), 1_u8);
+        assert!(off_specular_pdf(&mirror) > 0.0 as Float);
+        // glass (one lobe for reflection and transmission)
+        let mut glass: Bsdf = local_bsdf(vec![Arc::new(FresnelSpecular::new(
+            Spectrum::new(1.0 as Float),
+            Spectrum::new(1.0 as Float),
+            1.0 as Float,
+            1.5 as Float,
+            TransportMode::Radiance,
+        ))]);
+        glass.regularize(0.3 as Float);
+        assert_eq!(glass.num_components(specular), 0_u8);
+        assert_eq!(glass.num_components(glossy), 2_u8);
+        // a zero minimum disables regularization
+        let mut glass: Bsdf = local_bsdf(vec![Arc::new(SpecularTransmission::new(
+            Spectrum::new(1.0 as Float),
+            1.0 as Float,
+            1.5 as Float,
+            TransportMode::Radiance,
+        ))]);
+        glass.regularize(0.0 as Float);
+        assert_eq!(glass.num_components(specular), 1_u8);
+    }
+
+    /// Displacement rising with slope *k* in u.
+    struct Slope {
+        k: Float,
+    }
+
+    impl Texture<Float> for Slope {
+        fn evaluate(&self, si: &SurfaceInteraction) -> Float {
+            self.k * si.uv.x
+        }
+    }
+
+    /// Lambertian BSDF of the plane z = 0, bump mapped with *slope*
+    /// (the shading normal tilts towards -x).
+    fn bumped_plane(slope: Float) -> Bsdf {
+        let mut si: SurfaceInteraction = SurfaceInteraction::default();
+        si.n = Normal3f {
+            x: 0.0 as Float,
+            y: 0.0 as Float,
+            z: 1.0 as Float,
+        };
+        si.dpdu = Vector3f {
+            x: 1.0 as Float,
+            y: 0.0 as Float,
+            z: 0.0 as Float,
+        };
+        si.dpdv = Vector3f {
+            x: 0.0 as Float,
+            y: 1.0 as Float,
+            z: 0.0 as Float,
+        };
+        si.shading.n = si.n;
+        si.shading.dpdu = si.dpdu;
+        si.shading.dpdv = si.dpdv;
+        let d: Arc<Texture<Float> + Send + Sync> = Arc::new(Slope { k: slope });
+        MatteMaterial::bump(&d, &mut si);
+        assert!(si.shading.bumped);
+        let lambertian: Arc<Bxdf + Sync + Send> =
+            Arc::new(LambertianReflection::new(Spectrum::new(0.5 as Float)));
+        Bsdf::new(&si, 1.0 as Float, vec![lambertian])
+    }
+
+    #[test]
+    fn bump_mapping_does_not_leak_light() {
+        let mut bsdf: Bsdf = bumped_plane(3.0 as Float);
+        assert!(bsdf.check_sides);
+        assert!(bsdf.ns.x < -0.9 as Float);
+        let all: u8 = BxdfType::BsdfAll as u8;
+        let up: Vector3f = Vector3f {
+            x: 0.0 as Float,
+            y: 0.0 as Float,
+            z: 1.0 as Float,
+        };
+        // grazing, below the geometric but above the shading horizon
+        let occluded: Vector3f = Vector3f {
+            x: -1.0 as Float,
+            y: 0.0 as Float,
+            z: -0.1 as Float,
+        }
+        .normalize();
+        assert_eq!(bsdf.f(&up, &occluded, all).y(), 0.0 as Float);
+        assert_eq!(bsdf.pdf(&up, &occluded, all), 0.0 as Float);
+        // above the geometric but below the shading horizon
+        let wo: Vector3f = Vector3f {
+            x: 1.0 as Float,
+            y: 0.0 as Float,
+            z: 0.1 as Float,
+        }
+        .normalize();
+        let wi: Vector3f = Vector3f {
+            x: 1.0 as Float,
+            y: 0.2 as Float,
+            z: 0.1 as Float,
+        }
+        .normalize();
+        assert_eq!(bsdf.f(&wo, &wi, all).y(), 0.0 as Float);
+        assert_eq!(bsdf.pdf(&wo, &wi, all), 0.0 as Float);
+        // without bump mapping (e.g. interpolated normals) nothing is
+        // rejected
+        bsdf.check_sides = false;
+        assert!(bsdf.f(&wo, &wi, all).y() > 0.0 as Float);
+        assert!(bsdf.pdf(&wo, &wi, all) > 0.0 as Float);
+    }
+
+    /// Writes a tabulated BSDF file (see FourierBSDFTable::read()
+    /// and the "SCATFUN" format of pbrt) with four zenith angles and
+    /// two Fourier coefficients per channel and angle pair. All
+    /// channels store the same coefficients, except for a *tint* added
+    /// to the red channel.
+    pub fn write_bsdf_file(filename: &String, n_channels: i32, tint: Float) {
+        let n_mu: i32 = 4_i32;
+        let m: i32 = 2_i32;
+        let n_pairs: i32 = n_mu * n_mu;
+        let mut data: Vec<u8> = Vec::new();
+        data.extend_from_slice(b"SCATFUN\x01");
+        // flags, nMu, nCoeffs, mMax, nChannels, nBases, 3 unused
+        for i in [1, n_mu, n_pairs * m * n_channels, m, n_channels, 1, 0, 0, 0].iter() {
+            data.write_i32::<LittleEndian>(*i).unwrap();
+        }
+        data.write_f32::<LittleEndian>(1.5_f32).unwrap();
+        for _ in 0..4 {
+            data.write_i32::<LittleEndian>(0_i32).unwrap();
+        }
+        for mu in [-1.0_f32, -1.0 / 3.0, 1.0 / 3.0, 1.0].iter() {
+            data.write_f32::<LittleEndian>(*mu).unwrap();
+        }
+        // cdf (not needed by f())
+        for _ in 0..n_pairs {
+            data.write_f32::<LittleEndian>(0.0_f32).unwrap();
+        }
+        // offset and length
+        for pair in 0..n_pairs {
+            data.write_i32::<LittleEndian>(pair * m * n_channels).unwrap();
+            data.write_i32::<LittleEndian>(m).unwrap();
+        }
+        for pair in 0..n_pairs {
+            for c in 0..n_channels {
+                let a0: f32 = 0.2_f32 + 0.01_f32 * pair as f32;
+                if c == 1 {
+                    data.write_f32::<LittleEndian>(a0 + tint as f32).unwrap();
+                } else {
+                    data.write_f32::<LittleEndian>(a0).unwrap();
+                }
+                data.write_f32::<LittleEndian>(0.05_f32).unwrap();
+            }
+        }
+        File::create(Path::new(filename)).unwrap().write_all(&data).unwrap();
+    }
+
+    fn read_bsdf_file(name: &str, n_channels: i32, tint: Float) -> FourierBSDFTable {
+        let path = std::env::temp_dir().join(name);
+        let filename: String = String::from(path.to_str().unwrap());
+        write_bsdf_file(&filename, n_channels, tint);
+        let mut table: FourierBSDFTable = FourierBSDFTable::default();
+        assert!(table.read(&filename));
+        std::fs::remove_file(&path).unwrap();
+        table
+    }
+
+    #[test]
+    fn grayscale_fourier_table_is_stored_with_one_channel() {
+        let gray: FourierBSDFTable = read_bsdf_file("pbrt_gray.bsdf", 3, 0.0 as Float);
+        assert!(gray.is_monochrome());
+        assert_eq!(gray.n_channels, 1_i32);
+        assert_eq!(gray.a.len(), 16 * 2);
+        // the same coefficients stored as a single channel file
+        let single: FourierBSDFTable = read_bsdf_file("pbrt_single.bsdf", 1, 0.0 as Float);
+        assert_eq!(gray.a, single.a);
+        assert_eq!(gray.a_offset, single.a_offset);
+        // a colored table keeps its channels
+        let colored: FourierBSDFTable = read_bsdf_file("pbrt_colored.bsdf", 3, 0.1 as Float);
+        assert!(!colored.is_monochrome());
+        assert_eq!(colored.n_channels, 3_i32);
+        // evaluation matches the three channel table
+        let mut rgb: FourierBSDFTable = read_bsdf_file("pbrt_rgb.bsdf", 3, 1e-3 as Float);
+        assert_eq!(rgb.n_channels, 3_i32);
+        // restore the grayscale coefficients of the red channel
+        for i in 0..rgb.a_offset.len() {
+            let offset: usize = rgb.a_offset[i] as usize;
+            rgb.a[offset + 2] = rgb.a[offset];
+        }
+        assert!(rgb.is_monochrome());
+        let gray_bsdf = FourierBSDF::new(Arc::new(gray), TransportMode::Importance);
+        let rgb_bsdf = FourierBSDF::new(Arc::new(rgb), TransportMode::Importance);
+        for (theta_o, theta_i, phi) in [(0.3, 2.5, 0.0), (1.0, 2.0, 1.0), (0.7, 0.4, 2.5)].iter() {
+            let wo: Vector3f = spherical_direction(
+                (*theta_o as Float).sin(),
+                (*theta_o as Float).cos(),
+                0.0 as Float,
+            );
+            let wi: Vector3f = spherical_direction(
+                (*theta_i as Float).sin(),
+                (*theta_i as Float).cos(),
+                *phi as Float,
+            );
+            let expected: Spectrum = rgb_bsdf.f(&wo, &wi);
+            let f: Spectrum = gray_bsdf.f(&wo, &wi);
+            assert!(expected[0] > 0.0 as Float);
+            for c in 0..3 {
+                assert!((f[c] - expected[c]).abs() < 1e-4 as Float * expected[c].abs());
+            }
+        }
+    }
+
+    #[test]
+    fn fourier_channels_convert_to_a_spectrum_of_the_same_luminance() {
+        // DC and first order term of the luminance, red and blue channel
+        let ak: Vec<Float> = vec![0.5, 0.1, 0.7, 0.2, 0.3, 0.05];
+        let cos_phi: Float = 0.5 as Float;
+        let scale: Float = 2.0 as Float;
+        let mut table: FourierBSDFTable = FourierBSDFTable::default();
+        table.m_max = 2_i32;
+        table.n_channels = 3_i32;
+        let rgb_bsdf = FourierBSDF::new(Arc::new(table), TransportMode::Radiance);
+        let y: Float = fourier(&ak, 0, 2_i32, cos_phi as f64) * scale;
+        let s: Spectrum = rgb_bsdf.to_spectrum(&ak, 2_i32, cos_phi, y, scale);
+        assert!((s.y() - y).abs() < 1e-4 as Float, "{} != {}", s.y(), y);
+        let rgb: [Float; 3] = s.to_rgb_array();
+        assert!((rgb[0] - (0.7 + 0.2 * cos_phi) * scale).abs() < 1e-4 as Float);
+        assert!((rgb[2] - (0.3 + 0.05 * cos_phi) * scale).abs() < 1e-4 as Float);
+        // a single channel table is gray
+        let mut table: FourierBSDFTable = FourierBSDFTable::default();
+        table.m_max = 2_i32;
+        table.n_channels = 1_i32;
+        let gray_bsdf = FourierBSDF::new(Arc::new(table), TransportMode::Radiance);
+        let s: Spectrum = gray_bsdf.to_spectrum(&ak, 2_i32, cos_phi, y, scale);
+        assert_eq!(s.to_rgb_array(), [y, y, y]);
+    }
+}
@@ -2,10 +2,13 @@
 //! samplers but also provides some common functionality for use by
 //! **Sampler** implementations.
 
+// std
+use std::sync::atomic::{AtomicUsize, Ordering};
 // pbrt
 use core::camera::CameraSample;
 use core::geometry::{Point2f, Point2i};
 use core::pbrt::Float;
+use core::sampling::cmj_sample_2d;
 
 // see sampler.h
 
@@ -13,6 +16,10 @@ pub trait Sampler: SamplerClone {
     fn start_pixel(&mut self, p: &Point2i);
     fn get_1d(&mut self) -> Float;
     fn get_2d(&mut self) -> Point2f;
+    /// Assembles the **CameraSample** for the pixel *p_raster* in the
+    /// canonical dimension order: film offset (2D), time (1D), lens
+    /// position (2D). Samplers reserving the first dimensions for the
+    /// camera (see **ARRAY_START_DIM**) rely on this order.
     fn get_camera_sample(&mut self, p_raster: &Point2i) -> CameraSample {
         let mut cs: CameraSample = CameraSample::default();
         cs.p_film = Point2f {
@@ -23,14 +30,75 @@ pub trait Sampler: SamplerClone {
         cs.p_lens = self.get_2d();
         cs
     }
+    /// Returns a correlated multi-jittered 2D sample for the current
+    /// sample number, using *strata* strata per pixel. The pattern is
+    /// shuffled per pixel to decorrelate neighbouring pixels.
+    fn get_2d_jittered(&mut self, strata: i32) -> Point2f {
+        assert!(strata > 0);
+        let m: u32 = (strata as Float).sqrt().ceil() as u32;
+        let n: u32 = (strata as u32 + m - 1) / m;
+        let s: u32 = (self.get_current_sample_number() % strata as i64) as u32;
+        let p: Point2i = self.get_current_pixel();
+        let pattern: u32 =
+            (p.x as u32).wrapping_mul(0x8da6b343) ^ (p.y as u32).wrapping_mul(0xd8163841);
+        cmj_sample_2d(s, m, n, pattern)
+    }
+    /// Produces *n* 2D samples for the current pixel, starting the
+    /// next pixel sample (and restarting the pixel) as needed. Meant
+    /// to measure sampler throughput.
+    fn benchmark_fill(&mut self, n: usize) -> Vec<Point2f> {
+        let mut samples: Vec<Point2f> = Vec::with_capacity(n);
+        let pixel: Point2i = self.get_current_pixel();
+        self.start_pixel(&pixel);
+        while samples.len() < n {
+            samples.push(self.get_2d());
+            if !self.start_next_sample() {
+                self.start_pixel(&pixel);
+            }
+        }
+        samples
+    }
+    /// Starts *pixel* and returns the first 2D sample of each pixel
+    /// sample (in [0,1)^2). Useful to inspect the distribution of a
+    /// sampler, e.g. its per stratum occupancy.
+    fn fill_pixel_2d(&mut self, pixel: &Point2i) -> Vec<Point2f> {
+        let mut samples: Vec<Point2f> = Vec::with_capacity(self.get_samples_per_pixel() as usize);
+        self.start_pixel(pixel);
+        loop {
+            samples.push(self.get_2d());
+            if !self.start_next_sample() {
+                break;
+            }
+        }
+        samples
+    }
+    /// Requests an array of *n* 1D samples per pixel sample, fetched
+    /// in the same order with **get_1d_array()**. Samplers without 1D
+    /// array support ignore the request and return empty arrays.
+    fn request_1d_array(&mut self, _n: i32) {}
     fn request_2d_array(&mut self, n: i32);
+    /// Whether sample arrays (see **request_2d_array()**) are
+    /// meaningful for this sampler. Primary sample space samplers
+    /// (**MLTSampler**) return false, integrators then fall back to
+    /// individual **get_2d()** calls.
+    fn supports_arrays(&self) -> bool {
+        true
+    }
     fn round_count(&self, count: i32) -> i32;
+    fn get_1d_array(&mut self, _n: i32) -> Vec<Float> {
+        Vec::new()
+    }
     fn get_2d_array(&mut self, n: i32) -> Vec<Point2f>;
     fn start_next_sample(&mut self) -> bool;
     fn reseed(&mut self, seed: u64);
     fn get_current_pixel(&self) -> Point2i;
     fn get_current_sample_number(&self) -> i64;
     fn get_samples_per_pixel(&self) -> i64;
+    /// Number of dimensions consumed so far by the current pixel
+    /// sample (0 for samplers which don't keep track).
+    fn dimensions_used(&self) -> i64 {
+        0_i64
+    }
 }
 
 pub trait PixelSampler: Sampler {}
@@ -39,6 +107,51 @@ pub trait GlobalSampler: Sampler {
     fn set_sample_number(&mut self, sample_num: i64) -> bool;
 }
 
+/// Default of **max_sampler_dimensions()**. Paths consuming more
+/// sample dimensions than that trigger a warning in debug builds; low
+/// discrepancy samplers are less well distributed in high dimensions.
+pub const MAX_SAMPLER_DIMENSIONS: i64 = 200_i64;
+
+static SAMPLER_DIMENSION_BUDGET: AtomicUsize = AtomicUsize::new(MAX_SAMPLER_DIMENSIONS as usize);
+
+/// Current sample dimension budget of a path (see
+/// **MAX_SAMPLER_DIMENSIONS**).
+pub fn max_sampler_dimensions() -> i64 {
+    SAMPLER_DIMENSION_BUDGET.load(Ordering::Relaxed) as i64
+}
+
+/// Changes the sample dimension budget, e.g. for deep paths which are
+/// known to need more dimensions (integrator parameter
+/// "maxsamplerdimensions").
+pub fn set_max_sampler_dimensions(dimensions: i64) {
+    SAMPLER_DIMENSION_BUDGET.store(dimensions.max(1_i64) as usize, Ordering::Relaxed);
+}
+
+/// Default first dimension used for array samples by a
+/// **GlobalSampler**; the dimensions below it are left to the camera
+/// sample (film position, time, and lens position).
+pub const ARRAY_START_DIM: i64 = 5_i64;
+
+/// Remaps the next dimension for a **GlobalSampler** so that the *n*
+/// dimensions consumed by get_1d() (*n* == 1) or get_2d() (*n* == 2)
+/// don't overlap the dimensions [*array_start_dim*,
+/// *array_end_dim*) reserved for array samples. A larger
+/// *array_start_dim* keeps more per-sample draws (e.g. the first BSDF
+/// and light samples) in the better distributed low dimensions and
+/// pushes the array samples higher.
+pub fn remap_global_dimension(
+    dimension: i64,
+    n: i64,
+    array_start_dim: i64,
+    array_end_dim: i64,
+) -> i64 {
+    if dimension + n > array_start_dim && dimension < array_end_dim {
+        array_end_dim
+    } else {
+        dimension
+    }
+}
+
 pub trait SamplerClone {
     fn box_clone(&self) -> Box<Sampler + Send + Sync>;
 }
@@ -57,3 +170,85 @@ impl Clone for Box<Sampler + Send + Sync> {
         self.box_clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::geometry::Bounds2i;
+    use samplers::halton::HaltonSampler;
+    use samplers::stratified::StratifiedSampler;
+
+    fn check_fill(sampler: &mut Sampler, spp: usize) {
+        let n: usize = 2 * spp + 3;
+        let samples: Vec<Point2f> = sampler.benchmark_fill(n);
+        assert_eq!(samples.len(), n);
+        for u in &samples {
+            assert!(u.x >= 0.0 as Float && u.x < 1.0 as Float, "{:?}", u);
+            assert!(u.y >= 0.0 as Float && u.y < 1.0 as Float, "{:?}", u);
+        }
+    }
+
+    #[test]
+    fn benchmark_fill_returns_n_samples_in_the_unit_square() {
+        let bounds: Bounds2i = Bounds2i {
+            p_min: Point2i { x: 0, y: 0 },
+            p_max: Point2i { x: 16, y: 16 },
+        };
+        let mut halton: HaltonSampler = HaltonSampler::new(16, bounds, false, 5_i64);
+        halton.start_pixel(&Point2i { x: 3, y: 7 });
+        check_fill(&mut halton, 16);
+        // restarting the pixel repeats its (deterministic) pattern
+        let samples: Vec<Point2f> = halton.benchmark_fill(32);
+        for i in 0..16 {
+            assert_eq!(samples[i].x, samples[i + 16].x);
+            assert_eq!(samples[i].y, samples[i + 16].y);
+        }
+        let mut stratified: StratifiedSampler = StratifiedSampler::new(2, 2, true, 4_i64);
+        stratified.start_pixel(&Point2i { x: 3, y: 7 });
+        check_fill(&mut stratified, 4);
+    }
+
+    #[test]
+    fn deep_paths_exceed_the_dimension_budget() {
+        let bounds: Bounds2i = Bounds2i {
+            p_min: Point2i { x: 0, y: 0 },
+            p_max: Point2i { x: 16, y: 16 },
+        };
+        let mut halton: HaltonSampler = HaltonSampler::new(16, bounds, false, 5_i64);
+        halton.start_pixel(&Point2i { x: 3, y: 7 });
+        for _ in 0..(MAX_SAMPLER_DIMENSIONS / 2 + 1) {
+            halton.get_2d();
+        }
+        assert!(halton.dimensions_used() > max_sampler_dimensions());
+        // a larger budget (see "maxsamplerdimensions") silences the warning
+        set_max_sampler_dimensions(4 * MAX_SAMPLER_DIMENSIONS);
+        assert_eq!(max_sampler_dimensions(), 4 * MAX_SAMPLER_DIMENSIONS);
+        assert!(halton.dimensions_used() <= max_sampler_dimensions());
+        set_max_sampler_dimensions(MAX_SAMPLER_DIMENSIONS);
+        assert_eq!(max_sampler_dimensions(), MAX_SAMPLER_DIMENSIONS);
+    }
+
+    #[test]
+    fn camera_samples_lie_in_their_pixel() {
+        let bounds: Bounds2i = Bounds2i {
+            p_min: Point2i { x: 0, y: 0 },
+            p_max: Point2i { x: 16, y: 16 },
+        };
+        let mut halton: HaltonSampler = HaltonSampler::new(16, bounds, false, 5_i64);
+        let unit = |u: Float| -> bool { u >= 0.0 as Float && u < 1.0 as Float };
+        for &(x, y) in [(0, 0), (3, 7), (15, 15)].iter() {
+            let p_raster: Point2i = Point2i { x: x, y: y };
+            halton.start_pixel(&p_raster);
+            loop {
+                let cs: CameraSample = halton.get_camera_sample(&p_raster);
+                assert!(unit(cs.p_film.x - x as Float), "{:?}", cs.p_film);
+                assert!(unit(cs.p_film.y - y as Float), "{:?}", cs.p_film);
+                assert!(unit(cs.p_lens.x) && unit(cs.p_lens.y), "{:?}", cs.p_lens);
+                assert!(unit(cs.time), "{}", cs.time);
+                if !halton.start_next_sample() {
+                    break;
+                }
+            }
+        }
+    }
+}
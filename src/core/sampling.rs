@@ -9,6 +9,7 @@ use core::pbrt::clamp_t;
 use core::pbrt::Float;
 use core::pbrt::{INV_2_PI, INV_4_PI, INV_PI, PI_OVER_2, PI_OVER_4};
 use core::rng::Rng;
+use core::rng::FLOAT_ONE_MINUS_EPSILON;
 
 // see sampling.h
 
@@ -195,6 +196,55 @@ impl Distribution2D {
     }
 }
 
+/// Stratified samples in [0,1): one sample per stratum, either
+/// jittered within the stratum or placed at its center.
+pub fn stratified_sample_1d(samp: &mut [Float], n_samples: i32, rng: &mut Rng, jitter: bool) {
+    let inv_n_samples: Float = 1.0 as Float / n_samples as Float;
+    for i in 0..n_samples as usize {
+        let delta: Float = if jitter { rng.uniform_float() } else { 0.5 as Float };
+        samp[i] = ((i as Float + delta) * inv_n_samples).min(FLOAT_ONE_MINUS_EPSILON);
+    }
+}
+
+/// Stratified samples in [0,1)^2 for *nx* x *ny* strata, either
+/// jittered within each stratum or placed at its center.
+pub fn stratified_sample_2d(samp: &mut [Point2f], nx: i32, ny: i32, rng: &mut Rng, jitter: bool) {
+    let dx: Float = 1.0 as Float / nx as Float;
+    let dy: Float = 1.0 as Float / ny as Float;
+    let mut i: usize = 0;
+    for y in 0..ny {
+        for x in 0..nx {
+            let jx: Float = if jitter { rng.uniform_float() } else { 0.5 as Float };
+            let jy: Float = if jitter { rng.uniform_float() } else { 0.5 as Float };
+            samp[i].x = ((x as Float + jx) * dx).min(FLOAT_ONE_MINUS_EPSILON);
+            samp[i].y = ((y as Float + jy) * dy).min(FLOAT_ONE_MINUS_EPSILON);
+            i += 1;
+        }
+    }
+}
+
+/// Latin hypercube sampling of *n_samples* 2D samples: each dimension
+/// is stratified on its own and the strata are shuffled independently.
+pub fn latin_hypercube(samples: &mut [Point2f], n_samples: i32, rng: &mut Rng) {
+    // generate LHS samples along diagonal
+    let inv_n_samples: Float = 1.0 as Float / n_samples as Float;
+    for i in 0..n_samples as usize {
+        let sx: Float = (i as Float + rng.uniform_float()) * inv_n_samples;
+        let sy: Float = (i as Float + rng.uniform_float()) * inv_n_samples;
+        samples[i].x = sx.min(FLOAT_ONE_MINUS_EPSILON);
+        samples[i].y = sy.min(FLOAT_ONE_MINUS_EPSILON);
+    }
+    // permute LHS samples in each dimension
+    for dim in 0..2_u8 {
+        for j in 0..n_samples {
+            let other: i32 = j + rng.uniform_uint32_bounded((n_samples - j) as u32) as i32;
+            let tmp: Float = samples[j as usize][dim];
+            samples[j as usize][dim] = samples[other as usize][dim];
+            samples[other as usize][dim] = tmp;
+        }
+    }
+}
+
 /// Randomly permute an array of *count* sample values, each of which
 /// has *n_dimensions* dimensions.
 pub fn shuffle<T>(samp: &mut [T], count: i32, n_dimensions: i32, rng: &mut Rng) {
@@ -209,6 +259,75 @@ pub fn shuffle<T>(samp: &mut [T], count: i32, n_dimensions: i32, rng: &mut Rng)
     }
 }
 
+/// Hash based permutation of *i* within [0, *l*) for pattern *p*
+/// (see Kensler: Correlated Multi-Jittered Sampling).
+pub fn cmj_permute(i: u32, l: u32, p: u32) -> u32 {
+    let mut i: u32 = i;
+    let mut w: u32 = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % l
+}
+
+/// Hash based pseudo-random value in [0, 1) for index *i* and
+/// pattern *p*.
+pub fn cmj_randfloat(i: u32, p: u32) -> Float {
+    let mut i: u32 = i;
+    i ^= p;
+    i ^= i >> 17;
+    i ^= i >> 10;
+    i = i.wrapping_mul(0xb36534e5);
+    i ^= i >> 12;
+    i ^= i >> 21;
+    i = i.wrapping_mul(0x93fc4795);
+    i ^= 0xdf6e307f;
+    i ^= i >> 17;
+    i = i.wrapping_mul(1 | p >> 18);
+    (i as Float * (1.0 as Float / 4294967808.0 as Float)).min(FLOAT_ONE_MINUS_EPSILON)
+}
+
+/// Returns sample *s* of a correlated multi-jittered pattern with
+/// *m* x *n* strata. Different patterns *p* are decorrelated by
+/// shuffling.
+pub fn cmj_sample_2d(s: u32, m: u32, n: u32, p: u32) -> Point2f {
+    let sx: u32 = cmj_permute(s % m, m, p.wrapping_mul(0xa511e9b3));
+    let sy: u32 = cmj_permute(s / m, n, p.wrapping_mul(0x63d83595));
+    let jx: Float = cmj_randfloat(s, p.wrapping_mul(0xa399d265));
+    let jy: Float = cmj_randfloat(s, p.wrapping_mul(0x711ad6a5));
+    Point2f {
+        x: (((s % m) as Float + (sy as Float + jx) / n as Float) / m as Float)
+            .min(FLOAT_ONE_MINUS_EPSILON),
+        y: (((s / m) as Float + (sx as Float + jy) / m as Float) / n as Float)
+            .min(FLOAT_ONE_MINUS_EPSILON),
+    }
+}
+
 /// Cosine-weighted hemisphere sampling using Malley's method.
 pub fn cosine_sample_hemisphere(u: &Point2f) -> Vector3f {
     let d: Point2f = concentric_sample_disk(u);
@@ -295,6 +414,46 @@ pub fn concentric_sample_disk(u: &Point2f) -> Point2f {
     } * r
 }
 
+/// Inverse of **concentric_sample_disk()**: maps a point on the unit
+/// disk back to the sample in $[0,1]^2$ which generated it.
+pub fn concentric_sample_disk_inverse(p: &Point2f) -> Point2f {
+    let r: Float = (p.x * p.x + p.y * p.y).sqrt();
+    // handle degeneracy at the origin
+    if r == 0.0 as Float {
+        return Point2f { x: 0.5, y: 0.5 };
+    }
+    // undo the concentric mapping (the sign of _r_ selects the wedge)
+    let u_offset: Point2f;
+    if p.x.abs() > p.y.abs() {
+        let r: Float = if p.x > 0.0 as Float { r } else { -r };
+        let theta: Float = (p.y / r).atan2(p.x / r);
+        u_offset = Point2f {
+            x: r,
+            y: r * theta / PI_OVER_4,
+        };
+    } else {
+        let r: Float = if p.y > 0.0 as Float { r } else { -r };
+        let theta: Float = (p.y / r).atan2(p.x / r);
+        u_offset = Point2f {
+            x: r * (PI_OVER_2 - theta) / PI_OVER_4,
+            y: r,
+        };
+    }
+    // map $[-1,1]^2$ back to $[0,1]^2$
+    Point2f {
+        x: clamp_t(
+            (u_offset.x + 1.0 as Float) * 0.5 as Float,
+            0.0 as Float,
+            1.0 as Float,
+        ),
+        y: clamp_t(
+            (u_offset.y + 1.0 as Float) * 0.5 as Float,
+            0.0 as Float,
+            1.0 as Float,
+        ),
+    }
+}
+
 /// Uniformly sample rays in a cone of directions. Probability density
 /// function (PDF).
 pub fn uniform_cone_pdf(cos_theta_max: Float) -> Float {
@@ -322,3 +481,41 @@ pub fn uniform_sample_triangle(u: &Point2f) -> Point2f {
         y: u[1] * su0,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_round_trip(u: &Point2f) {
+        let back: Point2f = concentric_sample_disk_inverse(&concentric_sample_disk(u));
+        assert!(
+            (back.x - u.x).abs() < 1e-4 as Float && (back.y - u.y).abs() < 1e-4 as Float,
+            "{:?} -> {:?}",
+            u,
+            back
+        );
+    }
+
+    #[test]
+    fn concentric_disk_samples_invert() {
+        let mut rng: Rng = Rng::default();
+        for _ in 0..10000 {
+            assert_round_trip(&Point2f {
+                x: rng.uniform_float(),
+                y: rng.uniform_float(),
+            });
+        }
+        // the center, the corners and edges of the square (the rim of
+        // the disk), and the diagonals between the wedges
+        for &x in &[0.0 as Float, 1e-3, 0.25, 0.5, 0.5 + 1e-3, 0.75, 0.999, 1.0] {
+            for &y in &[0.0 as Float, 0.25, 0.5, 0.75, 1.0] {
+                assert_round_trip(&Point2f { x: x, y: y });
+            }
+            assert_round_trip(&Point2f { x: x, y: x });
+            assert_round_trip(&Point2f {
+                x: x,
+                y: 1.0 as Float - x,
+            });
+        }
+    }
+}
@@ -107,3 +107,79 @@ impl Scene {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use accelerators::bvh::SplitMethod;
+    use core::geometry::Point3f;
+    use core::light::AreaLight;
+    use core::medium::MediumInterface;
+    use core::primitive::GeometricPrimitive;
+    use core::shape::Shape;
+    use core::transform::Transform;
+    use lights::diffuse::DiffuseAreaLight;
+    use shapes::sphere::Sphere;
+
+    fn sphere_primitive(x: Float, emissive: bool) -> Arc<Primitive + Sync + Send> {
+        let object_to_world: Transform = Transform::translate(&Vector3f {
+            x: x,
+            y: 0.0,
+            z: 0.0,
+        });
+        let sphere: Arc<Shape + Send + Sync> = Arc::new(Sphere::new(
+            object_to_world,
+            Transform::inverse(&object_to_world),
+            false,
+            1.0 as Float,
+            -1.0 as Float,
+            1.0 as Float,
+            360.0 as Float,
+        ));
+        let area_light: Option<Arc<AreaLight + Send + Sync>> = if emissive {
+            Some(Arc::new(DiffuseAreaLight::new(
+                &object_to_world,
+                &MediumInterface::default(),
+                &Spectrum::new(1.0 as Float),
+                None,
+                1_i32,
+                sphere.clone(),
+                false,
+            )))
+        } else {
+            None
+        };
+        Arc::new(GeometricPrimitive::new(sphere, None, area_light, None))
+    }
+
+    fn hit(scene: &Scene, x: Float) -> SurfaceInteraction {
+        let mut ray: Ray = Ray {
+            o: Point3f {
+                x: x,
+                y: 0.0,
+                z: 5.0,
+            },
+            d: Vector3f {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            t_max: std::f32::INFINITY,
+            ..Ray::default()
+        };
+        scene.intersect(&mut ray).unwrap()
+    }
+
+    #[test]
+    fn intersect_reports_the_area_light_of_emissive_hits() {
+        let primitives: Vec<Arc<Primitive + Sync + Send>> =
+            vec![sphere_primitive(-2.0, true), sphere_primitive(2.0, false)];
+        let bvh: Arc<BVHAccel> = Arc::new(BVHAccel::new(primitives, 4, SplitMethod::SAH));
+        let scene: Scene = Scene::new(bvh, Vec::new());
+        let emissive: SurfaceInteraction = hit(&scene, -2.0);
+        assert!((emissive.p.z - 1.0).abs() < 1e-4);
+        assert!(emissive.get_area_light().is_some());
+        let plain: SurfaceInteraction = hit(&scene, 2.0);
+        assert!(plain.get_area_light().is_none());
+    }
+}
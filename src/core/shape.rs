@@ -18,6 +18,12 @@ pub trait Shape {
     fn world_bound(&self) -> Bounds3f;
     fn intersect(&self, r: &Ray) -> Option<(SurfaceInteraction, Float)>;
     fn intersect_p(&self, r: &Ray) -> bool;
+    /// Cheap early-out test before calling an expensive intersection
+    /// routine (e.g. for curves). Returns *false* only if the ray
+    /// (in world space) can't hit the object bound.
+    fn object_bound_may_intersect(&self, _r: &Ray) -> bool {
+        true
+    }
     fn get_reverse_orientation(&self) -> bool;
     fn get_transform_swaps_handedness(&self) -> bool;
     fn area(&self) -> Float;
@@ -3,7 +3,9 @@
 //! only requires changing the **Spectrum** implementation.
 
 // std
-use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub};
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign,
+};
 // others
 use num::Zero;
 // pbrt
@@ -1531,6 +1533,14 @@ impl RGBSpectrum {
     pub fn rgb(r: Float, g: Float, b: Float) -> RGBSpectrum {
         RGBSpectrum { c: [r, g, b] }
     }
+    /// Zero in all channels (same as **RGBSpectrum::default()**).
+    pub fn black() -> RGBSpectrum {
+        RGBSpectrum::new(0.0 as Float)
+    }
+    /// One in all channels.
+    pub fn white() -> RGBSpectrum {
+        RGBSpectrum::new(1.0 as Float)
+    }
     pub fn from_srgb(rgb: &[u8; 3]) -> RGBSpectrum {
         fn as_float(v: u8) -> Float {
             v as Float / 255.0
@@ -1548,11 +1558,19 @@ impl RGBSpectrum {
             inverse_gamma_convert_float(self.c[2]),
         )
     }
-    pub fn from_rgb(rgb: &[Float; 3]) -> RGBSpectrum {
+    /// Reflectances are bounded to [0, 1], illuminants keep their
+    /// (possibly HDR) values.
+    pub fn from_rgb(rgb: &[Float; 3], spectrum_type: SpectrumType) -> RGBSpectrum {
         let mut s: RGBSpectrum = RGBSpectrum::new(0.0 as Float);
         s.c[0] = rgb[0];
         s.c[1] = rgb[1];
         s.c[2] = rgb[2];
+        match spectrum_type {
+            SpectrumType::Reflectance => {
+                s = s.clamp(0.0 as Float, 1.0 as Float);
+            }
+            SpectrumType::Illuminant => {}
+        }
         // TODO: DCHECK(!s.HasNaNs());
         s
     }
@@ -1561,6 +1579,15 @@ impl RGBSpectrum {
         rgb[1] = self.c[1];
         rgb[2] = self.c[2];
     }
+    /// Same as **from_rgb()**, but without clamping (like
+    /// _SpectrumType::Illuminant_). Inverse of **to_rgb_array()**.
+    pub fn from_rgb_array(rgb: [Float; 3]) -> RGBSpectrum {
+        RGBSpectrum { c: rgb }
+    }
+    /// Returns the linear RGB values (see **to_rgb()**).
+    pub fn to_rgb_array(&self) -> [Float; 3] {
+        self.c
+    }
     pub fn to_xyz(&self, xyz: &mut [Float; 3]) {
         rgb_to_xyz(&self.c, xyz);
     }
@@ -1605,6 +1632,15 @@ impl RGBSpectrum {
         }
         true
     }
+    /// True if all channels are exactly one (see **white()**).
+    pub fn is_white(&self) -> bool {
+        self.c.iter().all(|v| *v == 1.0 as Float)
+    }
+    /// True if all channels have the same value (i.e. the spectrum is
+    /// gray).
+    pub fn is_constant(&self) -> bool {
+        self.c[1] == self.c[0] && self.c[2] == self.c[0]
+    }
     pub fn sqrt(&self) -> RGBSpectrum {
         RGBSpectrum::rgb(self.c[0].sqrt(), self.c[1].sqrt(), self.c[2].sqrt())
     }
@@ -1714,6 +1750,15 @@ impl MulAssign for RGBSpectrum {
     }
 }
 
+impl MulAssign<Float> for RGBSpectrum {
+    fn mul_assign(&mut self, rhs: Float) {
+        assert!(!rhs.is_nan());
+        self.c[0] *= rhs;
+        self.c[1] *= rhs;
+        self.c[2] *= rhs;
+    }
+}
+
 impl Sub for RGBSpectrum {
     type Output = RGBSpectrum;
     fn sub(self, rhs: RGBSpectrum) -> RGBSpectrum {
@@ -1727,6 +1772,15 @@ impl Sub for RGBSpectrum {
     }
 }
 
+impl SubAssign for RGBSpectrum {
+    fn sub_assign(&mut self, rhs: RGBSpectrum) {
+        // TODO: DCHECK(!s2.HasNaNs());
+        self.c[0] -= rhs.c[0];
+        self.c[1] -= rhs.c[1];
+        self.c[2] -= rhs.c[2];
+    }
+}
+
 impl Div for RGBSpectrum {
     type Output = RGBSpectrum;
     fn div(self, rhs: RGBSpectrum) -> RGBSpectrum {
@@ -1753,6 +1807,14 @@ impl Div<Float> for RGBSpectrum {
     }
 }
 
+impl DivAssign for RGBSpectrum {
+    fn div_assign(&mut self, rhs: RGBSpectrum) {
+        self.c[0] /= rhs.c[0];
+        self.c[1] /= rhs.c[1];
+        self.c[2] /= rhs.c[2];
+    }
+}
+
 impl DivAssign<Float> for RGBSpectrum {
     fn div_assign(&mut self, rhs: Float) {
         assert_ne!(rhs, 0.0 as Float);
@@ -1825,6 +1887,69 @@ pub fn rgb_to_xyz(rgb: &[Float; 3], xyz: &mut [Float; 3]) {
     xyz[2] = 0.019334 * rgb[0] + 0.119193 * rgb[1] + 0.950227 * rgb[2];
 }
 
+/// Output color spaces (all with a D65 white point). Rendering
+/// happens in linear sRGB (Rec.709 primaries).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColorSpace {
+    SRgb,
+    Rec2020,
+    /// P3 primaries with a D65 white point (not DCI-P3, which uses
+    /// the DCI white point)
+    DisplayP3,
+}
+
+impl ColorSpace {
+    /// Maps the film's "colorspace" parameter to a **ColorSpace**.
+    pub fn from_name(name: &str) -> Option<ColorSpace> {
+        match name {
+            "srgb" => Some(ColorSpace::SRgb),
+            "rec2020" => Some(ColorSpace::Rec2020),
+            "display-p3" => Some(ColorSpace::DisplayP3),
+            _ => None,
+        }
+    }
+    /// CIE xy chromaticities of the red, green and blue primaries and
+    /// of the white point (in that order), e.g. for the
+    /// "chromaticities" attribute of OpenEXR files.
+    pub fn chromaticities(&self) -> [Float; 8] {
+        match *self {
+            ColorSpace::SRgb => [0.64, 0.33, 0.30, 0.60, 0.15, 0.06, 0.3127, 0.3290],
+            ColorSpace::Rec2020 => [0.708, 0.292, 0.170, 0.797, 0.131, 0.046, 0.3127, 0.3290],
+            ColorSpace::DisplayP3 => [0.680, 0.320, 0.265, 0.690, 0.150, 0.060, 0.3127, 0.3290],
+        }
+    }
+}
+
+/// Linear Rec.709 to linear Rec.2020 (see ITU-R BT.2087).
+pub const REC709_TO_REC2020: [[Float; 3]; 3] = [
+    [0.627404, 0.329283, 0.043313],
+    [0.069097, 0.919540, 0.011362],
+    [0.016391, 0.088013, 0.895595],
+];
+
+/// Linear Rec.709 to linear Display P3 (P3 primaries, D65 white
+/// point).
+pub const REC709_TO_DISPLAY_P3: [[Float; 3]; 3] = [
+    [0.822462, 0.177538, 0.000000],
+    [0.033194, 0.966806, 0.000000],
+    [0.017083, 0.072397, 0.910520],
+];
+
+/// Converts linear Rec.709 RGB coefficients into the given color
+/// space (still linear, apply the transfer function afterwards).
+pub fn convert_color_space(rgb: &[Float; 3], color_space: ColorSpace) -> [Float; 3] {
+    let m: &[[Float; 3]; 3] = match color_space {
+        ColorSpace::SRgb => return *rgb,
+        ColorSpace::Rec2020 => &REC709_TO_REC2020,
+        ColorSpace::DisplayP3 => &REC709_TO_DISPLAY_P3,
+    };
+    [
+        m[0][0] * rgb[0] + m[0][1] * rgb[1] + m[0][2] * rgb[2],
+        m[1][0] * rgb[0] + m[1][1] * rgb[1] + m[1][2] * rgb[2],
+        m[2][0] * rgb[0] + m[2][1] * rgb[1] + m[2][2] * rgb[2],
+    ]
+}
+
 // see spectrum.cpp
 
 /// Are the values sorted by wavelength?
@@ -1870,3 +1995,104 @@ pub fn gamma_correct(v: Float) -> Float {
         1.055 * Float::powf(v, 1.0 / 2.4) - 0.055
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_rgb_clamps_reflectance_only() {
+        let rgb: [Float; 3] = [1.5, 0.5, 0.5];
+        let reflectance: RGBSpectrum = RGBSpectrum::from_rgb(&rgb, SpectrumType::Reflectance);
+        assert_eq!(reflectance[0], 1.0 as Float);
+        assert_eq!(reflectance[1], 0.5 as Float);
+        assert_eq!(reflectance[2], 0.5 as Float);
+        let illuminant: RGBSpectrum = RGBSpectrum::from_rgb(&rgb, SpectrumType::Illuminant);
+        assert_eq!(illuminant[0], 1.5 as Float);
+        assert_eq!(illuminant[1], 0.5 as Float);
+        assert_eq!(illuminant[2], 0.5 as Float);
+    }
+
+    #[test]
+    fn convert_color_space_saturates_green() {
+        let green: [Float; 3] = [0.0, 1.0, 0.0];
+        assert_eq!(convert_color_space(&green, ColorSpace::SRgb), green);
+        let rec2020: [Float; 3] = convert_color_space(&green, ColorSpace::Rec2020);
+        // the sRGB green lies inside the Rec.2020 gamut, so it needs
+        // less of the (more saturated) Rec.2020 green primary
+        assert!((rec2020[0] - 0.329283 as Float).abs() < 1e-5 as Float);
+        assert!((rec2020[1] - 0.919540 as Float).abs() < 1e-5 as Float);
+        assert!((rec2020[2] - 0.088013 as Float).abs() < 1e-5 as Float);
+        assert!(rec2020[1] < 1.0 as Float);
+        // all spaces share the D65 white point
+        let white: [Float; 3] = [1.0, 1.0, 1.0];
+        for color_space in &[ColorSpace::Rec2020, ColorSpace::DisplayP3] {
+            let rgb: [Float; 3] = convert_color_space(&white, *color_space);
+            for c in &rgb {
+                assert!((c - 1.0 as Float).abs() < 1e-4 as Float);
+            }
+            assert_eq!(color_space.chromaticities()[6..], ColorSpace::SRgb.chromaticities()[6..]);
+        }
+    }
+
+    #[test]
+    fn black_white_and_constant() {
+        let white: RGBSpectrum = RGBSpectrum::white();
+        assert!(white.is_white() && white.is_constant() && !white.is_black());
+        let black: RGBSpectrum = RGBSpectrum::black();
+        assert!(black.is_black() && black.is_constant() && !black.is_white());
+        assert!(RGBSpectrum::default().is_black());
+        // gray is constant, but neither black nor white
+        let gray: RGBSpectrum = RGBSpectrum::new(0.5 as Float);
+        assert!(gray.is_constant() && !gray.is_black() && !gray.is_white());
+        // a mixed spectrum reports none of them
+        let mixed: RGBSpectrum = RGBSpectrum::rgb(1.0, 0.0, 1.0);
+        assert!(!mixed.is_black() && !mixed.is_white() && !mixed.is_constant());
+    }
+
+    #[test]
+    fn assignment_operators_match_binary_operators() {
+        let a: RGBSpectrum = RGBSpectrum::rgb(0.5, 2.0, 4.0);
+        let b: RGBSpectrum = RGBSpectrum::rgb(0.25, 1.5, 8.0);
+        let same = |x: RGBSpectrum, y: RGBSpectrum| -> bool { x.c == y.c };
+        let mut s: RGBSpectrum = a;
+        s += b;
+        assert!(same(s, a + b));
+        let mut s: RGBSpectrum = a;
+        s -= b;
+        assert!(same(s, a - b));
+        let mut s: RGBSpectrum = a;
+        s *= b;
+        assert!(same(s, a * b));
+        let mut s: RGBSpectrum = a;
+        s *= 3.0 as Float;
+        assert!(same(s, a * 3.0 as Float));
+        let mut s: RGBSpectrum = a;
+        s /= b;
+        assert!(same(s, a / b));
+        let mut s: RGBSpectrum = a;
+        s /= 4.0 as Float;
+        assert!(same(s, a / 4.0 as Float));
+        // beta *= f * cos / pdf, as in the path integrator
+        let mut beta: RGBSpectrum = RGBSpectrum::new(1.0 as Float);
+        beta *= b * 0.5 as Float / 0.25 as Float;
+        assert!(same(beta, b * 2.0 as Float));
+    }
+
+    #[test]
+    fn rgb_arrays_round_trip() {
+        let s: RGBSpectrum = RGBSpectrum::rgb(0.25, 1.5, 8.0);
+        assert_eq!(RGBSpectrum::from_rgb_array(s.to_rgb_array()).c, s.c);
+        // unclamped, like an illuminant
+        assert_eq!(RGBSpectrum::from_rgb_array([1.5, -0.5, 0.0]).c, [1.5, -0.5, 0.0]);
+        let red: RGBSpectrum = RGBSpectrum::rgb(1.0, 0.0, 0.0);
+        assert_eq!(red.to_rgb_array(), [1.0, 0.0, 0.0]);
+        // the film stores XYZ
+        let mut xyz: [Float; 3] = [0.0 as Float; 3];
+        s.to_xyz(&mut xyz);
+        let rgb: [Float; 3] = RGBSpectrum::from_xyz(&xyz, SpectrumType::Illuminant).to_rgb_array();
+        for c in 0..3 {
+            assert!((rgb[c] - s.c[c]).abs() < 1e-4 as Float * s.c[c], "{:?}", rgb);
+        }
+    }
+}
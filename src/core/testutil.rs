@@ -0,0 +1,154 @@
+//! Synthetic images to validate the **Film** and image writing
+//! pipeline end-to-end without rendering a scene.
+
+// std
+use std::sync::Arc;
+// pbrt
+use core::film::Film;
+use core::filter::Filter;
+use core::geometry::{Bounds2f, Point2f, Point2i, Vector2f};
+use core::pbrt::{Float, Spectrum};
+use core::spectrum::ColorSpace;
+use filters::boxfilter::BoxFilter;
+
+/// The color bars of **test_card()** from left to right: white,
+/// yellow, cyan, green, magenta, red, blue, and black.
+pub const TEST_CARD_BARS: [[Float; 3]; 8] = [
+    [1.0, 1.0, 1.0],
+    [1.0, 1.0, 0.0],
+    [0.0, 1.0, 1.0],
+    [0.0, 1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0],
+    [0.0, 0.0, 0.0],
+];
+
+/// Check sizes (in pixels) of the resolution wedges of
+/// **test_card()**, from left to right.
+pub const TEST_CARD_WEDGES: [i32; 4] = [8, 4, 2, 1];
+
+/// Whether pixel (*x*, *y*) lies in a white check of a checkerboard
+/// with square checks of *size* pixels (the check at the origin is
+/// white).
+pub fn checkerboard(x: i32, y: i32, size: i32) -> bool {
+    (x / size + y / size) % 2 == 0
+}
+
+/// Linear ramp from 0 (first pixel) to 1 (last pixel) for *x* in
+/// [0, *width*).
+pub fn gradient(x: i32, width: i32) -> Float {
+    if width <= 1 {
+        return 0.0 as Float;
+    }
+    x as Float / (width - 1) as Float
+}
+
+/// A **Film** of *resolution* pixels (no crop window, 8-bit sRGB
+/// output to *filename*) with a box filter of *filter_radius* pixels.
+pub fn test_film(resolution: Point2i, filter_radius: Float, filename: &str) -> Film {
+    let filter: Arc<Filter + Sync + Send> = Arc::new(BoxFilter {
+        radius: Vector2f {
+            x: filter_radius,
+            y: filter_radius,
+        },
+        inv_radius: Vector2f {
+            x: 1.0 as Float / filter_radius,
+            y: 1.0 as Float / filter_radius,
+        },
+    });
+    Film::new(
+        resolution,
+        Bounds2f {
+            p_min: Point2f { x: 0.0, y: 0.0 },
+            p_max: Point2f { x: 1.0, y: 1.0 },
+        },
+        filter,
+        35.0 as Float,
+        String::from(filename),
+        1.0 as Float,
+        std::f32::INFINITY,
+        8_u8,
+        None,
+        ColorSpace::SRgb,
+    )
+}
+
+/// Returns a calibration image of *resolution* pixels (stored row by
+/// row, starting top left):
+///
+/// - the top half shows eight vertical color bars (see
+///   **TEST_CARD_BARS**), bar *i* covers the columns [*i* * width / 8,
+///   (*i* + 1) * width / 8)
+/// - the third quarter is a horizontal gray ramp from black to white
+/// - the last quarter shows checkerboards with decreasing check sizes
+///   (see **TEST_CARD_WEDGES**), each covering a quarter of the width
+pub fn test_card(resolution: Point2i) -> Vec<Spectrum> {
+    let width: i32 = resolution.x;
+    let height: i32 = resolution.y;
+    assert!(width > 0 && height > 0);
+    let mut pixels: Vec<Spectrum> = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let pixel: Spectrum = if y < height / 2 {
+                // color bars
+                let bar: usize = (x * 8 / width) as usize;
+                let c: [Float; 3] = TEST_CARD_BARS[bar];
+                Spectrum::rgb(c[0], c[1], c[2])
+            } else if y < height * 3 / 4 {
+                // gray ramp
+                Spectrum::new(gradient(x, width))
+            } else {
+                // resolution wedges
+                let wedge: usize = (x * 4 / width) as usize;
+                if checkerboard(x, y, TEST_CARD_WEDGES[wedge]) {
+                    Spectrum::new(1.0 as Float)
+                } else {
+                    Spectrum::new(0.0 as Float)
+                }
+            };
+            pixels.push(pixel);
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_card_layout() {
+        let resolution: Point2i = Point2i { x: 64, y: 32 };
+        let card: Vec<Spectrum> = test_card(resolution);
+        assert_eq!(card.len(), 64 * 32);
+        let pixel = |x: i32, y: i32| -> Spectrum { card[(y * resolution.x + x) as usize] };
+        // color bars in the top half, 8 pixels wide each
+        for (i, c) in TEST_CARD_BARS.iter().enumerate() {
+            for x in &[i as i32 * 8, i as i32 * 8 + 7] {
+                for y in &[0, 15] {
+                    let p: Spectrum = pixel(*x, *y);
+                    assert_eq!((p[0], p[1], p[2]), (c[0], c[1], c[2]), "bar {}", i);
+                }
+            }
+        }
+        // the primaries are where the documentation says
+        let red: Spectrum = pixel(5 * 8, 0);
+        assert_eq!((red[0], red[1], red[2]), (1.0, 0.0, 0.0));
+        let green: Spectrum = pixel(3 * 8, 0);
+        assert_eq!((green[0], green[1], green[2]), (0.0, 1.0, 0.0));
+        let blue: Spectrum = pixel(6 * 8, 0);
+        assert_eq!((blue[0], blue[1], blue[2]), (0.0, 0.0, 1.0));
+        // gray ramp from black to white
+        assert_eq!(pixel(0, 16)[0], 0.0 as Float);
+        assert_eq!(pixel(63, 23)[1], 1.0 as Float);
+        assert!(pixel(20, 20)[2] < pixel(21, 20)[2]);
+        // resolution wedges: the last one alternates every pixel
+        assert_eq!(pixel(48, 24)[0], 1.0 as Float);
+        assert_eq!(pixel(49, 24)[0], 0.0 as Float);
+        assert_eq!(pixel(48, 25)[0], 0.0 as Float);
+        // the first one every 8 pixels
+        assert_eq!(pixel(0, 24)[0], pixel(7, 24)[0]);
+        assert!(pixel(7, 24)[0] != pixel(8, 24)[0]);
+    }
+}
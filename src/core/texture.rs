@@ -138,6 +138,12 @@ impl TextureMapping3D for IdentityMapping3D {
 
 pub trait Texture<T> {
     fn evaluate(&self, si: &SurfaceInteraction) -> T;
+    /// Returns the texture value plus an estimate of how much the
+    /// texture varies across the filter footprint (e.g. to guide an
+    /// adaptive sampler). Textures which can't tell report 0.
+    fn evaluate_with_variance(&self, si: &SurfaceInteraction) -> (T, Float) {
+        (self.evaluate(si), 0.0 as Float)
+    }
 }
 
 pub fn smooth_step(min: Float, max: Float, value: Float) -> Float {
@@ -227,14 +233,21 @@ pub fn noise_weight(t: Float) -> Float {
     6.0 as Float * t4 * t - 15.0 as Float * t4 + 10.0 as Float * t3
 }
 
-pub fn fbm(p: &Point3f, dpdx: &Vector3f, dpdy: &Vector3f, omega: Float, max_octaves: i32) -> Float {
-    // compute number of octaves for antialiased FBm
+/// Number of noise octaves (possibly fractional) **fbm()** sums up
+/// for the filter footprint given by *dpdx* and *dpdy*: larger
+/// footprints leave fewer octaves.
+pub fn fbm_octaves(dpdx: &Vector3f, dpdy: &Vector3f, max_octaves: i32) -> Float {
     let len2: Float = dpdx.length_squared().max(dpdy.length_squared());
-    let n: Float = clamp_t(
+    clamp_t(
         -1.0 as Float - 0.5 as Float * log_2(len2),
         0.0 as Float,
         max_octaves as Float,
-    );
+    )
+}
+
+pub fn fbm(p: &Point3f, dpdx: &Vector3f, dpdy: &Vector3f, omega: Float, max_octaves: i32) -> Float {
+    // compute number of octaves for antialiased FBm
+    let n: Float = fbm_octaves(dpdx, dpdy, max_octaves);
     let n_int: i32 = n.floor() as i32;
     // compute sum of octaves of noise for FBm
     let mut sum: Float = 0.0;
@@ -250,6 +263,31 @@ pub fn fbm(p: &Point3f, dpdx: &Vector3f, dpdy: &Vector3f, omega: Float, max_octa
     sum
 }
 
+/// Estimates the variance of **fbm()** across the filter footprint,
+/// using the same octaves: each octave contributes its (squared)
+/// amplitude scaled by how much of a noise period the footprint
+/// covers at that frequency.
+pub fn fbm_variance(dpdx: &Vector3f, dpdy: &Vector3f, omega: Float, max_octaves: i32) -> Float {
+    let len2: Float = dpdx.length_squared().max(dpdy.length_squared());
+    let n: Float = fbm_octaves(dpdx, dpdy, max_octaves);
+    let n_int: i32 = n.floor() as i32;
+    let len: Float = len2.sqrt();
+    let mut variance: Float = 0.0;
+    let mut lambda: Float = 1.0;
+    let mut o: Float = 1.0;
+    for _i in 0..n_int {
+        let d: Float = o * (lambda * len).min(1.0 as Float);
+        variance += d * d;
+        lambda *= 1.99 as Float;
+        o *= omega;
+    }
+    let n_partial: Float = n - n_int as Float;
+    let d: Float = o
+        * smooth_step(0.3 as Float, 0.7 as Float, n_partial)
+        * (lambda * len).min(1.0 as Float);
+    variance + d * d
+}
+
 pub fn lanczos(x: Float, tau: Float) -> Float {
     let mut x: Float = x;
     x = x.abs();
@@ -63,7 +63,7 @@ use core::geometry::{
 use core::geometry::{Bounds3f, Normal3, Point3, Point3f, Ray, RayDifferential, Vector3, Vector3f};
 use core::interaction::SurfaceInteraction;
 use core::pbrt::Float;
-use core::pbrt::{clamp_t, gamma, lerp, radians};
+use core::pbrt::{clamp_t, find_interval, gamma, lerp, radians};
 use core::quaternion::Quaternion;
 use core::quaternion::{quat_dot_quat, quat_normalize, quat_slerp};
 
@@ -311,6 +311,20 @@ impl Transform {
             m_inv: t.m,
         }
     }
+    /// Element-wise comparison of both matrices (**m** and **m_inv**)
+    /// with an absolute tolerance *eps*.
+    pub fn approx_eq(&self, other: &Transform, eps: Float) -> bool {
+        for i in 0..4 {
+            for j in 0..4 {
+                if (self.m.m[i][j] - other.m.m[i][j]).abs() > eps
+                    || (self.m_inv.m[i][j] - other.m_inv.m[i][j]).abs() > eps
+                {
+                    return false;
+                }
+            }
+        }
+        true
+    }
     pub fn is_identity(&self) -> bool {
         self.m.m[0][0] == 1.0 as Float
             && self.m.m[0][1] == 0.0 as Float
@@ -373,6 +387,7 @@ impl Transform {
             ),
         }
     }
+    /// Rotation by *theta* degrees around the x axis.
     pub fn rotate_x(theta: Float) -> Transform {
         let sin_theta: Float = radians(theta).sin();
         let cos_theta: Float = radians(theta).cos();
@@ -385,6 +400,7 @@ impl Transform {
             m_inv: Matrix4x4::transpose(&m),
         }
     }
+    /// Rotation by *theta* degrees around the y axis.
     pub fn rotate_y(theta: Float) -> Transform {
         let sin_theta: Float = radians(theta).sin();
         let cos_theta: Float = radians(theta).cos();
@@ -397,6 +413,7 @@ impl Transform {
             m_inv: Matrix4x4::transpose(&m),
         }
     }
+    /// Rotation by *theta* degrees around the z axis.
     pub fn rotate_z(theta: Float) -> Transform {
         let sin_theta: Float = radians(theta).sin();
         let cos_theta: Float = radians(theta).cos();
@@ -409,6 +426,8 @@ impl Transform {
             m_inv: Matrix4x4::transpose(&m),
         }
     }
+    /// Rotation by *theta* degrees around an arbitrary *axis*
+    /// (which doesn't need to be normalized).
     pub fn rotate(theta: Float, axis: &Vector3f) -> Transform {
         let a: Vector3f = axis.normalize();
         let sin_theta: Float = radians(theta).sin();
@@ -583,6 +602,7 @@ impl Transform {
                     d: d,
                     t_max: t_max,
                     time: r.time,
+                    ray_type: r.ray_type,
                     differential: Some(diff),
                     medium: Some(medium_arc.clone()),
                 }
@@ -592,6 +612,7 @@ impl Transform {
                     d: d,
                     t_max: t_max,
                     time: r.time,
+                    ray_type: r.ray_type,
                     differential: Some(diff),
                     medium: None,
                 }
@@ -603,6 +624,7 @@ impl Transform {
                     d: d,
                     t_max: t_max,
                     time: r.time,
+                    ray_type: r.ray_type,
                     differential: None,
                     medium: Some(medium_arc.clone()),
                 }
@@ -612,76 +634,22 @@ impl Transform {
                     d: d,
                     t_max: t_max,
                     time: r.time,
+                    ray_type: r.ray_type,
                     differential: None,
                     medium: None,
                 }
             }
         }
     }
+    /// Transforms all eight corners of the bounding box and returns
+    /// the axis-aligned bounds of the results (which are larger than
+    /// the original box for rotations).
     pub fn transform_bounds(&self, b: &Bounds3f) -> Bounds3f {
-        let m: Transform = *self;
-        let p: Point3f = self.transform_point(&Point3f {
-            x: b.p_min.x,
-            y: b.p_min.y,
-            z: b.p_min.z,
-        });
+        let p: Point3f = self.transform_point(&b.corner(0_u8));
         let mut ret: Bounds3f = Bounds3f { p_min: p, p_max: p };
-        ret = bnd3_union_pnt3(
-            &ret,
-            &m.transform_point(&Point3f {
-                x: b.p_max.x,
-                y: b.p_min.y,
-                z: b.p_min.z,
-            }),
-        );
-        ret = bnd3_union_pnt3(
-            &ret,
-            &m.transform_point(&Point3f {
-                x: b.p_min.x,
-                y: b.p_max.y,
-                z: b.p_min.z,
-            }),
-        );
-        ret = bnd3_union_pnt3(
-            &ret,
-            &m.transform_point(&Point3f {
-                x: b.p_min.x,
-                y: b.p_min.y,
-                z: b.p_max.z,
-            }),
-        );
-        ret = bnd3_union_pnt3(
-            &ret,
-            &m.transform_point(&Point3f {
-                x: b.p_min.x,
-                y: b.p_max.y,
-                z: b.p_max.z,
-            }),
-        );
-        ret = bnd3_union_pnt3(
-            &ret,
-            &m.transform_point(&Point3f {
-                x: b.p_max.x,
-                y: b.p_max.y,
-                z: b.p_min.z,
-            }),
-        );
-        ret = bnd3_union_pnt3(
-            &ret,
-            &m.transform_point(&Point3f {
-                x: b.p_max.x,
-                y: b.p_min.y,
-                z: b.p_max.z,
-            }),
-        );
-        ret = bnd3_union_pnt3(
-            &ret,
-            &m.transform_point(&Point3f {
-                x: b.p_max.x,
-                y: b.p_max.y,
-                z: b.p_max.z,
-            }),
-        );
+        for corner in 1..8_u8 {
+            ret = bnd3_union_pnt3(&ret, &self.transform_point(&b.corner(corner)));
+        }
         ret
     }
     pub fn transform_point_with_error(
@@ -841,6 +809,7 @@ impl Transform {
             d: d,
             t_max: r.t_max,
             time: r.time,
+            ray_type: r.ray_type,
             differential: None,
             medium: r.medium.clone(),
         }
@@ -912,6 +881,10 @@ impl DerivativeTerm {
     }
 }
 
+/// Start and end transforms of an **AnimatedTransform** closer than
+/// this (see **Transform::approx_eq()**) are treated as static.
+pub const ANIMATED_TRANSFORM_EPSILON: Float = 1e-6 as Float;
+
 #[derive(Debug, Default, Copy, Clone)]
 pub struct AnimatedTransform {
     start_transform: Transform,
@@ -942,7 +915,12 @@ impl AnimatedTransform {
         at.end_transform = end_transform.clone();
         at.start_time = start_time;
         at.end_time = end_time;
-        at.actually_animated = *start_transform != *end_transform;
+        at.actually_animated =
+            !start_transform.approx_eq(end_transform, ANIMATED_TRANSFORM_EPSILON);
+        if !at.actually_animated {
+            // all lookups use _start_transform_, no need to decompose
+            return at;
+        }
         AnimatedTransform::decompose(&start_transform.m, &mut at.t[0], &mut at.r[0], &mut at.s[0]);
         AnimatedTransform::decompose(&end_transform.m, &mut at.t[1], &mut at.r[1], &mut at.s[1]);
         // flip _r[1]_ if needed to select shortest path
@@ -2221,6 +2199,76 @@ impl AnimatedTransform {
     }
 }
 
+/// Like **AnimatedTransform**, but for an arbitrary number of
+/// keyframes. Each pair of consecutive keyframes is interpolated like
+/// an **AnimatedTransform** (translation, rotation via slerp, and
+/// scale are interpolated separately).
+#[derive(Debug, Default, Clone)]
+pub struct KeyframedTransform {
+    pub keyframes: Vec<(Float, Transform)>,
+    segments: Vec<AnimatedTransform>,
+}
+
+impl KeyframedTransform {
+    /// The *keyframes* have to be sorted by time.
+    pub fn new(keyframes: Vec<(Float, Transform)>) -> Self {
+        assert!(!keyframes.is_empty());
+        let mut segments: Vec<AnimatedTransform> = Vec::with_capacity(keyframes.len());
+        for i in 0..keyframes.len() - 1 {
+            assert!(keyframes[i].0 <= keyframes[i + 1].0);
+            segments.push(AnimatedTransform::new(
+                &keyframes[i].1,
+                keyframes[i].0,
+                &keyframes[i + 1].1,
+                keyframes[i + 1].0,
+            ));
+        }
+        if segments.is_empty() {
+            // a single keyframe is a static transform
+            segments.push(AnimatedTransform::new(
+                &keyframes[0].1,
+                keyframes[0].0,
+                &keyframes[0].1,
+                keyframes[0].0,
+            ));
+        }
+        KeyframedTransform {
+            keyframes: keyframes,
+            segments: segments,
+        }
+    }
+    /// Returns the segment (pair of keyframes) bracketing *time*;
+    /// times outside the keyframes use the first or last segment.
+    fn find_segment(&self, time: Float) -> &AnimatedTransform {
+        if self.keyframes.len() < 2 {
+            return &self.segments[0];
+        }
+        let index: i32 = find_interval(self.keyframes.len() as i32, |index| {
+            self.keyframes[index as usize].0 <= time
+        });
+        &self.segments[index as usize]
+    }
+    pub fn interpolate(&self, time: Float, t: &mut Transform) {
+        self.find_segment(time).interpolate(time, t)
+    }
+    pub fn transform_ray(&self, r: &Ray) -> Ray {
+        self.find_segment(r.time).transform_ray(r)
+    }
+    pub fn transform_point(&self, time: Float, p: &Point3<Float>) -> Point3<Float> {
+        self.find_segment(time).transform_point(time, p)
+    }
+    pub fn transform_vector(&self, time: Float, v: &Vector3<Float>) -> Vector3<Float> {
+        self.find_segment(time).transform_vector(time, v)
+    }
+    pub fn motion_bounds(&self, b: &Bounds3f) -> Bounds3f {
+        let mut bounds: Bounds3f = self.segments[0].motion_bounds(b);
+        for segment in self.segments.iter().skip(1) {
+            bounds = bnd3_union_bnd3(&bounds, &segment.motion_bounds(b));
+        }
+        bounds
+    }
+}
+
 #[derive(Debug, Default, Copy, Clone)]
 pub struct Interval {
     pub low: Float,
@@ -2368,3 +2416,176 @@ pub fn interval_find_zeros(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearly_identical_keyframes_are_static() {
+        let t: Transform = Transform::default();
+        let nudged: Transform = Transform::translate(&Vector3f {
+            x: 1e-9 as Float,
+            y: 0.0 as Float,
+            z: 0.0 as Float,
+        });
+        assert!(nudged != t);
+        assert!(nudged.approx_eq(&t, ANIMATED_TRANSFORM_EPSILON));
+        assert!(t == Transform::default());
+        let rotated: Transform = Transform::rotate_z(10.0 as Float);
+        assert!(!rotated.approx_eq(&t, ANIMATED_TRANSFORM_EPSILON));
+        assert!(!AnimatedTransform::new(&t, 0.0 as Float, &nudged, 1.0 as Float).actually_animated);
+        assert!(AnimatedTransform::new(&t, 0.0 as Float, &rotated, 1.0 as Float).actually_animated);
+    }
+
+    fn assert_same_point(a: &Point3f, b: &Point3f) {
+        let d: Vector3f = *a - *b;
+        assert!(d.length() < 1e-4 as Float, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn keyframes_interpolate_within_their_segment() {
+        let t0: Transform = Transform::default();
+        let t1: Transform = Transform::translate(&Vector3f {
+            x: 2.0 as Float,
+            y: 0.0 as Float,
+            z: 0.0 as Float,
+        }) * Transform::rotate_z(60.0 as Float);
+        let t2: Transform = Transform::translate(&Vector3f {
+            x: 2.0 as Float,
+            y: 4.0 as Float,
+            z: 1.0 as Float,
+        }) * Transform::rotate_z(150.0 as Float)
+            * Transform::scale(2.0 as Float, 2.0 as Float, 2.0 as Float);
+        let keyframed: KeyframedTransform =
+            KeyframedTransform::new(vec![(0.0 as Float, t0), (1.0, t1), (3.0, t2)]);
+        let first: AnimatedTransform = AnimatedTransform::new(&t0, 0.0, &t1, 1.0);
+        let second: AnimatedTransform = AnimatedTransform::new(&t1, 1.0, &t2, 3.0);
+        let p: Point3f = Point3f {
+            x: 1.0,
+            y: 0.5,
+            z: -0.25,
+        };
+        for &(time, segment) in &[
+            (0.5 as Float, &first),
+            (1.0, &second),
+            (1.7, &second),
+            (2.5, &second),
+        ] {
+            assert_same_point(
+                &keyframed.transform_point(time, &p),
+                &segment.transform_point(time, &p),
+            );
+            let mut a: Transform = Transform::default();
+            let mut b: Transform = Transform::default();
+            keyframed.interpolate(time, &mut a);
+            segment.interpolate(time, &mut b);
+            assert!(a.approx_eq(&b, 1e-4 as Float));
+        }
+        // the keyframes themselves are hit exactly, times outside clamp
+        assert_same_point(&keyframed.transform_point(1.0, &p), &t1.transform_point(&p));
+        assert_same_point(&keyframed.transform_point(-1.0, &p), &p);
+        assert_same_point(&keyframed.transform_point(5.0, &p), &t2.transform_point(&p));
+        // rays use their own time
+        let ray: Ray = Ray {
+            o: p,
+            d: Vector3f {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+            t_max: std::f32::INFINITY,
+            time: 2.5 as Float,
+            ..Ray::default()
+        };
+        assert_same_point(
+            &keyframed.transform_ray(&ray).o,
+            &second.transform_point(2.5, &p),
+        );
+    }
+
+    #[test]
+    fn rotations_about_the_axes() {
+        let x: Point3f = Point3f {
+            x: 1.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let y: Point3f = Point3f {
+            x: 0.0,
+            y: 1.0,
+            z: 0.0,
+        };
+        let z: Point3f = Point3f {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        };
+        // counter-clockwise when looking down the axis
+        assert_same_point(&Transform::rotate_z(90.0 as Float).transform_point(&x), &y);
+        assert_same_point(&Transform::rotate_x(90.0 as Float).transform_point(&y), &z);
+        assert_same_point(&Transform::rotate_y(90.0 as Float).transform_point(&z), &x);
+        // the general rotation agrees with the axis aligned ones
+        let p: Point3f = Point3f {
+            x: 0.3,
+            y: -1.2,
+            z: 2.0,
+        };
+        for &theta in &[30.0 as Float, 90.0, -135.0] {
+            for &(axis, ref r) in &[
+                (x, Transform::rotate_x(theta)),
+                (y, Transform::rotate_y(theta)),
+                (z, Transform::rotate_z(theta)),
+            ] {
+                let v: Vector3f = Vector3f {
+                    x: axis.x,
+                    y: axis.y,
+                    z: axis.z,
+                };
+                assert_same_point(
+                    &Transform::rotate(theta, &v).transform_point(&p),
+                    &r.transform_point(&p),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rotated_bounds_enclose_the_rotated_corners() {
+        let b: Bounds3f = Bounds3f {
+            p_min: Point3f {
+                x: -1.0,
+                y: -1.0,
+                z: -1.0,
+            },
+            p_max: Point3f {
+                x: 1.0,
+                y: 1.0,
+                z: 1.0,
+            },
+        };
+        let r: Transform = Transform::rotate_z(45.0 as Float);
+        let rotated: Bounds3f = r.transform_bounds(&b);
+        // the corners now lie on the axes, at a distance of sqrt(2)
+        let s: Float = (2.0 as Float).sqrt();
+        assert_same_point(
+            &rotated.p_min,
+            &Point3f {
+                x: -s,
+                y: -s,
+                z: -1.0,
+            },
+        );
+        assert_same_point(&rotated.p_max, &Point3f { x: s, y: s, z: 1.0 });
+        for corner in 0..8_u8 {
+            let p: Point3f = r.transform_point(&b.corner(corner));
+            assert!(p.x >= rotated.p_min.x - 1e-5 as Float && p.x <= rotated.p_max.x + 1e-5);
+            assert!(p.y >= rotated.p_min.y - 1e-5 as Float && p.y <= rotated.p_max.y + 1e-5);
+            assert!(p.z >= rotated.p_min.z && p.z <= rotated.p_max.z);
+        }
+        // the identity keeps the box
+        let same: Bounds3f = Transform::default().transform_bounds(&b);
+        assert_same_point(&same.p_min, &b.p_min);
+        assert_same_point(&same.p_max, &b.p_max);
+    }
+}
@@ -5,6 +5,7 @@ use core::integrator::SamplerIntegrator;
 use core::interaction::Interaction;
 use core::material::TransportMode;
 use core::pbrt::{Float, Spectrum};
+use core::profile::{Prof, ProfilePhase};
 use core::sampler::Sampler;
 use core::sampling::{
     cosine_hemisphere_pdf, cosine_sample_hemisphere, uniform_hemisphere_pdf,
@@ -41,7 +42,10 @@ impl AOIntegrator {
 
 impl SamplerIntegrator for AOIntegrator {
     fn preprocess(&mut self, _scene: &Scene, sampler: &mut Box<Sampler + Send + Sync>) {
-        sampler.request_2d_array(self.n_samples);
+        if sampler.supports_arrays() {
+            self.n_samples = sampler.round_count(self.n_samples);
+            sampler.request_2d_array(self.n_samples);
+        }
     }
     fn li(
         &self,
@@ -51,13 +55,14 @@ impl SamplerIntegrator for AOIntegrator {
         // arena: &mut Arena,
         _depth: i32,
     ) -> Spectrum {
-        // TODO: ProfilePhase p(Prof::SamplerIntegratorLi);
+        let _p = ProfilePhase::new(Prof::SamplerIntegratorLi);
         let mut l: Spectrum = Spectrum::default();
         let mut ray: Ray = Ray {
             o: r.o,
             d: r.d,
             t_max: r.t_max,
             time: r.time,
+            ray_type: r.ray_type,
             differential: r.differential,
             medium: r.medium.clone(),
         };
@@ -74,7 +79,16 @@ impl SamplerIntegrator for AOIntegrator {
             let n: Normal3f = nrm_faceforward_vec3(&isect.n, &-ray.d);
             let s: Vector3f = isect.dpdu.normalize();
             let t: Vector3f = nrm_cross_vec3(&isect.n, &s);
-            let u: Vec<Point2f> = sampler.get_2d_array(self.n_samples);
+            let mut u: Vec<Point2f> = Vec::new();
+            if sampler.supports_arrays() {
+                u = sampler.get_2d_array(self.n_samples);
+            }
+            if u.is_empty() {
+                // one sample after the other (see Sampler::supports_arrays())
+                for _i in 0..self.n_samples {
+                    u.push(sampler.get_2d());
+                }
+            }
             for i in 0..self.n_samples as usize {
                 // Vector3f wi;
                 let mut wi: Vector3f;
@@ -7,6 +7,7 @@ use std;
 use std::f32::consts::PI;
 use std::sync::mpsc;
 use std::sync::Arc;
+use std::time::Instant;
 // pbrt
 use blockqueue::BlockQueue;
 use core::camera::{Camera, CameraSample};
@@ -15,7 +16,10 @@ use core::geometry::{
 };
 use core::geometry::{
     Bounds2i, Bounds3f, Normal3f, Point2f, Point2i, Point3f, Ray, Vector2i, Vector3f,
+    VisibilityFlags,
 };
+use core::integrator::escaped_radiance;
+use core::integrator::MAX_RAY_DEPTH;
 use core::interaction::{Interaction, InteractionCommon, MediumInteraction, SurfaceInteraction};
 use core::light::is_delta_light;
 use core::light::{Light, LightFlags, VisibilityTester};
@@ -24,8 +28,9 @@ use core::material::TransportMode;
 use core::medium::{Medium, MediumInterface, PhaseFunction};
 use core::pbrt::{Float, Spectrum};
 use core::primitive::Primitive;
+use core::profile::{report_profile, Prof, ProfilePhase};
 use core::reflection::Bsdf;
-use core::reflection::BxdfType;
+use core::reflection::{bxdf_type_to_ray_type, BxdfType};
 use core::sampler::Sampler;
 use core::sampling::Distribution1D;
 use core::scene::Scene;
@@ -155,6 +160,7 @@ impl<'a> Interaction for EndpointInteraction<'a> {
             d: *d,
             t_max: std::f32::INFINITY,
             time: self.time,
+            ray_type: VisibilityFlags::Untyped as u8,
             differential: None,
             medium: self.get_medium(d),
         }
@@ -538,19 +544,16 @@ impl<'a, 'p, 's> Vertex<'a, 'p, 's> {
         w = w.normalize();
         if self.is_infinite_light() {
             // return emitted radiance for infinite light sources
-            let mut le: Spectrum = Spectrum::default();
-            for light in &scene.infinite_lights {
-                let mut ray: Ray = Ray {
-                    o: self.p(),
-                    d: -w,
-                    t_max: Float::default(),
-                    time: Float::default(),
-                    differential: None,
-                    medium: None,
-                };
-                le += light.le(&mut ray);
-            }
-            return le;
+            let mut ray: Ray = Ray {
+                o: self.p(),
+                d: -w,
+                t_max: Float::default(),
+                time: Float::default(),
+                ray_type: VisibilityFlags::Untyped as u8,
+                differential: None,
+                medium: None,
+            };
+            return escaped_radiance(scene, &mut ray);
         } else {
             if let Some(ref si) = self.si {
                 if let Some(primitive) = si.primitive {
@@ -664,6 +667,7 @@ impl<'a, 'p, 's> Vertex<'a, 'p, 's> {
                                 d: w,
                                 t_max: std::f32::INFINITY,
                                 time: self.time(),
+                                ray_type: VisibilityFlags::Untyped as u8,
                                 differential: None,
                                 medium: None,
                             },
@@ -688,6 +692,7 @@ impl<'a, 'p, 's> Vertex<'a, 'p, 's> {
                                     d: w,
                                     t_max: std::f32::INFINITY,
                                     time: self.time(),
+                                    ray_type: VisibilityFlags::Untyped as u8,
                                     differential: None,
                                     medium: None,
                                 },
@@ -748,6 +753,7 @@ impl<'a, 'p, 's> Vertex<'a, 'p, 's> {
                                         d: w,
                                         t_max: std::f32::INFINITY,
                                         time: self.time(),
+                                        ray_type: VisibilityFlags::Untyped as u8,
                                         differential: None,
                                         medium: None,
                                     },
@@ -782,6 +788,7 @@ impl<'a, 'p, 's> Vertex<'a, 'p, 's> {
                                             d: w,
                                             t_max: std::f32::INFINITY,
                                             time: self.time(),
+                                            ray_type: VisibilityFlags::Untyped as u8,
                                             differential: None,
                                             medium: None,
                                         },
@@ -822,7 +829,7 @@ impl BDPTIntegrator {
         light_sample_strategy: String,
     ) -> Self {
         BDPTIntegrator {
-            max_depth: max_depth,
+            max_depth: max_depth.min(MAX_RAY_DEPTH),
             // visualize_strategies: visualize_strategies,
             // visualize_weights: visualize_weights,
             pixel_bounds: pixel_bounds,
@@ -869,7 +876,7 @@ pub fn generate_camera_subpath<'a>(
     if max_depth == 0 {
         return (0_usize, Point3f::default(), Float::default());
     }
-    // TODO: ProfilePhase _(Prof::BDPTGenerateSubpath);
+    let _p = ProfilePhase::new(Prof::BDPTGenerateSubpath);
     // sample initial ray for camera subpath
     let mut camera_sample: CameraSample = CameraSample::default();
     camera_sample.p_film = *p_film;
@@ -916,7 +923,7 @@ pub fn generate_light_subpath<'a>(
     if max_depth == 0_u32 {
         return 0_usize;
     }
-    // TODO: ProfilePhase _(Prof::BDPTGenerateSubpath);
+    let _p = ProfilePhase::new(Prof::BDPTGenerateSubpath);
     // sample initial ray for light subpath
     let mut light_pdf: Option<Float> = Some(0.0 as Float);
     let light_num: usize = light_distr.sample_discrete(sampler.get_1d(), light_pdf.as_mut());
@@ -1080,7 +1087,8 @@ pub fn random_walk<'a>(
                 isect.compute_scattering_functions(&ray /*, arena, */, true, mode.clone());
                 if let Some(ref _bsdf) = isect.clone().bsdf {
                 } else {
-                    let new_ray = isect.spawn_ray(&ray.d);
+                    let mut new_ray = isect.spawn_ray(&ray.d);
+                    new_ray.ray_type = ray.ray_type;
                     ray = new_ray;
                     continue;
                 }
@@ -1133,7 +1141,8 @@ pub fn random_walk<'a>(
                     //     "Random walk beta after shading normal correction {:?}",
                     //     beta
                     // );
-                    let new_ray = isect.spawn_ray(&wi);
+                    let mut new_ray = isect.spawn_ray(&wi);
+                    new_ray.ray_type = bxdf_type_to_ray_type(sampled_type);
                     ray = new_ray;
                 }
                 // compute reverse area density at preceding vertex
@@ -1908,7 +1917,7 @@ pub fn connect_bdpt<'a>(
     p_raster: &mut Point2f,
     mis_weight_opt: Option<&mut Float>,
 ) -> Spectrum {
-    // TODO: ProfilePhase _(Prof::BDPTConnectSubpaths);
+    let _p = ProfilePhase::new(Prof::BDPTConnectSubpaths);
     let mut l: Spectrum = Spectrum::default();
     // ignore invalid connections related to infinite area lights
     if t > 1 && s != 0 && camera_vertices[t - 1].vertex_type == VertexType::Light {
@@ -2196,6 +2205,7 @@ pub fn render_bdpt(
     integrator: &mut Box<BDPTIntegrator>,
     num_threads: u8,
 ) {
+    let start: Instant = Instant::now();
     // TODO
     // Compute a reverse mapping from light pointers to offsets into
     // the scene lights vector (and, equivalently, offsets into
@@ -2391,6 +2401,10 @@ pub fn render_bdpt(
                                         //     l,
                                         //     l.y()
                                         // );
+                                        if l.has_nans() || l.y().is_infinite() {
+                                            film.add_dropped_sample();
+                                            l = Spectrum::new(0.0 as Float);
+                                        }
                                         film_tile.add_sample(&p_film, &mut l, 1.0 as Float);
                                         done = !tile_sampler.start_next_sample();
                                     }
@@ -2414,7 +2428,10 @@ pub fn render_bdpt(
             })
             .unwrap();
         }
+        film.set_render_time(start.elapsed());
         film.write_image(1.0 as Float / samples_per_pixel as Float);
         // TODO: Write buffers for debug visualization
+        film.report_dropped_samples();
+        report_profile();
     }
 }
@@ -2,11 +2,13 @@
 use core::geometry::{vec3_abs_dot_nrm, vec3_dot_nrm};
 use core::geometry::{Bounds2i, Normal3f, Ray, RayDifferential, Vector3f};
 use core::integrator::SamplerIntegrator;
-use core::integrator::{uniform_sample_all_lights, uniform_sample_one_light};
+use core::integrator::MAX_RAY_DEPTH;
+use core::integrator::{escaped_radiance, uniform_sample_all_lights, uniform_sample_one_light};
 use core::interaction::{Interaction, SurfaceInteraction};
 use core::material::TransportMode;
 use core::pbrt::{Float, Spectrum};
-use core::reflection::BxdfType;
+use core::profile::{Prof, ProfilePhase};
+use core::reflection::{bxdf_type_to_ray_type, BxdfType};
 use core::sampler::Sampler;
 use core::scene::Scene;
 
@@ -33,7 +35,7 @@ impl DirectLightingIntegrator {
         DirectLightingIntegrator {
             pixel_bounds: pixel_bounds,
             strategy: strategy,
-            max_depth: max_depth,
+            max_depth: max_depth.min(MAX_RAY_DEPTH as i64),
             n_light_samples: Vec::new(),
         }
     }
@@ -66,6 +68,7 @@ impl DirectLightingIntegrator {
             if pdf > 0.0 as Float && !f.is_black() && vec3_abs_dot_nrm(&wi, &ns) != 0.0 as Float {
                 // compute ray differential _rd_ for specular reflection
                 let mut rd: Ray = isect.spawn_ray(&wi);
+                rd.ray_type = bxdf_type_to_ray_type(sampled_type);
                 if let Some(d) = ray.differential.iter().next() {
                     let dndx: Normal3f =
                         isect.shading.dndu * isect.dudx + isect.shading.dndv * isect.dvdx;
@@ -127,6 +130,7 @@ impl DirectLightingIntegrator {
             if pdf > 0.0 as Float && !f.is_black() && vec3_abs_dot_nrm(&wi, &ns) != 0.0 as Float {
                 // compute ray differential _rd_ for specular transmission
                 let mut rd: Ray = isect.spawn_ray(&wi);
+                rd.ray_type = bxdf_type_to_ray_type(sampled_type);
                 if let Some(d) = ray.differential.iter().next() {
                     let mut eta: Float = bsdf.eta;
                     let w: Vector3f = -wo;
@@ -178,10 +182,12 @@ impl SamplerIntegrator for DirectLightingIntegrator {
                     .push(sampler.round_count(light.get_n_samples()));
             }
             // request samples for sampling all lights
-            for _i in 0..self.max_depth {
-                for j in 0..scene.lights.len() {
-                    sampler.request_2d_array(self.n_light_samples[j]);
-                    sampler.request_2d_array(self.n_light_samples[j]);
+            if sampler.supports_arrays() {
+                for _i in 0..self.max_depth {
+                    for j in 0..scene.lights.len() {
+                        sampler.request_2d_array(self.n_light_samples[j]);
+                        sampler.request_2d_array(self.n_light_samples[j]);
+                    }
                 }
             }
         }
@@ -194,7 +200,7 @@ impl SamplerIntegrator for DirectLightingIntegrator {
         // arena: &mut Arena,
         depth: i32,
     ) -> Spectrum {
-        // TODO: ProfilePhase p(Prof::SamplerIntegratorLi);
+        let _p = ProfilePhase::new(Prof::SamplerIntegratorLi);
         let mut l: Spectrum = Spectrum::new(0.0 as Float);
         // find closest ray intersection or return background radiance
         if let Some(mut isect) = scene.intersect(ray) {
@@ -231,9 +237,7 @@ impl SamplerIntegrator for DirectLightingIntegrator {
                 );
             }
         } else {
-            for light in &scene.lights {
-                l += light.le(ray);
-            }
+            l += escaped_radiance(scene, ray);
         }
         l
     }
@@ -241,3 +245,151 @@ impl SamplerIntegrator for DirectLightingIntegrator {
         self.pixel_bounds
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use accelerators::bvh::{BVHAccel, SplitMethod};
+    use core::geometry::{Point2f, Point2i, Point3f};
+    use core::light::Light;
+    use core::material::Material;
+    use core::medium::MediumInterface;
+    use core::primitive::{GeometricPrimitive, Primitive};
+    use core::shape::Shape;
+    use core::transform::Transform;
+    use lights::point::PointLight;
+    use materials::matte::MatteMaterial;
+    use samplers::random::RandomSampler;
+    use shapes::disk::Disk;
+    use std;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use textures::constant::ConstantTexture;
+
+    /// Stands in for a primary sample space sampler (like
+    /// **MLTSampler**): refuses sample arrays and counts the 2D
+    /// samples handed out.
+    #[derive(Clone)]
+    struct NoArraySampler {
+        sampler: RandomSampler,
+        get_2d_calls: Arc<AtomicUsize>,
+    }
+
+    impl Sampler for NoArraySampler {
+        fn start_pixel(&mut self, p: &Point2i) {
+            self.sampler.start_pixel(p);
+        }
+        fn get_1d(&mut self) -> Float {
+            self.sampler.get_1d()
+        }
+        fn get_2d(&mut self) -> Point2f {
+            self.get_2d_calls.fetch_add(1, Ordering::SeqCst);
+            self.sampler.get_2d()
+        }
+        fn request_2d_array(&mut self, _n: i32) {
+            panic!("sample arrays requested from a sampler without array support");
+        }
+        fn supports_arrays(&self) -> bool {
+            false
+        }
+        fn round_count(&self, count: i32) -> i32 {
+            count
+        }
+        fn get_2d_array(&mut self, _n: i32) -> Vec<Point2f> {
+            panic!("sample arrays used with a sampler without array support");
+        }
+        fn start_next_sample(&mut self) -> bool {
+            self.sampler.start_next_sample()
+        }
+        fn reseed(&mut self, seed: u64) {
+            self.sampler.reseed(seed);
+        }
+        fn get_current_pixel(&self) -> Point2i {
+            self.sampler.get_current_pixel()
+        }
+        fn get_current_sample_number(&self) -> i64 {
+            self.sampler.get_current_sample_number()
+        }
+        fn get_samples_per_pixel(&self) -> i64 {
+            self.sampler.get_samples_per_pixel()
+        }
+    }
+
+    /// A diffuse disk at z = 0, lit by a point light at z = 1.
+    fn lit_disk() -> Scene {
+        let matte: Arc<Material + Send + Sync> = Arc::new(MatteMaterial::new(
+            Arc::new(ConstantTexture {
+                value: Spectrum::new(0.5 as Float),
+            }),
+            Arc::new(ConstantTexture {
+                value: 0.0 as Float,
+            }),
+            None,
+        ));
+        let disk: Arc<Shape + Send + Sync> = Arc::new(Disk::new(
+            Transform::default(),
+            Transform::default(),
+            false,
+            0.0 as Float,
+            10.0 as Float,
+            0.0 as Float,
+            360.0 as Float,
+        ));
+        let mut primitives: Vec<Arc<Primitive + Sync + Send>> = Vec::new();
+        primitives.push(Arc::new(GeometricPrimitive::new(
+            disk,
+            Some(matte),
+            None,
+            None,
+        )));
+        let bvh: Arc<BVHAccel> = Arc::new(BVHAccel::new(primitives, 4, SplitMethod::SAH));
+        let light_to_world: Transform = Transform::translate(&Vector3f {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        });
+        let light: Arc<Light + Sync + Send> = Arc::new(PointLight::new(
+            &light_to_world,
+            &MediumInterface::default(),
+            &Spectrum::new(1.0 as Float),
+        ));
+        Scene::new(bvh, vec![light])
+    }
+
+    #[test]
+    fn samplers_without_arrays_fall_back_to_get_2d() {
+        let scene: Scene = lit_disk();
+        let get_2d_calls: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let mut sampler: Box<Sampler + Send + Sync> = Box::new(NoArraySampler {
+            sampler: RandomSampler::new(1_i64),
+            get_2d_calls: get_2d_calls.clone(),
+        });
+        let mut integrator = DirectLightingIntegrator::new(
+            LightStrategy::UniformSampleAll,
+            1_i64,
+            Bounds2i::default(),
+        );
+        // must not request any arrays
+        integrator.preprocess(&scene, &mut sampler);
+        sampler.start_pixel(&Point2i::default());
+        // hit the disk off center, where its parameterization is fine
+        let mut ray: Ray = Ray {
+            o: Point3f {
+                x: 0.25,
+                y: 0.0,
+                z: 0.5,
+            },
+            d: Vector3f {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            t_max: std::f32::INFINITY,
+            ..Ray::default()
+        };
+        let l: Spectrum = integrator.li(&mut ray, &scene, &mut sampler, 0_i32);
+        // one light and one scattering sample for the single light
+        assert!(get_2d_calls.load(Ordering::SeqCst) >= 2_usize);
+        assert!(l[0] > 0.0 as Float, "point light does not reach the disk");
+    }
+}
@@ -6,14 +6,17 @@ extern crate pbr;
 use std::sync::mpsc;
 use std::sync::Arc;
 use std::thread;
+use std::time::Instant;
 // pbrt
 use core::camera::Camera;
 use core::film::Film;
 use core::geometry::{Bounds2f, Bounds2i, Point2f, Point2i};
 use core::integrator::compute_light_power_distribution;
+use core::integrator::MAX_RAY_DEPTH;
 use core::pbrt::erf_inv;
 use core::pbrt::SQRT_2;
 use core::pbrt::{Float, Spectrum};
+use core::profile::{report_profile, Prof, ProfilePhase};
 use core::rng::Rng;
 use core::sampler::{Sampler, SamplerClone};
 use core::sampling::Distribution1D;
@@ -179,7 +182,7 @@ impl Sampler for MLTSampler {
         self.array_2d_offset = 0_usize;
     }
     fn get_1d(&mut self) -> Float {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         let index: i32 = self.get_next_index();
         self.ensure_ready(index);
         self.x[index as usize].value
@@ -199,6 +202,10 @@ impl Sampler for MLTSampler {
         let additional_points: Vec<Point2f> = vec![Point2f::default(); size];
         self.sample_array_2d.push(additional_points);
     }
+    fn supports_arrays(&self) -> bool {
+        // arrays would bypass the mutations of the primary samples
+        false
+    }
     fn round_count(&self, count: i32) -> i32 {
         count
     }
@@ -287,7 +294,7 @@ impl MLTIntegrator {
     ) -> Self {
         MLTIntegrator {
             camera: camera,
-            max_depth: max_depth,
+            max_depth: max_depth.min(MAX_RAY_DEPTH),
             n_bootstrap: n_bootstrap,
             n_chains: n_chains,
             mutations_per_pixel: mutations_per_pixel,
@@ -396,6 +403,7 @@ pub fn render_mlt(
     integrator: &mut Box<MLTIntegrator>,
     num_threads: u8,
 ) {
+    let start: Instant = Instant::now();
     let num_cores: usize;
     if num_threads == 0_u8 {
         num_cores = num_cpus::get();
@@ -529,6 +537,9 @@ pub fn render_mlt(
             finish.join().unwrap();
         }
         // Store final image computed with MLT
+        film.set_render_time(start.elapsed());
         film.write_image(b / integrator.mutations_per_pixel as Float);
+        film.report_dropped_samples();
+        report_profile();
     }
 }
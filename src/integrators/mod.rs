@@ -63,16 +63,19 @@ extern crate num_cpus;
 extern crate pbr;
 // std
 use std;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::sync::Arc;
+use std::time::Instant;
 // pbrt
 use blockqueue::BlockQueue;
 use core::camera::{Camera, CameraSample};
-use core::geometry::pnt2_inside_exclusive;
-use core::geometry::{Bounds2i, Point2i, Ray, Vector2i};
-use core::integrator::SamplerIntegrator;
+use core::geometry::{bnd2_intersect_bnd2, pnt2_inside_exclusive};
+use core::geometry::{Bounds2i, Point2i, Ray};
+use core::integrator::{AdaptiveSampling, PixelEstimate, SamplerIntegrator};
 use core::pbrt::{Float, Spectrum};
-use core::sampler::Sampler;
+use core::profile::report_profile;
+use core::sampler::{max_sampler_dimensions, Sampler};
 use core::scene::Scene;
 
 pub mod ao;
@@ -83,6 +86,15 @@ pub mod path;
 pub mod sppm;
 pub mod volpath;
 
+/// Edge length (in pixels, a power of two) of the image tiles
+/// rendered in parallel (see **Film::tile_bounds()**).
+pub const TILE_SIZE: i32 = 16_i32;
+
+/// Called with the bounds and the reconstructed pixel values (in
+/// scanline order) of each finished image tile, e.g. to display the
+/// image progressively.
+pub type TileCallback = Box<Fn(&Bounds2i, &[Spectrum]) + Send + Sync>;
+
 /// **Main function** to **render** a scene mutli-threaded (using all
 /// available cores).
 pub fn render(
@@ -92,16 +104,42 @@ pub fn render(
     integrator: &mut Box<SamplerIntegrator + Send + Sync>,
     num_threads: u8,
 ) {
+    render_tiles(scene, camera, sampler, integrator, None, None, num_threads);
+}
+
+/// Same as **render()**, but stops sampling a pixel as soon as its
+/// estimate converged (see **AdaptiveSampling**), so noisy pixels
+/// receive more samples than flat ones. The sampler's samples per
+/// pixel act as an upper limit.
+pub fn render_adaptive(
+    scene: &Scene,
+    camera: &Arc<Camera + Send + Sync>,
+    sampler: &mut Box<Sampler + Send + Sync>,
+    integrator: &mut Box<SamplerIntegrator + Send + Sync>,
+    adaptive: &AdaptiveSampling,
+    num_threads: u8,
+) {
+    render_tiles(scene, camera, sampler, integrator, Some(adaptive), None, num_threads);
+}
+
+/// The general version of **render()**: optionally uses adaptive
+/// sampling (see **render_adaptive()**) and calls *tile_callback*
+/// whenever an image tile is finished.
+pub fn render_tiles(
+    scene: &Scene,
+    camera: &Arc<Camera + Send + Sync>,
+    sampler: &mut Box<Sampler + Send + Sync>,
+    integrator: &mut Box<SamplerIntegrator + Send + Sync>,
+    adaptive: Option<&AdaptiveSampling>,
+    tile_callback: Option<&TileCallback>,
+    num_threads: u8,
+) {
+    let start: Instant = Instant::now();
     // SamplerIntegrator::Render (integrator.cpp)
     let film = camera.get_film();
-    let sample_bounds: Bounds2i = film.get_sample_bounds();
     integrator.preprocess(scene, sampler);
     // use camera below
-    let sample_extent: Vector2i = sample_bounds.diagonal();
-    let tile_size: i32 = 16;
-    let x: i32 = (sample_extent.x + tile_size - 1) / tile_size;
-    let y: i32 = (sample_extent.y + tile_size - 1) / tile_size;
-    let n_tiles: Point2i = Point2i { x: x, y: y };
+    let n_tiles: Point2i = film.tile_count(TILE_SIZE);
     // TODO: ProgressReporter reporter(nTiles.x * nTiles.y, "Rendering");
     let num_cores: usize;
     if num_threads == 0_u8 {
@@ -113,10 +151,10 @@ pub fn render(
     {
         let block_queue = BlockQueue::new(
             (
-                (n_tiles.x * tile_size) as u32,
-                (n_tiles.y * tile_size) as u32,
+                (n_tiles.x * TILE_SIZE) as u32,
+                (n_tiles.y * TILE_SIZE) as u32,
             ),
-            (tile_size as u32, tile_size as u32),
+            (TILE_SIZE as u32, TILE_SIZE as u32),
             (0, 0),
         );
         let integrator = &integrator;
@@ -125,6 +163,10 @@ pub fn render(
         let camera = &camera;
         let film = &film;
         let pixel_bounds = integrator.get_pixel_bounds().clone();
+        // warn only once per render about exhausted sample dimensions
+        let dimension_warning: AtomicBool = AtomicBool::new(false);
+        let dimension_warning = &dimension_warning;
+        let max_dimensions: i64 = max_sampler_dimensions();
         crossbeam::scope(|scope| {
             let (pixel_tx, pixel_rx) = mpsc::channel();
             // spawn worker threads
@@ -137,14 +179,10 @@ pub fn render(
                             x: x as i32,
                             y: y as i32,
                         };
-                        let seed: i32 = tile.y * n_tiles.x + tile.x;
-                        tile_sampler.reseed(seed as u64);
-                        let x0: i32 = sample_bounds.p_min.x + tile.x * tile_size;
-                        let x1: i32 = std::cmp::min(x0 + tile_size, sample_bounds.p_max.x);
-                        let y0: i32 = sample_bounds.p_min.y + tile.y * tile_size;
-                        let y1: i32 = std::cmp::min(y0 + tile_size, sample_bounds.p_max.y);
+                        let tile_index: i32 = tile.y * n_tiles.x + tile.x;
+                        tile_sampler.reseed(tile_index as u64);
                         let tile_bounds: Bounds2i =
-                            Bounds2i::new(Point2i { x: x0, y: y0 }, Point2i { x: x1, y: y1 });
+                            film.sample_tile_bounds(tile_index as usize, TILE_SIZE);
                         // println!("Starting image tile {:?}", tile_bounds);
                         let mut film_tile = film.get_film_tile(&tile_bounds);
                         for pixel in &tile_bounds {
@@ -152,6 +190,7 @@ pub fn render(
                             if !pnt2_inside_exclusive(&pixel, &pixel_bounds) {
                                 continue;
                             }
+                            let mut estimate: PixelEstimate = PixelEstimate::default();
                             let mut done: bool = false;
                             while !done {
                                 // let's use the copy_arena crate instead of pbrt's MemoryArena
@@ -171,7 +210,6 @@ pub fn render(
                                 // TODO: ++nCameraRays;
                                 // evaluate radiance along camera ray
                                 let mut l: Spectrum = Spectrum::new(0.0 as Float);
-                                let y: Float = l.y();
                                 if ray_weight > 0.0 {
                                     l = integrator.li(
                                         &mut ray,
@@ -180,45 +218,73 @@ pub fn render(
                                         0_i32,
                                     );
                                 }
+                                if cfg!(debug_assertions)
+                                    && tile_sampler.dimensions_used() > max_dimensions
+                                    && !dimension_warning.swap(true, Ordering::Relaxed)
+                                {
+                                    println!(
+                                        "WARNING: A path consumed {} sample dimensions (more \
+                                         than {}). Consider reducing \"maxdepth\".",
+                                        tile_sampler.dimensions_used(),
+                                        max_dimensions
+                                    );
+                                }
+                                // drop sample if unexpected radiance value returned
+                                // (like **Film::add_splat()**)
+                                let y: Float = l.y();
+                                let mut valid: bool = true;
                                 if l.has_nans() {
                                     println!(
                                         "Not-a-number radiance value returned for pixel \
-                                         ({:?}, {:?}), sample {:?}. Setting to black.",
+                                         ({:?}, {:?}), sample {:?}. Ignoring sample.",
                                         pixel.x,
                                         pixel.y,
                                         tile_sampler.get_current_sample_number()
                                     );
-                                    l = Spectrum::new(0.0);
+                                    valid = false;
                                 } else if y < -10.0e-5 as Float {
                                     println!(
                                         "Negative luminance value, {:?}, returned for pixel \
-                                         ({:?}, {:?}), sample {:?}. Setting to black.",
+                                         ({:?}, {:?}), sample {:?}. Ignoring sample.",
                                         y,
                                         pixel.x,
                                         pixel.y,
                                         tile_sampler.get_current_sample_number()
                                     );
-                                    l = Spectrum::new(0.0);
+                                    valid = false;
                                 } else if y.is_infinite() {
                                     println!(
                                         "Infinite luminance value returned for pixel ({:?}, \
-                                         {:?}), sample {:?}. Setting to black.",
+                                         {:?}), sample {:?}. Ignoring sample.",
                                         pixel.x,
                                         pixel.y,
                                         tile_sampler.get_current_sample_number()
                                     );
-                                    l = Spectrum::new(0.0);
+                                    valid = false;
+                                }
+                                // dropped samples still advance the sampler
+                                done = !tile_sampler.start_next_sample();
+                                if let Some(adaptive) = adaptive {
+                                    if valid {
+                                        estimate.add(y * ray_weight);
+                                    }
+                                    if adaptive.is_converged(&estimate) {
+                                        done = true;
+                                    }
+                                }
+                                if !valid {
+                                    film.add_dropped_sample();
+                                    continue;
                                 }
                                 // println!("Camera sample: {:?} -> ray: {:?} -> L = {:?}",
                                 //          camera_sample, ray, l);
                                 // add camera ray's contribution to image
                                 film_tile.add_sample(&camera_sample.p_film, &mut l, ray_weight);
-                                done = !tile_sampler.start_next_sample();
                             } // arena is dropped here !
                         }
                         // send the tile through the channel to main thread
                         pixel_tx
-                            .send(film_tile)
+                            .send((tile_bounds, film_tile))
                             .expect(&format!("Failed to send tile"));
                     }
                 });
@@ -226,13 +292,335 @@ pub fn render(
             // spawn thread to collect pixels and render image to file
             scope.spawn(move |_| {
                 for _ in pbr::PbIter::new(0..bq.len()) {
-                    let film_tile = pixel_rx.recv().unwrap();
+                    let (tile_bounds, film_tile) = pixel_rx.recv().unwrap();
                     // merge image tile into _Film_
                     film.merge_film_tile(&film_tile);
+                    if let Some(tile_callback) = tile_callback {
+                        let bounds: Bounds2i =
+                            bnd2_intersect_bnd2(&tile_bounds, &film.cropped_pixel_bounds);
+                        if bounds.p_max.x > bounds.p_min.x && bounds.p_max.y > bounds.p_min.y {
+                            tile_callback(&bounds, &film.get_pixel_values(&bounds));
+                        }
+                    }
                 }
             });
         })
         .unwrap();
     }
+    film.set_render_time(start.elapsed());
     film.write_image(1.0 as Float);
+    film.report_dropped_samples();
+    report_profile();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use accelerators::bvh::{BVHAccel, SplitMethod};
+    use cameras::perspective::PerspectiveCamera;
+    use core::film::Film;
+    use core::geometry::{Bounds2f, Point2f, Vector3f};
+    use core::primitive::{GeometricPrimitive, Primitive};
+    use core::shape::Shape;
+    use core::testutil::test_film;
+    use core::transform::{AnimatedTransform, Transform};
+    use integrators::ao::AOIntegrator;
+    use samplers::random::RandomSampler;
+    use shapes::disk::Disk;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+
+    /// A disk in front of a perspective camera, rendered with ambient
+    /// occlusion into a film of *resolution* pixels (written to the
+    /// temporary directory).
+    fn ao_setup(
+        resolution: Point2i,
+        name: &str,
+    ) -> (
+        Scene,
+        Arc<Camera + Send + Sync>,
+        Box<Sampler + Send + Sync>,
+        Box<SamplerIntegrator + Send + Sync>,
+    ) {
+        let disk: Arc<Shape + Send + Sync> = Arc::new(Disk::new(
+            Transform::translate(&Vector3f {
+                x: 0.0 as Float,
+                y: 0.0 as Float,
+                z: 5.0 as Float,
+            }),
+            Transform::translate(&Vector3f {
+                x: 0.0 as Float,
+                y: 0.0 as Float,
+                z: -5.0 as Float,
+            }),
+            true,
+            0.0 as Float,
+            2.0 as Float,
+            0.0 as Float,
+            360.0 as Float,
+        ));
+        let prims: Vec<Arc<Primitive + Send + Sync>> =
+            vec![Arc::new(GeometricPrimitive::new(disk, None, None, None))];
+        let scene: Scene = Scene::new(
+            Arc::new(BVHAccel::new(prims, 4, SplitMethod::SAH)),
+            Vec::new(),
+        );
+        let filename: String = std::env::temp_dir()
+            .join(name)
+            .to_string_lossy()
+            .into_owned();
+        let film: Arc<Film> = Arc::new(test_film(resolution, 0.5 as Float, &filename));
+        let frame: Float = resolution.x as Float / resolution.y as Float;
+        let camera: Arc<Camera + Send + Sync> = Arc::new(PerspectiveCamera::new(
+            AnimatedTransform::new(
+                &Transform::default(),
+                0.0 as Float,
+                &Transform::default(),
+                1.0 as Float,
+            ),
+            Bounds2f {
+                p_min: Point2f {
+                    x: -frame,
+                    y: -1.0 as Float,
+                },
+                p_max: Point2f {
+                    x: frame,
+                    y: 1.0 as Float,
+                },
+            },
+            0.0 as Float,
+            1.0 as Float,
+            0.0 as Float,
+            1e6 as Float,
+            60.0 as Float,
+            film.clone(),
+            None,
+        ));
+        let sampler: Box<Sampler + Send + Sync> = Box::new(RandomSampler::new(2_i64));
+        let integrator: Box<SamplerIntegrator + Send + Sync> = Box::new(AOIntegrator::new(
+            true,
+            4_i32,
+            film.get_cropped_pixel_bounds(),
+        ));
+        (scene, camera, sampler, integrator)
+    }
+
+    /// Returns NaN radiance for the left half of the image, constant
+    /// radiance for the right half.
+    struct HalfNaNIntegrator {
+        pixel_bounds: Bounds2i,
+    }
+
+    impl SamplerIntegrator for HalfNaNIntegrator {
+        fn preprocess(&mut self, _scene: &Scene, _sampler: &mut Box<Sampler + Send + Sync>) {}
+        fn li(
+            &self,
+            ray: &mut Ray,
+            _scene: &Scene,
+            _sampler: &mut Box<Sampler + Send + Sync>,
+            _depth: i32,
+        ) -> Spectrum {
+            if ray.d.x < 0.0 as Float {
+                Spectrum::new(std::f32::NAN)
+            } else {
+                Spectrum::new(1.0 as Float)
+            }
+        }
+        fn get_pixel_bounds(&self) -> Bounds2i {
+            self.pixel_bounds
+        }
+    }
+
+    /// Returns the distance between the directions of the camera ray
+    /// and its rx differential.
+    struct DifferentialIntegrator {
+        pixel_bounds: Bounds2i,
+    }
+
+    impl SamplerIntegrator for DifferentialIntegrator {
+        fn preprocess(&mut self, _scene: &Scene, _sampler: &mut Box<Sampler + Send + Sync>) {}
+        fn li(
+            &self,
+            ray: &mut Ray,
+            _scene: &Scene,
+            _sampler: &mut Box<Sampler + Send + Sync>,
+            _depth: i32,
+        ) -> Spectrum {
+            let d = ray.differential.unwrap();
+            Spectrum::new((d.rx_direction - ray.d).length())
+        }
+        fn get_pixel_bounds(&self) -> Bounds2i {
+            self.pixel_bounds
+        }
+    }
+
+    /// Rare bright paths (10 with a probability of 10%) for the left
+    /// half of the image, constant radiance for the right half.
+    /// Counts the samples taken for each half.
+    struct NoisyIntegrator {
+        pixel_bounds: Bounds2i,
+        samples: Arc<[AtomicUsize; 2]>,
+    }
+
+    impl SamplerIntegrator for NoisyIntegrator {
+        fn preprocess(&mut self, _scene: &Scene, _sampler: &mut Box<Sampler + Send + Sync>) {}
+        fn li(
+            &self,
+            ray: &mut Ray,
+            _scene: &Scene,
+            sampler: &mut Box<Sampler + Send + Sync>,
+            _depth: i32,
+        ) -> Spectrum {
+            if ray.d.x < 0.0 as Float {
+                self.samples[0].fetch_add(1, Ordering::Relaxed);
+                if sampler.get_1d() < 0.1 as Float {
+                    Spectrum::new(10.0 as Float)
+                } else {
+                    Spectrum::new(0.0 as Float)
+                }
+            } else {
+                self.samples[1].fetch_add(1, Ordering::Relaxed);
+                Spectrum::new(1.0 as Float)
+            }
+        }
+        fn get_pixel_bounds(&self) -> Bounds2i {
+            self.pixel_bounds
+        }
+    }
+
+    #[test]
+    fn noisy_pixels_get_more_samples() {
+        let (scene, camera, _sampler, _ao) =
+            ao_setup(Point2i { x: 8, y: 4 }, "rs_pbrt_adaptive.png");
+        let film: Arc<Film> = camera.get_film();
+        let mut sampler: Box<Sampler + Send + Sync> = Box::new(RandomSampler::new(256_i64));
+        let samples: Arc<[AtomicUsize; 2]> = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0)]);
+        let mut integrator: Box<SamplerIntegrator + Send + Sync> = Box::new(NoisyIntegrator {
+            pixel_bounds: film.get_cropped_pixel_bounds(),
+            samples: samples.clone(),
+        });
+        let adaptive: AdaptiveSampling = AdaptiveSampling::new(4_i64, 256_i64, 0.1 as Float);
+        render_tiles(
+            &scene,
+            &camera,
+            &mut sampler,
+            &mut integrator,
+            Some(&adaptive),
+            None,
+            1_u8,
+        );
+        // 16 pixels per half: the flat ones stop after 3 / 0.1
+        // samples, not after the first 4
+        let noisy: usize = samples[0].load(Ordering::Relaxed);
+        let flat: usize = samples[1].load(Ordering::Relaxed);
+        assert_eq!(flat, 16_usize * 30_usize);
+        assert!(noisy > 4_usize * flat, "noisy: {}, flat: {}", noisy, flat);
+    }
+
+    fn differential_spacing(spp: i64) -> Vec<Spectrum> {
+        let (scene, camera, _sampler, _ao) =
+            ao_setup(Point2i { x: 32, y: 32 }, "rs_pbrt_differentials.png");
+        let film: Arc<Film> = camera.get_film();
+        let mut sampler: Box<Sampler + Send + Sync> = Box::new(RandomSampler::new(spp));
+        let mut integrator: Box<SamplerIntegrator + Send + Sync> =
+            Box::new(DifferentialIntegrator {
+                pixel_bounds: film.get_cropped_pixel_bounds(),
+            });
+        render_tiles(
+            &scene,
+            &camera,
+            &mut sampler,
+            &mut integrator,
+            None,
+            None,
+            1_u8,
+        );
+        film.get_pixel_values(&film.get_cropped_pixel_bounds())
+    }
+
+    #[test]
+    fn differentials_are_scaled_by_the_samples_per_pixel() {
+        // 1/sqrt(spp): four samples per pixel halve the footprint
+        let one: Vec<Spectrum> = differential_spacing(1_i64);
+        let four: Vec<Spectrum> = differential_spacing(4_i64);
+        for (a, b) in one.iter().zip(four.iter()) {
+            assert!(a[0] > 0.0 as Float);
+            assert!(
+                (b[0] / a[0] - 0.5 as Float).abs() < 0.01 as Float,
+                "{:?} {:?}",
+                a,
+                b
+            );
+        }
+    }
+
+    #[test]
+    fn bad_samples_are_dropped_but_advance_the_sampler() {
+        let resolution: Point2i = Point2i { x: 8, y: 4 };
+        for adaptive in &[
+            None,
+            Some(AdaptiveSampling::new(1_i64, 2_i64, 0.01 as Float)),
+        ] {
+            let (scene, camera, mut sampler, _ao) = ao_setup(resolution, "rs_pbrt_nan.png");
+            let film: Arc<Film> = camera.get_film();
+            let mut integrator: Box<SamplerIntegrator + Send + Sync> =
+                Box::new(HalfNaNIntegrator {
+                    pixel_bounds: film.get_cropped_pixel_bounds(),
+                });
+            render_tiles(
+                &scene,
+                &camera,
+                &mut sampler,
+                &mut integrator,
+                adaptive.as_ref(),
+                None,
+                1_u8,
+            );
+            // every sample of the left half was dropped (and the
+            // estimate never converged there)
+            assert_eq!(film.get_dropped_samples(), 4_usize * 4_usize * 2_usize);
+            let pixels: Vec<Spectrum> = film.get_pixel_values(&film.get_cropped_pixel_bounds());
+            for (i, pixel) in pixels.iter().enumerate() {
+                let expected: Float = if i % 8 < 4 { 0.0 } else { 1.0 };
+                assert!(
+                    (pixel[0] - expected).abs() < 1e-5 as Float,
+                    "{}: {:?}",
+                    i,
+                    pixel
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn tile_callback_covers_film_once() {
+        let resolution: Point2i = Point2i { x: 32, y: 16 };
+        let (scene, camera, mut sampler, mut integrator) =
+            ao_setup(resolution, "rs_pbrt_tile_callback.png");
+        let tiles: Arc<Mutex<Vec<Bounds2i>>> = Arc::new(Mutex::new(Vec::new()));
+        let tiles_seen = tiles.clone();
+        let callback: TileCallback = Box::new(move |bounds: &Bounds2i, pixels: &[Spectrum]| {
+            assert_eq!(pixels.len(), bounds.area() as usize);
+            tiles_seen.lock().unwrap().push(*bounds);
+        });
+        render_tiles(
+            &scene,
+            &camera,
+            &mut sampler,
+            &mut integrator,
+            None,
+            Some(&callback),
+            2_u8,
+        );
+        let tiles: Vec<Bounds2i> = tiles.lock().unwrap().clone();
+        assert_eq!(tiles.len(), 2_usize);
+        // the tiles don't overlap and cover the film
+        let mut covered: Vec<u8> = vec![0_u8; (resolution.x * resolution.y) as usize];
+        for tile in &tiles {
+            for p in tile {
+                covered[(p.y * resolution.x + p.x) as usize] += 1_u8;
+            }
+        }
+        assert!(covered.iter().all(|c| *c == 1_u8));
+    }
 }
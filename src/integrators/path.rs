@@ -5,14 +5,16 @@ use std::sync::Arc;
 use core::bssrdf::Bssrdf;
 use core::geometry::{vec3_abs_dot_nrm, vec3_dot_nrm};
 use core::geometry::{Bounds2i, Point2f, Ray, Vector3f};
-use core::integrator::uniform_sample_one_light;
 use core::integrator::SamplerIntegrator;
+use core::integrator::MAX_RAY_DEPTH;
+use core::integrator::{escaped_radiance, uniform_sample_one_light};
 use core::interaction::Interaction;
 use core::lightdistrib::create_light_sample_distribution;
 use core::lightdistrib::LightDistribution;
 use core::material::TransportMode;
 use core::pbrt::{Float, Spectrum};
-use core::reflection::BxdfType;
+use core::profile::{Prof, ProfilePhase};
+use core::reflection::{bxdf_type_to_ray_type, BxdfType};
 use core::sampler::Sampler;
 use core::sampling::Distribution1D;
 use core::scene::Scene;
@@ -27,6 +29,9 @@ pub struct PathIntegrator {
     max_depth: u32,
     rr_threshold: Float,           // 1.0
     light_sample_strategy: String, // "spatial"
+    intensity_clamp: Float,        // infinity
+    regularize: bool,              // false
+    regularize_min_alpha: Float,   // 0.3
     light_distribution: Option<Arc<LightDistribution + Send + Sync>>,
 }
 
@@ -36,15 +41,35 @@ impl PathIntegrator {
         pixel_bounds: Bounds2i,
         rr_threshold: Float,
         light_sample_strategy: String,
+        intensity_clamp: Float,
+        regularize: bool,
+        regularize_min_alpha: Float,
     ) -> Self {
         PathIntegrator {
             pixel_bounds: pixel_bounds,
-            max_depth: max_depth,
+            max_depth: max_depth.min(MAX_RAY_DEPTH),
             rr_threshold: rr_threshold,
             light_sample_strategy: light_sample_strategy,
+            intensity_clamp: intensity_clamp,
+            regularize: regularize,
+            regularize_min_alpha: regularize_min_alpha,
             light_distribution: None,
         }
     }
+    /// Scales down contributions of indirect bounces to a maximum
+    /// luminance of **intensity_clamp** (to avoid fireflies). Direct
+    /// lighting of the first hit (*bounces* == 0) is left untouched.
+    fn clamp_indirect(&self, bounces: u32, contribution: Spectrum) -> Spectrum {
+        if bounces == 0_u32 {
+            return contribution;
+        }
+        let y: Float = contribution.y();
+        if y > self.intensity_clamp {
+            contribution * (self.intensity_clamp / y)
+        } else {
+            contribution
+        }
+    }
 }
 
 impl SamplerIntegrator for PathIntegrator {
@@ -60,7 +85,7 @@ impl SamplerIntegrator for PathIntegrator {
         // arena: &mut Arena,
         _depth: i32,
     ) -> Spectrum {
-        // TODO: ProfilePhase p(Prof::SamplerIntegratorLi);
+        let _p = ProfilePhase::new(Prof::SamplerIntegratorLi);
         let mut l: Spectrum = Spectrum::default();
         let mut beta: Spectrum = Spectrum::new(1.0 as Float);
         let mut ray: Ray = Ray {
@@ -68,10 +93,12 @@ impl SamplerIntegrator for PathIntegrator {
             d: r.d,
             t_max: r.t_max,
             time: r.time,
+            ray_type: r.ray_type,
             differential: r.differential,
             medium: r.medium.clone(),
         };
         let mut specular_bounce: bool = false;
+        let mut any_non_specular_bounces: bool = false;
         let mut bounces: u32 = 0_u32;
         // Added after book publication: etaScale tracks the
         // accumulated effect of radiance scaling due to rays passing
@@ -91,7 +118,7 @@ impl SamplerIntegrator for PathIntegrator {
                 // possibly add emitted light at intersection
                 if bounces == 0 || specular_bounce {
                     // add emitted light at path vertex
-                    l += beta * isect.le(&-ray.d);
+                    l += self.clamp_indirect(bounces, beta * isect.le(&-ray.d));
                     // println!("Added Le -> L = {:?}", l);
                 }
                 // terminate path if _maxDepth_ was reached
@@ -105,10 +132,17 @@ impl SamplerIntegrator for PathIntegrator {
                     // we are fine (for below)
                 } else {
                     // TODO: println!("Skipping intersection due to null bsdf");
+                    let ray_type: u8 = ray.ray_type;
                     ray = isect.spawn_ray(&ray.d);
+                    ray.ray_type = ray_type;
                     // bounces--;
                     continue;
                 }
+                if self.regularize && any_non_specular_bounces {
+                    if let Some(ref mut bsdf) = isect.bsdf {
+                        Arc::make_mut(bsdf).regularize(self.regularize_min_alpha);
+                    }
+                }
                 if let Some(ref light_distribution) = self.light_distribution {
                     let distrib: Arc<Distribution1D> = light_distribution.lookup(&isect.p);
                     // Sample illumination from lights to find path contribution.
@@ -130,7 +164,7 @@ impl SamplerIntegrator for PathIntegrator {
                             //     ++zero_radiance_paths;
                             // }
                             assert!(ld.y() >= 0.0 as Float, "ld = {:?}", ld);
-                            l += ld;
+                            l += self.clamp_indirect(bounces, ld);
                         }
                         // Sample BSDF to get new path direction
                         let wo: Vector3f = -ray.d;
@@ -165,6 +199,7 @@ impl SamplerIntegrator for PathIntegrator {
                             pdf
                         );
                         specular_bounce = (sampled_type & BxdfType::BsdfSpecular as u8) != 0_u8;
+                        any_non_specular_bounces |= !specular_bounce;
                         if ((sampled_type & BxdfType::BsdfSpecular as u8) != 0_u8)
                             && ((sampled_type & BxdfType::BsdfTransmission as u8) != 0_u8)
                         {
@@ -180,6 +215,7 @@ impl SamplerIntegrator for PathIntegrator {
                             }
                         }
                         ray = isect.spawn_ray(&wi);
+                        ray.ray_type = bxdf_type_to_ray_type(sampled_type);
 
                         // account for subsurface scattering, if applicable
                         if let Some(ref bssrdf) = isect.bssrdf {
@@ -207,7 +243,7 @@ impl SamplerIntegrator for PathIntegrator {
                                     // account for the direct subsurface scattering component
                                     let distrib: Arc<Distribution1D> =
                                         light_distribution.lookup(&pi.p);
-                                    l += beta
+                                    let ld: Spectrum = beta
                                         * uniform_sample_one_light(
                                             &pi,
                                             scene,
@@ -215,6 +251,7 @@ impl SamplerIntegrator for PathIntegrator {
                                             false,
                                             Some(Arc::borrow(&distrib)),
                                         );
+                                    l += self.clamp_indirect(bounces + 1_u32, ld);
                                     // account for the indirect subsurface scattering component
                                     let mut wi: Vector3f = Vector3f::default();
                                     let mut pdf: Float = 0.0 as Float;
@@ -265,10 +302,7 @@ impl SamplerIntegrator for PathIntegrator {
             } else {
                 // add emitted light from the environment
                 if bounces == 0 || specular_bounce {
-                    // for (const auto &light : scene.infiniteLights)
-                    for light in &scene.infinite_lights {
-                        l += beta * light.le(&mut ray);
-                    }
+                    l += self.clamp_indirect(bounces, beta * escaped_radiance(scene, &mut ray));
                     // println!("Added infinite area lights -> L = {:?}", l);
                 }
                 // terminate path if ray escaped
@@ -282,3 +316,283 @@ impl SamplerIntegrator for PathIntegrator {
         self.pixel_bounds
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use accelerators::bvh::{BVHAccel, SplitMethod};
+    use core::geometry::{Bounds2i, Point2i, Point3f, Ray, Vector3f};
+    use core::light::{AreaLight, Light};
+    use core::material::Material;
+    use core::medium::MediumInterface;
+    use core::primitive::{GeometricPrimitive, Primitive};
+    use core::reflection::REGULARIZE_MIN_ALPHA;
+    use core::shape::Shape;
+    use core::transform::Transform;
+    use lights::diffuse::DiffuseAreaLight;
+    use lights::point::PointLight;
+    use materials::matte::MatteMaterial;
+    use materials::mirror::MirrorMaterial;
+    use samplers::random::RandomSampler;
+    use shapes::disk::Disk;
+    use shapes::sphere::Sphere;
+    use std;
+    use textures::constant::ConstantTexture;
+
+    /// Two mirrors facing each other (at z = 0 and z = 1) with a
+    /// point light in between.
+    fn two_mirrors() -> Scene {
+        let mirror: Arc<Material + Send + Sync> = Arc::new(MirrorMaterial::new(
+            Arc::new(ConstantTexture {
+                value: Spectrum::new(0.9 as Float),
+            }),
+            None,
+        ));
+        let mut primitives: Vec<Arc<Primitive + Sync + Send>> = Vec::new();
+        for height in [0.0 as Float, 1.0 as Float].iter() {
+            let disk: Arc<Shape + Send + Sync> = Arc::new(Disk::new(
+                Transform::default(),
+                Transform::default(),
+                *height > 0.5 as Float,
+                *height,
+                10.0 as Float,
+                0.0 as Float,
+                360.0 as Float,
+            ));
+            primitives.push(Arc::new(GeometricPrimitive::new(
+                disk,
+                Some(mirror.clone()),
+                None,
+                None,
+            )));
+        }
+        let bvh: Arc<BVHAccel> = Arc::new(BVHAccel::new(primitives, 4, SplitMethod::SAH));
+        let light_to_world: Transform = Transform::translate(&Vector3f {
+            x: 0.0,
+            y: 0.0,
+            z: 0.5,
+        });
+        let light: Arc<Light + Sync + Send> = Arc::new(PointLight::new(
+            &light_to_world,
+            &MediumInterface::default(),
+            &Spectrum::new(1.0 as Float),
+        ));
+        Scene::new(bvh, vec![light])
+    }
+
+    /// A diffuse floor (at z = 0) under a mirror ceiling (at z = 2,
+    /// around the origin). A small, bright spherical light between
+    /// them causes a caustic on the floor below the mirror, a point
+    /// light lights the floor far away from the mirror (at x = 50).
+    fn caustic_scene() -> Scene {
+        let matte: Arc<Material + Send + Sync> = Arc::new(MatteMaterial::new(
+            Arc::new(ConstantTexture {
+                value: Spectrum::new(0.5 as Float),
+            }),
+            Arc::new(ConstantTexture {
+                value: 0.0 as Float,
+            }),
+            None,
+        ));
+        let mirror: Arc<Material + Send + Sync> = Arc::new(MirrorMaterial::new(
+            Arc::new(ConstantTexture {
+                value: Spectrum::new(0.9 as Float),
+            }),
+            None,
+        ));
+        let mut primitives: Vec<Arc<Primitive + Sync + Send>> = Vec::new();
+        for &(height, radius, ref material) in
+            [(0.0 as Float, 100.0 as Float, &matte), (2.0, 10.0, &mirror)].iter()
+        {
+            let disk: Arc<Shape + Send + Sync> = Arc::new(Disk::new(
+                Transform::default(),
+                Transform::default(),
+                height > 0.0 as Float,
+                height,
+                radius,
+                0.0 as Float,
+                360.0 as Float,
+            ));
+            primitives.push(Arc::new(GeometricPrimitive::new(
+                disk,
+                Some((*material).clone()),
+                None,
+                None,
+            )));
+        }
+        let sphere_to_world: Transform = Transform::translate(&Vector3f {
+            x: 0.5,
+            y: 0.0,
+            z: 1.7,
+        });
+        let sphere: Arc<Shape + Send + Sync> = Arc::new(Sphere::new(
+            sphere_to_world,
+            Transform::inverse(&sphere_to_world),
+            false,
+            0.25 as Float,
+            -0.25 as Float,
+            0.25 as Float,
+            360.0 as Float,
+        ));
+        let area_light: Arc<DiffuseAreaLight> = Arc::new(DiffuseAreaLight::new(
+            &sphere_to_world,
+            &MediumInterface::default(),
+            &Spectrum::new(50.0 as Float),
+            None,
+            1_i32,
+            sphere.clone(),
+            false,
+        ));
+        let emitter: Arc<AreaLight + Send + Sync> = area_light.clone();
+        primitives.push(Arc::new(GeometricPrimitive::new(
+            sphere,
+            Some(matte.clone()),
+            Some(emitter),
+            None,
+        )));
+        let bvh: Arc<BVHAccel> = Arc::new(BVHAccel::new(primitives, 4, SplitMethod::SAH));
+        let light_to_world: Transform = Transform::translate(&Vector3f {
+            x: 50.0,
+            y: 0.0,
+            z: 1.0,
+        });
+        let point_light: Arc<Light + Sync + Send> = Arc::new(PointLight::new(
+            &light_to_world,
+            &MediumInterface::default(),
+            &Spectrum::new(10.0 as Float),
+        ));
+        Scene::new(bvh, vec![point_light, area_light])
+    }
+
+    /// Luminance of *n* radiance estimates along a ray straight down
+    /// to (*x*, 0, 0).
+    fn floor_estimates(scene: &Scene, x: Float, intensity_clamp: Float, n: i64) -> Vec<Float> {
+        let mut sampler: Box<Sampler + Send + Sync> = Box::new(RandomSampler::new(n));
+        let mut integrator = PathIntegrator::new(
+            5_u32,
+            Bounds2i::default(),
+            1.0 as Float,
+            String::from("uniform"),
+            intensity_clamp,
+            false,
+            REGULARIZE_MIN_ALPHA,
+        );
+        integrator.preprocess(scene, &mut sampler);
+        sampler.start_pixel(&Point2i::default());
+        let mut estimates: Vec<Float> = Vec::new();
+        loop {
+            let mut ray: Ray = Ray {
+                o: Point3f {
+                    x: x,
+                    y: 0.0,
+                    z: 1.0,
+                },
+                d: Vector3f {
+                    x: 0.0,
+                    y: 0.0,
+                    z: -1.0,
+                },
+                t_max: std::f32::INFINITY,
+                ..Ray::default()
+            };
+            estimates.push(integrator.li(&mut ray, scene, &mut sampler, 0_i32).y());
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        estimates
+    }
+
+    fn mean_and_variance(estimates: &Vec<Float>) -> (Float, Float) {
+        let n: Float = estimates.len() as Float;
+        let mean: Float = estimates.iter().sum::<Float>() / n;
+        let variance: Float = estimates
+            .iter()
+            .map(|e| (e - mean) * (e - mean))
+            .sum::<Float>()
+            / n;
+        (mean, variance)
+    }
+
+    #[test]
+    fn clampindirect_reduces_fireflies_but_keeps_direct_light() {
+        let scene: Scene = caustic_scene();
+        let clamp: Float = 1.0 as Float;
+        // the caustic (floor -> mirror -> light) below the mirror
+        let caustic: Vec<Float> = floor_estimates(&scene, -0.25 as Float, std::f32::INFINITY, 1024);
+        let clamped: Vec<Float> = floor_estimates(&scene, -0.25 as Float, clamp, 1024);
+        let max: Float = caustic.iter().cloned().fold(0.0 as Float, Float::max);
+        assert!(max > 10.0 as Float, "no firefly found: {}", max);
+        let (_, variance) = mean_and_variance(&caustic);
+        let (_, clamped_variance) = mean_and_variance(&clamped);
+        assert!(
+            clamped_variance < 0.1 as Float * variance,
+            "{} vs. {}",
+            clamped_variance,
+            variance
+        );
+        // brighter than the clamp, but direct lighting only
+        let direct: Vec<Float> = floor_estimates(&scene, 50.25 as Float, std::f32::INFINITY, 256);
+        let (mean, _) = mean_and_variance(&direct);
+        assert!(mean > clamp, "{}", mean);
+        let (clamped_mean, _) = mean_and_variance(&floor_estimates(&scene, 50.25, clamp, 256));
+        assert!(
+            (mean - clamped_mean).abs() < 1e-4 as Float * mean,
+            "{} vs. {}",
+            mean,
+            clamped_mean
+        );
+    }
+
+    #[test]
+    fn max_depth_is_capped() {
+        let integrator = PathIntegrator::new(
+            u32::max_value(),
+            Bounds2i::default(),
+            1.0 as Float,
+            String::from("uniform"),
+            std::f32::INFINITY,
+            false,
+            REGULARIZE_MIN_ALPHA,
+        );
+        assert_eq!(integrator.max_depth, MAX_RAY_DEPTH);
+    }
+
+    #[test]
+    fn facing_mirrors_terminate_with_finite_radiance() {
+        let scene: Scene = two_mirrors();
+        let mut sampler: Box<Sampler + Send + Sync> = Box::new(RandomSampler::new(1_i64));
+        sampler.start_pixel(&Point2i::default());
+        let mut integrator = PathIntegrator::new(
+            1_000_000_u32,
+            Bounds2i::default(),
+            // no russian roulette, only the depth limit ends the path
+            0.0 as Float,
+            String::from("uniform"),
+            std::f32::INFINITY,
+            false,
+            REGULARIZE_MIN_ALPHA,
+        );
+        integrator.preprocess(&scene, &mut sampler);
+        // bounces back and forth parallel to the z axis (off the disk
+        // centers, where the disk parameterization degenerates)
+        let mut ray: Ray = Ray {
+            o: Point3f {
+                x: 0.25,
+                y: 0.0,
+                z: 0.25,
+            },
+            d: Vector3f {
+                x: 0.0,
+                y: 0.0,
+                z: 1.0,
+            },
+            t_max: std::f32::INFINITY,
+            ..Ray::default()
+        };
+        let l: Spectrum = integrator.li(&mut ray, &scene, &mut sampler, 0_i32);
+        for i in 0..3 {
+            assert!(l[i].is_finite(), "channel {} not finite: {}", i, l[i]);
+        }
+    }
+}
@@ -8,6 +8,7 @@ use std;
 use std::f32::consts::PI;
 use std::sync::mpsc;
 use std::sync::Arc;
+use std::time::Instant;
 // others
 use atom::*;
 use atomic::Atomic;
@@ -22,15 +23,19 @@ use core::geometry::{
 use core::geometry::{
     Bounds2i, Bounds3f, Normal3f, Point2f, Point2i, Point3f, Point3i, Ray, Vector2i, Vector3f,
 };
-use core::integrator::{compute_light_power_distribution, uniform_sample_one_light};
+use core::integrator::{
+    compute_light_power_distribution, escaped_radiance, uniform_sample_one_light,
+};
+use core::integrator::MAX_RAY_DEPTH;
 use core::interaction::Interaction;
 use core::lowdiscrepancy::radical_inverse;
 use core::material::TransportMode;
 use core::parallel::AtomicFloat;
 use core::pbrt::{clamp_t, lerp};
 use core::pbrt::{Float, Spectrum};
+use core::profile::{report_profile, Prof, ProfilePhase};
 use core::reflection::{Bsdf, BxdfType};
-use core::sampler::{GlobalSampler, Sampler, SamplerClone};
+use core::sampler::{GlobalSampler, Sampler, SamplerClone, ARRAY_START_DIM};
 use core::scene::Scene;
 use samplers::halton::HaltonSampler;
 
@@ -60,7 +65,7 @@ impl SPPMIntegrator {
         SPPMIntegrator {
             initial_search_radius: initial_search_radius,
             n_iterations: n_iterations,
-            max_depth: max_depth,
+            max_depth: max_depth.min(MAX_RAY_DEPTH),
             photons_per_iteration: photons_per_iter,
             write_frequency: write_frequency,
         }
@@ -130,6 +135,7 @@ pub fn render_sppm(
     integrator: &mut Box<SPPMIntegrator>,
     num_threads: u8,
 ) {
+    let start: Instant = Instant::now();
     let num_cores: usize;
     if num_threads == 0_u8 {
         num_cores = num_cpus::get();
@@ -137,7 +143,7 @@ pub fn render_sppm(
         num_cores = num_threads as usize;
     }
     println!("Rendering with {:?} thread(s) ...", num_cores);
-    // TODO: ProfilePhase p(Prof::IntegratorRender);
+    let prof = ProfilePhase::new(Prof::IntegratorRender);
 
     // initialize _pixel_bounds_ and _pixels_ array for SPPM
     let film: Arc<Film> = camera.get_film();
@@ -159,6 +165,7 @@ pub fn render_sppm(
             integrator.n_iterations as i64,
             pixel_bounds,
             false,
+            ARRAY_START_DIM,
         ));
         // compute number of tiles to use for SPPM camera pass
         let pixel_extent: Vector2i = pixel_bounds.diagonal();
@@ -171,7 +178,7 @@ pub fn render_sppm(
         for iteration in pbr::PbIter::new(0..integrator.n_iterations) {
             // generate SPPM visible points
             {
-                // TODO: ProfilePhase _(Prof::SPPMCameraPass);
+                let _p = ProfilePhase::new(Prof::SPPMCameraPass);
                 // println!("Generate SPPM visible points ...");
                 {
                     let block_queue = BlockQueue::new(
@@ -340,9 +347,8 @@ pub fn render_sppm(
                                             } else {
                                                 // accumulate light contributions for
                                                 // ray with no intersection
-                                                for light in &scene.lights {
-                                                    pixel.1 += beta * light.le(&mut ray);
-                                                }
+                                                pixel.1 +=
+                                                    beta * escaped_radiance(scene, &mut ray);
                                                 break;
                                             }
                                         }
@@ -386,7 +392,7 @@ pub fn render_sppm(
                 grid_once.push(AtomSetOnce::empty());
             }
             {
-                // TODO: ProfilePhase _(Prof::SPPMGridConstruction);
+                let _p = ProfilePhase::new(Prof::SPPMGridConstruction);
 
                 // compute grid bounds for SPPM visible points
                 let mut max_radius: Float = 0.0 as Float;
@@ -503,7 +509,7 @@ pub fn render_sppm(
             }
             std::mem::drop(grid);
             {
-                // TODO: ProfilePhase _(Prof::SPPMPhotonPass);
+                let _p = ProfilePhase::new(Prof::SPPMPhotonPass);
                 // println!("Trace photons and accumulate contributions ...");
                 let chunk_size: usize =
                     (integrator.photons_per_iteration / num_cores as i32) as usize;
@@ -764,7 +770,7 @@ pub fn render_sppm(
             }
             // update pixel values from this pass's photons
             {
-                // TODO: ProfilePhase _(Prof::SPPMStatsUpdate);
+                let _p = ProfilePhase::new(Prof::SPPMStatsUpdate);
                 // println!("Update pixel values from this pass's photons ...");
                 let chunk_size: usize = (n_pixels / num_cores as i32) as usize;
                 {
@@ -835,6 +841,7 @@ pub fn render_sppm(
                     }
                 }
                 film.set_image(&image[..]);
+                film.set_render_time(start.elapsed());
                 film.write_image(1.0 as Float);
                 // TODO: write SPPM radius image, if requested
                 // if (getenv("SPPM_RADIUS")) {
@@ -873,4 +880,6 @@ pub fn render_sppm(
         }
         // TODO: progress.Done();
     }
+    std::mem::drop(prof);
+    report_profile();
 }
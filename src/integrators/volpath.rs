@@ -5,14 +5,16 @@ use std::sync::Arc;
 use core::bssrdf::Bssrdf;
 use core::geometry::{vec3_abs_dot_nrm, vec3_dot_nrm};
 use core::geometry::{Bounds2i, Point2f, Ray, Vector3f};
-use core::integrator::uniform_sample_one_light;
+use core::integrator::{escaped_radiance, uniform_sample_one_light};
 use core::integrator::SamplerIntegrator;
+use core::integrator::MAX_RAY_DEPTH;
 use core::interaction::{Interaction, MediumInteraction};
 use core::lightdistrib::create_light_sample_distribution;
 use core::lightdistrib::LightDistribution;
 use core::material::TransportMode;
 use core::pbrt::{Float, Spectrum};
-use core::reflection::BxdfType;
+use core::profile::{Prof, ProfilePhase};
+use core::reflection::{bxdf_type_to_ray_type, BxdfType};
 use core::sampler::Sampler;
 use core::sampling::Distribution1D;
 use core::scene::Scene;
@@ -28,6 +30,8 @@ pub struct VolPathIntegrator {
     pub max_depth: u32,
     rr_threshold: Float,           // 1.0
     light_sample_strategy: String, // "spatial"
+    regularize: bool,              // false
+    regularize_min_alpha: Float,   // 0.3
     light_distribution: Option<Arc<LightDistribution + Send + Sync>>,
 }
 
@@ -37,12 +41,16 @@ impl VolPathIntegrator {
         pixel_bounds: Bounds2i,
         rr_threshold: Float,
         light_sample_strategy: String,
+        regularize: bool,
+        regularize_min_alpha: Float,
     ) -> Self {
         VolPathIntegrator {
             pixel_bounds: pixel_bounds,
-            max_depth: max_depth,
+            max_depth: max_depth.min(MAX_RAY_DEPTH),
             rr_threshold: rr_threshold,
             light_sample_strategy: light_sample_strategy,
+            regularize: regularize,
+            regularize_min_alpha: regularize_min_alpha,
             light_distribution: None,
         }
     }
@@ -61,7 +69,7 @@ impl SamplerIntegrator for VolPathIntegrator {
         // arena: &mut Arena,
         _depth: i32,
     ) -> Spectrum {
-        // TODO: ProfilePhase p(Prof::SamplerIntegratorLi);
+        let _p = ProfilePhase::new(Prof::SamplerIntegratorLi);
         let mut l: Spectrum = Spectrum::default();
         let mut beta: Spectrum = Spectrum::new(1.0 as Float);
         let mut ray: Ray = Ray {
@@ -69,10 +77,12 @@ impl SamplerIntegrator for VolPathIntegrator {
             d: r.d,
             t_max: r.t_max,
             time: r.time,
+            ray_type: r.ray_type,
             differential: r.differential,
             medium: r.medium.clone(),
         };
         let mut specular_bounce: bool = false;
+        let mut any_non_specular_bounces: bool = false;
         let mut bounces: u32 = 0_u32;
         // Added after book publication: etaScale tracks the
         // accumulated effect of radiance scaling due to rays passing
@@ -123,6 +133,7 @@ impl SamplerIntegrator for VolPathIntegrator {
                             phase.sample_p(&(-ray.d), &mut wi, &sampler.get_2d());
                             ray = mi.spawn_ray(&wi);
                             specular_bounce = false;
+                            any_non_specular_bounces = true;
                         }
                     }
                 } else {
@@ -142,10 +153,17 @@ impl SamplerIntegrator for VolPathIntegrator {
                     if let Some(ref _bsdf) = isect.bsdf {
                         // we are fine (for below)
                     } else {
+                        let ray_type: u8 = ray.ray_type;
                         ray = isect.spawn_ray(&ray.d);
+                        ray.ray_type = ray_type;
                         // bounces--;
                         continue;
                     }
+                    if self.regularize && any_non_specular_bounces {
+                        if let Some(ref mut bsdf) = isect.bsdf {
+                            Arc::make_mut(bsdf).regularize(self.regularize_min_alpha);
+                        }
+                    }
                     if let Some(ref light_distribution) = self.light_distribution {
                         let light_distrib: Arc<Distribution1D> =
                             light_distribution.lookup(&isect.p);
@@ -189,6 +207,7 @@ impl SamplerIntegrator for VolPathIntegrator {
                                 pdf
                             );
                             specular_bounce = (sampled_type & BxdfType::BsdfSpecular as u8) != 0_u8;
+                            any_non_specular_bounces |= !specular_bounce;
                             if ((sampled_type & BxdfType::BsdfSpecular as u8) != 0_u8)
                                 && ((sampled_type & BxdfType::BsdfTransmission as u8) != 0_u8)
                             {
@@ -204,6 +223,7 @@ impl SamplerIntegrator for VolPathIntegrator {
                                 }
                             }
                             ray = isect.spawn_ray(&wi);
+                            ray.ray_type = bxdf_type_to_ray_type(sampled_type);
                             // account for attenuated subsurface
                             // scattering, if applicable
                             if let Some(ref bssrdf) = isect.bssrdf {
@@ -262,6 +282,7 @@ impl SamplerIntegrator for VolPathIntegrator {
                                                 & BxdfType::BsdfSpecular as u8)
                                                 != 0_u8;
                                             ray = pi.spawn_ray(&wi);
+                                            ray.ray_type = bxdf_type_to_ray_type(sampled_type);
                                         } else {
                                             panic!("no pi.bsdf found");
                                         }
@@ -290,9 +311,7 @@ impl SamplerIntegrator for VolPathIntegrator {
             } else {
                 // add emitted light from the environment
                 if bounces == 0 || specular_bounce {
-                    for light in &scene.infinite_lights {
-                        l += beta * light.le(&mut ray);
-                    }
+                    l += beta * escaped_radiance(scene, &mut ray);
                 }
                 // terminate path if ray escaped
                 break;
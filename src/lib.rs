@@ -35,6 +35,8 @@ extern crate num;
 #[cfg(feature = "openexr")]
 extern crate openexr;
 extern crate ply_rs;
+#[cfg(test)]
+extern crate png;
 extern crate rayon;
 extern crate time;
 extern crate typed_arena;
@@ -5,20 +5,26 @@ use std::sync::Arc;
 // pbrt
 use core::geometry::{nrm_abs_dot_vec3, nrm_dot_vec3, vec3_coordinate_system};
 use core::geometry::{Normal3f, Point2f, Ray, Vector3f};
-use core::interaction::{Interaction, InteractionCommon};
+use core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
 use core::light::{AreaLight, Light, LightFlags, VisibilityTester};
 use core::medium::{Medium, MediumInterface};
 use core::pbrt::{Float, Spectrum};
+use core::profile::{Prof, ProfilePhase};
 use core::rng::FLOAT_ONE_MINUS_EPSILON;
 use core::sampling::{cosine_hemisphere_pdf, cosine_sample_hemisphere};
 use core::scene::Scene;
 use core::shape::Shape;
+use core::texture::Texture;
 use core::transform::Transform;
 
 // see diffuse.h
 
 pub struct DiffuseAreaLight {
     pub l_emit: Spectrum,
+    /// Optional texture modulating **l_emit** across the surface
+    pub l_emit_texture: Option<Arc<Texture<Spectrum> + Send + Sync>>,
+    /// Emitted radiance averaged over the surface (used by **power()**)
+    pub l_emit_average: Spectrum,
     pub shape: Arc<Shape + Send + Sync>,
     pub two_sided: bool,
     pub area: Float,
@@ -35,6 +41,7 @@ impl DiffuseAreaLight {
         _light_to_world: &Transform,
         medium_interface: &MediumInterface,
         l_emit: &Spectrum,
+        l_emit_texture: Option<Arc<Texture<Spectrum> + Send + Sync>>,
         n_samples: i32,
         shape: Arc<Shape + Send + Sync>,
         two_sided: bool,
@@ -48,8 +55,14 @@ impl DiffuseAreaLight {
         if let Some(ref mi_outside) = medium_interface.outside {
             outside = Some(mi_outside.clone());
         }
+        let mut l_emit_average: Spectrum = *l_emit;
+        if let Some(ref texture) = l_emit_texture {
+            l_emit_average = *l_emit * average_uv(texture);
+        }
         DiffuseAreaLight {
             l_emit: *l_emit,
+            l_emit_texture: l_emit_texture,
+            l_emit_average: l_emit_average,
             shape: shape,
             two_sided: two_sided,
             area: area,
@@ -75,7 +88,7 @@ impl Light for DiffuseAreaLight {
         pdf: &mut Float,
         vis: &mut VisibilityTester,
     ) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::LightSample);
+        let _p = ProfilePhase::new(Prof::LightSample);
         let p_shape: InteractionCommon = self.shape.sample_with_ref_point(&iref, &*u, pdf);
         // TODO: iref.mediumInterface = mediumInterface;
         if *pdf == 0.0 as Float || (p_shape.p - iref.p).length_squared() == 0.0 as Float {
@@ -91,6 +104,7 @@ impl Light for DiffuseAreaLight {
             wo: iref.wo,
             n: iref.n,
             medium_interface: None,
+            uv: Point2f::default(),
         };
         vis.p1 = InteractionCommon {
             p: p_shape.p,
@@ -99,6 +113,7 @@ impl Light for DiffuseAreaLight {
             wo: p_shape.wo,
             n: p_shape.n,
             medium_interface: None,
+            uv: p_shape.uv,
         };
         self.l(&p_shape, &-new_wi)
     }
@@ -110,7 +125,7 @@ impl Light for DiffuseAreaLight {
         } else {
             factor = 1.0 as Float;
         }
-        self.l_emit * factor * self.area * PI
+        self.l_emit_average * factor * self.area * PI
     }
     fn preprocess(&self, _scene: &Scene) {
         // TODO?
@@ -119,7 +134,7 @@ impl Light for DiffuseAreaLight {
         Spectrum::default()
     }
     fn pdf_li(&self, iref: &Interaction, wi: Vector3f) -> Float {
-        // TODO: ProfilePhase _(Prof::LightPdf);
+        let _p = ProfilePhase::new(Prof::LightPdf);
         self.shape.pdf_with_ref_point(iref, &wi)
     }
     fn sample_le(
@@ -132,7 +147,7 @@ impl Light for DiffuseAreaLight {
         pdf_pos: &mut Float,
         pdf_dir: &mut Float,
     ) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::LightSample);
+        let _p = ProfilePhase::new(Prof::LightSample);
 
         // sample a point on the area light's _Shape_, _p_shape_
         let ic: InteractionCommon = self.shape.sample(u1, pdf_pos);
@@ -185,9 +200,121 @@ impl Light for DiffuseAreaLight {
 impl AreaLight for DiffuseAreaLight {
     fn l(&self, intr: &InteractionCommon, w: &Vector3f) -> Spectrum {
         if self.two_sided || nrm_dot_vec3(&intr.n, &w) > 0.0 as Float {
-            self.l_emit
+            if let Some(ref l_emit_texture) = self.l_emit_texture {
+                let mut si: SurfaceInteraction = SurfaceInteraction::default();
+                si.p = intr.p;
+                si.time = intr.time;
+                si.wo = intr.wo;
+                si.n = intr.n;
+                si.uv = intr.uv;
+                self.l_emit * l_emit_texture.evaluate(&si)
+            } else {
+                self.l_emit
+            }
         } else {
             Spectrum::new(0.0 as Float)
         }
     }
 }
+
+/// Average of a texture over the (*u*, *v*) parameter domain, using
+/// the midpoints of a regular grid. Textures which depend on the
+/// surface position (instead of *u* and *v*) are evaluated at the
+/// origin.
+fn average_uv(texture: &Arc<Texture<Spectrum> + Send + Sync>) -> Spectrum {
+    let n: usize = 16;
+    let mut sum: Spectrum = Spectrum::new(0.0 as Float);
+    let mut si: SurfaceInteraction = SurfaceInteraction::default();
+    for v in 0..n {
+        for u in 0..n {
+            si.uv = Point2f {
+                x: (u as Float + 0.5 as Float) / n as Float,
+                y: (v as Float + 0.5 as Float) / n as Float,
+            };
+            sum += texture.evaluate(&si);
+        }
+    }
+    sum / (n * n) as Float
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::texture::UVMapping2D;
+    use shapes::disk::Disk;
+    use textures::checkerboard::Checkerboard2DTexture;
+    use textures::constant::ConstantTexture;
+
+    fn checkerboard_light() -> DiffuseAreaLight {
+        let disk: Arc<Shape + Send + Sync> = Arc::new(Disk::new(
+            Transform::default(),
+            Transform::default(),
+            false,
+            0.0 as Float,
+            1.0 as Float,
+            0.0 as Float,
+            360.0 as Float,
+        ));
+        let checks: Arc<Texture<Spectrum> + Send + Sync> =
+            Arc::new(Checkerboard2DTexture::new(
+                Box::new(UVMapping2D {
+                    su: 4.0 as Float,
+                    sv: 4.0 as Float,
+                    du: 0.0 as Float,
+                    dv: 0.0 as Float,
+                }),
+                Arc::new(ConstantTexture {
+                    value: Spectrum::new(1.0 as Float),
+                }),
+                Arc::new(ConstantTexture {
+                    value: Spectrum::new(0.0 as Float),
+                }),
+            ));
+        DiffuseAreaLight::new(
+            &Transform::default(),
+            &MediumInterface::default(),
+            &Spectrum::new(2.0 as Float),
+            Some(checks),
+            1_i32,
+            disk,
+            false,
+        )
+    }
+
+    #[test]
+    fn checkerboard_emission_alternates() {
+        let light: DiffuseAreaLight = checkerboard_light();
+        let w: Vector3f = Vector3f {
+            x: 0.0 as Float,
+            y: 0.0 as Float,
+            z: 1.0 as Float,
+        };
+        let mut n_bright: usize = 0;
+        let mut n_dark: usize = 0;
+        for i in 0..8 {
+            for j in 0..8 {
+                let u: Point2f = Point2f {
+                    x: (i as Float + 0.5 as Float) / 8.0 as Float,
+                    y: (j as Float + 0.5 as Float) / 8.0 as Float,
+                };
+                let mut pdf: Float = 0.0 as Float;
+                let ic: InteractionCommon = light.shape.sample(&u, &mut pdf);
+                let check: u32 = (ic.uv.x * 4.0 as Float).floor() as u32
+                    + (ic.uv.y * 4.0 as Float).floor() as u32;
+                let l: Spectrum = light.l(&ic, &w);
+                if check % 2 == 0 {
+                    assert_eq!(l[0], 2.0 as Float);
+                    n_bright += 1;
+                } else {
+                    assert_eq!(l[0], 0.0 as Float);
+                    n_dark += 1;
+                }
+            }
+        }
+        assert!(n_bright > 0 && n_dark > 0);
+        // half of the surface emits
+        let power: Spectrum = light.power();
+        let expected: Float = 2.0 as Float * 0.5 as Float * light.area * PI;
+        assert!((power[0] - expected).abs() < 1e-4 as Float * expected);
+    }
+}
@@ -4,11 +4,12 @@ use std::f32::consts::PI;
 use std::sync::RwLock;
 // pbrt
 use core::geometry::{vec3_coordinate_system};
-use core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector3f};
+use core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector3f, VisibilityFlags};
 use core::interaction::{Interaction, InteractionCommon};
 use core::light::{Light, LightFlags, VisibilityTester};
 use core::medium::MediumInterface;
 use core::pbrt::{Float, Spectrum};
+use core::profile::{Prof, ProfilePhase};
 use core::sampling::concentric_sample_disk;
 use core::scene::Scene;
 use core::transform::Transform;
@@ -54,7 +55,7 @@ impl Light for DistantLight {
         pdf: &mut Float,
         vis: &mut VisibilityTester,
     ) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::LightSample);
+        let _p = ProfilePhase::new(Prof::LightSample);
         *wi = self.w_light;
         *pdf = 1.0 as Float;
         let p_outside: Point3f =
@@ -67,6 +68,7 @@ impl Light for DistantLight {
                 wo: iref.wo,
                 n: iref.n,
                 medium_interface: None,
+                uv: Point2f::default(),
             },
             p1: InteractionCommon {
                 p: p_outside,
@@ -75,6 +77,7 @@ impl Light for DistantLight {
                 wo: Vector3f::default(),
                 n: Normal3f::default(),
                 medium_interface: None,
+                uv: Point2f::default(),
             },
         };
         self.l
@@ -117,7 +120,7 @@ impl Light for DistantLight {
         pdf_pos: &mut Float,
         pdf_dir: &mut Float,
     ) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::LightSample);
+        let _p = ProfilePhase::new(Prof::LightSample);
 
         // choose point on disk oriented toward infinite light direction
         let mut v1: Vector3f = Vector3f::default();
@@ -133,6 +136,7 @@ impl Light for DistantLight {
             d: -self.w_light,
             t_max: std::f32::INFINITY,
             time: time,
+            ray_type: VisibilityFlags::Untyped as u8,
             differential: None,
             medium: None,
         };
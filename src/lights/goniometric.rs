@@ -0,0 +1,325 @@
+extern crate image;
+
+// std
+use std;
+use std::f32::consts::PI;
+use std::path::Path;
+use std::sync::Arc;
+// others
+use image::{DynamicImage, ImageResult};
+// pbrt
+use core::geometry::pnt3_distance_squared;
+use core::geometry::{spherical_phi, spherical_theta};
+use core::geometry::{Normal3f, Point2f, Point2i, Point3f, Ray, Vector3f, VisibilityFlags};
+use core::interaction::{Interaction, InteractionCommon};
+use core::light::{Light, LightFlags, VisibilityTester};
+use core::medium::{Medium, MediumInterface};
+use core::pbrt::{Float, Spectrum};
+use core::pbrt::{INV_2_PI, INV_PI};
+use core::profile::{Prof, ProfilePhase};
+use core::sampling::{uniform_sample_sphere, uniform_sphere_pdf};
+use core::scene::Scene;
+use core::transform::Transform;
+
+// see goniometric.h
+
+#[derive(Clone)]
+pub struct GonioPhotometricLight {
+    // private data (see goniometric.h)
+    pub p_light: Point3f,
+    pub i: Spectrum,
+    /// Goniometric diagram, indexed by (phi / 2pi, theta / pi); empty
+    /// if no (readable) image was given
+    pub texels: Vec<Spectrum>,
+    pub resolution: Point2i,
+    // inherited from class Light (see light.h)
+    pub flags: u8,
+    pub n_samples: i32,
+    pub medium_interface: MediumInterface,
+    pub light_to_world: Transform,
+    pub world_to_light: Transform,
+}
+
+impl GonioPhotometricLight {
+    pub fn new(
+        light_to_world: &Transform,
+        medium_interface: &MediumInterface,
+        i: &Spectrum,
+        texname: String,
+    ) -> Self {
+        let mut inside: Option<Arc<Medium + Send + Sync>> = None;
+        let mut outside: Option<Arc<Medium + Send + Sync>> = None;
+        if let Some(ref mi_outside) = medium_interface.outside {
+            // in C++: MediumInterface(const Medium *medium) : inside(medium), outside(medium)
+            inside = Some(mi_outside.clone());
+            outside = Some(mi_outside.clone());
+        }
+        // read texel data from _texname_
+        let mut texels: Vec<Spectrum> = Vec::new();
+        let mut resolution: Point2i = Point2i::default();
+        if texname != String::from("") {
+            let img_result: ImageResult<DynamicImage> = image::open(Path::new(&texname));
+            if let Ok(buf) = img_result {
+                let rgb = buf.to_rgb();
+                resolution.x = rgb.width() as i32;
+                resolution.y = rgb.height() as i32;
+                texels = rgb
+                    .pixels()
+                    .map(|p| {
+                        let r = Float::from(p[0]) / 255.0;
+                        let g = Float::from(p[1]) / 255.0;
+                        let b = Float::from(p[2]) / 255.0;
+                        // 8-bit images are stored gamma corrected
+                        Spectrum::rgb(r, g, b).inverse_gamma_correct()
+                    }).collect();
+            } else {
+                println!(
+                    "WARNING: Unable to read goniometric diagram \"{}\". Using constant intensity.",
+                    texname
+                );
+            }
+        }
+        GonioPhotometricLight {
+            p_light: light_to_world.transform_point(&Point3f::default()),
+            i: *i,
+            texels: texels,
+            resolution: resolution,
+            flags: LightFlags::DeltaPosition as u8,
+            n_samples: 1_i32,
+            medium_interface: MediumInterface {
+                inside: inside,
+                outside: outside,
+            },
+            light_to_world: *light_to_world,
+            world_to_light: Transform::inverse(light_to_world),
+        }
+    }
+    fn texel(&self, x: i32, y: i32) -> Spectrum {
+        self.texels[(y * self.resolution.x + x) as usize]
+    }
+    /// Bilinear lookup in the goniometric diagram. The image wraps
+    /// around in *s* (phi) and is clamped in *t* (theta), so there is
+    /// no seam at phi = 0 and no bleeding across the poles.
+    pub fn lookup(&self, st: &Point2f) -> Spectrum {
+        if self.texels.is_empty() {
+            return Spectrum::new(1.0 as Float);
+        }
+        let s: Float = st.x * self.resolution.x as Float - 0.5 as Float;
+        let t: Float = st.y * self.resolution.y as Float - 0.5 as Float;
+        let s0: Float = s.floor();
+        let t0: Float = t.floor();
+        let ds: Float = s - s0;
+        let dt: Float = t - t0;
+        let wrap = |x: i32| -> i32 {
+            let r: i32 = x % self.resolution.x;
+            if r < 0 {
+                r + self.resolution.x
+            } else {
+                r
+            }
+        };
+        let clamp = |y: i32| -> i32 { y.max(0_i32).min(self.resolution.y - 1_i32) };
+        let x0: i32 = wrap(s0 as i32);
+        let x1: i32 = wrap(s0 as i32 + 1_i32);
+        let y0: i32 = clamp(t0 as i32);
+        let y1: i32 = clamp(t0 as i32 + 1_i32);
+        self.texel(x0, y0) * ((1.0 as Float - ds) * (1.0 as Float - dt))
+            + self.texel(x1, y0) * (ds * (1.0 as Float - dt))
+            + self.texel(x0, y1) * ((1.0 as Float - ds) * dt)
+            + self.texel(x1, y1) * (ds * dt)
+    }
+    pub fn scale(&self, w: &Vector3f) -> Spectrum {
+        let mut wp: Vector3f = self.world_to_light.transform_vector(w).normalize();
+        std::mem::swap(&mut wp.y, &mut wp.z);
+        let theta: Float = spherical_theta(&wp);
+        let phi: Float = spherical_phi(&wp);
+        let st: Point2f = Point2f {
+            x: phi * INV_2_PI,
+            y: theta * INV_PI,
+        };
+        self.lookup(&st)
+    }
+}
+
+impl Light for GonioPhotometricLight {
+    fn sample_li(
+        &self,
+        iref: &InteractionCommon,
+        _u: &Point2f,
+        wi: &mut Vector3f,
+        pdf: &mut Float,
+        vis: &mut VisibilityTester,
+    ) -> Spectrum {
+        let _p = ProfilePhase::new(Prof::LightSample);
+        *wi = (self.p_light - iref.p).normalize();
+        *pdf = 1.0 as Float;
+        // medium_interface1
+        let mut inside: Option<Arc<Medium + Send + Sync>> = None;
+        let mut outside: Option<Arc<Medium + Send + Sync>> = None;
+        if let Some(ref mi_arc) = iref.medium_interface {
+            if let Some(ref mi_inside_arc) = mi_arc.get_inside() {
+                inside = Some(mi_inside_arc.clone());
+            }
+            if let Some(ref mi_outside_arc) = mi_arc.get_outside() {
+                outside = Some(mi_outside_arc.clone());
+            }
+        }
+        let medium_interface1_arc: Arc<MediumInterface> =
+            Arc::new(MediumInterface::new(inside, outside));
+        // medium_interface2
+        let mut inside: Option<Arc<Medium + Send + Sync>> = None;
+        let mut outside: Option<Arc<Medium + Send + Sync>> = None;
+        if let Some(ref mi_inside_arc) = self.medium_interface.inside {
+            inside = Some(mi_inside_arc.clone());
+        }
+        if let Some(ref mi_outside_arc) = self.medium_interface.outside {
+            outside = Some(mi_outside_arc.clone());
+        }
+        let medium_interface2_arc: Arc<MediumInterface> =
+            Arc::new(MediumInterface::new(inside, outside));
+        *vis = VisibilityTester {
+            p0: InteractionCommon {
+                p: iref.p,
+                time: iref.time,
+                p_error: iref.p_error,
+                wo: iref.wo,
+                n: iref.n,
+                medium_interface: Some(medium_interface1_arc.clone()),
+                uv: Point2f::default(),
+            },
+            p1: InteractionCommon {
+                p: self.p_light,
+                time: iref.time,
+                p_error: Vector3f::default(),
+                wo: Vector3f::default(),
+                n: Normal3f::default(),
+                medium_interface: Some(medium_interface2_arc.clone()),
+                uv: Point2f::default(),
+            },
+        };
+        self.i * self.scale(&-*wi) / pnt3_distance_squared(&self.p_light, &iref.p)
+    }
+    fn power(&self) -> Spectrum {
+        // average over the diagram (instead of the coarsest MIPMap level)
+        let mut average: Spectrum = Spectrum::new(1.0 as Float);
+        if !self.texels.is_empty() {
+            average = Spectrum::new(0.0 as Float);
+            for texel in &self.texels {
+                average += *texel;
+            }
+            average = average / self.texels.len() as Float;
+        }
+        self.i * 4.0 as Float * PI * average
+    }
+    fn preprocess(&self, _scene: &Scene) {}
+    /// Default implementation returns no emitted radiance for a ray
+    /// that escapes the scene bounds.
+    fn le(&self, _ray: &mut Ray) -> Spectrum {
+        Spectrum::new(0.0 as Float)
+    }
+    fn pdf_li(&self, _iref: &Interaction, _wi: Vector3f) -> Float {
+        0.0 as Float
+    }
+    fn sample_le(
+        &self,
+        u1: &Point2f,
+        _u2: &Point2f,
+        time: Float,
+        ray: &mut Ray,
+        n_light: &mut Normal3f,
+        pdf_pos: &mut Float,
+        pdf_dir: &mut Float,
+    ) -> Spectrum {
+        let _p = ProfilePhase::new(Prof::LightSample);
+        let mut inside: Option<Arc<Medium + Send + Sync>> = None;
+        if let Some(ref mi_inside) = self.medium_interface.inside {
+            inside = Some(mi_inside.clone());
+        }
+        *ray = Ray {
+            o: self.p_light,
+            d: self
+                .light_to_world
+                .transform_vector(&uniform_sample_sphere(u1)),
+            t_max: std::f32::INFINITY,
+            time: time,
+            ray_type: VisibilityFlags::Untyped as u8,
+            differential: None,
+            medium: inside,
+        };
+        *n_light = Normal3f::from(ray.d);
+        *pdf_pos = 1.0 as Float;
+        *pdf_dir = uniform_sphere_pdf();
+        self.i * self.scale(&ray.d)
+    }
+    fn get_flags(&self) -> u8 {
+        self.flags
+    }
+    fn get_n_samples(&self) -> i32 {
+        self.n_samples
+    }
+    fn pdf_le(&self, _ray: &Ray, _n_light: &Normal3f, pdf_pos: &mut Float, pdf_dir: &mut Float) {
+        *pdf_pos = 0.0 as Float;
+        *pdf_dir = uniform_sphere_pdf();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Goniometric diagram with a smooth radial gradient, brightest in
+    /// the center of the image.
+    fn radial_gradient_light() -> GonioPhotometricLight {
+        let mut light: GonioPhotometricLight = GonioPhotometricLight::new(
+            &Transform::default(),
+            &MediumInterface::default(),
+            &Spectrum::new(1.0 as Float),
+            String::new(),
+        );
+        let resolution: Point2i = Point2i { x: 16, y: 8 };
+        let mut texels: Vec<Spectrum> = Vec::new();
+        for y in 0..resolution.y {
+            for x in 0..resolution.x {
+                let dx: Float = (x as Float + 0.5 as Float) / resolution.x as Float - 0.5 as Float;
+                let dy: Float = (y as Float + 0.5 as Float) / resolution.y as Float - 0.5 as Float;
+                texels.push(Spectrum::new(1.0 as Float - (dx * dx + dy * dy).sqrt()));
+            }
+        }
+        light.texels = texels;
+        light.resolution = resolution;
+        light
+    }
+
+    #[test]
+    fn lookup_is_continuous_across_the_phi_seam() {
+        let light: GonioPhotometricLight = radial_gradient_light();
+        for i in 0..21 {
+            let t: Float = i as Float / 20.0 as Float;
+            let at_zero: Spectrum = light.lookup(&Point2f { x: 0.0, y: t });
+            let at_one: Spectrum = light.lookup(&Point2f { x: 1.0, y: t });
+            assert!((at_zero[0] - at_one[0]).abs() < 1e-6 as Float, "t = {}", t);
+            // approaching the seam from both sides
+            let before: Spectrum = light.lookup(&Point2f { x: 0.999, y: t });
+            let after: Spectrum = light.lookup(&Point2f { x: 0.001, y: t });
+            assert!((before[0] - at_zero[0]).abs() < 1e-3 as Float, "t = {}", t);
+            assert!((after[0] - at_zero[0]).abs() < 1e-3 as Float, "t = {}", t);
+            assert!(at_zero[0].is_finite() && at_zero[0] > 0.0 as Float);
+        }
+        // the gradient is still there: darker at the seam than in the center
+        let center: Spectrum = light.lookup(&Point2f { x: 0.5, y: 0.5 });
+        let seam: Spectrum = light.lookup(&Point2f { x: 0.0, y: 0.5 });
+        assert!(center[0] > seam[0]);
+        // directions on both sides of phi = 0 see (nearly) the same intensity
+        let w0: Vector3f = Vector3f {
+            x: 1.0 as Float,
+            y: 0.2 as Float,
+            z: -1e-3 as Float,
+        };
+        let w1: Vector3f = Vector3f {
+            x: 1.0 as Float,
+            y: 0.2 as Float,
+            z: 1e-3 as Float,
+        };
+        assert!((light.scale(&w0)[0] - light.scale(&w1)[0]).abs() < 1e-2 as Float);
+    }
+}
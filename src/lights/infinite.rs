@@ -12,14 +12,18 @@ use half::f16;
 use openexr::{FrameBufferMut, InputFile, PixelType};
 // pbrt
 use core::geometry::{spherical_phi, spherical_theta, vec3_coordinate_system};
-use core::geometry::{Bounds3f, Normal3f, Point2f, Point2i, Point3f, Ray, Vector3f};
+use core::geometry::{
+    Bounds3f, Normal3f, Point2f, Point2i, Point3f, Ray, Vector3f, VisibilityFlags,
+};
 use core::interaction::{Interaction, InteractionCommon};
 use core::light::{Light, LightFlags, VisibilityTester};
 use core::medium::MediumInterface;
 use core::mipmap::{ImageWrap, MipMap};
 use core::pbrt::{Float, Spectrum};
 use core::pbrt::{INV_2_PI, INV_PI};
+use core::profile::{Prof, ProfilePhase};
 use core::sampling::concentric_sample_disk;
+use core::sampling::{uniform_sample_sphere, uniform_sphere_pdf};
 use core::sampling::Distribution2D;
 use core::scene::Scene;
 use core::transform::Transform;
@@ -54,6 +58,8 @@ pub struct InfiniteAreaLight {
     pub world_center: RwLock<Point3f>,
     pub world_radius: RwLock<Float>,
     pub distribution: Arc<Distribution2D>,
+    /// No environment map, just a constant **L** (uniform sampling)
+    pub constant: bool,
     // inherited from class Light (see light.h)
     pub flags: u8,
     pub n_samples: i32,
@@ -156,6 +162,7 @@ impl InfiniteAreaLight {
                         world_center: RwLock::new(Point3f::default()),
                         world_radius: RwLock::new(0.0),
                         distribution: distribution,
+                        constant: false,
                         flags: LightFlags::Infinite as u8,
                         n_samples: std::cmp::max(1_i32, n_samples),
                         medium_interface: MediumInterface::default(),
@@ -171,7 +178,7 @@ impl InfiniteAreaLight {
                 InfiniteAreaLight::new_hdr(light_to_world, l, n_samples, texmap)
             }
         } else {
-            InfiniteAreaLight::default(n_samples, l)
+            InfiniteAreaLight::default(light_to_world, n_samples, l)
         }
     }
     pub fn new_hdr(
@@ -235,6 +242,7 @@ impl InfiniteAreaLight {
                             world_center: RwLock::new(Point3f::default()),
                             world_radius: RwLock::new(0.0),
                             distribution: distribution,
+                            constant: false,
                             flags: LightFlags::Infinite as u8,
                             n_samples: std::cmp::max(1_i32, n_samples),
                             medium_interface: MediumInterface::default(),
@@ -247,9 +255,11 @@ impl InfiniteAreaLight {
                 println!("WARNING: InfiniteAreaLight::new() ... no OpenEXR support !!!");
             }
         }
-        InfiniteAreaLight::default(n_samples, l)
+        InfiniteAreaLight::default(light_to_world, n_samples, l)
     }
-    fn default(n_samples: i32, l: &Spectrum) -> Self {
+    /// Without an environment map the light falls back to a 1x1
+    /// image of the constant radiance **L**.
+    fn default(light_to_world: &Transform, n_samples: i32, l: &Spectrum) -> Self {
         let resolution: Point2i = Point2i { x: 1_i32, y: 1_i32 };
         let texels: Vec<Spectrum> = vec![*l];
         let do_trilinear: bool = false;
@@ -286,11 +296,12 @@ impl InfiniteAreaLight {
             world_center: RwLock::new(Point3f::default()),
             world_radius: RwLock::new(0.0),
             distribution: distribution,
+            constant: true,
             flags: LightFlags::Infinite as u8,
             n_samples: std::cmp::max(1_i32, n_samples),
             medium_interface: MediumInterface::default(),
-            light_to_world: Transform::default(),
-            world_to_light: Transform::default(),
+            light_to_world: *light_to_world,
+            world_to_light: Transform::inverse(&*light_to_world),
         }
     }
 }
@@ -304,30 +315,38 @@ impl Light for InfiniteAreaLight {
         pdf: &mut Float,
         vis: &mut VisibilityTester,
     ) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::LightSample);
-        // find $(u,v)$ sample coordinates in infinite light texture
-        let mut map_pdf: Float = 0.0 as Float;
-        let uv: Point2f = self.distribution.sample_continuous(&u, &mut map_pdf);
-        if map_pdf == 0 as Float {
-            return Spectrum::default();
-        }
-        // convert infinite light sample point to direction
-        let theta: Float = uv[1] * PI;
-        let phi: Float = uv[0] * 2.0 as Float * PI;
-        let cos_theta: Float = theta.cos();
-        let sin_theta: Float = theta.sin();
-        let sin_phi: Float = phi.sin();
-        let cos_phi: Float = phi.cos();
-        let vec: Vector3f = Vector3f {
-            x: sin_theta * cos_phi,
-            y: sin_theta * sin_phi,
-            z: cos_theta,
-        };
-        *wi = self.light_to_world.transform_vector(&vec);
-        // compute PDF for sampled infinite light direction
-        *pdf = map_pdf / (2.0 as Float * PI * PI * sin_theta);
-        if sin_theta == 0.0 as Float {
-            *pdf = 0.0 as Float;
+        let _p = ProfilePhase::new(Prof::LightSample);
+        let uv: Point2f;
+        if self.constant {
+            // no need for the _Distribution2D_, sample uniformly
+            *wi = uniform_sample_sphere(u);
+            *pdf = uniform_sphere_pdf();
+            uv = Point2f { x: 0.5, y: 0.5 };
+        } else {
+            // find $(u,v)$ sample coordinates in infinite light texture
+            let mut map_pdf: Float = 0.0 as Float;
+            uv = self.distribution.sample_continuous(&u, &mut map_pdf);
+            if map_pdf == 0 as Float {
+                return Spectrum::default();
+            }
+            // convert infinite light sample point to direction
+            let theta: Float = uv[1] * PI;
+            let phi: Float = uv[0] * 2.0 as Float * PI;
+            let cos_theta: Float = theta.cos();
+            let sin_theta: Float = theta.sin();
+            let sin_phi: Float = phi.sin();
+            let cos_phi: Float = phi.cos();
+            let vec: Vector3f = Vector3f {
+                x: sin_theta * cos_phi,
+                y: sin_theta * sin_phi,
+                z: cos_theta,
+            };
+            *wi = self.light_to_world.transform_vector(&vec);
+            // compute PDF for sampled infinite light direction
+            *pdf = map_pdf / (2.0 as Float * PI * PI * sin_theta);
+            if sin_theta == 0.0 as Float {
+                *pdf = 0.0 as Float;
+            }
         }
         // return radiance value for infinite light direction
         let world_radius: Float = *self.world_radius.read().unwrap();
@@ -343,6 +362,7 @@ impl Light for InfiniteAreaLight {
                 wo: iref.wo,
                 n: iref.n,
                 medium_interface: medium_interface,
+                uv: Point2f::default(),
             },
             p1: InteractionCommon {
                 p: iref.p + *wi * (2.0 as Float * world_radius),
@@ -351,6 +371,7 @@ impl Light for InfiniteAreaLight {
                 wo: Vector3f::default(),
                 n: Normal3f::default(),
                 medium_interface: Some(Arc::new(MediumInterface::default())),
+                uv: Point2f::default(),
             },
         };
         // TODO: SpectrumType::Illuminant
@@ -394,7 +415,10 @@ impl Light for InfiniteAreaLight {
         self.lmap.lookup_pnt_flt(&st, 0.0 as Float)
     }
     fn pdf_li(&self, _iref: &Interaction, w: Vector3f) -> Float {
-        // TODO: ProfilePhase _(Prof::LightPdf);
+        let _p = ProfilePhase::new(Prof::LightPdf);
+        if self.constant {
+            return uniform_sphere_pdf();
+        }
         let wi: Vector3f = self.world_to_light.transform_vector(&w);
         let theta: Float = spherical_theta(&wi);
         let phi: Float = spherical_phi(&wi);
@@ -418,7 +442,7 @@ impl Light for InfiniteAreaLight {
         pdf_pos: &mut Float,
         pdf_dir: &mut Float,
     ) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::LightSample);
+        let _p = ProfilePhase::new(Prof::LightSample);
 
         // find $(u,v)$ sample coordinates in infinite light texture
         let mut map_pdf: Float = 0.0 as Float;
@@ -451,6 +475,7 @@ impl Light for InfiniteAreaLight {
             d: d,
             t_max: std::f32::INFINITY,
             time: time,
+            ray_type: VisibilityFlags::Untyped as u8,
             differential: None,
             medium: None,
         };
@@ -484,3 +509,54 @@ impl Light for InfiniteAreaLight {
         self.n_samples
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::interaction::SurfaceInteraction;
+
+    #[test]
+    fn constant_light_is_sampled_uniformly() {
+        let l: Spectrum = Spectrum::rgb(0.2, 0.4, 0.8);
+        let light: InfiniteAreaLight = InfiniteAreaLight::new(
+            &Transform::rotate_x(30.0 as Float),
+            &l,
+            1_i32,
+            String::new(),
+        );
+        assert!(light.constant);
+        let iref: SurfaceInteraction = SurfaceInteraction::default();
+        let common: InteractionCommon = InteractionCommon::default();
+        let n: usize = 8;
+        for i in 0..n {
+            for j in 0..n {
+                let u: Point2f = Point2f {
+                    x: (i as Float + 0.5 as Float) / n as Float,
+                    y: (j as Float + 0.5 as Float) / n as Float,
+                };
+                // every escaping ray sees L
+                let mut ray: Ray = Ray {
+                    d: uniform_sample_sphere(&u),
+                    ..Ray::default()
+                };
+                let le: Spectrum = light.le(&mut ray);
+                for c in 0..3 {
+                    assert!(
+                        (le[c] - l[c]).abs() < 1e-5 as Float,
+                        "{:?} for {:?}",
+                        le,
+                        ray.d
+                    );
+                }
+                // sampled with the uniform sphere pdf, which pdf_li() reports
+                let mut wi: Vector3f = Vector3f::default();
+                let mut pdf: Float = 0.0 as Float;
+                let mut vis: VisibilityTester = VisibilityTester::default();
+                let li: Spectrum = light.sample_li(&common, &u, &mut wi, &mut pdf, &mut vis);
+                assert!((li[2] - l[2]).abs() < 1e-5 as Float);
+                assert_eq!(pdf, 1.0 as Float / (4.0 as Float * PI));
+                assert_eq!(light.pdf_li(&iref, wi), pdf);
+            }
+        }
+    }
+}
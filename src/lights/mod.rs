@@ -30,7 +30,10 @@
 //!
 //! ## Goniophotometric Diagram Lights
 //!
-//! TODO
+//! A goniophotometric diagram describes the angular distribution of
+//! luminance from a point light source. **GonioPhotometricLight**
+//! scales the intensity **I** by an image, indexed by the spherical
+//! angles (phi, theta) of the emitted direction.
 //!
 //! ## Infinite Area Lights
 //!
@@ -58,6 +61,7 @@
 
 pub mod diffuse;
 pub mod distant;
+pub mod goniometric;
 pub mod infinite;
 pub mod point;
 pub mod spot;
@@ -4,11 +4,12 @@ use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
 use core::geometry::{pnt3_distance_squared};
-use core::geometry::{Normal3f, Point2f, Point3f, Ray, Vector3f};
+use core::geometry::{Normal3f, Point2f, Point3f, Ray, Vector3f, VisibilityFlags};
 use core::interaction::{Interaction, InteractionCommon};
 use core::light::{Light, LightFlags, VisibilityTester};
 use core::medium::{Medium, MediumInterface};
 use core::pbrt::{Float, Spectrum};
+use core::profile::{Prof, ProfilePhase};
 use core::sampling::{uniform_sample_sphere, uniform_sphere_pdf};
 use core::scene::Scene;
 use core::transform::Transform;
@@ -62,7 +63,7 @@ impl Light for PointLight {
         pdf: &mut Float,
         vis: &mut VisibilityTester,
     ) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::LightSample);
+        let _p = ProfilePhase::new(Prof::LightSample);
         *wi = (self.p_light - iref.p).normalize();
         *pdf = 1.0 as Float;
         *vis = VisibilityTester {
@@ -73,6 +74,7 @@ impl Light for PointLight {
                 wo: iref.wo,
                 n: iref.n,
                 medium_interface: None,
+                uv: Point2f::default(),
             },
             p1: InteractionCommon {
                 p: self.p_light,
@@ -81,6 +83,7 @@ impl Light for PointLight {
                 wo: Vector3f::default(),
                 n: Normal3f::default(),
                 medium_interface: None,
+                uv: Point2f::default(),
             },
         };
         self.i / pnt3_distance_squared(&self.p_light, &iref.p)
@@ -107,12 +110,13 @@ impl Light for PointLight {
         pdf_pos: &mut Float,
         pdf_dir: &mut Float,
     ) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::LightSample);
+        let _p = ProfilePhase::new(Prof::LightSample);
         *ray = Ray {
             o: self.p_light,
             d: uniform_sample_sphere(u1),
             t_max: std::f32::INFINITY,
             time: time,
+            ray_type: VisibilityFlags::Untyped as u8,
             differential: None,
             medium: None,
         };
@@ -132,3 +136,60 @@ impl Light for PointLight {
         *pdf_dir = uniform_sphere_pdf();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::geometry::vec3_dot_nrm;
+
+    #[test]
+    fn sample_le_emits_uniformly_from_the_light_position() {
+        let light_to_world: Transform = Transform::translate(&Vector3f {
+            x: 1.0,
+            y: -2.0,
+            z: 3.0,
+        });
+        let light: PointLight = PointLight::new(
+            &light_to_world,
+            &MediumInterface::default(),
+            &Spectrum::new(2.0 as Float),
+        );
+        let n: usize = 16;
+        let mut mean: Vector3f = Vector3f::default();
+        for i in 0..n {
+            for j in 0..n {
+                let u1: Point2f = Point2f {
+                    x: (i as Float + 0.5 as Float) / n as Float,
+                    y: (j as Float + 0.5 as Float) / n as Float,
+                };
+                let mut ray: Ray = Ray::default();
+                let mut n_light: Normal3f = Normal3f::default();
+                let mut pdf_pos: Float = 0.0 as Float;
+                let mut pdf_dir: Float = 0.0 as Float;
+                let le: Spectrum = light.sample_le(
+                    &u1,
+                    &Point2f::default(),
+                    0.5 as Float,
+                    &mut ray,
+                    &mut n_light,
+                    &mut pdf_pos,
+                    &mut pdf_dir,
+                );
+                assert_eq!(le[0], 2.0 as Float);
+                assert_eq!((ray.o.x, ray.o.y, ray.o.z), (1.0, -2.0, 3.0));
+                assert!((ray.d.length() - 1.0 as Float).abs() < 1e-5 as Float);
+                assert!((vec3_dot_nrm(&ray.d, &n_light) - 1.0 as Float).abs() < 1e-5 as Float);
+                // delta position, uniform sphere direction
+                assert_eq!(pdf_pos, 1.0 as Float);
+                assert_eq!(pdf_dir, 1.0 as Float / (4.0 as Float * PI));
+                mean += ray.d / (n * n) as Float;
+                // the delta position can't be hit by chance
+                light.pdf_le(&ray, &n_light, &mut pdf_pos, &mut pdf_dir);
+                assert_eq!(pdf_pos, 0.0 as Float);
+                assert_eq!(pdf_dir, uniform_sphere_pdf());
+            }
+        }
+        // no preferred direction
+        assert!(mean.length() < 1e-2 as Float, "{:?}", mean);
+    }
+}
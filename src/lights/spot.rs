@@ -4,12 +4,13 @@ use std::f32::consts::PI;
 use std::sync::Arc;
 // pbrt
 use core::geometry::{pnt3_distance_squared};
-use core::geometry::{Normal3f, Point2f, Point3f, Ray, Vector3f};
+use core::geometry::{Normal3f, Point2f, Point3f, Ray, Vector3f, VisibilityFlags};
 use core::interaction::{Interaction, InteractionCommon};
 use core::light::{Light, LightFlags, VisibilityTester};
 use core::medium::{Medium, MediumInterface};
 use core::pbrt::radians;
 use core::pbrt::{Float, Spectrum};
+use core::profile::{Prof, ProfilePhase};
 use core::reflection::cos_theta;
 use core::sampling::{uniform_cone_pdf, uniform_sample_cone};
 use core::scene::Scene;
@@ -87,7 +88,7 @@ impl Light for SpotLight {
         pdf: &mut Float,
         vis: &mut VisibilityTester,
     ) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::LightSample);
+        let _p = ProfilePhase::new(Prof::LightSample);
         *wi = (self.p_light - iref.p).normalize();
         *pdf = 1.0 as Float;
         // medium_interface1
@@ -122,6 +123,7 @@ impl Light for SpotLight {
                 wo: iref.wo,
                 n: iref.n,
                 medium_interface: Some(medium_interface1_arc.clone()),
+                uv: Point2f::default(),
             },
             p1: InteractionCommon {
                 p: self.p_light,
@@ -130,6 +132,7 @@ impl Light for SpotLight {
                 wo: Vector3f::default(),
                 n: Normal3f::default(),
                 medium_interface: Some(medium_interface2_arc.clone()),
+                uv: Point2f::default(),
             },
         };
         self.i * self.falloff(&-*wi) / pnt3_distance_squared(&self.p_light, &iref.p)
@@ -159,7 +162,7 @@ impl Light for SpotLight {
         pdf_pos: &mut Float,
         pdf_dir: &mut Float,
     ) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::LightSample);
+        let _p = ProfilePhase::new(Prof::LightSample);
         let w: Vector3f = uniform_sample_cone(u1, self.cos_total_width);
         let mut inside: Option<Arc<Medium + Send + Sync>> = None;
         if let Some(ref mi_inside) = self.medium_interface.inside {
@@ -170,6 +173,7 @@ impl Light for SpotLight {
             d: self.light_to_world.transform_vector(&w),
             t_max: std::f32::INFINITY,
             time: time,
+            ray_type: VisibilityFlags::Untyped as u8,
             differential: None,
             medium: inside,
         };
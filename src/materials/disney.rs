@@ -53,7 +53,7 @@ impl DisneyMaterial {
         let thin = mp.find_bool("thin", false);
         let flatness = mp.get_float_texture("flatness", 0.0);
         let diff_trans = mp.get_float_texture("difftrans", 1.0);
-        let bump_map = mp.get_float_texture_or_null("bumpmap");
+        let bump_map = mp.get_bump_map("disney");
 
         Arc::new(DisneyMaterial {
             color,
@@ -26,28 +26,31 @@ impl FourierMaterial {
             bsdf_table: bsdf_table,
         }
     }
+    /// Tables are cached in *bsdf_state* by file name, so materials
+    /// sharing a "bsdffile" share one **FourierBSDFTable**. Returns
+    /// None if the table can't be read.
     pub fn create(
         mp: &mut TextureParams,
         bsdf_state: &mut BsdfState,
-    ) -> Arc<Material + Send + Sync> {
-        let bump_map: Option<Arc<Texture<Float> + Send + Sync>> =
-            mp.get_float_texture_or_null("bumpmap");
+    ) -> Option<Arc<Material + Send + Sync>> {
+        let bump_map: Option<Arc<Texture<Float> + Send + Sync>> = mp.get_bump_map("fourier");
         let bsdffile: String = mp.find_filename("bsdffile", String::new());
-        if let Some(bsdf_table) = bsdf_state.loaded_bsdfs.get(&bsdffile.clone()) {
+        let cached: Option<Arc<FourierBSDFTable>> = bsdf_state.loaded_bsdfs.get(&bsdffile).cloned();
+        if let Some(bsdf_table) = cached {
             // use the BSDF table found
-            Arc::new(FourierMaterial::new(bsdf_table.clone(), bump_map))
-        } else {
-            // read BSDF table from file
-            let mut bsdf_table: FourierBSDFTable = FourierBSDFTable::default();
-            println!(
-                "reading {:?} returns {}",
-                bsdffile,
-                bsdf_table.read(&bsdffile)
-            );
-            let bsdf_table_arc: Arc<FourierBSDFTable> = Arc::new(bsdf_table);
-            // TODO: bsdf_state.loaded_bsdfs.insert(bsdffile.clone(), bsdf_table_arc.clone());
-            Arc::new(FourierMaterial::new(bsdf_table_arc.clone(), bump_map))
+            return Some(Arc::new(FourierMaterial::new(bsdf_table, bump_map)));
         }
+        // read BSDF table from file
+        let mut bsdf_table: FourierBSDFTable = FourierBSDFTable::default();
+        if !bsdf_table.read(&bsdffile) {
+            println!("ERROR: Unable to read tabulated BSDF file {:?}", bsdffile);
+            return None;
+        }
+        let bsdf_table_arc: Arc<FourierBSDFTable> = Arc::new(bsdf_table);
+        bsdf_state
+            .loaded_bsdfs
+            .insert(bsdffile, bsdf_table_arc.clone());
+        Some(Arc::new(FourierMaterial::new(bsdf_table_arc, bump_map)))
     }
 }
 
@@ -68,3 +71,52 @@ impl Material for FourierMaterial {
         si.bsdf = Some(Arc::new(Bsdf::new(si, 1.0, bxdfs)));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::paramset::ParamSet;
+    use core::reflection::tests::write_bsdf_file;
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+
+    fn params(bsdffile: &str) -> TextureParams {
+        let mut material_params: ParamSet = ParamSet::default();
+        material_params.add_string(String::from("bsdffile"), String::from(bsdffile));
+        TextureParams::new(
+            ParamSet::default(),
+            material_params,
+            Arc::new(HashMap::new()),
+            Arc::new(HashMap::new()),
+        )
+    }
+
+    #[test]
+    fn materials_sharing_a_file_share_the_table() {
+        let path = env::temp_dir().join("pbrt_shared.bsdf");
+        let filename: String = String::from(path.to_str().unwrap());
+        write_bsdf_file(&filename, 1, 0.0 as Float);
+        let mut bsdf_state: BsdfState = BsdfState::default();
+        let first = FourierMaterial::create(&mut params(&filename), &mut bsdf_state);
+        assert!(first.is_some());
+        // the second material doesn't read the file again
+        fs::remove_file(&path).unwrap();
+        let second = FourierMaterial::create(&mut params(&filename), &mut bsdf_state);
+        assert!(second.is_some());
+        assert_eq!(bsdf_state.loaded_bsdfs.len(), 1_usize);
+        let table: &Arc<FourierBSDFTable> = &bsdf_state.loaded_bsdfs[&filename];
+        // the cache and both materials
+        assert_eq!(Arc::strong_count(table), 3_usize);
+        assert_eq!(table.n_mu, 4_i32);
+    }
+
+    #[test]
+    fn unreadable_files_are_reported() {
+        let path = env::temp_dir().join("pbrt_missing.bsdf");
+        let filename: String = String::from(path.to_str().unwrap());
+        let mut bsdf_state: BsdfState = BsdfState::default();
+        assert!(FourierMaterial::create(&mut params(&filename), &mut bsdf_state).is_none());
+        assert!(bsdf_state.loaded_bsdfs.is_empty());
+    }
+}
@@ -52,7 +52,7 @@ impl GlassMaterial {
         let kt = mp.get_spectrum_texture("Kt", Spectrum::new(1.0 as Float));
         let roughu = mp.get_float_texture("uroughness", 0.0 as Float);
         let roughv = mp.get_float_texture("vroughness", 0.0 as Float);
-        let bump_map = mp.get_float_texture_or_null("bumpmap");
+        let bump_map = mp.get_bump_map("glass");
         let remap_roughness: bool = mp.find_bool("remaproughness", true);
         let eta_option: Option<Arc<Texture<Float> + Send + Sync>> =
             mp.get_float_texture_or_null("eta");
@@ -145,3 +145,21 @@ impl Material for GlassMaterial {
         si.bsdf = Some(Arc::new(Bsdf::new(si, eta, bxdfs)));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::reflection::test_util::check_dielectric_energy;
+
+    #[test]
+    fn rough_glass_conserves_energy() {
+        // roughness values as given in scene files (remapped by default)
+        for roughness in [0.001 as Float, 0.01 as Float, 0.1 as Float, 0.5 as Float].iter() {
+            let alpha: Float = TrowbridgeReitzDistribution::roughness_to_alpha(*roughness);
+            for eta in [1.33 as Float, 1.5 as Float, 2.0 as Float].iter() {
+                let energy: Float = check_dielectric_energy(*eta, alpha);
+                assert!(energy > 0.5 as Float, "eta {}: {}", eta, energy);
+            }
+        }
+    }
+}
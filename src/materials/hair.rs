@@ -11,6 +11,7 @@ use core::pbrt::{clamp_t, radians};
 use core::pbrt::{Float, Spectrum};
 use core::reflection::{abs_cos_theta, fr_dielectric};
 use core::reflection::{Bsdf, Bxdf, BxdfType};
+use core::spectrum::SpectrumType;
 use core::texture::Texture;
 use textures::constant::ConstantTexture;
 
@@ -292,7 +293,7 @@ impl HairBSDF {
         for i in 0..3 {
             sigma_a[i] = ce * eumelanin_sigma_a[i] + cp * pheomelanin_sigma_a[i];
         }
-        Spectrum::from_rgb(&sigma_a)
+        Spectrum::from_rgb(&sigma_a, SpectrumType::Illuminant)
     }
     pub fn sigma_a_from_reflectance(c: Spectrum, beta_n: Float) -> Spectrum {
         let mut sigma_a: Spectrum = Spectrum::default();
@@ -35,12 +35,8 @@ impl MatteMaterial {
         let kd: Arc<Texture<Spectrum> + Sync + Send> =
             mp.get_spectrum_texture("Kd", Spectrum::new(0.5));
         let sigma: Arc<Texture<Float> + Sync + Send> = mp.get_float_texture("sigma", 0.0);
-        let bump_map = mp.get_float_texture_or_null("bumpmap");
-        Arc::new(MatteMaterial::new(
-            kd,
-            sigma,
-            bump_map,
-        ))
+        let bump_map = mp.get_bump_map("matte");
+        Arc::new(MatteMaterial::new(kd, sigma, bump_map))
     }
 }
 
@@ -76,3 +72,71 @@ impl Material for MatteMaterial {
         si.bsdf = Some(Arc::new(Bsdf::new(si, 1.0, bxdfs)));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::geometry::{spherical_direction, Normal3f, Vector3f};
+    use core::reflection::BxdfType;
+    use std::f32::consts::PI;
+    use textures::constant::ConstantTexture;
+
+    /// Reflection of a matte plane (normal +z) with roughness *sigma*
+    /// (in degrees) for the given pairs of directions.
+    fn matte_f(sigma: Float, directions: &[(Vector3f, Vector3f)]) -> Vec<Float> {
+        let material: MatteMaterial = MatteMaterial::new(
+            Arc::new(ConstantTexture::new(Spectrum::new(0.5 as Float))),
+            Arc::new(ConstantTexture::new(sigma)),
+            None,
+        );
+        let mut si: SurfaceInteraction = SurfaceInteraction::default();
+        si.n = Normal3f {
+            x: 0.0 as Float,
+            y: 0.0 as Float,
+            z: 1.0 as Float,
+        };
+        si.dpdu = Vector3f {
+            x: 1.0 as Float,
+            y: 0.0 as Float,
+            z: 0.0 as Float,
+        };
+        si.dpdv = Vector3f {
+            x: 0.0 as Float,
+            y: 1.0 as Float,
+            z: 0.0 as Float,
+        };
+        si.shading.n = si.n;
+        si.shading.dpdu = si.dpdu;
+        si.shading.dpdv = si.dpdv;
+        material.compute_scattering_functions(&mut si, TransportMode::Radiance, false, None);
+        let bsdf: Arc<Bsdf> = si.bsdf.unwrap();
+        assert_eq!(bsdf.bxdfs.len(), 1_usize);
+        directions
+            .iter()
+            .map(|&(wo, wi)| bsdf.f(&wo, &wi, BxdfType::BsdfAll as u8)[0])
+            .collect()
+    }
+
+    #[test]
+    fn rough_matte_surfaces_retroreflect() {
+        let theta: Float = 80.0 as Float * PI / 180.0 as Float;
+        let grazing: Vector3f = spherical_direction(theta.sin(), theta.cos(), 0.0 as Float);
+        let opposite: Vector3f = spherical_direction(theta.sin(), theta.cos(), PI);
+        let normal: Vector3f = spherical_direction(0.0 as Float, 1.0 as Float, 0.0 as Float);
+        let directions: [(Vector3f, Vector3f); 3] = [
+            // back towards the light, forward scattering, head on
+            (grazing, grazing),
+            (grazing, opposite),
+            (normal, normal),
+        ];
+        // sigma = 0 is Lambertian
+        let lambertian: Float = 0.5 as Float / PI;
+        for f in matte_f(0.0 as Float, &directions) {
+            assert!((f - lambertian).abs() < 1e-6 as Float, "{}", f);
+        }
+        let rough: Vec<Float> = matte_f(30.0 as Float, &directions);
+        assert!(rough[0] > lambertian, "{:?}", rough);
+        assert!(rough[1] < lambertian, "{:?}", rough);
+        assert!(rough[0] > rough[2], "{:?}", rough);
+    }
+}
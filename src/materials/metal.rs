@@ -127,7 +127,7 @@ impl MetalMaterial {
             mp.get_float_texture_or_null("uroughness");
         let v_roughness: Option<Arc<Texture<Float> + Send + Sync>> =
             mp.get_float_texture_or_null("vroughness");
-        let bump_map = mp.get_float_texture_or_null("bumpmap");
+        let bump_map = mp.get_bump_map("metal");
         let remap_roughness: bool = mp.find_bool("remaproughness", true);
         Arc::new(MetalMaterial::new(
             eta,
@@ -29,7 +29,7 @@ impl MirrorMaterial {
     }
     pub fn create(mp: &mut TextureParams) -> Arc<Material + Send + Sync> {
         let kr = mp.get_spectrum_texture("Kr", Spectrum::new(0.9 as Float));
-        let bump_map = mp.get_float_texture_or_null("bumpmap");
+        let bump_map = mp.get_bump_map("mirror");
         Arc::new(MirrorMaterial::new(
             kr,
             bump_map,
@@ -42,7 +42,7 @@ impl PlasticMaterial {
         let kd = mp.get_spectrum_texture("Kd", Spectrum::new(0.25 as Float));
         let ks = mp.get_spectrum_texture("Ks", Spectrum::new(0.25 as Float));
         let roughness = mp.get_float_texture("roughness", 0.1 as Float);
-        let bump_map = mp.get_float_texture_or_null("bumpmap");
+        let bump_map = mp.get_bump_map("plastic");
         let remap_roughness: bool = mp.find_bool("remaproughness", true);
         Arc::new(PlasticMaterial::new(
             kd,
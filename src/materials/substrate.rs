@@ -46,7 +46,7 @@ impl SubstrateMaterial {
             mp.get_spectrum_texture("Ks", Spectrum::new(0.5));
         let uroughness: Arc<Texture<Float> + Sync + Send> = mp.get_float_texture("uroughness", 0.1);
         let vroughness: Arc<Texture<Float> + Sync + Send> = mp.get_float_texture("vroughness", 0.1);
-        let bump_map = mp.get_float_texture_or_null("bumpmap");
+        let bump_map = mp.get_bump_map("substrate");
         let remap_roughness: bool = mp.find_bool("remaproughness", true);
         Arc::new(SubstrateMaterial::new(
             kd,
@@ -15,6 +15,7 @@ use core::reflection::{
     Bsdf, Bxdf, FresnelDielectric, FresnelSpecular, MicrofacetReflection, MicrofacetTransmission,
     SpecularReflection, SpecularTransmission,
 };
+use core::spectrum::SpectrumType;
 use core::texture::Texture;
 
 // see subsurface.h
@@ -66,8 +67,8 @@ impl SubsurfaceMaterial {
     pub fn create(mp: &mut TextureParams) -> Arc<Material + Send + Sync> {
         let sig_a_rgb: [Float; 3] = [0.0011, 0.0024, 0.014];
         let sig_s_rgb: [Float; 3] = [2.55, 3.21, 3.77];
-        let mut sig_a: Spectrum = Spectrum::from_rgb(&sig_a_rgb);
-        let mut sig_s: Spectrum = Spectrum::from_rgb(&sig_s_rgb);
+        let mut sig_a: Spectrum = Spectrum::from_rgb(&sig_a_rgb, SpectrumType::Illuminant);
+        let mut sig_s: Spectrum = Spectrum::from_rgb(&sig_s_rgb, SpectrumType::Illuminant);
         let name: String = mp.find_string("name", String::from(""));
         let found: bool = get_medium_scattering_properties(&name, &mut sig_a, &mut sig_s);
         let mut g: Float = mp.find_float("g", 0.0 as Float);
@@ -97,7 +98,7 @@ impl SubsurfaceMaterial {
             mp.get_float_texture("uroughness", 0.0 as Float);
         let roughv: Arc<Texture<Float> + Sync + Send> =
             mp.get_float_texture("vroughness", 0.0 as Float);
-        let bump_map = mp.get_float_texture_or_null("bumpmap");
+        let bump_map = mp.get_bump_map("subsurface");
         let remap_roughness: bool = mp.find_bool("remaproughness", true);
         Arc::new(SubsurfaceMaterial::new(
             scale,
@@ -48,7 +48,7 @@ impl TranslucentMaterial {
         let reflect = mp.get_spectrum_texture("reflect", Spectrum::new(0.5 as Float));
         let transmit = mp.get_spectrum_texture("transmit", Spectrum::new(0.5 as Float));
         let roughness = mp.get_float_texture("roughness", 0.1 as Float);
-        let bump_map = mp.get_float_texture_or_null("bumpmap");
+        let bump_map = mp.get_bump_map("translucent");
         let remap_roughness: bool = mp.find_bool("remaproughness", true);
         Arc::new(TranslucentMaterial::new(
             kd,
@@ -73,9 +73,8 @@ impl UberMaterial {
         let v_roughness: Option<Arc<Texture<Float> + Send + Sync>> =
             mp.get_float_texture_or_null("vroughness");
         let opacity: Arc<Texture<Spectrum> + Send + Sync> =
-            mp.get_spectrum_texture("opacity", Spectrum::new(1.0));
-        let bump_map: Option<Arc<Texture<Float> + Send + Sync>> =
-            mp.get_float_texture_or_null("bumpmap");
+            mp.get_spectrum_texture("opacity", Spectrum::white());
+        let bump_map: Option<Arc<Texture<Float> + Send + Sync>> = mp.get_bump_map("uber");
         let remap_roughness: bool = mp.find_bool("remaproughness", true);
         let eta_option: Option<Arc<Texture<Float> + Send + Sync>> =
             mp.get_float_texture_or_null("eta");
@@ -126,12 +125,10 @@ impl Material for UberMaterial {
         }
         let mut bxdfs: Vec<Arc<Bxdf + Send + Sync>> = Vec::new();
         let e: Float = self.eta.evaluate(si);
-        let op: Spectrum = self
-            .opacity
-            .evaluate(si)
-            .clamp(0.0 as Float, std::f32::INFINITY as Float);
-        let t: Spectrum =
-            (Spectrum::new(1.0) - op).clamp(0.0 as Float, std::f32::INFINITY as Float);
+        // opacity weights the lobes below, clamp to [0, 1] so HDR
+        // opacity textures can't create energy
+        let op: Spectrum = self.opacity.evaluate(si).clamp(0.0 as Float, 1.0 as Float);
+        let t: Spectrum = Spectrum::white() - op;
         if !t.is_black() {
             bxdfs.push(Arc::new(SpecularTransmission::new(
                 t,
@@ -205,3 +202,155 @@ impl Material for UberMaterial {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::geometry::{Normal3f, Point2f, Point3f, Vector3f};
+    use core::pbrt::INV_PI;
+    use core::reflection::BxdfType;
+    use textures::constant::ConstantTexture;
+
+    fn spectrum(value: Float) -> Arc<Texture<Spectrum> + Sync + Send> {
+        Arc::new(ConstantTexture {
+            value: Spectrum::new(value),
+        })
+    }
+
+    /// An **UberMaterial** with the given diffuse (*kd*), specular
+    /// reflection (*kr*) and transmission (*kt*) colors.
+    fn uber(kd: Float, kr: Float, kt: Float, opacity: Float) -> UberMaterial {
+        UberMaterial::new(
+            spectrum(kd),
+            spectrum(0.0 as Float),
+            spectrum(kr),
+            spectrum(kt),
+            Arc::new(ConstantTexture {
+                value: 0.1 as Float,
+            }),
+            None,
+            None,
+            spectrum(opacity),
+            Arc::new(ConstantTexture {
+                value: 1.5 as Float,
+            }),
+            None,
+            false,
+        )
+    }
+
+    /// The BSDF of *material* at the origin of a plane with normal +z.
+    fn plane_bsdf(material: &UberMaterial, wo: &Vector3f) -> Arc<Bsdf> {
+        let mut si: SurfaceInteraction = SurfaceInteraction::new(
+            &Point3f::default(),
+            &Vector3f::default(),
+            &Point2f::default(),
+            wo,
+            &Vector3f {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            &Vector3f {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+            &Normal3f::default(),
+            &Normal3f::default(),
+            0.0 as Float,
+            None,
+        );
+        material.compute_scattering_functions(&mut si, TransportMode::Radiance, true, None);
+        si.bsdf.unwrap()
+    }
+
+    /// Diffuse reflection of a gray **UberMaterial** with the given
+    /// *opacity*, and whether it transmits light.
+    fn diffuse_reflection(opacity: Float) -> (Float, bool) {
+        let material: UberMaterial = uber(0.5 as Float, 0.0 as Float, 0.0 as Float, opacity);
+        let wo: Vector3f = Vector3f {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        };
+        let bsdf: Arc<Bsdf> = plane_bsdf(&material, &wo);
+        let wi: Vector3f = Vector3f {
+            x: 0.6,
+            y: 0.0,
+            z: 0.8,
+        };
+        let f: Spectrum = bsdf.f(&wo, &wi, BxdfType::BsdfAll as u8);
+        let transmits: bool = bsdf
+            .num_components(BxdfType::BsdfTransmission as u8 | BxdfType::BsdfSpecular as u8)
+            > 0;
+        (f[0], transmits)
+    }
+
+    #[test]
+    fn hdr_opacity_does_not_brighten_the_lobes() {
+        let (opaque, opaque_transmits) = diffuse_reflection(1.0 as Float);
+        assert!((opaque - 0.5 as Float * INV_PI).abs() < 1e-5 as Float);
+        assert!(!opaque_transmits);
+        let (hdr, hdr_transmits) = diffuse_reflection(2.0 as Float);
+        assert_eq!(hdr, opaque);
+        assert!(!hdr_transmits);
+        // partial opacity scales the lobes and transmits the rest
+        let (half, half_transmits) = diffuse_reflection(0.5 as Float);
+        assert!((half - 0.5 as Float * opaque).abs() < 1e-6 as Float);
+        assert!(half_transmits);
+    }
+
+    #[test]
+    fn specular_only_sampling_skips_the_diffuse_lobe() {
+        let material: UberMaterial = uber(0.5 as Float, 0.5 as Float, 0.3 as Float, 1.0 as Float);
+        let wo: Vector3f = Vector3f {
+            x: 0.6,
+            y: 0.0,
+            z: 0.8,
+        };
+        let bsdf: Arc<Bsdf> = plane_bsdf(&material, &wo);
+        let specular: u8 = BxdfType::BsdfSpecular as u8
+            | BxdfType::BsdfReflection as u8
+            | BxdfType::BsdfTransmission as u8;
+        let mut specular_samples: usize = 0;
+        let mut diffuse_samples: usize = 0;
+        for i in 0..64 {
+            for j in 0..8 {
+                let u: Point2f = Point2f {
+                    x: (i as Float + 0.5) / 64.0,
+                    y: (j as Float + 0.5) / 8.0,
+                };
+                let mut wi: Vector3f = Vector3f::default();
+                let mut pdf: Float = 0.0 as Float;
+                let mut sampled_type: u8 = u8::max_value(); // != 0
+                bsdf.sample_f(&wo, &mut wi, &u, &mut pdf, specular, &mut sampled_type);
+                if pdf > 0.0 as Float {
+                    specular_samples += 1;
+                    assert_eq!(sampled_type & BxdfType::BsdfDiffuse as u8, 0_u8);
+                    assert_ne!(sampled_type & BxdfType::BsdfSpecular as u8, 0_u8);
+                    if wi.z > 0.0 as Float {
+                        // perfect mirror direction
+                        assert!((wi.x + wo.x).abs() < 1e-5 as Float, "{:?}", wi);
+                        assert!((wi.z - wo.z).abs() < 1e-5 as Float, "{:?}", wi);
+                    }
+                }
+                sampled_type = u8::max_value();
+                bsdf.sample_f(
+                    &wo,
+                    &mut wi,
+                    &u,
+                    &mut pdf,
+                    BxdfType::BsdfAll as u8,
+                    &mut sampled_type,
+                );
+                if sampled_type & BxdfType::BsdfDiffuse as u8 != 0_u8 {
+                    diffuse_samples += 1;
+                }
+            }
+        }
+        assert!(specular_samples > 0);
+        // without the filter the diffuse lobe is sampled as well
+        assert!(diffuse_samples > 0);
+    }
+}
@@ -7,6 +7,7 @@ use core::interaction::MediumInteraction;
 use core::medium::{HenyeyGreenstein, Medium};
 use core::pbrt::lerp;
 use core::pbrt::{Float, Spectrum};
+use core::profile::{Prof, ProfilePhase};
 use core::sampler::Sampler;
 use core::transform::Transform;
 
@@ -154,7 +155,7 @@ impl GridDensityMedium {
 
 impl Medium for GridDensityMedium {
     fn tr(&self, r_world: &Ray, sampler: &mut Box<Sampler + Send + Sync>) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::MediumTr);
+        let _p = ProfilePhase::new(Prof::MediumTr);
         // TODO: ++nTrCalls;
         let mut in_ray: Ray = Ray::default();
         in_ray.o = r_world.o;
@@ -210,7 +211,7 @@ impl Medium for GridDensityMedium {
         r_world: &Ray,
         sampler: &mut Box<Sampler + Send + Sync>,
     ) -> (Spectrum, Option<MediumInteraction>) {
-        // TODO: ProfilePhase _(Prof::MediumSample);
+        let _p = ProfilePhase::new(Prof::MediumSample);
         let mut in_ray: Ray = Ray::default();
         in_ray.o = r_world.o;
         in_ray.d = r_world.d.normalize();
@@ -6,6 +6,7 @@ use core::geometry::Ray;
 use core::interaction::MediumInteraction;
 use core::medium::{HenyeyGreenstein, Medium};
 use core::pbrt::{Float, Spectrum};
+use core::profile::{Prof, ProfilePhase};
 use core::sampler::Sampler;
 
 // see homogeneous.h
@@ -30,7 +31,7 @@ impl HomogeneousMedium {
 
 impl Medium for HomogeneousMedium {
     fn tr(&self, ray: &Ray, _sampler: &mut Box<Sampler + Send + Sync>) -> Spectrum {
-        // TODO: ProfilePhase _(Prof::MediumTr);
+        let _p = ProfilePhase::new(Prof::MediumTr);
         (-self.sigma_t * (ray.t_max * ray.d.length()).min(f32::MAX)).exp()
     }
     fn sample(
@@ -38,7 +39,7 @@ impl Medium for HomogeneousMedium {
         ray: &Ray,
         sampler: &mut Box<Sampler + Send + Sync>,
     ) -> (Spectrum, Option<MediumInteraction>) {
-        // TODO: ProfilePhase _(Prof::MediumSample);
+        let _p = ProfilePhase::new(Prof::MediumSample);
         // sample a channel and distance along the ray
         let channel: usize = ((sampler.get_1d() * 3.0 as Float) as usize).min(2_usize);
         let dist: Float = -((1.0 as Float - sampler.get_1d()).ln()) / self.sigma_t[channel];
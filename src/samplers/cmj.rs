@@ -0,0 +1,264 @@
+// pbrt
+use core::geometry::{Point2f, Point2i};
+use core::pbrt::Float;
+use core::profile::{Prof, ProfilePhase};
+use core::sampler::{PixelSampler, Sampler};
+use core::sampling::{cmj_permute, cmj_randfloat, cmj_sample_2d};
+
+// see Kensler: Correlated Multi-Jittered Sampling (Pixar Technical Memo 13-01)
+
+#[derive(Debug, Clone)]
+pub struct CmjSampler {
+    pub samples_per_pixel: i64,
+    pub seed: u32,
+    pub current_1d_dimension: u32,
+    pub current_2d_dimension: u32,
+    // inherited from class Sampler (see sampler.h)
+    pub current_pixel: Point2i,
+    pub current_pixel_sample_index: i64,
+    pub samples_1d_array_sizes: Vec<i32>,
+    pub samples_2d_array_sizes: Vec<i32>,
+    pub sample_array_1d: Vec<Vec<Float>>,
+    pub sample_array_2d: Vec<Vec<Point2f>>,
+    pub array_1d_offset: usize,
+    pub array_2d_offset: usize,
+}
+
+impl CmjSampler {
+    pub fn new(samples_per_pixel: i64) -> Self {
+        CmjSampler {
+            samples_per_pixel: samples_per_pixel,
+            seed: 0_u32,
+            current_1d_dimension: 0_u32,
+            current_2d_dimension: 0_u32,
+            current_pixel: Point2i::default(),
+            current_pixel_sample_index: 0_i64,
+            samples_1d_array_sizes: Vec::new(),
+            samples_2d_array_sizes: Vec::new(),
+            sample_array_1d: Vec::new(),
+            sample_array_2d: Vec::new(),
+            array_1d_offset: 0_usize,
+            array_2d_offset: 0_usize,
+        }
+    }
+    /// Per pixel (and per dimension) pattern used to shuffle the
+    /// strata, so neighbouring pixels don't share the same samples.
+    fn pattern(&self, dimension: u32) -> u32 {
+        let mut h: u32 = self.seed;
+        h ^= (self.current_pixel.x as u32).wrapping_mul(0x8da6b343);
+        h ^= (self.current_pixel.y as u32).wrapping_mul(0xd8163841);
+        h ^= dimension.wrapping_mul(0xcb1ab31f);
+        h
+    }
+}
+
+impl Sampler for CmjSampler {
+    fn start_pixel(&mut self, p: &Point2i) {
+        let _p = ProfilePhase::new(Prof::StartPixel);
+        // Sampler::StartPixel(p);
+        self.current_pixel = *p;
+        self.current_pixel_sample_index = 0_i64;
+        self.current_1d_dimension = 0_u32;
+        self.current_2d_dimension = 0_u32;
+        self.array_1d_offset = 0_usize;
+        self.array_2d_offset = 0_usize;
+        // generate arrays of stratified samples for the pixel
+        for i in 0..self.sample_array_1d.len() {
+            let count: u32 = self.sample_array_1d[i].len() as u32;
+            // use dimensions which don't collide with get_1d()
+            let pattern: u32 = self.pattern((!(i as u32)).wrapping_mul(0x68bc21eb));
+            for j in 0..count {
+                let stratum: u32 = cmj_permute(j, count, pattern);
+                let jitter: Float = cmj_randfloat(j, pattern.wrapping_mul(0x967a889b));
+                self.sample_array_1d[i][j as usize] = (stratum as Float + jitter) / count as Float;
+            }
+        }
+        for i in 0..self.sample_array_2d.len() {
+            let count: u32 = self.sample_array_2d[i].len() as u32;
+            let m: u32 = (count as Float).sqrt().ceil() as u32;
+            let n: u32 = (count + m - 1) / m;
+            // use dimensions which don't collide with get_2d()
+            let pattern: u32 = self.pattern(!(i as u32));
+            for j in 0..count {
+                self.sample_array_2d[i][j as usize] = cmj_sample_2d(j, m, n, pattern);
+            }
+        }
+    }
+    fn get_1d(&mut self) -> Float {
+        let _p = ProfilePhase::new(Prof::GetSample);
+        assert!(self.current_pixel_sample_index < self.samples_per_pixel);
+        let count: u32 = self.samples_per_pixel as u32;
+        let s: u32 = self.current_pixel_sample_index as u32;
+        let pattern: u32 = self.pattern(self.current_1d_dimension.wrapping_mul(0x68bc21eb));
+        self.current_1d_dimension += 1;
+        let stratum: u32 = cmj_permute(s, count, pattern);
+        let jitter: Float = cmj_randfloat(s, pattern.wrapping_mul(0x967a889b));
+        (stratum as Float + jitter) / count as Float
+    }
+    fn get_2d(&mut self) -> Point2f {
+        let _p = ProfilePhase::new(Prof::GetSample);
+        assert!(self.current_pixel_sample_index < self.samples_per_pixel);
+        let dimension: u32 = self.current_2d_dimension;
+        self.current_2d_dimension += 1;
+        let count: u32 = self.samples_per_pixel as u32;
+        let m: u32 = (count as Float).sqrt().ceil() as u32;
+        let n: u32 = (count + m - 1) / m;
+        cmj_sample_2d(
+            self.current_pixel_sample_index as u32,
+            m,
+            n,
+            self.pattern(dimension),
+        )
+    }
+    fn get_2d_jittered(&mut self, strata: i32) -> Point2f {
+        assert!(strata > 0);
+        let m: u32 = (strata as Float).sqrt().ceil() as u32;
+        let n: u32 = (strata as u32 + m - 1) / m;
+        let s: u32 = (self.current_pixel_sample_index % strata as i64) as u32;
+        let dimension: u32 = self.current_2d_dimension;
+        self.current_2d_dimension += 1;
+        cmj_sample_2d(s, m, n, self.pattern(dimension))
+    }
+    fn request_1d_array(&mut self, n: i32) {
+        assert_eq!(self.round_count(n), n);
+        self.samples_1d_array_sizes.push(n);
+        let size: usize = (n * self.samples_per_pixel as i32) as usize;
+        let additional_samples: Vec<Float> = vec![0.0 as Float; size];
+        self.sample_array_1d.push(additional_samples);
+    }
+    fn request_2d_array(&mut self, n: i32) {
+        assert_eq!(self.round_count(n), n);
+        self.samples_2d_array_sizes.push(n);
+        let size: usize = (n * self.samples_per_pixel as i32) as usize;
+        let additional_points: Vec<Point2f> = vec![Point2f::default(); size];
+        self.sample_array_2d.push(additional_points);
+    }
+    fn round_count(&self, count: i32) -> i32 {
+        count
+    }
+    fn get_1d_array(&mut self, n: i32) -> Vec<Float> {
+        let mut samples: Vec<Float> = Vec::new();
+        if self.array_1d_offset == self.sample_array_1d.len() {
+            return samples;
+        }
+        assert_eq!(self.samples_1d_array_sizes[self.array_1d_offset], n);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "self.current_pixel_sample_index ({}) < self.samples_per_pixel ({})",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        let start: usize = (self.current_pixel_sample_index * n as i64) as usize;
+        let end: usize = start + n as usize;
+        samples = self.sample_array_1d[self.array_1d_offset][start..end].to_vec();
+        self.array_1d_offset += 1;
+        samples
+    }
+    fn get_2d_array(&mut self, n: i32) -> Vec<Point2f> {
+        let mut samples: Vec<Point2f> = Vec::new();
+        if self.array_2d_offset == self.sample_array_2d.len() {
+            return samples;
+        }
+        assert_eq!(self.samples_2d_array_sizes[self.array_2d_offset], n);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "self.current_pixel_sample_index ({}) < self.samples_per_pixel ({})",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        let start: usize = (self.current_pixel_sample_index * n as i64) as usize;
+        let end: usize = start + n as usize;
+        samples = self.sample_array_2d[self.array_2d_offset][start..end].to_vec();
+        self.array_2d_offset += 1;
+        samples
+    }
+    fn start_next_sample(&mut self) -> bool {
+        self.current_1d_dimension = 0_u32;
+        self.current_2d_dimension = 0_u32;
+        // reset array offsets for next pixel sample
+        self.array_1d_offset = 0_usize;
+        self.array_2d_offset = 0_usize;
+        self.current_pixel_sample_index += 1_i64;
+        self.current_pixel_sample_index < self.samples_per_pixel
+    }
+    fn reseed(&mut self, seed: u64) {
+        self.seed = (seed ^ (seed >> 32)) as u32;
+    }
+    fn get_current_pixel(&self) -> Point2i {
+        self.current_pixel
+    }
+    fn get_current_sample_number(&self) -> i64 {
+        self.current_pixel_sample_index
+    }
+    fn get_samples_per_pixel(&self) -> i64 {
+        self.samples_per_pixel
+    }
+    fn dimensions_used(&self) -> i64 {
+        self.current_1d_dimension as i64 + 2_i64 * self.current_2d_dimension as i64
+    }
+}
+
+impl PixelSampler for CmjSampler {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that *samples* (16 points) hold exactly one point per
+    /// column and row of a 16 x 16 grid and one per cell of a 4 x 4
+    /// grid.
+    fn assert_multi_jittered(samples: &Vec<Point2f>) {
+        assert_eq!(samples.len(), 16);
+        let mut columns: [u32; 16] = [0_u32; 16];
+        let mut rows: [u32; 16] = [0_u32; 16];
+        let mut cells: [u32; 16] = [0_u32; 16];
+        for p in samples {
+            assert!(p.x >= 0.0 as Float && p.x < 1.0 as Float);
+            assert!(p.y >= 0.0 as Float && p.y < 1.0 as Float);
+            columns[(p.x * 16.0 as Float) as usize] += 1;
+            rows[(p.y * 16.0 as Float) as usize] += 1;
+            cells[(p.x * 4.0 as Float) as usize + 4 * (p.y * 4.0 as Float) as usize] += 1;
+        }
+        assert!(columns.iter().all(|c| *c == 1), "columns {:?}", columns);
+        assert!(rows.iter().all(|c| *c == 1), "rows {:?}", rows);
+        assert!(cells.iter().all(|c| *c == 1), "cells {:?}", cells);
+    }
+
+    #[test]
+    fn samples_are_stratified_and_decorrelated() {
+        let mut sampler: CmjSampler = CmjSampler::new(16_i64);
+        let a: Vec<Point2f> = sampler.fill_pixel_2d(&Point2i { x: 0, y: 0 });
+        let b: Vec<Point2f> = sampler.fill_pixel_2d(&Point2i { x: 1, y: 0 });
+        assert_multi_jittered(&a);
+        assert_multi_jittered(&b);
+        // neighbouring pixels use differently shuffled patterns
+        let same: usize = a.iter().zip(b.iter()).filter(|&(p, q)| p == q).count();
+        assert!(same < 4, "{} identical samples", same);
+        // the same pixel reproduces its pattern
+        assert!(sampler.fill_pixel_2d(&Point2i { x: 0, y: 0 }) == a);
+    }
+
+    #[test]
+    fn sample_arrays_are_stratified() {
+        let mut sampler: CmjSampler = CmjSampler::new(4_i64);
+        sampler.request_1d_array(4);
+        sampler.request_2d_array(4);
+        sampler.start_pixel(&Point2i { x: 2, y: 3 });
+        let mut values: Vec<Float> = Vec::new();
+        let mut points: Vec<Point2f> = Vec::new();
+        loop {
+            values.extend(sampler.get_1d_array(4));
+            points.extend(sampler.get_2d_array(4));
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        // one value per stratum over all samples of the pixel
+        let mut strata: [u32; 16] = [0_u32; 16];
+        for v in &values {
+            strata[(*v * 16.0 as Float) as usize] += 1;
+        }
+        assert!(strata.iter().all(|c| *c == 1), "strata {:?}", strata);
+        assert_multi_jittered(&points);
+    }
+}
@@ -1,16 +1,18 @@
 // std
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 // pbrt
 use core::geometry::{Bounds2i, Point2f, Point2i, Vector2i};
 use core::lowdiscrepancy::{
     compute_radical_inverse_permutations, inverse_radical_inverse, radical_inverse,
     scrambled_radical_inverse,
 };
-use core::lowdiscrepancy::{PRIME_SUMS, PRIME_TABLE_SIZE};
+use core::lowdiscrepancy::{PRIMES, PRIME_SUMS, PRIME_TABLE_SIZE};
 use core::pbrt::mod_t;
 use core::pbrt::Float;
+use core::profile::{Prof, ProfilePhase};
 use core::rng::Rng;
-use core::sampler::{GlobalSampler, Sampler};
+use core::sampler::{remap_global_dimension, GlobalSampler, Sampler};
+use core::sampling::shuffle;
 
 // Generate random digit permutations for Halton sampler
 lazy_static! {
@@ -26,6 +28,30 @@ lazy_static! {
 
 pub const K_MAX_RESOLUTION: i32 = 128_i32;
 
+/// Number of (low) dimensions which get their own digit permutations
+/// after **reseed()** (see **HaltonSampler::reseed_permutations**);
+/// higher dimensions keep using the sampler's permutation table.
+/// Keeps reseeding (once per tile) cheap.
+pub const SEEDED_PERMUTATION_DIMENSIONS: usize = 64_usize;
+
+/// Random digit permutations for the first
+/// **SEEDED_PERMUTATION_DIMENSIONS** prime bases, derived from *seed*
+/// (same layout as **RADICAL_INVERSE_PERMUTATIONS**).
+fn compute_seeded_permutations(seed: u64) -> Vec<u16> {
+    let mut rng: Rng = Rng::new_sequence(seed);
+    let size: usize = PRIME_SUMS[SEEDED_PERMUTATION_DIMENSIONS] as usize;
+    let mut perms: Vec<u16> = vec![0_u16; size];
+    for i in 0..SEEDED_PERMUTATION_DIMENSIONS {
+        let p: usize = PRIME_SUMS[i] as usize;
+        let n: usize = PRIMES[i] as usize;
+        for j in 0..n {
+            perms[p + j] = j as u16;
+        }
+        shuffle(&mut perms[p..(p + n)], n as i32, 1, &mut rng);
+    }
+    perms
+}
+
 fn multiplicative_inverse(a: i64, n: i64) -> u64 {
     let mut x: i64 = 0;
     let mut y: i64 = 0;
@@ -56,6 +82,26 @@ pub struct HaltonSampler {
     pub pixel_for_offset: RwLock<Point2i>,
     pub offset_for_current_pixel: RwLock<u64>,
     pub sample_at_pixel_center: bool, // default: false
+    /// Whether **reseed()** selects seed specific digit permutations.
+    /// Off by default, so the per tile reseeds of the renderer leave
+    /// the samples identical to pbrt's.
+    pub reseed_permutations: bool,
+    /// Single sample preview mode (see **set_preview()**)
+    pub preview: bool,
+    /// Last value passed to **reseed()** (0: not reseeded)
+    pub seed: u64,
+    /// Digit permutations selected by **seed** (empty for seed 0,
+    /// see **compute_seeded_permutations()**)
+    pub seeded_permutations: Vec<u16>,
+    /// Seed of an explicit permutation table (see
+    /// **with_permutations()**), None for the shared table
+    pub permutation_seed: Option<u64>,
+    /// Digit permutations for all prime bases, generated from
+    /// **permutation_seed** (instead of **RADICAL_INVERSE_PERMUTATIONS**)
+    pub permutations: Option<Arc<Vec<u16>>>,
+    /// Fallback for dimensions beyond the prime table, seeded per
+    /// pixel sample (see **get_1d()**)
+    pub rng: Rng,
     // inherited from class GlobalSampler (see sampler.h)
     pub dimension: i64,
     pub interval_sample_index: u64,
@@ -77,7 +123,65 @@ impl HaltonSampler {
         samples_per_pixel: i64,
         sample_bounds: Bounds2i,
         sample_at_pixel_center: bool,
+        array_start_dim: i64,
+    ) -> Self {
+        let (base_scales, base_exponents, sample_stride, mult_inverse) =
+            HaltonSampler::compute_strides(&sample_bounds);
+        HaltonSampler {
+            samples_per_pixel: samples_per_pixel,
+            base_scales: base_scales,
+            base_exponents: base_exponents,
+            sample_stride: sample_stride,
+            mult_inverse: mult_inverse,
+            pixel_for_offset: RwLock::new(Point2i::default()),
+            offset_for_current_pixel: RwLock::new(0_u64),
+            sample_at_pixel_center: sample_at_pixel_center,
+            reseed_permutations: false,
+            preview: false,
+            seed: 0_u64,
+            seeded_permutations: Vec::new(),
+            permutation_seed: None,
+            permutations: None,
+            rng: Rng::new_sequence(0_u64),
+            dimension: 0_i64,
+            interval_sample_index: 0_u64,
+            array_start_dim: array_start_dim,
+            array_end_dim: 0_i64,
+            current_pixel: Point2i::default(),
+            current_pixel_sample_index: 0_i64,
+            samples_1d_array_sizes: Vec::new(),
+            samples_2d_array_sizes: Vec::new(),
+            sample_array_1d: Vec::new(),
+            sample_array_2d: Vec::new(),
+            array_1d_offset: 0_usize,
+            array_2d_offset: 0_usize,
+        }
+    }
+    /// Like **new()**, but the digit permutations are generated from
+    /// *permutation_seed* instead of using the lazily initialized
+    /// **RADICAL_INVERSE_PERMUTATIONS**, so the scrambled dimensions
+    /// don't depend on the default state of **Rng::new()**.
+    pub fn with_permutations(
+        permutation_seed: u64,
+        samples_per_pixel: i64,
+        sample_bounds: Bounds2i,
+        sample_at_pixel_center: bool,
+        array_start_dim: i64,
     ) -> Self {
+        let mut sampler: HaltonSampler = HaltonSampler::new(
+            samples_per_pixel,
+            sample_bounds,
+            sample_at_pixel_center,
+            array_start_dim,
+        );
+        let mut rng: Rng = Rng::new_sequence(permutation_seed);
+        sampler.permutation_seed = Some(permutation_seed);
+        sampler.permutations = Some(Arc::new(compute_radical_inverse_permutations(&mut rng)));
+        sampler
+    }
+    /// Base scales, base exponents, sample stride and multiplicative
+    /// inverses needed to cover *sample_bounds*.
+    fn compute_strides(sample_bounds: &Bounds2i) -> (Point2i, Point2i, u64, [i64; 2]) {
         // find radical inverse base scales and exponents that cover sampling area
         let res: Vector2i = sample_bounds.p_max - sample_bounds.p_min;
         let mut base_scales: Point2i = Point2i::default();
@@ -105,27 +209,45 @@ impl HaltonSampler {
             multiplicative_inverse(base_scales[1] as i64, base_scales[0] as i64) as i64,
             multiplicative_inverse(base_scales[0] as i64, base_scales[1] as i64) as i64,
         ];
-        HaltonSampler {
-            samples_per_pixel: samples_per_pixel,
-            base_scales: base_scales,
-            base_exponents: base_exponents,
-            sample_stride: sample_stride,
-            mult_inverse: mult_inverse,
-            pixel_for_offset: RwLock::new(Point2i::default()),
-            offset_for_current_pixel: RwLock::new(0_u64),
-            sample_at_pixel_center: sample_at_pixel_center,
-            dimension: 0_i64,
-            interval_sample_index: 0_u64,
-            array_start_dim: 5_i64, // static const int arrayStartDim = 5;
-            array_end_dim: 0_i64,
-            current_pixel: Point2i::default(),
-            current_pixel_sample_index: 0_i64,
-            samples_1d_array_sizes: Vec::new(),
-            samples_2d_array_sizes: Vec::new(),
-            sample_array_1d: Vec::new(),
-            sample_array_2d: Vec::new(),
-            array_1d_offset: 0_usize,
-            array_2d_offset: 0_usize,
+        (base_scales, base_exponents, sample_stride, mult_inverse)
+    }
+    /// Adapts the sampler to new *sample_bounds* (e.g. after the film
+    /// crop window changed). The sample arrays are kept, only the
+    /// stride related fields are recomputed.
+    pub fn reconfigure(&mut self, sample_bounds: Bounds2i) {
+        let (base_scales, base_exponents, sample_stride, mult_inverse) =
+            HaltonSampler::compute_strides(&sample_bounds);
+        self.base_scales = base_scales;
+        self.base_exponents = base_exponents;
+        self.sample_stride = sample_stride;
+        self.mult_inverse = mult_inverse;
+        // invalidate the cached offset, it depends on the stride
+        *self.pixel_for_offset.write().unwrap() = Point2i {
+            x: i32::max_value(),
+            y: i32::max_value(),
+        };
+        *self.offset_for_current_pixel.write().unwrap() = 0_u64;
+    }
+    /// Switches the single sample preview mode on or off. In preview
+    /// mode requests for sample arrays are ignored (and
+    /// **supports_arrays()** returns false), so **start_pixel()** has
+    /// nothing to precompute and integrators fall back to individual
+    /// **get_2d()** calls. The pixel and lens samples are the same as
+    /// without preview.
+    pub fn set_preview(&mut self, preview: bool) {
+        if preview && self.samples_per_pixel != 1_i64 {
+            println!(
+                "WARNING: Halton preview mode needs one sample per pixel (not {}). Ignoring.",
+                self.samples_per_pixel
+            );
+            return;
+        }
+        self.preview = preview;
+        if preview {
+            self.samples_1d_array_sizes.clear();
+            self.samples_2d_array_sizes.clear();
+            self.sample_array_1d.clear();
+            self.sample_array_2d.clear();
         }
     }
     pub fn get_index_for_sample(&self, sample_num: u64) -> u64 {
@@ -179,13 +301,28 @@ impl HaltonSampler {
                 PRIME_TABLE_SIZE
             );
         }
+        if (dim as usize) < SEEDED_PERMUTATION_DIMENSIONS && !self.seeded_permutations.is_empty() {
+            return &self.seeded_permutations[PRIME_SUMS[dim as usize] as usize..];
+        }
+        if let Some(ref permutations) = self.permutations {
+            return &permutations[PRIME_SUMS[dim as usize] as usize..];
+        }
         &RADICAL_INVERSE_PERMUTATIONS[PRIME_SUMS[dim as usize] as usize..]
     }
+    /// Like **sample_dimension()**, but uses the fallback _Rng_
+    /// instead of panicking once the prime table is exhausted.
+    fn sample_or_fallback(&mut self, dim: i64) -> Float {
+        if dim >= PRIME_TABLE_SIZE as i64 {
+            self.rng.uniform_float()
+        } else {
+            self.sample_dimension(self.interval_sample_index, dim)
+        }
+    }
 }
 
 impl Sampler for HaltonSampler {
     fn start_pixel(&mut self, p: &Point2i) {
-        // TODO: ProfilePhase _(Prof::StartPixel);
+        let _p = ProfilePhase::new(Prof::StartPixel);
         // Sampler::StartPixel(p);
         self.current_pixel = *p;
         self.current_pixel_sample_index = 0_i64;
@@ -194,6 +331,7 @@ impl Sampler for HaltonSampler {
         // GlobalSampler::StartPixel(p);
         self.dimension = 0_i64;
         self.interval_sample_index = self.get_index_for_sample(0_u64);
+        self.rng.set_sequence(self.interval_sample_index);
         // compute _self.array_end_dim_ for dimensions used for array samples
         self.array_end_dim = self.array_start_dim
             + self.sample_array_1d.len() as i64
@@ -222,38 +360,80 @@ impl Sampler for HaltonSampler {
         assert!(self.array_end_dim == dim);
     }
     fn get_1d(&mut self) -> Float {
-        // TODO: ProfilePhase _(Prof::GetSample);
-        if self.dimension >= self.array_start_dim && self.dimension < self.array_end_dim {
-            self.dimension = self.array_end_dim;
-        }
+        let _p = ProfilePhase::new(Prof::GetSample);
+        self.dimension = remap_global_dimension(
+            self.dimension,
+            1_i64,
+            self.array_start_dim,
+            self.array_end_dim,
+        );
         // call first (in C++: return SampleDimension(intervalSampleIndex, dimension++));
-        let ret: Float = self.sample_dimension(self.interval_sample_index, self.dimension);
+        let dim: i64 = self.dimension;
+        let ret: Float = self.sample_or_fallback(dim);
         self.dimension += 1;
         // then return
         ret
     }
     fn get_2d(&mut self) -> Point2f {
-        // TODO: ProfilePhase _(Prof::GetSample);
-        if self.dimension + 1 >= self.array_start_dim && self.dimension < self.array_end_dim {
-            self.dimension = self.array_end_dim;
-        }
+        let _p = ProfilePhase::new(Prof::GetSample);
+        self.dimension = remap_global_dimension(
+            self.dimension,
+            2_i64,
+            self.array_start_dim,
+            self.array_end_dim,
+        );
         // C++: call y first
-        let y = self.sample_dimension(self.interval_sample_index, self.dimension + 1);
-        let x = self.sample_dimension(self.interval_sample_index, self.dimension);
+        let dim: i64 = self.dimension;
+        let y = self.sample_or_fallback(dim + 1);
+        let x = self.sample_or_fallback(dim);
         let p: Point2f = Point2f { x: x, y: y };
         self.dimension += 2;
         return p;
     }
+    fn request_1d_array(&mut self, n: i32) {
+        if self.preview {
+            return;
+        }
+        assert_eq!(self.round_count(n), n);
+        self.samples_1d_array_sizes.push(n);
+        let size: usize = (n * self.samples_per_pixel as i32) as usize;
+        let additional_samples: Vec<Float> = vec![0.0 as Float; size];
+        self.sample_array_1d.push(additional_samples);
+    }
     fn request_2d_array(&mut self, n: i32) {
+        if self.preview {
+            return;
+        }
         assert_eq!(self.round_count(n), n);
         self.samples_2d_array_sizes.push(n);
         let size: usize = (n * self.samples_per_pixel as i32) as usize;
         let additional_points: Vec<Point2f> = vec![Point2f::default(); size];
         self.sample_array_2d.push(additional_points);
     }
+    fn supports_arrays(&self) -> bool {
+        !self.preview
+    }
     fn round_count(&self, count: i32) -> i32 {
         count
     }
+    fn get_1d_array(&mut self, n: i32) -> Vec<Float> {
+        let mut samples: Vec<Float> = Vec::new();
+        if self.array_1d_offset == self.sample_array_1d.len() {
+            return samples;
+        }
+        assert_eq!(self.samples_1d_array_sizes[self.array_1d_offset], n);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "self.current_pixel_sample_index ({}) < self.samples_per_pixel ({})",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        let start: usize = (self.current_pixel_sample_index * n as i64) as usize;
+        let end: usize = start + n as usize;
+        samples = self.sample_array_1d[self.array_1d_offset][start..end].to_vec();
+        self.array_1d_offset += 1;
+        samples
+    }
     fn get_2d_array(&mut self, n: i32) -> Vec<Point2f> {
         let mut samples: Vec<Point2f> = Vec::new();
         if self.array_2d_offset == self.sample_array_2d.len() {
@@ -276,6 +456,7 @@ impl Sampler for HaltonSampler {
         self.dimension = 0_i64;
         self.interval_sample_index =
             self.get_index_for_sample(self.current_pixel_sample_index as u64 + 1_u64);
+        self.rng.set_sequence(self.interval_sample_index);
         // Sampler::StartNextSample();
         // reset array offsets for next pixel sample
         self.array_1d_offset = 0_usize;
@@ -283,8 +464,27 @@ impl Sampler for HaltonSampler {
         self.current_pixel_sample_index += 1_i64;
         self.current_pixel_sample_index < self.samples_per_pixel
     }
-    fn reseed(&mut self, _seed: u64) {
-        // do nothing
+    /// If **reseed_permutations** is set, selects a different set of
+    /// digit permutations (Owen style scrambling) for the scrambled
+    /// dimensions, derived from *seed* (and the **permutation_seed**,
+    /// if any). Dimensions 0 and 1 (the film position) aren't
+    /// scrambled and stay consistent per pixel. Seed 0 (or leaving
+    /// **reseed_permutations** off) keeps the permutations the
+    /// sampler was created with.
+    fn reseed(&mut self, seed: u64) {
+        if seed == self.seed {
+            return;
+        }
+        self.seed = seed;
+        if seed == 0_u64 || !self.reseed_permutations {
+            self.seeded_permutations.clear();
+        } else {
+            let sequence: u64 = match self.permutation_seed {
+                Some(permutation_seed) => seed ^ permutation_seed.wrapping_mul(0x9e3779b97f4a7c15),
+                None => seed,
+            };
+            self.seeded_permutations = compute_seeded_permutations(sequence);
+        }
     }
     fn get_current_pixel(&self) -> Point2i {
         self.current_pixel
@@ -295,6 +495,9 @@ impl Sampler for HaltonSampler {
     fn get_samples_per_pixel(&self) -> i64 {
         self.samples_per_pixel
     }
+    fn dimensions_used(&self) -> i64 {
+        self.dimension
+    }
 }
 
 impl GlobalSampler for HaltonSampler {
@@ -302,6 +505,7 @@ impl GlobalSampler for HaltonSampler {
         // GlobalSampler::SetSampleNumber(...)
         self.dimension = 0_i64;
         self.interval_sample_index = self.get_index_for_sample(sample_num as u64);
+        self.rng.set_sequence(self.interval_sample_index);
         // reset array offsets for next pixel sample
         self.array_1d_offset = 0_usize;
         self.array_2d_offset = 0_usize;
@@ -323,6 +527,13 @@ impl Clone for HaltonSampler {
             pixel_for_offset: RwLock::new(pixel_for_offset),
             offset_for_current_pixel: RwLock::new(offset_for_current_pixel),
             sample_at_pixel_center: self.sample_at_pixel_center,
+            reseed_permutations: self.reseed_permutations,
+            preview: self.preview,
+            seed: self.seed,
+            seeded_permutations: self.seeded_permutations.clone(),
+            permutation_seed: self.permutation_seed,
+            permutations: self.permutations.clone(),
+            rng: self.rng,
             dimension: self.dimension,
             interval_sample_index: self.interval_sample_index,
             array_start_dim: self.array_start_dim,
@@ -338,3 +549,234 @@ impl Clone for HaltonSampler {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preview_bounds() -> Bounds2i {
+        Bounds2i::new(Point2i { x: 0, y: 0 }, Point2i { x: 16, y: 16 })
+    }
+
+    #[test]
+    fn single_sample_without_arrays_matches_full_sampler() {
+        // preview: one sample per pixel, no sample arrays requested
+        let mut preview: HaltonSampler = HaltonSampler::new(1_i64, preview_bounds(), false, 5_i64);
+        let mut full: HaltonSampler = HaltonSampler::new(1_i64, preview_bounds(), false, 5_i64);
+        full.request_1d_array(4);
+        full.request_2d_array(4);
+        let pixel: Point2i = Point2i { x: 3, y: 7 };
+        preview.start_pixel(&pixel);
+        full.start_pixel(&pixel);
+        assert!(preview.sample_array_1d.is_empty());
+        assert!(preview.sample_array_2d.is_empty());
+        let p_film: Point2f = preview.get_2d();
+        assert_eq!(p_film, full.get_2d());
+        assert_eq!(preview.get_1d(), full.get_1d());
+        assert_eq!(preview.get_2d(), full.get_2d());
+    }
+
+    #[test]
+    fn preview_mode_ignores_array_requests() {
+        let mut preview: HaltonSampler = HaltonSampler::new(1_i64, preview_bounds(), false, 5_i64);
+        preview.set_preview(true);
+        let mut full: HaltonSampler = HaltonSampler::new(1_i64, preview_bounds(), false, 5_i64);
+        for sampler in &mut [&mut preview, &mut full] {
+            sampler.request_1d_array(4);
+            sampler.request_2d_array(4);
+        }
+        assert!(!preview.supports_arrays());
+        assert!(preview.sample_array_1d.is_empty());
+        assert!(preview.sample_array_2d.is_empty());
+        let pixel: Point2i = Point2i { x: 3, y: 7 };
+        preview.start_pixel(&pixel);
+        full.start_pixel(&pixel);
+        // pixel and lens samples are the same as without preview
+        assert_eq!(preview.get_2d(), full.get_2d());
+        assert_eq!(preview.get_1d(), full.get_1d());
+        assert_eq!(preview.get_2d(), full.get_2d());
+        assert!(preview.get_2d_array(4).is_empty());
+        // preview needs a single sample per pixel
+        let mut more: HaltonSampler = HaltonSampler::new(4_i64, preview_bounds(), false, 5_i64);
+        more.set_preview(true);
+        assert!(!more.preview);
+    }
+
+    #[test]
+    fn dimensions_beyond_prime_table_fall_back_to_rng() {
+        let mut sampler: HaltonSampler = HaltonSampler::new(4_i64, preview_bounds(), false, 5_i64);
+        sampler.start_pixel(&Point2i { x: 1, y: 2 });
+        let n: usize = PRIME_TABLE_SIZE as usize + 64_usize;
+        let mut values: Vec<Float> = Vec::with_capacity(n);
+        for _i in 0..n {
+            values.push(sampler.get_1d());
+        }
+        let fallback: &[Float] = &values[PRIME_TABLE_SIZE as usize..];
+        for v in fallback {
+            assert!(v.is_finite());
+            assert!(*v >= 0.0 as Float && *v < 1.0 as Float);
+        }
+        // not a constant
+        assert!(fallback.iter().any(|v| *v != fallback[0]));
+    }
+
+    #[test]
+    fn reconfigure_updates_stride() {
+        let mut sampler: HaltonSampler = HaltonSampler::new(4_i64, preview_bounds(), false, 5_i64);
+        assert_eq!(sampler.sample_stride, 16_u64 * 27_u64);
+        sampler.start_pixel(&Point2i { x: 3, y: 7 });
+        sampler.reconfigure(Bounds2i::new(Point2i { x: 0, y: 0 }, Point2i { x: 64, y: 64 }));
+        assert_eq!(sampler.base_scales, Point2i { x: 64, y: 81 });
+        assert_eq!(sampler.base_exponents, Point2i { x: 6, y: 4 });
+        assert_eq!(sampler.sample_stride, 64_u64 * 81_u64);
+        // only reachable with the new stride
+        let pixel: Point2i = Point2i { x: 40, y: 50 };
+        sampler.start_pixel(&pixel);
+        let index: u64 = sampler.get_index_for_sample(0_u64);
+        assert!(index < sampler.sample_stride);
+        assert_eq!(sampler.get_index_for_sample(1_u64) - index, sampler.sample_stride);
+        // the low digits of the index select the pixel
+        let x: Float = radical_inverse(0_u16, index) * 64.0 as Float;
+        let y: Float = radical_inverse(1_u16, index) * 81.0 as Float;
+        assert_eq!(x.floor() as i32, pixel.x);
+        assert_eq!(y.floor() as i32, pixel.y);
+    }
+
+    #[test]
+    fn array_start_dim_shifts_the_array_dimensions() {
+        for &start in &[5_i64, 9_i64] {
+            let mut sampler: HaltonSampler =
+                HaltonSampler::new(1_i64, preview_bounds(), false, start);
+            sampler.request_2d_array(1);
+            sampler.start_pixel(&Point2i { x: 3, y: 7 });
+            let index: u64 = sampler.get_index_for_sample(0_u64);
+            let array: Vec<Point2f> = sampler.get_2d_array(1);
+            assert_eq!(array[0].x, sampler.sample_dimension(index, start));
+            assert_eq!(array[0].y, sampler.sample_dimension(index, start + 1));
+            // per sample draws below the array start keep the low dimensions ...
+            let mut dim: i64 = 0_i64;
+            while dim + 2 <= start {
+                let u: Point2f = sampler.get_2d();
+                assert_eq!(u.x, sampler.sample_dimension(index, dim));
+                assert_eq!(u.y, sampler.sample_dimension(index, dim + 1));
+                dim += 2;
+            }
+            // ... and the next one skips the array dimensions
+            let u: Point2f = sampler.get_2d();
+            assert_eq!(u.x, sampler.sample_dimension(index, start + 2));
+            assert_eq!(u.y, sampler.sample_dimension(index, start + 3));
+        }
+    }
+
+    #[test]
+    fn one_d_arrays_are_sliced_per_pixel_sample() {
+        let mut sampler: HaltonSampler = HaltonSampler::new(4_i64, preview_bounds(), false, 5_i64);
+        sampler.request_1d_array(4);
+        assert_eq!(sampler.samples_1d_array_sizes, vec![4_i32]);
+        sampler.start_pixel(&Point2i { x: 3, y: 7 });
+        let mut values: Vec<Float> = Vec::new();
+        loop {
+            let array: Vec<Float> = sampler.get_1d_array(4);
+            assert_eq!(array.len(), 4_usize);
+            for v in &array {
+                assert!(*v >= 0.0 as Float && *v < 1.0 as Float, "{}", v);
+            }
+            values.extend(array);
+            // only one array was requested
+            assert!(sampler.get_1d_array(4).is_empty());
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        // each pixel sample gets its own slice
+        assert_eq!(values.len(), 16_usize);
+        assert!(values[..4] != values[4..8]);
+        assert!(values[4..8] != values[12..]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn one_d_arrays_must_be_fetched_with_the_requested_size() {
+        let mut sampler: HaltonSampler = HaltonSampler::new(4_i64, preview_bounds(), false, 5_i64);
+        sampler.request_1d_array(4);
+        sampler.start_pixel(&Point2i { x: 3, y: 7 });
+        sampler.get_1d_array(2);
+    }
+    fn pixel_samples(sampler: &mut HaltonSampler, pixel: &Point2i) -> Vec<Float> {
+        sampler.start_pixel(pixel);
+        let mut values: Vec<Float> = Vec::new();
+        loop {
+            for _dim in 0..8 {
+                values.push(sampler.get_1d());
+            }
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn reseed_keeps_the_shared_permutations_by_default() {
+        let pixel: Point2i = Point2i { x: 5, y: 9 };
+        let mut default: HaltonSampler = HaltonSampler::new(4_i64, preview_bounds(), false, 5_i64);
+        let expected: Vec<Float> = pixel_samples(&mut default, &pixel);
+        // the shared table scrambles dimension 5
+        let index: u64 = default.get_index_for_sample(0_u64);
+        let shared: &[u16] = &RADICAL_INVERSE_PERMUTATIONS[PRIME_SUMS[5] as usize..];
+        assert_eq!(expected[5], scrambled_radical_inverse(5_u16, index, shared));
+        // per tile reseeds don't change the samples ...
+        let mut tiled: HaltonSampler = default.clone();
+        for seed in &[3_u64, 0_u64] {
+            tiled.reseed(*seed);
+            assert_eq!(pixel_samples(&mut tiled, &pixel), expected);
+        }
+        // ... unless asked for
+        let mut scrambled: HaltonSampler = default.clone();
+        scrambled.reseed_permutations = true;
+        scrambled.reseed(0_u64);
+        assert_eq!(pixel_samples(&mut scrambled, &pixel), expected);
+        scrambled.reseed(1_u64);
+        let one: Vec<Float> = pixel_samples(&mut scrambled, &pixel);
+        scrambled.reseed(2_u64);
+        let two: Vec<Float> = pixel_samples(&mut scrambled, &pixel);
+        for sample in 0..4 {
+            let (a, b): (&[Float], &[Float]) = (&one[sample * 8..], &two[sample * 8..]);
+            // the film position stays, the scrambled dimensions change
+            assert_eq!((a[0], a[1]), (expected[sample * 8], expected[sample * 8 + 1]));
+            assert_eq!((a[0], a[1]), (b[0], b[1]));
+        }
+        assert!((5..8).any(|dim| one[dim] != two[dim]));
+        scrambled.reseed(0_u64);
+        assert_eq!(pixel_samples(&mut scrambled, &pixel), expected);
+    }
+
+    #[test]
+    fn permutation_seed_selects_the_digit_permutations() {
+        let pixel: Point2i = Point2i { x: 2, y: 11 };
+        let dimension_5 = |permutation_seed: u64| -> Vec<Float> {
+            let mut sampler: HaltonSampler = HaltonSampler::with_permutations(
+                permutation_seed,
+                4_i64,
+                preview_bounds(),
+                false,
+                5_i64,
+            );
+            // the per tile reseed keeps the explicit table
+            sampler.reseed(9_u64);
+            let samples: Vec<Float> = pixel_samples(&mut sampler, &pixel);
+            let index: u64 = sampler.get_index_for_sample(0_u64);
+            let table: Arc<Vec<u16>> = sampler.permutations.clone().unwrap();
+            assert_eq!(
+                samples[5],
+                scrambled_radical_inverse(5_u16, index, &table[PRIME_SUMS[5] as usize..])
+            );
+            (0..4).map(|i| samples[i * 8 + 5]).collect()
+        };
+        let a: Vec<Float> = dimension_5(42_u64);
+        assert_eq!(a, dimension_5(42_u64));
+        assert_ne!(a, dimension_5(43_u64));
+        // seeds beyond 32 bits work, too
+        assert_ne!(dimension_5(1_u64 << 40), dimension_5((1_u64 << 40) + 1_u64));
+    }
+}
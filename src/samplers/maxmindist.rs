@@ -0,0 +1,318 @@
+// pbrt
+use core::geometry::{Point2f, Point2i};
+use core::lowdiscrepancy::{sample_generator_matrix, sobol_2d, van_der_corput, C_MAX_MIN_DIST};
+use core::paramset::ParamSet;
+use core::pbrt::Float;
+use core::pbrt::{is_power_of_2, log_2_int_i32, round_up_pow2_32, round_up_pow2_64};
+use core::profile::{Prof, ProfilePhase};
+use core::rng::Rng;
+use core::sampler::{PixelSampler, Sampler};
+use core::sampling::shuffle;
+
+// see maxmin.h
+
+#[derive(Debug, Clone)]
+pub struct MaxMinDistSampler {
+    pub samples_per_pixel: i64,
+    pub n_sampled_dimensions: i64,
+    /// generator matrix of the first 2D dimension (see
+    /// **C_MAX_MIN_DIST**)
+    pub c_pixel: [u32; 32],
+    // inherited from class PixelSampler (see sampler.h)
+    pub samples_1d: Vec<Vec<Float>>,
+    pub samples_2d: Vec<Vec<Point2f>>,
+    pub current_1d_dimension: i32,
+    pub current_2d_dimension: i32,
+    pub rng: Rng,
+    // inherited from class Sampler (see sampler.h)
+    pub current_pixel: Point2i,
+    pub current_pixel_sample_index: i64,
+    pub samples_1d_array_sizes: Vec<i32>,
+    pub samples_2d_array_sizes: Vec<i32>,
+    pub sample_array_1d: Vec<Vec<Float>>,
+    pub sample_array_2d: Vec<Vec<Point2f>>,
+    pub array_1d_offset: usize,
+    pub array_2d_offset: usize,
+}
+
+impl MaxMinDistSampler {
+    /// Rounds *samples_per_pixel* up to a power of 2 (there is one
+    /// generator matrix per power of 2), clamped to the largest
+    /// tabulated one.
+    pub fn new(samples_per_pixel: i64, n_sampled_dimensions: i64) -> Self {
+        let max_samples: i64 = 1_i64 << (C_MAX_MIN_DIST.len() - 1);
+        let samples_per_pixel: i64 =
+            round_up_pow2_64(samples_per_pixel.max(1_i64)).min(max_samples);
+        let c_index: usize = log_2_int_i32(samples_per_pixel as i32) as usize;
+        let mut mmd: MaxMinDistSampler = MaxMinDistSampler {
+            samples_per_pixel: samples_per_pixel,
+            n_sampled_dimensions: n_sampled_dimensions,
+            c_pixel: C_MAX_MIN_DIST[c_index],
+            samples_1d: Vec::new(),
+            samples_2d: Vec::new(),
+            current_1d_dimension: 0_i32,
+            current_2d_dimension: 0_i32,
+            rng: Rng::default(),
+            current_pixel: Point2i::default(),
+            current_pixel_sample_index: 0_i64,
+            samples_1d_array_sizes: Vec::new(),
+            samples_2d_array_sizes: Vec::new(),
+            sample_array_1d: Vec::new(),
+            sample_array_2d: Vec::new(),
+            array_1d_offset: 0_usize,
+            array_2d_offset: 0_usize,
+        };
+        for _i in 0..mmd.n_sampled_dimensions {
+            let additional_1d: Vec<Float> = vec![0.0; mmd.samples_per_pixel as usize];
+            let additional_2d: Vec<Point2f> =
+                vec![Point2f::default(); mmd.samples_per_pixel as usize];
+            mmd.samples_1d.push(additional_1d);
+            mmd.samples_2d.push(additional_2d);
+        }
+        mmd
+    }
+    /// Reads "pixelsamples" and "dimensions" (see
+    /// CreateMaxMinDistSampler() in maxmin.cpp).
+    pub fn create(params: &ParamSet) -> Box<Sampler + Sync + Send> {
+        let nsamp: i32 = params.find_one_int("pixelsamples", 16);
+        let sd: i32 = params.find_one_int("dimensions", 4);
+        // TODO: if (PbrtOptions.quickRender) nsamp = 1;
+        let max_samples: i64 = 1_i64 << (C_MAX_MIN_DIST.len() - 1);
+        if nsamp as i64 > max_samples {
+            println!(
+                "WARNING: MaxMinDistSampler supports no more than {} samples per pixel.",
+                max_samples
+            );
+        } else if !is_power_of_2(nsamp as i64) {
+            println!(
+                "WARNING: Non power-of-two sample count rounded up to {} for MaxMinDistSampler.",
+                round_up_pow2_64(nsamp as i64)
+            );
+        }
+        Box::new(MaxMinDistSampler::new(nsamp as i64, sd as i64))
+    }
+}
+
+impl Sampler for MaxMinDistSampler {
+    fn start_pixel(&mut self, p: &Point2i) {
+        let _p = ProfilePhase::new(Prof::StartPixel);
+        let inv_spp: Float = 1.0 as Float / self.samples_per_pixel as Float;
+        // the first 2D dimension uses the max-min-dist point set
+        if let Some(samples) = self.samples_2d.first_mut() {
+            for i in 0..self.samples_per_pixel as usize {
+                samples[i] = Point2f {
+                    x: i as Float * inv_spp,
+                    y: sample_generator_matrix(&self.c_pixel, i as u32, 0_u32),
+                };
+            }
+            shuffle(
+                samples.as_mut_slice(),
+                self.samples_per_pixel as i32,
+                1,
+                &mut self.rng,
+            );
+        }
+        // generate remaining samples for _MaxMinDistSampler_
+        for samples in &mut self.samples_1d {
+            van_der_corput(1, self.samples_per_pixel as i32, samples, &mut self.rng);
+        }
+        for samples in self.samples_2d.iter_mut().skip(1) {
+            sobol_2d(1, self.samples_per_pixel as i32, samples, &mut self.rng);
+        }
+        for i in 0..self.samples_1d_array_sizes.len() {
+            let samples: &mut [Float] = self.sample_array_1d[i].as_mut_slice();
+            van_der_corput(
+                self.samples_1d_array_sizes[i],
+                self.samples_per_pixel as i32,
+                samples,
+                &mut self.rng,
+            );
+        }
+        for i in 0..self.samples_2d_array_sizes.len() {
+            let samples: &mut [Point2f] = self.sample_array_2d[i].as_mut_slice();
+            sobol_2d(
+                self.samples_2d_array_sizes[i],
+                self.samples_per_pixel as i32,
+                samples,
+                &mut self.rng,
+            );
+        }
+        // PixelSampler::StartPixel(p);
+        self.current_pixel = *p;
+        self.current_pixel_sample_index = 0_i64;
+        self.current_1d_dimension = 0_i32;
+        self.current_2d_dimension = 0_i32;
+        // reset array offsets for next pixel sample
+        self.array_1d_offset = 0_usize;
+        self.array_2d_offset = 0_usize;
+    }
+    fn get_1d(&mut self) -> Float {
+        let _p = ProfilePhase::new(Prof::GetSample);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "current_pixel_sample_index = {}, samples_per_pixel = {}",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        if self.current_1d_dimension < self.samples_1d.len() as i32 {
+            let sample: Float = self.samples_1d[self.current_1d_dimension as usize]
+                [self.current_pixel_sample_index as usize];
+            self.current_1d_dimension += 1;
+            sample
+        } else {
+            self.rng.uniform_float()
+        }
+    }
+    fn get_2d(&mut self) -> Point2f {
+        let _p = ProfilePhase::new(Prof::GetSample);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "current_pixel_sample_index = {}, samples_per_pixel = {}",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        if self.current_2d_dimension < self.samples_2d.len() as i32 {
+            let sample: Point2f = self.samples_2d[self.current_2d_dimension as usize]
+                [self.current_pixel_sample_index as usize];
+            self.current_2d_dimension += 1;
+            sample
+        } else {
+            // C++ call order for Point2f(rng.UniformFloat(), rng.UniformFloat());
+            let y = self.rng.uniform_float();
+            let x = self.rng.uniform_float();
+            Point2f { x: x, y: y }
+        }
+    }
+    fn request_1d_array(&mut self, n: i32) {
+        assert_eq!(self.round_count(n), n);
+        self.samples_1d_array_sizes.push(n);
+        let size: usize = (n * self.samples_per_pixel as i32) as usize;
+        let additional_samples: Vec<Float> = vec![0.0 as Float; size];
+        self.sample_array_1d.push(additional_samples);
+    }
+    fn request_2d_array(&mut self, n: i32) {
+        assert_eq!(self.round_count(n), n);
+        self.samples_2d_array_sizes.push(n);
+        let size: usize = (n * self.samples_per_pixel as i32) as usize;
+        let additional_points: Vec<Point2f> = vec![Point2f::default(); size];
+        self.sample_array_2d.push(additional_points);
+    }
+    fn round_count(&self, count: i32) -> i32 {
+        round_up_pow2_32(count)
+    }
+    fn get_1d_array(&mut self, n: i32) -> Vec<Float> {
+        let mut samples: Vec<Float> = Vec::new();
+        if self.array_1d_offset == self.sample_array_1d.len() {
+            return samples;
+        }
+        assert_eq!(self.samples_1d_array_sizes[self.array_1d_offset], n);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "self.current_pixel_sample_index ({}) < self.samples_per_pixel ({})",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        let start: usize = (self.current_pixel_sample_index * n as i64) as usize;
+        let end: usize = start + n as usize;
+        samples = self.sample_array_1d[self.array_1d_offset][start..end].to_vec();
+        self.array_1d_offset += 1;
+        samples
+    }
+    fn get_2d_array(&mut self, n: i32) -> Vec<Point2f> {
+        let mut samples: Vec<Point2f> = Vec::new();
+        if self.array_2d_offset == self.sample_array_2d.len() {
+            return samples;
+        }
+        assert_eq!(self.samples_2d_array_sizes[self.array_2d_offset], n);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "self.current_pixel_sample_index ({}) < self.samples_per_pixel ({})",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        let start: usize = (self.current_pixel_sample_index * n as i64) as usize;
+        let end: usize = start + n as usize;
+        samples = self.sample_array_2d[self.array_2d_offset][start..end].to_vec();
+        self.array_2d_offset += 1;
+        samples
+    }
+    fn start_next_sample(&mut self) -> bool {
+        self.current_1d_dimension = 0_i32;
+        self.current_2d_dimension = 0_i32;
+        // Sampler::StartNextSample()
+        // reset array offsets for next pixel sample
+        self.array_1d_offset = 0_usize;
+        self.array_2d_offset = 0_usize;
+        self.current_pixel_sample_index += 1_i64;
+        self.current_pixel_sample_index < self.samples_per_pixel
+    }
+    fn reseed(&mut self, seed: u64) {
+        self.rng.set_sequence(seed);
+    }
+    fn get_current_pixel(&self) -> Point2i {
+        self.current_pixel
+    }
+    fn get_current_sample_number(&self) -> i64 {
+        self.current_pixel_sample_index
+    }
+    fn get_samples_per_pixel(&self) -> i64 {
+        self.samples_per_pixel
+    }
+    fn dimensions_used(&self) -> i64 {
+        self.current_1d_dimension as i64 + 2_i64 * self.current_2d_dimension as i64
+    }
+}
+
+impl PixelSampler for MaxMinDistSampler {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use samplers::statistics::stratum_counts;
+
+    /// Smallest distance between two of the *points*.
+    fn min_distance(points: &[Point2f]) -> Float {
+        let mut d2: Float = ::std::f32::INFINITY;
+        for i in 0..points.len() {
+            for j in (i + 1)..points.len() {
+                let dx: Float = points[i].x - points[j].x;
+                let dy: Float = points[i].y - points[j].y;
+                d2 = d2.min(dx * dx + dy * dy);
+            }
+        }
+        d2.sqrt()
+    }
+
+    #[test]
+    fn pixel_samples_keep_their_distance() {
+        for &spp in &[4_i64, 16_i64, 64_i64, 256_i64] {
+            let mut sampler: MaxMinDistSampler = MaxMinDistSampler::new(spp, 4_i64);
+            for pixel in &[Point2i { x: 0, y: 0 }, Point2i { x: 7, y: 3 }] {
+                let samples: Vec<Point2f> = sampler.fill_pixel_2d(pixel);
+                assert_eq!(samples.len(), spp as usize);
+                // normalized by the distance of spp points on a square grid
+                let d: Float = min_distance(&samples) * (spp as Float).sqrt();
+                assert!(d > 0.6 as Float, "{} samples: {}", spp, d);
+                // a (0,m,2)-net, like the (0,2)-sequence
+                let mut nx: usize = 1_usize;
+                while nx <= spp as usize {
+                    let counts: Vec<usize> = stratum_counts(&samples, nx, spp as usize / nx);
+                    assert!(counts.iter().all(|c| *c == 1_usize), "{} x", nx);
+                    nx *= 2_usize;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sample_counts_are_rounded_to_powers_of_two() {
+        let mut params: ParamSet = ParamSet::default();
+        params.add_int(String::from("pixelsamples"), 12);
+        let sampler: Box<Sampler + Sync + Send> = MaxMinDistSampler::create(&params);
+        assert_eq!(sampler.get_samples_per_pixel(), 16_i64);
+        assert_eq!(sampler.round_count(5), 8);
+        assert_eq!(sampler.round_count(8), 8);
+        let sampler: MaxMinDistSampler = MaxMinDistSampler::new(1_i64 << 20, 4_i64);
+        assert_eq!(sampler.samples_per_pixel, 1_i64 << 16);
+    }
+}
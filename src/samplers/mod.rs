@@ -2,6 +2,7 @@
 //! samplers but also provides some common functionality for use by
 //! **Sampler** implementations.
 //!
+//! - CmjSampler
 //! - HaltonSampler
 //! - MaxMinDistSampler
 //! - RandomSampler
@@ -9,6 +10,13 @@
 //! - StratifiedSampler
 //! - ZeroTwoSequenceSampler
 //!
+//! ## Correlated Multi-Jittered Sampler
+//!
+//! The CMJ Sampler (Kensler) combines the stratification of
+//! multi-jittered sampling in 2D (one sample per row and column) with
+//! a hash based shuffling per pixel, so that neighbouring pixels use
+//! decorrelated patterns without storing any tables.
+//!
 //! ## Halton Sampler
 //!
 //! The Halton Sampler generates not only points that are guaranteed
@@ -18,6 +26,14 @@
 //!
 //! ![halton](/doc/img/cornell_box_pbrt_rust_halton.png)
 //!
+//! ## Max-Min-Distance Sampler
+//!
+//! The Max-Min-Distance Sampler (Grünschloß and Keller) uses
+//! generator matrices which were chosen to maximize the minimum
+//! distance between the points of the first 2D dimension (usually the
+//! film position), while still forming a (0,m,2)-net. The remaining
+//! dimensions use (0,2)-sequences.
+//!
 //! ## Random Sampler
 //!
 //! The Random Sampler is using the random number generetor class
@@ -36,6 +52,13 @@
 //!
 //! ![sobol](/doc/img/cornell_box_pbrt_rust_sobol.png)
 //!
+//! ## Stratified Sampler
+//!
+//! The Stratified Sampler subdivides the pixel area into rectangular
+//! regions (strata) and places one (optionally jittered) sample in
+//! each of them. Samples for the different dimensions are shuffled,
+//! higher dimensional sample arrays use Latin hypercube sampling.
+//!
 //! ## (0,2)-Sequence Sampler
 //!
 //! Certain low-discrepancy sequences allow us to satisfy two
@@ -49,7 +72,55 @@
 //! ![lowdiscrepancy](/doc/img/cornell_box_pbrt_rust_lowdiscrepancy.png)
 //!
 
+pub mod cmj;
 pub mod halton;
+pub mod maxmindist;
 pub mod random;
 pub mod sobol;
+pub mod stratified;
 pub mod zerotwosequence;
+
+/// Measures for the distribution of a pixel's samples (see
+/// **Sampler::fill_pixel_2d()**), shared by the sampler tests.
+#[cfg(test)]
+pub mod statistics {
+    use core::geometry::Point2f;
+    use core::pbrt::Float;
+
+    /// Number of *points* in each of the *nx* x *ny* strata of
+    /// [0,1)^2 (row by row).
+    pub fn stratum_counts(points: &[Point2f], nx: usize, ny: usize) -> Vec<usize> {
+        let mut counts: Vec<usize> = vec![0_usize; nx * ny];
+        for p in points {
+            assert!(p.x >= 0.0 as Float && p.x < 1.0 as Float);
+            assert!(p.y >= 0.0 as Float && p.y < 1.0 as Float);
+            let x: usize = (p.x * nx as Float) as usize;
+            let y: usize = (p.y * ny as Float) as usize;
+            counts[y * nx + x] += 1;
+        }
+        counts
+    }
+
+    /// Star discrepancy of *points*: the largest difference between
+    /// the area of a box [0,x) x [0,y) and the fraction of points in
+    /// it (brute force over the boxes touching the points).
+    pub fn star_discrepancy(points: &[Point2f]) -> Float {
+        let n: Float = points.len() as Float;
+        let mut xs: Vec<Float> = points.iter().map(|p| p.x).collect();
+        let mut ys: Vec<Float> = points.iter().map(|p| p.y).collect();
+        xs.push(1.0 as Float);
+        ys.push(1.0 as Float);
+        let mut discrepancy: Float = 0.0 as Float;
+        for x in &xs {
+            for y in &ys {
+                let area: Float = x * y;
+                let open: usize = points.iter().filter(|p| p.x < *x && p.y < *y).count();
+                let closed: usize = points.iter().filter(|p| p.x <= *x && p.y <= *y).count();
+                discrepancy = discrepancy
+                    .max(area - open as Float / n)
+                    .max(closed as Float / n - area);
+            }
+        }
+        discrepancy
+    }
+}
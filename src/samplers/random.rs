@@ -1,6 +1,8 @@
 // pbrt
 use core::geometry::{Point2f, Point2i};
+use core::paramset::ParamSet;
 use core::pbrt::Float;
+use core::profile::{Prof, ProfilePhase};
 use core::rng::Rng;
 use core::sampler::Sampler;
 
@@ -8,6 +10,7 @@ use core::sampler::Sampler;
 
 pub struct RandomSampler {
     pub samples_per_pixel: i64,
+    pub seed: u64,
     pub rng: Rng,
     // inherited from class Sampler (see sampler.h)
     pub current_pixel: Point2i,
@@ -24,6 +27,7 @@ impl RandomSampler {
     pub fn new(samples_per_pixel: i64) -> Self {
         RandomSampler {
             samples_per_pixel: samples_per_pixel,
+            seed: 0_u64,
             rng: Rng::default(),
             current_pixel: Point2i::default(),
             current_pixel_sample_index: 0_i64,
@@ -35,11 +39,28 @@ impl RandomSampler {
             array_2d_offset: 0_usize,
         }
     }
+    /// Reads "pixelsamples" (see CreateRandomSampler() in random.cpp).
+    pub fn create(params: &ParamSet) -> Box<Sampler + Sync + Send> {
+        let nsamp: i32 = params.find_one_int("pixelsamples", 4);
+        // TODO: if (PbrtOptions.quickRender) nsamp = 1;
+        Box::new(RandomSampler::new(nsamp as i64))
+    }
+    /// PCG stream for pixel *p*, so the samples of a pixel only
+    /// depend on the seed and the pixel coordinates (not on the
+    /// thread or the order the pixels are rendered in).
+    fn pixel_sequence(&self, p: &Point2i) -> u64 {
+        let mut h: u64 = self.seed.wrapping_mul(0x9e3779b97f4a7c15);
+        h ^= (p.x as u32 as u64).wrapping_mul(0x8da6b343);
+        h ^= (p.y as u32 as u64).wrapping_mul(0xd8163841) << 32;
+        h
+    }
 }
 
 impl Sampler for RandomSampler {
     fn start_pixel(&mut self, p: &Point2i) {
-        // TODO: ProfilePhase _(Prof::StartPixel);
+        let _p = ProfilePhase::new(Prof::StartPixel);
+        let sequence: u64 = self.pixel_sequence(p);
+        self.rng.set_sequence(sequence);
         for i in 0..self.sample_array_1d.len() {
             for j in 0..self.sample_array_1d[i].len() {
                 self.sample_array_1d[i][j] = self.rng.uniform_float();
@@ -61,12 +82,12 @@ impl Sampler for RandomSampler {
         self.array_2d_offset = 0_usize;
     }
     fn get_1d(&mut self) -> Float {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         assert!(self.current_pixel_sample_index < self.samples_per_pixel);
         self.rng.uniform_float()
     }
     fn get_2d(&mut self) -> Point2f {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         assert!(self.current_pixel_sample_index < self.samples_per_pixel);
         // C++: call x first
         let x = self.rng.uniform_float();
@@ -74,6 +95,7 @@ impl Sampler for RandomSampler {
         Point2f { x: x, y: y }
     }
     fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
         self.rng.set_sequence(seed);
     }
     fn request_2d_array(&mut self, n: i32) {
@@ -126,6 +148,7 @@ impl Clone for RandomSampler {
     fn clone(&self) -> RandomSampler {
         RandomSampler {
             samples_per_pixel: self.samples_per_pixel,
+            seed: self.seed,
             rng: Rng::default(),
             current_pixel: self.current_pixel,
             current_pixel_sample_index: self.current_pixel_sample_index,
@@ -138,3 +161,48 @@ impl Clone for RandomSampler {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel_samples(sampler: &mut RandomSampler, p: &Point2i) -> Vec<Float> {
+        sampler.start_pixel(p);
+        let mut values: Vec<Float> = Vec::new();
+        loop {
+            values.push(sampler.get_1d());
+            let u: Point2f = sampler.get_2d();
+            values.push(u.x);
+            values.push(u.y);
+            for u in sampler.get_2d_array(3) {
+                values.push(u.x);
+                values.push(u.y);
+            }
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn identical_seeds_reproduce_pixels() {
+        let p: Point2i = Point2i { x: 3, y: 5 };
+        let mut a: RandomSampler = RandomSampler::new(4_i64);
+        let mut b: RandomSampler = RandomSampler::new(4_i64);
+        a.request_2d_array(3);
+        b.request_2d_array(3);
+        a.reseed(7_u64);
+        b.reseed(7_u64);
+        // the order the pixels are rendered in doesn't matter
+        let other: Vec<Float> = pixel_samples(&mut a, &Point2i { x: 4, y: 5 });
+        let samples: Vec<Float> = pixel_samples(&mut a, &p);
+        assert_eq!(samples.len(), 4 * (3 + 2 * 3));
+        assert_eq!(samples, pixel_samples(&mut b, &p));
+        assert!(samples.iter().all(|u| *u >= 0.0 as Float && *u < 1.0 as Float));
+        // different pixels and seeds diverge
+        assert_ne!(samples, other);
+        b.reseed(8_u64);
+        assert_ne!(samples, pixel_samples(&mut b, &p));
+    }
+}
@@ -1,10 +1,12 @@
 // pbrt
 use core::geometry::{Bounds2i, Point2f, Point2i, Vector2i};
 use core::lowdiscrepancy::{sobol_interval_to_index, sobol_sample};
+use core::paramset::ParamSet;
 use core::pbrt::Float;
 use core::pbrt::{clamp_t, is_power_of_2, log_2_int_u32, round_up_pow2_32, round_up_pow2_64};
+use core::profile::{Prof, ProfilePhase};
 use core::rng::FLOAT_ONE_MINUS_EPSILON;
-use core::sampler::{GlobalSampler, Sampler};
+use core::sampler::{remap_global_dimension, GlobalSampler, Sampler, ARRAY_START_DIM};
 use core::sobolmatrices::NUM_SOBOL_DIMENSIONS;
 
 // see sobol.h
@@ -14,6 +16,9 @@ pub struct SobolSampler {
     pub sample_bounds: Bounds2i,
     pub resolution: i32,
     pub log_2_resolution: i32,
+    /// use the pixel center (instead of a Sobol sample) for the
+    /// camera's film position, e.g. for debugging
+    pub sample_at_pixel_center: bool, // default: false
     // inherited from class GlobalSampler (see sampler.h)
     pub dimension: i64,
     pub interval_sample_index: u64,
@@ -31,7 +36,11 @@ pub struct SobolSampler {
 }
 
 impl SobolSampler {
-    pub fn new(samples_per_pixel: i64, sample_bounds: Bounds2i) -> Self {
+    pub fn new(
+        samples_per_pixel: i64,
+        sample_bounds: Bounds2i,
+        sample_at_pixel_center: bool,
+    ) -> Self {
         let mut samples_per_pixel: i64 = samples_per_pixel;
         if !is_power_of_2(samples_per_pixel) {
             samples_per_pixel = round_up_pow2_64(samples_per_pixel);
@@ -51,9 +60,10 @@ impl SobolSampler {
             sample_bounds: sample_bounds,
             resolution: resolution,
             log_2_resolution: log_2_resolution,
+            sample_at_pixel_center: sample_at_pixel_center,
             dimension: 0_i64,
             interval_sample_index: 0_u64,
-            array_start_dim: 5_i64, // static const int arrayStartDim = 5;
+            array_start_dim: ARRAY_START_DIM,
             array_end_dim: 0_i64,
             current_pixel: Point2i::default(),
             current_pixel_sample_index: 0_i64,
@@ -65,6 +75,14 @@ impl SobolSampler {
             array_2d_offset: 0_usize,
         }
     }
+    /// Reads "pixelsamples" and "samplepixelcenter" (see
+    /// CreateSobolSampler() in sobol.cpp).
+    pub fn create(params: &ParamSet, sample_bounds: Bounds2i) -> Box<Sampler + Sync + Send> {
+        let nsamp: i32 = params.find_one_int("pixelsamples", 16);
+        // TODO: if (PbrtOptions.quickRender) nsamp = 1;
+        let sample_at_center: bool = params.find_one_bool("samplepixelcenter", false);
+        Box::new(SobolSampler::new(nsamp as i64, sample_bounds, sample_at_center))
+    }
     pub fn get_index_for_sample(&self, sample_num: u64) -> u64 {
         let v: Vector2i = self.current_pixel - self.sample_bounds.p_min;
         sobol_interval_to_index(
@@ -74,6 +92,9 @@ impl SobolSampler {
         )
     }
     pub fn sample_dimension(&self, index: u64, dim: i64) -> Float {
+        if self.sample_at_pixel_center && (dim == 0 || dim == 1) {
+            return 0.5 as Float;
+        }
         if dim >= NUM_SOBOL_DIMENSIONS as i64 {
             panic!(
                 "SobolSampler can only sample up to {} dimensions! Exiting.",
@@ -96,7 +117,7 @@ impl SobolSampler {
 
 impl Sampler for SobolSampler {
     fn start_pixel(&mut self, p: &Point2i) {
-        // TODO: ProfilePhase _(Prof::StartPixel);
+        let _p = ProfilePhase::new(Prof::StartPixel);
         // Sampler::StartPixel(p);
         self.current_pixel = *p;
         self.current_pixel_sample_index = 0_i64;
@@ -133,10 +154,13 @@ impl Sampler for SobolSampler {
         assert!(self.array_end_dim == dim);
     }
     fn get_1d(&mut self) -> Float {
-        // TODO: ProfilePhase _(Prof::GetSample);
-        if self.dimension >= self.array_start_dim && self.dimension < self.array_end_dim {
-            self.dimension = self.array_end_dim;
-        }
+        let _p = ProfilePhase::new(Prof::GetSample);
+        self.dimension = remap_global_dimension(
+            self.dimension,
+            1_i64,
+            self.array_start_dim,
+            self.array_end_dim,
+        );
         // call first (in C++: return SampleDimension(intervalSampleIndex, dimension++));
         let ret: Float = self.sample_dimension(self.interval_sample_index, self.dimension);
         self.dimension += 1;
@@ -144,10 +168,13 @@ impl Sampler for SobolSampler {
         ret
     }
     fn get_2d(&mut self) -> Point2f {
-        // TODO: ProfilePhase _(Prof::GetSample);
-        if self.dimension + 1 >= self.array_start_dim && self.dimension < self.array_end_dim {
-            self.dimension = self.array_end_dim;
-        }
+        let _p = ProfilePhase::new(Prof::GetSample);
+        self.dimension = remap_global_dimension(
+            self.dimension,
+            2_i64,
+            self.array_start_dim,
+            self.array_end_dim,
+        );
         // C++: call y first
         let y = self.sample_dimension(self.interval_sample_index, self.dimension + 1);
         let x = self.sample_dimension(self.interval_sample_index, self.dimension);
@@ -206,6 +233,9 @@ impl Sampler for SobolSampler {
     fn get_samples_per_pixel(&self) -> i64 {
         self.samples_per_pixel
     }
+    fn dimensions_used(&self) -> i64 {
+        self.dimension
+    }
 }
 
 impl GlobalSampler for SobolSampler {
@@ -228,6 +258,7 @@ impl Clone for SobolSampler {
             sample_bounds: self.sample_bounds,
             resolution: self.resolution,
             log_2_resolution: self.log_2_resolution,
+            sample_at_pixel_center: self.sample_at_pixel_center,
             dimension: self.dimension,
             interval_sample_index: self.interval_sample_index,
             array_start_dim: self.array_start_dim,
@@ -243,3 +274,46 @@ impl Clone for SobolSampler {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_two_dimensions_match_sobol() {
+        // the first eight points of the Sobol' sequence
+        let expected: [(Float, Float); 8] = [
+            (0.0, 0.0),
+            (0.5, 0.5),
+            (0.25, 0.75),
+            (0.75, 0.25),
+            (0.125, 0.625),
+            (0.625, 0.125),
+            (0.375, 0.375),
+            (0.875, 0.875),
+        ];
+        for (i, p) in expected.iter().enumerate() {
+            assert_eq!(sobol_sample(i as i64, 0, 0_u64), p.0, "index {}", i);
+            assert_eq!(sobol_sample(i as i64, 1, 0_u64), p.1, "index {}", i);
+        }
+        // with 2x2 pixels each pixel gets the (scaled) Sobol' points
+        // falling into it: (0, 0) and (0.375, 0.375) for the first
+        // pixel, (0.5, 0.5) and (0.875, 0.875) for the last one
+        let sample_bounds: Bounds2i = Bounds2i {
+            p_min: Point2i { x: 0, y: 0 },
+            p_max: Point2i { x: 2, y: 2 },
+        };
+        let mut sampler: SobolSampler = SobolSampler::new(4_i64, sample_bounds, false);
+        assert_eq!(sampler.log_2_resolution, 1);
+        for pixel in &[Point2i { x: 0, y: 0 }, Point2i { x: 1, y: 1 }] {
+            sampler.start_pixel(pixel);
+            assert_eq!(sampler.get_2d(), Point2f { x: 0.0, y: 0.0 });
+            assert!(sampler.start_next_sample());
+            assert_eq!(sampler.get_2d(), Point2f { x: 0.75, y: 0.75 });
+        }
+        // unless the pixel center is requested
+        let mut center: SobolSampler = SobolSampler::new(4_i64, sample_bounds, true);
+        center.start_pixel(&Point2i { x: 1, y: 0 });
+        assert_eq!(center.get_2d(), Point2f { x: 0.5, y: 0.5 });
+    }
+}
@@ -0,0 +1,314 @@
+// pbrt
+use core::geometry::{Point2f, Point2i};
+use core::paramset::ParamSet;
+use core::pbrt::{round_up_square_32, Float};
+use core::profile::{Prof, ProfilePhase};
+use core::rng::Rng;
+use core::sampler::{PixelSampler, Sampler};
+use core::sampling::{shuffle, stratified_sample_1d, stratified_sample_2d};
+
+// see stratified.h
+
+#[derive(Debug, Clone)]
+pub struct StratifiedSampler {
+    pub samples_per_pixel: i64,
+    pub x_pixel_samples: i32,
+    pub y_pixel_samples: i32,
+    pub jitter_samples: bool,
+    // inherited from class PixelSampler (see sampler.h)
+    pub samples_1d: Vec<Vec<Float>>,
+    pub samples_2d: Vec<Vec<Point2f>>,
+    pub current_1d_dimension: i32,
+    pub current_2d_dimension: i32,
+    pub rng: Rng,
+    // inherited from class Sampler (see sampler.h)
+    pub current_pixel: Point2i,
+    pub current_pixel_sample_index: i64,
+    pub samples_1d_array_sizes: Vec<i32>,
+    pub samples_2d_array_sizes: Vec<i32>,
+    pub sample_array_1d: Vec<Vec<Float>>,
+    pub sample_array_2d: Vec<Vec<Point2f>>,
+    pub array_1d_offset: usize,
+    pub array_2d_offset: usize,
+}
+
+impl StratifiedSampler {
+    pub fn new(
+        x_pixel_samples: i32,
+        y_pixel_samples: i32,
+        jitter_samples: bool,
+        n_sampled_dimensions: i64,
+    ) -> Self {
+        let samples_per_pixel: i64 = (x_pixel_samples * y_pixel_samples) as i64;
+        let mut ss: StratifiedSampler = StratifiedSampler {
+            samples_per_pixel: samples_per_pixel,
+            x_pixel_samples: x_pixel_samples,
+            y_pixel_samples: y_pixel_samples,
+            jitter_samples: jitter_samples,
+            samples_1d: Vec::new(),
+            samples_2d: Vec::new(),
+            current_1d_dimension: 0_i32,
+            current_2d_dimension: 0_i32,
+            rng: Rng::default(),
+            current_pixel: Point2i::default(),
+            current_pixel_sample_index: 0_i64,
+            samples_1d_array_sizes: Vec::new(),
+            samples_2d_array_sizes: Vec::new(),
+            sample_array_1d: Vec::new(),
+            sample_array_2d: Vec::new(),
+            array_1d_offset: 0_usize,
+            array_2d_offset: 0_usize,
+        };
+        for _i in 0..n_sampled_dimensions {
+            let additional_1d: Vec<Float> = vec![0.0; samples_per_pixel as usize];
+            let additional_2d: Vec<Point2f> = vec![Point2f::default(); samples_per_pixel as usize];
+            ss.samples_1d.push(additional_1d);
+            ss.samples_2d.push(additional_2d);
+        }
+        ss
+    }
+    /// Reads "xsamples", "ysamples", "jitter", and "dimensions" (see
+    /// CreateStratifiedSampler() in stratified.cpp).
+    pub fn create(params: &ParamSet) -> Box<Sampler + Sync + Send> {
+        let jitter: bool = params.find_one_bool("jitter", true);
+        let xsamp: i32 = params.find_one_int("xsamples", 4);
+        let ysamp: i32 = params.find_one_int("ysamples", 4);
+        let sd: i32 = params.find_one_int("dimensions", 4);
+        // TODO: if (PbrtOptions.quickRender) nsamp = 1;
+        Box::new(StratifiedSampler::new(xsamp, ysamp, jitter, sd as i64))
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel(&mut self, p: &Point2i) {
+        let _p = ProfilePhase::new(Prof::StartPixel);
+        let count: i32 = self.samples_per_pixel as i32;
+        // generate single stratified samples for the pixel
+        for i in 0..self.samples_1d.len() {
+            let samples: &mut [Float] = self.samples_1d[i].as_mut_slice();
+            stratified_sample_1d(samples, count, &mut self.rng, self.jitter_samples);
+            shuffle(samples, count, 1, &mut self.rng);
+        }
+        for i in 0..self.samples_2d.len() {
+            let samples: &mut [Point2f] = self.samples_2d[i].as_mut_slice();
+            stratified_sample_2d(
+                samples,
+                self.x_pixel_samples,
+                self.y_pixel_samples,
+                &mut self.rng,
+                self.jitter_samples,
+            );
+            shuffle(samples, count, 1, &mut self.rng);
+        }
+        // generate arrays of stratified samples for the pixel
+        for i in 0..self.samples_1d_array_sizes.len() {
+            let n: usize = self.samples_1d_array_sizes[i] as usize;
+            for j in 0..self.samples_per_pixel as usize {
+                let samples: &mut [Float] = &mut self.sample_array_1d[i][j * n..(j + 1) * n];
+                stratified_sample_1d(samples, n as i32, &mut self.rng, self.jitter_samples);
+                shuffle(samples, n as i32, 1, &mut self.rng);
+            }
+        }
+        for i in 0..self.samples_2d_array_sizes.len() {
+            let n: usize = self.samples_2d_array_sizes[i] as usize;
+            // the array sizes are squares (see **round_count()**)
+            let r: i32 = (n as Float).sqrt().round() as i32;
+            for j in 0..self.samples_per_pixel as usize {
+                let samples: &mut [Point2f] = &mut self.sample_array_2d[i][j * n..(j + 1) * n];
+                stratified_sample_2d(samples, r, r, &mut self.rng, self.jitter_samples);
+                shuffle(samples, n as i32, 1, &mut self.rng);
+            }
+        }
+        // PixelSampler::StartPixel(p);
+        self.current_pixel = *p;
+        self.current_pixel_sample_index = 0_i64;
+        self.current_1d_dimension = 0_i32;
+        self.current_2d_dimension = 0_i32;
+        // reset array offsets for next pixel sample
+        self.array_1d_offset = 0_usize;
+        self.array_2d_offset = 0_usize;
+    }
+    fn get_1d(&mut self) -> Float {
+        let _p = ProfilePhase::new(Prof::GetSample);
+        assert!(self.current_pixel_sample_index < self.samples_per_pixel);
+        if self.current_1d_dimension < self.samples_1d.len() as i32 {
+            let sample: Float = self.samples_1d[self.current_1d_dimension as usize]
+                [self.current_pixel_sample_index as usize];
+            self.current_1d_dimension += 1;
+            sample
+        } else {
+            self.rng.uniform_float()
+        }
+    }
+    fn get_2d(&mut self) -> Point2f {
+        let _p = ProfilePhase::new(Prof::GetSample);
+        assert!(self.current_pixel_sample_index < self.samples_per_pixel);
+        if self.current_2d_dimension < self.samples_2d.len() as i32 {
+            let sample: Point2f = self.samples_2d[self.current_2d_dimension as usize]
+                [self.current_pixel_sample_index as usize];
+            self.current_2d_dimension += 1;
+            sample
+        } else {
+            // C++ call order for Point2f(rng.UniformFloat(), rng.UniformFloat());
+            let y = self.rng.uniform_float();
+            let x = self.rng.uniform_float();
+            Point2f { x: x, y: y }
+        }
+    }
+    fn request_1d_array(&mut self, n: i32) {
+        assert_eq!(self.round_count(n), n);
+        self.samples_1d_array_sizes.push(n);
+        let size: usize = (n * self.samples_per_pixel as i32) as usize;
+        let additional_samples: Vec<Float> = vec![0.0 as Float; size];
+        self.sample_array_1d.push(additional_samples);
+    }
+    fn request_2d_array(&mut self, n: i32) {
+        assert_eq!(self.round_count(n), n);
+        self.samples_2d_array_sizes.push(n);
+        let size: usize = (n * self.samples_per_pixel as i32) as usize;
+        let additional_points: Vec<Point2f> = vec![Point2f::default(); size];
+        self.sample_array_2d.push(additional_points);
+    }
+    /// Rounds up to the next square, so 2D arrays can be stratified
+    /// on a square grid.
+    fn round_count(&self, count: i32) -> i32 {
+        round_up_square_32(count)
+    }
+    fn get_1d_array(&mut self, n: i32) -> Vec<Float> {
+        let mut samples: Vec<Float> = Vec::new();
+        if self.array_1d_offset == self.sample_array_1d.len() {
+            return samples;
+        }
+        assert_eq!(self.samples_1d_array_sizes[self.array_1d_offset], n);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "self.current_pixel_sample_index ({}) < self.samples_per_pixel ({})",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        let start: usize = (self.current_pixel_sample_index * n as i64) as usize;
+        let end: usize = start + n as usize;
+        samples = self.sample_array_1d[self.array_1d_offset][start..end].to_vec();
+        self.array_1d_offset += 1;
+        samples
+    }
+    fn get_2d_array(&mut self, n: i32) -> Vec<Point2f> {
+        let mut samples: Vec<Point2f> = Vec::new();
+        if self.array_2d_offset == self.sample_array_2d.len() {
+            return samples;
+        }
+        assert_eq!(self.samples_2d_array_sizes[self.array_2d_offset], n);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "self.current_pixel_sample_index ({}) < self.samples_per_pixel ({})",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        let start: usize = (self.current_pixel_sample_index * n as i64) as usize;
+        let end: usize = start + n as usize;
+        samples = self.sample_array_2d[self.array_2d_offset][start..end].to_vec();
+        self.array_2d_offset += 1;
+        samples
+    }
+    fn start_next_sample(&mut self) -> bool {
+        self.current_1d_dimension = 0_i32;
+        self.current_2d_dimension = 0_i32;
+        // Sampler::StartNextSample()
+        // reset array offsets for next pixel sample
+        self.array_1d_offset = 0_usize;
+        self.array_2d_offset = 0_usize;
+        self.current_pixel_sample_index += 1_i64;
+        self.current_pixel_sample_index < self.samples_per_pixel
+    }
+    fn reseed(&mut self, seed: u64) {
+        self.rng.set_sequence(seed);
+    }
+    fn get_current_pixel(&self) -> Point2i {
+        self.current_pixel
+    }
+    fn get_current_sample_number(&self) -> i64 {
+        self.current_pixel_sample_index
+    }
+    fn get_samples_per_pixel(&self) -> i64 {
+        self.samples_per_pixel
+    }
+    fn dimensions_used(&self) -> i64 {
+        self.current_1d_dimension as i64 + 2_i64 * self.current_2d_dimension as i64
+    }
+}
+
+impl PixelSampler for StratifiedSampler {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use samplers::random::RandomSampler;
+    use samplers::statistics::{star_discrepancy, stratum_counts};
+
+    #[test]
+    fn one_sample_per_stratum_and_low_discrepancy() {
+        let mut stratified: StratifiedSampler = StratifiedSampler::new(4, 4, true, 1_i64);
+        let mut random: RandomSampler = RandomSampler::new(16_i64);
+        let mut stratified_discrepancy: Float = 0.0 as Float;
+        let mut random_discrepancy: Float = 0.0 as Float;
+        for i in 0..8 {
+            let pixel: Point2i = Point2i { x: i, y: 2 * i };
+            let samples: Vec<Point2f> = stratified.fill_pixel_2d(&pixel);
+            assert_eq!(samples.len(), 16_usize);
+            assert!(stratum_counts(&samples, 4, 4).iter().all(|c| *c == 1_usize));
+            stratified_discrepancy += star_discrepancy(&samples);
+            random_discrepancy += star_discrepancy(&random.fill_pixel_2d(&pixel));
+        }
+        assert!(
+            stratified_discrepancy < random_discrepancy,
+            "{} >= {}",
+            stratified_discrepancy,
+            random_discrepancy
+        );
+    }
+
+    #[test]
+    fn arrays_are_rounded_to_squares_and_stratified() {
+        let mut sampler: StratifiedSampler = StratifiedSampler::new(2, 2, true, 1_i64);
+        assert_eq!(sampler.round_count(7), 9);
+        assert_eq!(sampler.round_count(16), 16);
+        let n: i32 = sampler.round_count(7);
+        sampler.request_2d_array(n);
+        sampler.start_pixel(&Point2i { x: 1, y: 2 });
+        loop {
+            let samples: Vec<Point2f> = sampler.get_2d_array(9);
+            assert_eq!(samples.len(), 9_usize);
+            // one sample per 1/3 x 1/3 cell
+            assert!(stratum_counts(&samples, 3, 3).iter().all(|c| *c == 1_usize));
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn samples_without_jitter_are_stratum_centers() {
+        let mut sampler: StratifiedSampler = StratifiedSampler::new(2, 2, false, 1_i64);
+        sampler.request_2d_array(9);
+        sampler.start_pixel(&Point2i { x: 0, y: 0 });
+        let is_center = |u: Float, n: i32| -> bool {
+            let v: Float = u * n as Float - 0.5 as Float;
+            (v - v.round()).abs() < 1e-5 as Float
+        };
+        let mut pixel_samples: Vec<Point2f> = Vec::new();
+        loop {
+            let u: Float = sampler.get_1d();
+            assert!(is_center(u, 4), "{}", u);
+            let p: Point2f = sampler.get_2d();
+            assert!(is_center(p.x, 2) && is_center(p.y, 2), "{:?}", p);
+            pixel_samples.push(p);
+            for p in sampler.get_2d_array(9) {
+                assert!(is_center(p.x, 3) && is_center(p.y, 3), "{:?}", p);
+            }
+            if !sampler.start_next_sample() {
+                break;
+            }
+        }
+        assert!(stratum_counts(&pixel_samples, 2, 2).iter().all(|c| *c == 1_usize));
+    }
+}
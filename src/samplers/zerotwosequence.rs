@@ -1,8 +1,10 @@
 // pbrt
 use core::geometry::{Point2f, Point2i};
 use core::lowdiscrepancy::{sobol_2d, van_der_corput};
-use core::pbrt::round_up_pow2_32;
+use core::paramset::ParamSet;
 use core::pbrt::Float;
+use core::pbrt::{is_power_of_2, round_up_pow2_32, round_up_pow2_64};
+use core::profile::{Prof, ProfilePhase};
 use core::rng::Rng;
 use core::sampler::{PixelSampler, Sampler};
 
@@ -60,7 +62,10 @@ impl Default for ZeroTwoSequenceSampler {
 }
 
 impl ZeroTwoSequenceSampler {
+    /// Rounds *samples_per_pixel* up to a power of 2, because
+    /// (0,2)-sequences are only well stratified for powers of 2.
     pub fn new(samples_per_pixel: i64, n_sampled_dimensions: i64) -> Self {
+        let samples_per_pixel: i64 = round_up_pow2_64(samples_per_pixel);
         let mut lds: ZeroTwoSequenceSampler = ZeroTwoSequenceSampler {
             samples_per_pixel: samples_per_pixel,
             n_sampled_dimensions: n_sampled_dimensions,
@@ -87,6 +92,21 @@ impl ZeroTwoSequenceSampler {
         }
         lds
     }
+    /// Reads "pixelsamples" and "dimensions" (see
+    /// CreateZeroTwoSequenceSampler() in zerotwosequence.cpp).
+    pub fn create(params: &ParamSet) -> Box<Sampler + Sync + Send> {
+        let nsamp: i32 = params.find_one_int("pixelsamples", 16);
+        let sd: i32 = params.find_one_int("dimensions", 4);
+        // TODO: if (PbrtOptions.quickRender) nsamp = 1;
+        if !is_power_of_2(nsamp as i64) {
+            println!(
+                "WARNING: Pixel samples being rounded up to power of 2 (from {} to {}).",
+                nsamp,
+                round_up_pow2_64(nsamp as i64)
+            );
+        }
+        Box::new(ZeroTwoSequenceSampler::new(nsamp as i64, sd as i64))
+    }
     pub fn get_current_sample_number(&self) -> i64 {
         self.current_pixel_sample_index
     }
@@ -94,7 +114,7 @@ impl ZeroTwoSequenceSampler {
 
 impl Sampler for ZeroTwoSequenceSampler {
     fn start_pixel(&mut self, p: &Point2i) {
-        // TODO: ProfilePhase _(Prof::StartPixel);
+        let _p = ProfilePhase::new(Prof::StartPixel);
         // generate 1D and 2D pixel sample components using $(0,2)$-sequence
         for samples in &mut self.samples_1d {
             van_der_corput(1, self.samples_per_pixel as i32, samples, &mut self.rng);
@@ -129,7 +149,7 @@ impl Sampler for ZeroTwoSequenceSampler {
         self.array_2d_offset = 0_usize;
     }
     fn get_1d(&mut self) -> Float {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         assert!(
             self.current_pixel_sample_index < self.samples_per_pixel,
             "current_pixel_sample_index = {}, samples_per_pixel = {}",
@@ -146,7 +166,7 @@ impl Sampler for ZeroTwoSequenceSampler {
         }
     }
     fn get_2d(&mut self) -> Point2f {
-        // TODO: ProfilePhase _(Prof::GetSample);
+        let _p = ProfilePhase::new(Prof::GetSample);
         assert!(
             self.current_pixel_sample_index < self.samples_per_pixel,
             "current_pixel_sample_index = {}, samples_per_pixel = {}",
@@ -165,6 +185,13 @@ impl Sampler for ZeroTwoSequenceSampler {
             Point2f { x: x, y: y }
         }
     }
+    fn request_1d_array(&mut self, n: i32) {
+        assert_eq!(self.round_count(n), n);
+        self.samples_1d_array_sizes.push(n);
+        let size: usize = (n * self.samples_per_pixel as i32) as usize;
+        let additional_samples: Vec<Float> = vec![0.0 as Float; size];
+        self.sample_array_1d.push(additional_samples);
+    }
     fn request_2d_array(&mut self, n: i32) {
         assert_eq!(self.round_count(n), n);
         self.samples_2d_array_sizes.push(n);
@@ -175,6 +202,24 @@ impl Sampler for ZeroTwoSequenceSampler {
     fn round_count(&self, count: i32) -> i32 {
         round_up_pow2_32(count)
     }
+    fn get_1d_array(&mut self, n: i32) -> Vec<Float> {
+        let mut samples: Vec<Float> = Vec::new();
+        if self.array_1d_offset == self.sample_array_1d.len() {
+            return samples;
+        }
+        assert_eq!(self.samples_1d_array_sizes[self.array_1d_offset], n);
+        assert!(
+            self.current_pixel_sample_index < self.samples_per_pixel,
+            "self.current_pixel_sample_index ({}) < self.samples_per_pixel ({})",
+            self.current_pixel_sample_index,
+            self.samples_per_pixel
+        );
+        let start: usize = (self.current_pixel_sample_index * n as i64) as usize;
+        let end: usize = start + n as usize;
+        samples = self.sample_array_1d[self.array_1d_offset][start..end].to_vec();
+        self.array_1d_offset += 1;
+        samples
+    }
     fn get_2d_array(&mut self, n: i32) -> Vec<Point2f> {
         let mut samples: Vec<Point2f> = Vec::new();
         if self.array_2d_offset == self.sample_array_2d.len() {
@@ -215,6 +260,39 @@ impl Sampler for ZeroTwoSequenceSampler {
     fn get_samples_per_pixel(&self) -> i64 {
         self.samples_per_pixel
     }
+    fn dimensions_used(&self) -> i64 {
+        self.current_1d_dimension as i64 + 2_i64 * self.current_2d_dimension as i64
+    }
 }
 
 impl PixelSampler for ZeroTwoSequenceSampler {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use samplers::statistics::stratum_counts;
+
+    #[test]
+    fn every_elementary_interval_holds_one_sample() {
+        let mut sampler: ZeroTwoSequenceSampler = ZeroTwoSequenceSampler::new(3_i64, 1_i64);
+        assert_eq!(sampler.samples_per_pixel, 4_i64);
+        let n: i32 = sampler.round_count(13);
+        assert_eq!(n, 16);
+        sampler.request_2d_array(n);
+        for pixel in &[Point2i { x: 0, y: 0 }, Point2i { x: 7, y: 3 }] {
+            sampler.start_pixel(pixel);
+            loop {
+                let samples: Vec<Point2f> = sampler.get_2d_array(n);
+                assert_eq!(samples.len(), 16_usize);
+                // all elementary intervals of area 1/16
+                for &(nx, ny) in &[(1, 16), (2, 8), (4, 4), (8, 2), (16, 1)] {
+                    let counts: Vec<usize> = stratum_counts(&samples, nx, ny);
+                    assert!(counts.iter().all(|c| *c == 1_usize), "{} x {}", nx, ny);
+                }
+                if !sampler.start_next_sample() {
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
 // std
+use std::cell::Cell;
 use std::sync::Arc;
 // pbrt
 use core::geometry::{
@@ -9,13 +10,27 @@ use core::geometry::{Bounds3f, Normal3f, Point2f, Point3f, Ray, Vector2f, Vector
 use core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
 use core::material::Material;
 use core::paramset::ParamSet;
-use core::pbrt::Float;
 use core::pbrt::{clamp_t, float_to_bits, lerp};
+use core::pbrt::{Float, PI};
+use core::profile::{Prof, ProfilePhase};
 use core::shape::Shape;
 use core::transform::Transform;
 
 // see curve.h
 
+thread_local! {
+    /// Per thread number of full ray-curve intersection tests (see
+    /// *nTests* in curve.cpp)
+    static N_TESTS: Cell<u64> = Cell::new(0_u64);
+}
+
+/// Number of full ray-curve intersection tests (including the Bezier
+/// subdivision) the calling thread did so far. Rays rejected by
+/// **object_bound_may_intersect()** don't count.
+pub fn curve_intersection_tests() -> u64 {
+    N_TESTS.with(|n| n.get())
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum CurveType {
     Flat,
@@ -44,9 +59,16 @@ impl CurveCommon {
         if let Some(norm) = norm {
             let n0: Normal3f = norm[0].normalize();
             let n1: Normal3f = norm[1].normalize();
+            // allow angles up to 180 degrees (see **ribbon_normal()**)
             let normal_angle: Float =
-                clamp_t(nrm_dot_nrm(&n0, &n1), 0.0 as Float, 1.0 as Float).acos();
-            let inv_sin_normal_angle: Float = 1.0 as Float / normal_angle.sin();
+                clamp_t(nrm_dot_nrm(&n0, &n1), -1.0 as Float, 1.0 as Float).acos();
+            let sin_normal_angle: Float = normal_angle.sin();
+            // (nearly) parallel or anti-parallel normals can't be slerped
+            let inv_sin_normal_angle: Float = if sin_normal_angle > 1e-4 as Float {
+                1.0 as Float / sin_normal_angle
+            } else {
+                0.0 as Float
+            };
             CurveCommon {
                 curve_type: curve_type,
                 cp_obj: [c[0], c[1], c[2], c[3]],
@@ -66,6 +88,34 @@ impl CurveCommon {
             }
         }
     }
+    /// Spherical linear interpolation of the two ribbon normals, so
+    /// the ribbon doesn't pinch in the middle. For anti-parallel
+    /// normals the normal is rotated around the curve's overall
+    /// direction.
+    pub fn ribbon_normal(&self, u: Float) -> Normal3f {
+        if self.inv_sin_normal_angle != 0.0 as Float {
+            let sin0: Float =
+                ((1.0 as Float - u) * self.normal_angle).sin() * self.inv_sin_normal_angle;
+            let sin1: Float = (u * self.normal_angle).sin() * self.inv_sin_normal_angle;
+            return (self.n[0] * sin0 + self.n[1] * sin1).normalize();
+        }
+        if nrm_dot_nrm(&self.n[0], &self.n[1]) >= 0.0 as Float {
+            // (nearly) identical normals
+            return (self.n[0] * (1.0 as Float - u) + self.n[1] * u).normalize();
+        }
+        // anti-parallel normals
+        let axis: Vector3f = self.cp_obj[3] - self.cp_obj[0];
+        let mut perp: Vector3f = nrm_cross_vec3(&self.n[0], &axis);
+        if perp.length_squared() == 0.0 as Float {
+            let mut v2: Vector3f = Vector3f::default();
+            let mut v3: Vector3f = Vector3f::default();
+            vec3_coordinate_system(&Vector3f::from(self.n[0]), &mut v2, &mut v3);
+            perp = v2;
+        }
+        let perp: Normal3f = Normal3f::from(perp.normalize());
+        let theta: Float = u * PI;
+        (self.n[0] * theta.cos() + perp * theta.sin()).normalize()
+    }
 }
 
 #[derive(Clone)]
@@ -253,11 +303,7 @@ impl Curve {
             let mut n_hit: Normal3f = Normal3f::default();
             if self.common.curve_type == CurveType::Ribbon {
                 // scale _hitWidth_ based on ribbon orientation
-                let sin0: Float = ((1.0 as Float - u) * self.common.normal_angle).sin()
-                    * self.common.inv_sin_normal_angle;
-                let sin1: Float =
-                    (u * self.common.normal_angle).sin() * self.common.inv_sin_normal_angle;
-                n_hit = self.common.n[0] * sin0 + self.common.n[1] * sin1;
+                n_hit = self.common.ribbon_normal(u);
                 hit_width *= nrm_abs_dot_vec3(&n_hit, &ray.d) / ray_length;
             }
 
@@ -367,9 +413,16 @@ impl Shape for Curve {
         // in C++: Bounds3f Shape::WorldBound() const { return (*ObjectToWorld)(ObjectBound()); }
         self.object_to_world.transform_bounds(&self.object_bound())
     }
+    fn object_bound_may_intersect(&self, r: &Ray) -> bool {
+        // transform _Ray_ to object space and test against object bound
+        let ray: Ray = self.world_to_object.transform_ray(r);
+        let mut t0: Float = 0.0 as Float;
+        let mut t1: Float = 0.0 as Float;
+        self.object_bound().intersect_b(&ray, &mut t0, &mut t1)
+    }
     fn intersect(&self, r: &Ray) -> Option<(SurfaceInteraction, Float)> {
-        // TODO: ProfilePhase p(isect ? Prof::CurveIntersect : Prof::CurveIntersectP);
-        // TODO: ++nTests;
+        let _p = ProfilePhase::new(Prof::CurveIntersect);
+        N_TESTS.with(|n| n.set(n.get() + 1_u64));
         // transform _Ray_ to object space
         let mut o_err: Vector3f = Vector3f::default();
         let mut d_err: Vector3f = Vector3f::default();
@@ -477,6 +530,7 @@ impl Shape for Curve {
         )
     }
     fn intersect_p(&self, r: &Ray) -> bool {
+        let _p = ProfilePhase::new(Prof::CurveIntersectP);
         if let Some((_isect, _t_hit)) = self.intersect(r) {
             true
         } else {
@@ -677,3 +731,163 @@ fn log2(v: Float) -> i32 {
     }
     (bits >> 23) - 127 + (bits & one_or_zero)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::primitive::{GeometricPrimitive, Primitive};
+
+    fn ray(x: Float) -> Ray {
+        Ray {
+            o: Point3f {
+                x: x,
+                y: 0.0,
+                z: 5.0,
+            },
+            d: Vector3f {
+                x: 0.0,
+                y: 0.0,
+                z: -1.0,
+            },
+            t_max: std::f32::INFINITY,
+            ..Ray::default()
+        }
+    }
+
+    #[test]
+    fn rays_missing_the_object_bound_skip_the_intersection_test() {
+        // a flat curve along the y axis, moved to x = 2
+        let cp: [Point3f; 4] = [
+            Point3f {
+                x: 0.0,
+                y: -1.0,
+                z: 0.0,
+            },
+            Point3f {
+                x: 0.0,
+                y: -0.3,
+                z: 0.0,
+            },
+            Point3f {
+                x: 0.0,
+                y: 0.3,
+                z: 0.0,
+            },
+            Point3f {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+        ];
+        let object_to_world: Transform = Transform::translate(&Vector3f {
+            x: 2.0,
+            y: 0.0,
+            z: 0.0,
+        });
+        let shapes: Vec<Arc<Shape + Send + Sync>> = Curve::create(
+            object_to_world,
+            Transform::inverse(&object_to_world),
+            false,
+            &cp,
+            0.2 as Float,
+            0.2 as Float,
+            CurveType::Flat,
+            None,
+            0_i32,
+        );
+        assert_eq!(shapes.len(), 1_usize);
+        let prim: GeometricPrimitive = GeometricPrimitive::new(shapes[0].clone(), None, None, None);
+        // passes next to the curve (the object bound is at x = 1.9 .. 2.1)
+        let mut miss: Ray = ray(1.0 as Float);
+        assert!(!shapes[0].object_bound_may_intersect(&miss));
+        let tests: u64 = curve_intersection_tests();
+        assert!(prim.intersect(&mut miss).is_none());
+        assert!(!prim.intersect_p(&miss));
+        assert_eq!(curve_intersection_tests(), tests);
+        // hits the curve, the full test runs
+        let mut hit: Ray = ray(2.0 as Float);
+        assert!(shapes[0].object_bound_may_intersect(&hit));
+        assert!(prim.intersect(&mut hit).is_some());
+        assert_eq!(curve_intersection_tests(), tests + 1_u64);
+        assert!(
+            (hit.t_max - 5.0 as Float).abs() < 1e-3 as Float,
+            "{}",
+            hit.t_max
+        );
+    }
+
+    fn straight_line() -> [Point3f; 4] {
+        let mut cp: [Point3f; 4] = [Point3f::default(); 4];
+        for i in 0..4 {
+            cp[i].y = -1.0 as Float + 2.0 as Float * i as Float / 3.0 as Float;
+        }
+        cp
+    }
+
+    fn normal(x: Float, y: Float, z: Float) -> Normal3f {
+        Normal3f { x: x, y: y, z: z }
+    }
+
+    fn assert_same_normal(a: &Normal3f, b: &Normal3f) {
+        let d: Normal3f = *a - *b;
+        assert!(d.length() < 1e-4 as Float, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn ribbon_normals_are_slerped() {
+        let ribbon = |n0: Normal3f, n1: Normal3f| -> CurveCommon {
+            CurveCommon::new(
+                &straight_line(),
+                0.2 as Float,
+                0.2 as Float,
+                CurveType::Ribbon,
+                Some([n0, n1]),
+            )
+        };
+        // 90 degrees apart: 45 degrees in the middle, no pinching
+        let z: Normal3f = normal(0.0, 0.0, 1.0);
+        let x: Normal3f = normal(1.0, 0.0, 0.0);
+        let common: CurveCommon = ribbon(z, x);
+        let half: Float = (0.5 as Float).sqrt();
+        assert_same_normal(&common.ribbon_normal(0.0 as Float), &z);
+        assert_same_normal(
+            &common.ribbon_normal(0.5 as Float),
+            &normal(half, 0.0, half),
+        );
+        assert_same_normal(&common.ribbon_normal(1.0 as Float), &x);
+        // identical normals
+        let common: CurveCommon = ribbon(z, z);
+        assert_same_normal(&common.ribbon_normal(0.3 as Float), &z);
+        // anti-parallel normals turn around the curve direction
+        let common: CurveCommon = ribbon(z, -z);
+        assert_same_normal(&common.ribbon_normal(0.0 as Float), &z);
+        assert_same_normal(&common.ribbon_normal(1.0 as Float), &-z);
+        let mid: Normal3f = common.ribbon_normal(0.5 as Float);
+        assert!((mid.length() - 1.0 as Float).abs() < 1e-4 as Float);
+        assert!(mid.z.abs() < 1e-4 as Float && mid.y.abs() < 1e-4 as Float);
+    }
+
+    #[test]
+    fn ribbons_are_hit_face_on_but_not_edge_on() {
+        let hit = |n: Normal3f| -> bool {
+            let shapes: Vec<Arc<Shape + Send + Sync>> = Curve::create(
+                Transform::default(),
+                Transform::default(),
+                false,
+                &straight_line(),
+                0.2 as Float,
+                0.2 as Float,
+                CurveType::Ribbon,
+                Some([n, n]),
+                0_i32,
+            );
+            let prim: GeometricPrimitive =
+                GeometricPrimitive::new(shapes[0].clone(), None, None, None);
+            prim.intersect(&mut ray(0.05 as Float)).is_some()
+        };
+        // the ray travels along -z
+        assert!(hit(normal(0.0, 0.0, 1.0)));
+        assert!(hit(normal(0.0, 0.0, -1.0)));
+        assert!(!hit(normal(1.0, 0.0, 0.0)));
+    }
+}
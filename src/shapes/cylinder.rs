@@ -10,6 +10,7 @@ use core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
 use core::material::Material;
 use core::pbrt::Float;
 use core::pbrt::{clamp_t, gamma, lerp, radians};
+use core::profile::{Prof, ProfilePhase};
 use core::shape::Shape;
 use core::transform::Transform;
 
@@ -94,7 +95,7 @@ impl Shape for Cylinder {
         self.object_to_world.transform_bounds(&self.object_bound())
     }
     fn intersect(&self, r: &Ray) -> Option<(SurfaceInteraction, Float)> {
-        // TODO: ProfilePhase p(Prof::ShapeIntersect);
+        let _p = ProfilePhase::new(Prof::ShapeIntersect);
         // transform _Ray_ to object space
         let mut o_err: Vector3f = Vector3f::default();
         let mut d_err: Vector3f = Vector3f::default();
@@ -253,7 +254,7 @@ impl Shape for Cylinder {
         Some((isect, t_shape_hit.v as Float))
     }
     fn intersect_p(&self, r: &Ray) -> bool {
-        // TODO: ProfilePhase p(Prof::ShapeIntersect);
+        let _p = ProfilePhase::new(Prof::ShapeIntersect);
         // transform _Ray_ to object space
         let mut o_err: Vector3f = Vector3f::default();
         let mut d_err: Vector3f = Vector3f::default();
@@ -373,6 +374,8 @@ impl Shape for Cylinder {
             &p_obj_error,
             &mut it.p_error,
         );
+        // _z_ and _phi_ were computed from _u_ (see intersect())
+        it.uv = Point2f { x: u[1], y: u[0] };
         *pdf = 1.0 as Float / self.area();
         it
     }
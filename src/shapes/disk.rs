@@ -8,6 +8,7 @@ use core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
 use core::material::Material;
 use core::pbrt::Float;
 use core::pbrt::{clamp_t, radians};
+use core::profile::{Prof, ProfilePhase};
 use core::sampling::concentric_sample_disk;
 use core::shape::Shape;
 use core::transform::Transform;
@@ -93,7 +94,7 @@ impl Shape for Disk {
         self.object_to_world.transform_bounds(&self.object_bound())
     }
     fn intersect(&self, r: &Ray) -> Option<(SurfaceInteraction, Float)> {
-        // TODO: ProfilePhase p(Prof::ShapeIntersect);
+        let _p = ProfilePhase::new(Prof::ShapeIntersect);
         // transform _Ray_ to object space
         let mut o_err: Vector3f = Vector3f::default();
         let mut d_err: Vector3f = Vector3f::default();
@@ -172,7 +173,7 @@ impl Shape for Disk {
         Some((isect, t_shape_hit))
     }
     fn intersect_p(&self, r: &Ray) -> bool {
-        // TODO: ProfilePhase p(Prof::ShapeIntersectP);
+        let _p = ProfilePhase::new(Prof::ShapeIntersectP);
         // transform _Ray_ to object space
         let mut o_err: Vector3f = Vector3f::default();
         let mut d_err: Vector3f = Vector3f::default();
@@ -240,6 +241,16 @@ impl Shape for Disk {
         it.p =
             self.object_to_world
                 .transform_point_with_abs_error(&p_obj, &pt_error, &mut it.p_error);
+        // parametric representation of the sampled point (see intersect())
+        let mut phi: Float = p_obj.y.atan2(p_obj.x);
+        if phi < 0.0 {
+            phi += 2.0_f32 * PI;
+        }
+        let r: Float = (p_obj.x * p_obj.x + p_obj.y * p_obj.y).sqrt();
+        it.uv = Point2f {
+            x: phi / self.phi_max,
+            y: 1.0 as Float - (r - self.inner_radius) / (self.radius - self.inner_radius),
+        };
         *pdf = 1.0 as Float / self.area();
         it
     }
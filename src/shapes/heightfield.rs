@@ -0,0 +1,203 @@
+// std
+use std::collections::HashMap;
+use std::sync::Arc;
+// pbrt
+use core::geometry::{Normal3f, Point2f, Point3f, Vector3f};
+use core::interaction::SurfaceInteraction;
+use core::paramset::ParamSet;
+use core::pbrt::Float;
+use core::shape::Shape;
+use core::texture::Texture;
+use core::transform::Transform;
+use shapes::triangle::{Triangle, TriangleMesh};
+
+// see heightfield.cpp
+
+/// Creates a triangle mesh for a regular grid of **nu** x **nv**
+/// heights (**Pz**) over the unit square. An optional float texture
+/// (**displacement**) is evaluated once per vertex and moves the
+/// vertex along the (object space) normal of the heightfield before
+/// the mesh is baked.
+pub fn create_heightfield(
+    o2w: &Transform,
+    w2o: &Transform,
+    reverse_orientation: bool,
+    params: &ParamSet,
+    float_textures: Arc<HashMap<String, Arc<Texture<Float> + Send + Sync>>>,
+) -> Vec<Arc<Shape + Send + Sync>> {
+    let nu: i32 = params.find_one_int("nu", -1);
+    let nv: i32 = params.find_one_int("nv", -1);
+    let z: Vec<Float> = params.find_float("Pz");
+    assert!(nu != -1 && nv != -1 && !z.is_empty());
+    assert!(nu >= 2 && nv >= 2);
+    assert_eq!((nu * nv) as usize, z.len());
+    let nu: usize = nu as usize;
+    let nv: usize = nv as usize;
+    let n_vertices: usize = nu * nv;
+    let n_triangles: usize = 2 * (nu - 1) * (nv - 1);
+    // compute heightfield vertex positions
+    let mut p: Vec<Point3f> = Vec::with_capacity(n_vertices);
+    let mut uvs: Vec<Point2f> = Vec::with_capacity(n_vertices);
+    for y in 0..nv {
+        for x in 0..nu {
+            let u: Float = x as Float / (nu - 1) as Float;
+            let v: Float = y as Float / (nv - 1) as Float;
+            uvs.push(Point2f { x: u, y: v });
+            p.push(Point3f {
+                x: u,
+                y: v,
+                z: z[x + y * nu],
+            });
+        }
+    }
+    // fill in heightfield vertex offset array
+    let mut vertex_indices: Vec<usize> = Vec::with_capacity(3 * n_triangles);
+    for y in 0..(nv - 1) {
+        for x in 0..(nu - 1) {
+            let vert = |x: usize, y: usize| -> usize { x + y * nu };
+            vertex_indices.push(vert(x, y));
+            vertex_indices.push(vert(x + 1, y));
+            vertex_indices.push(vert(x + 1, y + 1));
+            vertex_indices.push(vert(x, y));
+            vertex_indices.push(vert(x + 1, y + 1));
+            vertex_indices.push(vert(x, y + 1));
+        }
+    }
+    // displace vertices (if requested) along the heightfield normal
+    let displacement_name: String = params.find_texture("displacement");
+    if displacement_name != String::new() {
+        match float_textures.get(displacement_name.as_str()) {
+            Some(displacement) => {
+                let mut displaced: Vec<Point3f> = Vec::with_capacity(n_vertices);
+                for y in 0..nv {
+                    for x in 0..nu {
+                        let n: Normal3f = heightfield_normal(&p, nu, nv, x, y);
+                        let pos: usize = x + y * nu;
+                        let mut si: SurfaceInteraction = SurfaceInteraction::default();
+                        si.p = o2w.transform_point(&p[pos]);
+                        si.uv = uvs[pos];
+                        si.n = o2w.transform_normal(&n).normalize();
+                        si.shading.n = si.n;
+                        let d: Float = displacement.evaluate(&si);
+                        displaced.push(p[pos] + Vector3f::from(n) * d);
+                    }
+                }
+                p = displaced;
+            }
+            None => {
+                println!(
+                    "WARNING: Couldn't find float texture \"{}\" for \"displacement\" parameter",
+                    displacement_name
+                );
+            }
+        }
+    }
+    // transform mesh vertices to world space
+    let mut p_ws: Vec<Point3f> = Vec::with_capacity(n_vertices);
+    for i in 0..n_vertices {
+        p_ws.push(o2w.transform_point(&p[i]));
+    }
+    let s_ws: Vec<Vector3f> = Vec::new();
+    let n_ws: Vec<Normal3f> = Vec::new();
+    let mesh = Arc::new(TriangleMesh::new(
+        *o2w,
+        *w2o,
+        reverse_orientation,
+        n_triangles,
+        vertex_indices,
+        n_vertices,
+        p_ws, // in world space
+        s_ws, // in world space
+        n_ws, // in world space
+        uvs,
+    ));
+    let mut shapes: Vec<Arc<Shape + Send + Sync>> = Vec::new();
+    for id in 0..mesh.n_triangles {
+        let triangle = Arc::new(Triangle::new(
+            mesh.object_to_world,
+            mesh.world_to_object,
+            mesh.reverse_orientation,
+            mesh.clone(),
+            id,
+        ));
+        shapes.push(triangle.clone());
+    }
+    shapes
+}
+
+/// Object space normal of the (undisplaced) heightfield at grid
+/// vertex (x, y), using central differences (one-sided at the
+/// borders).
+fn heightfield_normal(p: &Vec<Point3f>, nu: usize, nv: usize, x: usize, y: usize) -> Normal3f {
+    let x0: usize = if x > 0 { x - 1 } else { x };
+    let x1: usize = if x + 1 < nu { x + 1 } else { x };
+    let y0: usize = if y > 0 { y - 1 } else { y };
+    let y1: usize = if y + 1 < nv { y + 1 } else { y };
+    let dzdx: Float =
+        (p[x1 + y * nu].z - p[x0 + y * nu].z) / (p[x1 + y * nu].x - p[x0 + y * nu].x);
+    let dzdy: Float =
+        (p[x + y1 * nu].z - p[x + y0 * nu].z) / (p[x + y1 * nu].y - p[x + y0 * nu].y);
+    Normal3f {
+        x: -dzdx,
+        y: -dzdy,
+        z: 1.0 as Float,
+    }
+    .normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::geometry::Bounds3f;
+    use textures::constant::ConstantTexture;
+
+    /// Displacement growing linearly with *u*.
+    struct Ramp {}
+
+    impl Texture<Float> for Ramp {
+        fn evaluate(&self, si: &SurfaceInteraction) -> Float {
+            0.5 as Float * si.uv.x
+        }
+    }
+
+    fn displaced_bounds(displacement: Arc<Texture<Float> + Send + Sync>) -> Vec<Bounds3f> {
+        let mut params: ParamSet = ParamSet::default();
+        params.add_int(String::from("nu"), 4);
+        params.add_int(String::from("nv"), 3);
+        params.add_floats(String::from("Pz"), vec![0.0 as Float; 12]);
+        params.add_texture(String::from("displacement"), String::from("height"));
+        let mut float_textures: HashMap<String, Arc<Texture<Float> + Send + Sync>> =
+            HashMap::new();
+        float_textures.insert(String::from("height"), displacement);
+        let t: Transform = Transform::default();
+        let shapes: Vec<Arc<Shape + Send + Sync>> =
+            create_heightfield(&t, &t, false, &params, Arc::new(float_textures));
+        assert_eq!(shapes.len(), 2 * 3 * 2);
+        shapes.iter().map(|shape| shape.world_bound()).collect()
+    }
+
+    #[test]
+    fn constant_displacement_lifts_all_vertices() {
+        let height: Arc<Texture<Float> + Send + Sync> =
+            Arc::new(ConstantTexture::new(0.25 as Float));
+        for bounds in displaced_bounds(height) {
+            assert!((bounds.p_min.z - 0.25 as Float).abs() < 1e-5 as Float);
+            assert!((bounds.p_max.z - 0.25 as Float).abs() < 1e-5 as Float);
+        }
+    }
+
+    #[test]
+    fn ramp_displacement_tilts_the_surface() {
+        let mut z_min: Float = std::f32::INFINITY;
+        let mut z_max: Float = -std::f32::INFINITY;
+        for bounds in displaced_bounds(Arc::new(Ramp {})) {
+            // z = x / 2 on the tilted plane
+            assert!((bounds.p_min.z - 0.5 as Float * bounds.p_min.x).abs() < 1e-5 as Float);
+            assert!((bounds.p_max.z - 0.5 as Float * bounds.p_max.x).abs() < 1e-5 as Float);
+            z_min = z_min.min(bounds.p_min.z);
+            z_max = z_max.max(bounds.p_max.z);
+        }
+        assert!(z_min.abs() < 1e-5 as Float);
+        assert!((z_max - 0.5 as Float).abs() < 1e-5 as Float);
+    }
+}
@@ -10,6 +10,7 @@
 //! - Curve
 //! - Cylinder
 //! - Disk
+//! - Heightfield
 //! - Hyperboloid
 //! - Paraboloid
 //! - Sphere
@@ -50,6 +51,12 @@
 //! Another useful quadric is the cylinder. Cylinder shapes are
 //! centered around the z axis.
 //!
+//! ## Heightfields
+//!
+//! A regular grid of heights over the unit square, which gets turned
+//! into a triangle mesh. An optional float texture can displace the
+//! vertices along the surface normal before the mesh is created.
+//!
 //! ## Hyperboloids
 //!
 //! TODO
@@ -62,6 +69,7 @@
 pub mod curve;
 pub mod cylinder;
 pub mod disk;
+pub mod heightfield;
 pub mod loopsubdiv;
 pub mod nurbs;
 pub mod plymesh;
@@ -82,6 +82,19 @@ impl Sphere {
             material: None,
         }
     }
+    /// Parametric (*u*, *v*) coordinates of a point on the sphere
+    /// (in object space), as computed by **intersect()**.
+    fn uv(&self, p_obj: &Point3f) -> Point2f {
+        let mut phi: Float = p_obj.y.atan2(p_obj.x);
+        if phi < 0.0 {
+            phi += 2.0_f32 * PI;
+        }
+        let theta: Float = clamp_t(p_obj.z / self.radius, -1.0, 1.0).acos();
+        Point2f {
+            x: phi / self.phi_max,
+            y: (theta - self.theta_min) / (self.theta_max - self.theta_min),
+        }
+    }
 }
 
 impl Shape for Sphere {
@@ -391,6 +404,7 @@ impl Shape for Sphere {
             &p_obj_error,
             &mut it.p_error,
         );
+        it.uv = self.uv(&p_obj);
         *pdf = 1.0 as Float / self.area();
         it
     }
@@ -429,22 +443,26 @@ impl Shape for Sphere {
         // sample sphere uniformly inside subtended cone
 
         // compute $\theta$ and $\phi$ values for sample in cone
-        let sin_theta_max2: Float =
-            self.radius * self.radius / pnt3_distance_squared(&iref.p, &p_center);
+        let dc: Float = pnt3_distance(&iref.p, &p_center);
+        let sin_theta_max: Float = self.radius / dc;
+        let sin_theta_max2: Float = sin_theta_max * sin_theta_max;
+        let inv_sin_theta_max: Float = 1.0 as Float / sin_theta_max;
         let cos_theta_max: Float = (0.0 as Float).max(1.0 as Float - sin_theta_max2).sqrt();
-        let cos_theta: Float = (1.0 as Float - u[0]) + u[0] * cos_theta_max;
-        let sin_theta: Float = (0.0 as Float)
-            .max(1.0 as Float - cos_theta * cos_theta)
-            .sqrt();
+        let mut cos_theta: Float = (cos_theta_max - 1.0 as Float) * u[0] + 1.0 as Float;
+        let mut sin_theta2: Float = 1.0 as Float - cos_theta * cos_theta;
+        if sin_theta_max2 < 0.00068523 as Float {
+            // sin^2(1.5 deg): fall back to a Taylor expansion for
+            // small angles, 1 - cos_theta loses too much precision
+            sin_theta2 = sin_theta_max2 * u[0];
+            cos_theta = (1.0 as Float - sin_theta2).sqrt();
+        }
         let phi: Float = u[1] * 2.0 as Float * PI;
         // compute angle $\alpha$ from center of sphere to sampled point on surface
-        let dc: Float = pnt3_distance(&iref.p, &p_center);
-        let ds: Float = dc * cos_theta
-            - (0.0 as Float)
-                .max(self.radius * self.radius - dc * dc * sin_theta * sin_theta)
-                .sqrt();
-        let cos_alpha: Float =
-            (dc * dc + self.radius * self.radius - ds * ds) / (2.0 as Float * dc * self.radius);
+        let cos_alpha: Float = sin_theta2 * inv_sin_theta_max
+            + cos_theta
+                * (0.0 as Float)
+                    .max(1.0 as Float - sin_theta2 * inv_sin_theta_max * inv_sin_theta_max)
+                    .sqrt();
         let sin_alpha: Float = (0.0 as Float)
             .max(1.0 as Float - cos_alpha * cos_alpha)
             .sqrt();
@@ -465,8 +483,9 @@ impl Shape for Sphere {
         if self.reverse_orientation {
             it.n *= -1.0 as Float;
         }
+        it.uv = self.uv(&self.world_to_object.transform_point(&p_world));
         // uniform cone PDF.
-        *pdf = 1.0 as Float / (2.0 as Float * PI * (1.0 as Float - cos_theta_max));
+        *pdf = uniform_cone_pdf(cos_theta_max);
         it
     }
     fn pdf_with_ref_point(&self, iref: &Interaction, wi: &Vector3f) -> Float {
@@ -505,3 +524,114 @@ impl Shape for Sphere {
         return uniform_cone_pdf(cos_theta_max);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sphere_at_distance(distance: Float) -> (Sphere, SurfaceInteraction<'static, 'static>) {
+        let object_to_world: Transform = Transform::translate(&Vector3f {
+            x: 0.0,
+            y: 0.0,
+            z: distance,
+        });
+        let sphere: Sphere = Sphere::new(
+            object_to_world,
+            Transform::inverse(&object_to_world),
+            false,
+            1.0 as Float,
+            -1.0 as Float,
+            1.0 as Float,
+            360.0 as Float,
+        );
+        let iref: SurfaceInteraction = SurfaceInteraction::default();
+        (sphere, iref)
+    }
+
+    fn check_cone_samples(distance: Float) {
+        let (sphere, iref) = sphere_at_distance(distance);
+        let common: InteractionCommon = InteractionCommon::default();
+        let wc: Vector3f = Vector3f {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        };
+        let sin_theta_max: Float = sphere.radius / distance;
+        for i in 0..32 {
+            for j in 0..32 {
+                let u: Point2f = Point2f {
+                    x: (i as Float + 0.5) / 32.0,
+                    y: (j as Float + 0.5) / 32.0,
+                };
+                let mut pdf: Float = 0.0 as Float;
+                let intr: InteractionCommon = sphere.sample_with_ref_point(&common, &u, &mut pdf);
+                let wi: Vector3f = (intr.p - common.p).normalize();
+                assert!(pdf.is_finite() && pdf > 0.0 as Float, "pdf {}", pdf);
+                assert!(intr.p.x.is_finite() && intr.p.y.is_finite() && intr.p.z.is_finite());
+                // inside the cone subtended by the sphere
+                assert!(vec3_dot_vec3(&wi, &wc) > 0.0 as Float);
+                assert!(
+                    vec3_cross_vec3(&wi, &wc).length() <= sin_theta_max * 1.001,
+                    "direction outside of the cone"
+                );
+                let expected: Float = sphere.pdf_with_ref_point(&iref, &wi);
+                assert!(
+                    (pdf - expected).abs() <= expected * 1e-5,
+                    "{} vs {}",
+                    pdf,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cone_samples_stay_inside_the_subtended_cone() {
+        check_cone_samples(2.0 as Float);
+    }
+
+    #[test]
+    fn distant_spheres_use_the_small_angle_expansion() {
+        // sin^2(theta_max) = 1e-4 is below the Taylor threshold
+        check_cone_samples(100.0 as Float);
+        let (sphere, iref) = sphere_at_distance(100.0 as Float);
+        let wc: Vector3f = Vector3f {
+            x: 0.0,
+            y: 0.0,
+            z: 1.0,
+        };
+        // the pdf times the solid angle of the cone is one
+        let sin_theta_max2: f64 = 1e-4;
+        let solid_angle: f64 =
+            2.0 * ::std::f64::consts::PI * sin_theta_max2 / (1.0 + (1.0 - sin_theta_max2).sqrt());
+        let pdf: f64 = sphere.pdf_with_ref_point(&iref, &wc) as f64;
+        assert!(
+            (pdf * solid_angle - 1.0).abs() < 0.01,
+            "{}",
+            pdf * solid_angle
+        );
+    }
+
+    #[test]
+    fn cone_pdf_integrates_to_one() {
+        // estimate the integral of the pdf over all directions hitting
+        // the sphere with stratified uniform sphere samples
+        let (sphere, iref) = sphere_at_distance(2.0 as Float);
+        let n: usize = 512;
+        let mut sum: f64 = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let u: Point2f = Point2f {
+                    x: (i as Float + 0.5) / n as Float,
+                    y: (j as Float + 0.5) / n as Float,
+                };
+                let wi: Vector3f = uniform_sample_sphere(&u);
+                if sphere.intersect_p(&iref.spawn_ray(&wi)) {
+                    sum += sphere.pdf_with_ref_point(&iref, &wi) as f64;
+                }
+            }
+        }
+        let integral: f64 = sum * 4.0 * ::std::f64::consts::PI / (n * n) as f64;
+        assert!((integral - 1.0).abs() < 0.01, "{}", integral);
+    }
+}
@@ -12,6 +12,7 @@ use core::interaction::{Interaction, InteractionCommon, SurfaceInteraction};
 use core::material::Material;
 use core::pbrt::gamma;
 use core::pbrt::Float;
+use core::profile::{Prof, ProfilePhase};
 use core::sampling::uniform_sample_triangle;
 use core::shape::Shape;
 use core::transform::Transform;
@@ -289,11 +290,12 @@ impl Shape for Triangle {
         }
         if degenerate_uv || vec3_cross_vec3(&dpdu, &dpdv).length_squared() == 0.0 {
             // handle zero determinant for triangle partial derivative matrix
-            vec3_coordinate_system(
-                &vec3_cross_vec3(&(p2 - p0), &(p1 - p0)).normalize(),
-                &mut dpdu,
-                &mut dpdv,
-            );
+            let ng: Vector3f = vec3_cross_vec3(&(p2 - p0), &(p1 - p0));
+            if ng.length_squared() == 0.0 as Float {
+                // the triangle is actually degenerate; the intersection is bogus
+                return None;
+            }
+            vec3_coordinate_system(&ng.normalize(), &mut dpdu, &mut dpdv);
         }
         // compute error bounds for triangle intersection
         let x_abs_sum: Float = (b0 * p0.x).abs() + (b1 * p1.x).abs() + (b2 * p2.x).abs();
@@ -414,7 +416,7 @@ impl Shape for Triangle {
         Some((si, t as Float))
     }
     fn intersect_p(&self, ray: &Ray) -> bool {
-        // TODO: ProfilePhase p(Prof::TriIntersectP);
+        let _p = ProfilePhase::new(Prof::TriIntersectP);
         // TODO: ++nTests;
         // get triangle vertices in _p0_, _p1_, and _p2_
         let p0: Point3f = self.mesh.p[self.mesh.vertex_indices[self.id * 3 + 0]];
@@ -595,6 +597,9 @@ impl Shape for Triangle {
             y: p_abs_sum.y,
             z: p_abs_sum.z,
         } * gamma(6);
+        // interpolate the parametric coordinates (see intersect())
+        let uv: [Point2f; 3] = self.get_uvs();
+        it.uv = uv[0] * b[0] + uv[1] * b[1] + uv[2] * (1.0 as Float - b[0] - b[1]);
         *pdf = 1.0 as Float / self.area();
         it
     }
@@ -638,3 +643,77 @@ impl Shape for Triangle {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::geometry::vec3_dot_vec3;
+
+    fn triangle(p: Vec<Point3f>, uv: Vec<Point2f>) -> Triangle {
+        let t: Transform = Transform::default();
+        let mesh: Arc<TriangleMesh> = Arc::new(TriangleMesh::new(
+            t,
+            t,
+            false,
+            1_usize,
+            vec![0_usize, 1_usize, 2_usize],
+            3_usize,
+            p,
+            Vec::new(),
+            Vec::new(),
+            uv,
+        ));
+        Triangle::new(t, t, false, mesh, 0_usize)
+    }
+
+    fn ray_down(x: Float, y: Float) -> Ray {
+        let mut ray: Ray = Ray::default();
+        ray.o = Point3f { x: x, y: y, z: 1.0 };
+        ray.d = Vector3f {
+            x: 0.0,
+            y: 0.0,
+            z: -1.0,
+        };
+        ray.t_max = std::f32::INFINITY;
+        ray
+    }
+
+    #[test]
+    fn shared_uvs_fall_back_to_an_orthonormal_frame() {
+        let p: Vec<Point3f> = vec![
+            Point3f {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Point3f {
+                x: 1.0,
+                y: 0.0,
+                z: 0.0,
+            },
+            Point3f {
+                x: 0.0,
+                y: 1.0,
+                z: 0.0,
+            },
+        ];
+        let uv: Vec<Point2f> = vec![Point2f { x: 0.5, y: 0.5 }; 3];
+        let tri: Triangle = triangle(p, uv);
+        let (si, t_hit) = tri.intersect(&ray_down(0.25, 0.25)).expect("missed");
+        assert!((t_hit - 1.0 as Float).abs() < 1e-5 as Float);
+        for v in &[si.dpdu, si.dpdv] {
+            assert!(
+                v.x.is_finite() && v.y.is_finite() && v.z.is_finite(),
+                "{:?}",
+                v
+            );
+            assert!((v.length() - 1.0 as Float).abs() < 1e-5 as Float, "{:?}", v);
+            // tangent to the triangle
+            assert!(v.z.abs() < 1e-5 as Float, "{:?}", v);
+        }
+        assert!(vec3_dot_vec3(&si.dpdu, &si.dpdv).abs() < 1e-5 as Float);
+        assert!(si.shading.dpdu.x.is_finite() && si.shading.dpdv.y.is_finite());
+        assert_eq!(si.uv.x, 0.5 as Float);
+        assert_eq!(si.uv.y, 0.5 as Float);
+    }
+}
@@ -1,9 +1,11 @@
 // std
+use std::ops::{Add, Mul};
 use std::sync::Arc;
 // pbrt
-use core::geometry::{Point2f, Vector2f};
+use core::geometry::{Point2f, Point3f, Vector2f, Vector3f};
 use core::interaction::SurfaceInteraction;
-use core::texture::{Texture, TextureMapping2D};
+use core::pbrt::Float;
+use core::texture::{Texture, TextureMapping2D, TextureMapping3D};
 
 // checkerboard.h
 
@@ -41,3 +43,178 @@ impl<T: Copy> Texture<T> for Checkerboard2DTexture<T> {
         }
     }
 }
+
+pub struct Checkerboard3DTexture<T> {
+    pub tex1: Arc<Texture<T> + Send + Sync>,
+    pub tex2: Arc<Texture<T> + Send + Sync>,
+    pub mapping: Box<TextureMapping3D + Send + Sync>,
+}
+
+impl<T: Copy> Checkerboard3DTexture<T> {
+    pub fn new(
+        mapping: Box<TextureMapping3D + Send + Sync>,
+        tex1: Arc<Texture<T> + Send + Sync>,
+        tex2: Arc<Texture<T> + Send + Sync>,
+    ) -> Self {
+        Checkerboard3DTexture {
+            tex1: tex1,
+            tex2: tex2,
+            mapping: mapping,
+        }
+    }
+}
+
+impl<T> Texture<T> for Checkerboard3DTexture<T>
+where
+    T: Copy + Add<Output = T> + Mul<Float, Output = T>,
+{
+    fn evaluate(&self, si: &SurfaceInteraction) -> T {
+        let mut dpdx: Vector3f = Vector3f::default();
+        let mut dpdy: Vector3f = Vector3f::default();
+        let p: Point3f = self.mapping.map(si, &mut dpdx, &mut dpdy);
+        // The checkerboard is the product of three square waves
+        // (+1/-1), so box filtering it over the footprint is separable.
+        let mut parity: Float = 1.0 as Float;
+        for i in 0..3_u8 {
+            let w: Float = dpdx[i].abs().max(dpdy[i].abs());
+            parity *= filtered_square_wave(p[i] - w, p[i] + w);
+        }
+        if parity == 1.0 as Float {
+            self.tex1.evaluate(si)
+        } else if parity == -1.0 as Float {
+            self.tex2.evaluate(si)
+        } else {
+            // blend towards the average of both textures for large footprints
+            self.tex1.evaluate(si) * (0.5 as Float + 0.5 as Float * parity)
+                + self.tex2.evaluate(si) * (0.5 as Float - 0.5 as Float * parity)
+        }
+    }
+}
+
+/// Average of the square wave `(-1)^floor(x)` over `[x0, x1]`.
+fn filtered_square_wave(x0: Float, x1: Float) -> Float {
+    if x1 - x0 <= 0.0 as Float || x0.floor() == x1.floor() {
+        if (x0.floor() as i64) % 2 == 0 {
+            1.0 as Float
+        } else {
+            -1.0 as Float
+        }
+    } else {
+        (square_wave_integral(x1) - square_wave_integral(x0)) / (x1 - x0)
+    }
+}
+
+/// Integral of the square wave `(-1)^floor(x)` from zero to *x*.
+fn square_wave_integral(x: Float) -> Float {
+    let f: Float = x - 2.0 as Float * (x / 2.0 as Float).floor();
+    if f < 1.0 as Float {
+        f
+    } else {
+        2.0 as Float - f
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::texture::IdentityMapping3D;
+    use core::transform::Transform;
+    use textures::constant::ConstantTexture;
+
+    /// Black and white (1 in even cells) solid checkerboard.
+    fn checkerboard() -> Checkerboard3DTexture<Float> {
+        Checkerboard3DTexture::new(
+            Box::new(IdentityMapping3D::new(Transform::default())),
+            Arc::new(ConstantTexture {
+                value: 1.0 as Float,
+            }),
+            Arc::new(ConstantTexture {
+                value: 0.0 as Float,
+            }),
+        )
+    }
+
+    fn lookup(p: Point3f, dpdx: Vector3f, dpdy: Vector3f) -> Float {
+        let mut si: SurfaceInteraction = SurfaceInteraction::default();
+        si.p = p;
+        si.dpdx = dpdx;
+        si.dpdy = dpdy;
+        checkerboard().evaluate(&si)
+    }
+
+    #[test]
+    fn tiny_footprints_are_sharp_large_ones_gray() {
+        let p: Point3f = Point3f {
+            x: 0.3,
+            y: 1.6,
+            z: -0.4,
+        };
+        let tiny: Vector3f = Vector3f {
+            x: 1e-3,
+            y: 1e-3,
+            z: 1e-3,
+        };
+        // cell (0, 1, -1) is even
+        assert_eq!(lookup(p, tiny, tiny), 1.0 as Float);
+        let q: Point3f = Point3f { x: 1.3, ..p };
+        assert_eq!(lookup(q, tiny, tiny), 0.0 as Float);
+        // a footprint spanning many cells averages both textures
+        let large: Vector3f = Vector3f {
+            x: 10.3,
+            y: 9.7,
+            z: 11.1,
+        };
+        assert!((lookup(p, large, large) - 0.5 as Float).abs() < 0.01 as Float);
+        assert!((lookup(q, large, large) - 0.5 as Float).abs() < 0.01 as Float);
+    }
+
+    #[test]
+    fn filtered_value_is_the_box_average() {
+        let p: Point3f = Point3f {
+            x: 0.3,
+            y: 1.6,
+            z: -0.4,
+        };
+        let dpdx: Vector3f = Vector3f {
+            x: 0.7,
+            y: -0.3,
+            z: 0.0,
+        };
+        let dpdy: Vector3f = Vector3f {
+            x: 0.2,
+            y: 0.9,
+            z: -1.3,
+        };
+        // brute force average over the box [p - w, p + w]
+        let w: Vector3f = Vector3f {
+            x: 0.7,
+            y: 0.9,
+            z: 1.3,
+        };
+        let n: usize = 40;
+        let mut sum: Float = 0.0 as Float;
+        for i in 0..n {
+            for j in 0..n {
+                for k in 0..n {
+                    let t = |l: usize| -> Float { (l as Float + 0.5 as Float) / n as Float };
+                    let x: Float = p.x - w.x + 2.0 as Float * w.x * t(i);
+                    let y: Float = p.y - w.y + 2.0 as Float * w.y * t(j);
+                    let z: Float = p.z - w.z + 2.0 as Float * w.z * t(k);
+                    let cell: i64 = x.floor() as i64 + y.floor() as i64 + z.floor() as i64;
+                    if cell % 2 == 0 {
+                        sum += 1.0 as Float;
+                    }
+                }
+            }
+        }
+        let average: Float = sum / (n * n * n) as Float;
+        let filtered: Float = lookup(p, dpdx, dpdy);
+        assert!(filtered > 0.0 as Float && filtered < 1.0 as Float);
+        assert!(
+            (filtered - average).abs() < 0.02 as Float,
+            "{} vs. {}",
+            filtered,
+            average
+        );
+    }
+}
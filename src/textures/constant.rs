@@ -1,5 +1,6 @@
 // pbrt
 use core::interaction::SurfaceInteraction;
+use core::pbrt::Float;
 use core::texture::Texture;
 
 // see constant.h
@@ -18,4 +19,7 @@ impl<T: Copy> Texture<T> for ConstantTexture<T> {
     fn evaluate(&self, _si: &SurfaceInteraction) -> T {
         self.value
     }
+    fn evaluate_with_variance(&self, _si: &SurfaceInteraction) -> (T, Float) {
+        (self.value, 0.0 as Float)
+    }
 }
@@ -2,9 +2,11 @@ extern crate image;
 
 // std
 use std;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::ops::{Add, AddAssign, Div, Mul};
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 // others
 use image::{DynamicImage, ImageResult};
 use num;
@@ -13,13 +15,69 @@ use core::geometry::{Point2f, Point2i, Vector2f};
 use core::interaction::SurfaceInteraction;
 use core::mipmap::{Clampable, ImageWrap, MipMap};
 use core::pbrt::{Float, Spectrum};
+use core::spectrum::SpectrumType;
 use core::texture::{Texture, TextureMapping2D};
 
 // see imagemap.h
 
+/// Key of the **MipMap** caches; all parameters which affect the
+/// data stored in a **MipMap**.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TexInfo {
+    pub filename: String,
+    pub do_trilinear: bool,
+    pub max_aniso: Float,
+    pub wrap_mode: ImageWrap,
+    pub scale: Float,
+    pub gamma: bool,
+}
+
+impl Eq for TexInfo {}
+
+impl Hash for TexInfo {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.filename.hash(state);
+        self.do_trilinear.hash(state);
+        self.max_aniso.to_bits().hash(state);
+        self.wrap_mode.hash(state);
+        self.scale.to_bits().hash(state);
+        self.gamma.hash(state);
+    }
+}
+
+// share one MipMap between all image textures reading the same file
+// with the same parameters (see ImageTexture<Tmemory,
+// Treturn>::GetTexture() in imagemap.cpp)
+lazy_static! {
+    static ref FLOAT_MIPMAPS: Mutex<HashMap<TexInfo, Arc<MipMap<Float>>>> =
+        Mutex::new(HashMap::new());
+    static ref SPECTRUM_MIPMAPS: Mutex<HashMap<TexInfo, Arc<MipMap<Spectrum>>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Gives access to the **MipMap** cache for texels of type *Self*.
+pub trait MipMapCache: Sized + 'static {
+    fn mipmap_cache() -> &'static Mutex<HashMap<TexInfo, Arc<MipMap<Self>>>>;
+}
+
+impl MipMapCache for Float {
+    fn mipmap_cache() -> &'static Mutex<HashMap<TexInfo, Arc<MipMap<Float>>>> {
+        &FLOAT_MIPMAPS
+    }
+}
+
+impl MipMapCache for Spectrum {
+    fn mipmap_cache() -> &'static Mutex<HashMap<TexInfo, Arc<MipMap<Spectrum>>>> {
+        &SPECTRUM_MIPMAPS
+    }
+}
+
 pub struct ImageTexture<T> {
     pub mapping: Box<TextureMapping2D + Send + Sync>,
     pub mipmap: Arc<MipMap<T>>,
+    /// Shifts the MIPMap level used for filtering (in levels):
+    /// negative values sharpen, positive values blur
+    pub lod_bias: Float,
 }
 
 impl<T> ImageTexture<T>
@@ -33,7 +91,8 @@ where
         + Copy
         + Div<Float, Output = T>
         + Mul<T, Output = T>
-        + Mul<Float, Output = T>,
+        + Mul<Float, Output = T>
+        + MipMapCache,
 {
     pub fn new<F: Fn(&Spectrum) -> T>(
         mapping: Box<TextureMapping2D + Send + Sync>,
@@ -43,9 +102,30 @@ where
         wrap_mode: ImageWrap,
         scale: Float,
         gamma: bool,
+        lod_bias: Float,
         convert: F,
     ) -> ImageTexture<T> {
-        let path = Path::new(&filename);
+        let tex_info: TexInfo = TexInfo {
+            filename: filename,
+            do_trilinear: do_trilinear,
+            max_aniso: max_aniso,
+            wrap_mode: wrap_mode,
+            scale: scale,
+            gamma: gamma,
+        };
+        // don't hold the lock while reading the file and building
+        // the pyramid, other textures can be created meanwhile
+        let cached: Option<Arc<MipMap<T>>> =
+            T::mipmap_cache().lock().unwrap().get(&tex_info).cloned();
+        if let Some(mipmap) = cached {
+            return ImageTexture {
+                mapping: mapping,
+                mipmap: mipmap,
+                lod_bias: lod_bias,
+            };
+        }
+        let filename: &String = &tex_info.filename;
+        let path = Path::new(filename);
         let img_result: ImageResult<DynamicImage> = image::open(path);
         if !img_result.is_ok() {
             panic!("Error reading \"{}\"", filename);
@@ -90,13 +170,44 @@ where
             &converted_texels[..],
             do_trilinear,
             max_aniso,
-            wrap_mode,
+            tex_info.wrap_mode.clone(),
         ));
+        // if another texture loaded the same file meanwhile, share its MipMap
+        let mipmap: Arc<MipMap<T>> = T::mipmap_cache()
+            .lock()
+            .unwrap()
+            .entry(tex_info)
+            .or_insert(mipmap)
+            .clone();
         ImageTexture {
             mapping: mapping,
             mipmap: mipmap,
+            lod_bias: lod_bias,
         }
     }
+    /// Drops the cached **MipMap**s (see **ImageTexture::new()**) for
+    /// texels of type *T*, e.g. once rendering is done. Textures
+    /// still referencing them keep theirs alive.
+    pub fn clear_cache() {
+        T::mipmap_cache().lock().unwrap().clear();
+    }
+    /// Maps *si* to texture space like **TextureMapping2D::map()**,
+    /// but scales the differentials by 2^*lod_bias*, which moves the
+    /// MIPMap lookup up or down by *lod_bias* levels.
+    fn map_with_bias(
+        &self,
+        si: &SurfaceInteraction,
+        dstdx: &mut Vector2f,
+        dstdy: &mut Vector2f,
+    ) -> Point2f {
+        let st: Point2f = self.mapping.map(si, dstdx, dstdy);
+        if self.lod_bias != 0.0 as Float {
+            let scale: Float = (2.0 as Float).powf(self.lod_bias);
+            *dstdx *= scale;
+            *dstdy *= scale;
+        }
+        st
+    }
 }
 
 pub trait ImageTextureConvert<T> {
@@ -107,7 +218,7 @@ impl ImageTextureConvert<Spectrum> for ImageTexture<Spectrum> {
     fn convert_out(from: &Spectrum, to: &mut Spectrum) {
         let mut rgb: [Float; 3] = [0.0 as Float; 3];
         from.to_rgb(&mut rgb);
-        *to = Spectrum::from_rgb(&rgb);
+        *to = Spectrum::from_rgb(&rgb, SpectrumType::Illuminant);
     }
 }
 
@@ -127,7 +238,7 @@ impl Texture<Float> for ImageTexture<Float> {
         // return ret;
         let mut dstdx: Vector2f = Vector2f::default();
         let mut dstdy: Vector2f = Vector2f::default();
-        let st: Point2f = self.mapping.map(si, &mut dstdx, &mut dstdy);
+        let st: Point2f = self.map_with_bias(si, &mut dstdx, &mut dstdy);
         let mem: Float = self.mipmap.lookup_pnt_vec_vec(&st, &mut dstdx, &mut dstdy);
         let mut ret: Float = 0.0 as Float;
         ImageTexture::<Float>::convert_out(&mem, &mut ret);
@@ -145,7 +256,7 @@ impl Texture<Spectrum> for ImageTexture<Spectrum> {
         // return ret;
         let mut dstdx: Vector2f = Vector2f::default();
         let mut dstdy: Vector2f = Vector2f::default();
-        let st: Point2f = self.mapping.map(si, &mut dstdx, &mut dstdy);
+        let st: Point2f = self.map_with_bias(si, &mut dstdx, &mut dstdy);
         let mem: Spectrum = self.mipmap.lookup_pnt_vec_vec(&st, &mut dstdx, &mut dstdy);
         let mut ret: Spectrum = Spectrum::new(0.0);
         ImageTexture::<Spectrum>::convert_out(&mem, &mut ret);
@@ -160,3 +271,102 @@ pub fn convert_to_spectrum(from: &Spectrum) -> Spectrum {
 pub fn convert_to_float(from: &Spectrum) -> Float {
     from.y()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::texture::UVMapping2D;
+
+    /// Trilinear lookup of a white texel of an 8x8 checkerboard (all
+    /// coarser levels are uniformly gray) with a footprint halfway
+    /// between MIPMap level 0 and 1.
+    fn lookup_white_texel(lod_bias: Float) -> Float {
+        let mut texels: Vec<Float> = Vec::with_capacity(64);
+        for t in 0..8 {
+            for s in 0..8 {
+                texels.push(((s + t) % 2) as Float);
+            }
+        }
+        let mipmap: MipMap<Float> = MipMap::new(
+            &Point2i { x: 8, y: 8 },
+            &texels,
+            true,
+            8.0 as Float,
+            ImageWrap::Repeat,
+        );
+        let texture: ImageTexture<Float> = ImageTexture {
+            mapping: Box::new(UVMapping2D {
+                su: 1.0 as Float,
+                sv: 1.0 as Float,
+                du: 0.0 as Float,
+                dv: 0.0 as Float,
+            }),
+            mipmap: Arc::new(mipmap),
+            lod_bias: lod_bias,
+        };
+        let mut si: SurfaceInteraction = SurfaceInteraction::default();
+        si.uv = Point2f {
+            x: 1.5 as Float / 8.0 as Float,
+            y: 0.5 as Float / 8.0 as Float,
+        };
+        // level = 3 + log2(width) = 0.5
+        let width: Float = (2.0 as Float).powf(-2.5 as Float);
+        si.dudx = width;
+        si.dvdy = width;
+        texture.evaluate(&si)
+    }
+
+    #[test]
+    fn lod_bias_shifts_the_mipmap_level() {
+        let unbiased: Float = lookup_white_texel(0.0 as Float);
+        assert!((unbiased - 0.75 as Float).abs() < 1e-3 as Float, "{}", unbiased);
+        // finer level, full contrast of the checks
+        let sharp: Float = lookup_white_texel(-0.5 as Float);
+        assert!((sharp - 1.0 as Float).abs() < 1e-3 as Float, "{}", sharp);
+        // coarser level, the checks are averaged out
+        let blurred: Float = lookup_white_texel(0.5 as Float);
+        assert!((blurred - 0.5 as Float).abs() < 1e-3 as Float, "{}", blurred);
+    }
+
+    fn uv_mapping() -> Box<TextureMapping2D + Send + Sync> {
+        Box::new(UVMapping2D {
+            su: 1.0 as Float,
+            sv: 1.0 as Float,
+            du: 0.0 as Float,
+            dv: 0.0 as Float,
+        })
+    }
+
+    fn spectrum_texture(filename: &str, scale: Float) -> ImageTexture<Spectrum> {
+        ImageTexture::new(
+            uv_mapping(),
+            String::from(filename),
+            false,
+            8.0 as Float,
+            ImageWrap::Repeat,
+            scale,
+            true,
+            0.0 as Float,
+            convert_to_spectrum,
+        )
+    }
+
+    #[test]
+    fn identical_textures_share_their_mipmap() {
+        let path = std::env::temp_dir().join("rs_pbrt_imagemap_cache.png");
+        let texels: Vec<u8> = vec![255_u8, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255];
+        image::save_buffer(&path, &texels, 2, 2, image::RGB(8)).unwrap();
+        let filename: &str = path.to_str().unwrap();
+        let a: ImageTexture<Spectrum> = spectrum_texture(filename, 1.0 as Float);
+        let b: ImageTexture<Spectrum> = spectrum_texture(filename, 1.0 as Float);
+        assert!(Arc::ptr_eq(&a.mipmap, &b.mipmap));
+        // the scale changes the stored texels
+        let scaled: ImageTexture<Spectrum> = spectrum_texture(filename, 2.0 as Float);
+        assert!(!Arc::ptr_eq(&a.mipmap, &scaled.mipmap));
+        // after clearing the cache the file is read again
+        ImageTexture::<Spectrum>::clear_cache();
+        let c: ImageTexture<Spectrum> = spectrum_texture(filename, 1.0 as Float);
+        assert!(!Arc::ptr_eq(&a.mipmap, &c.mipmap));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -6,6 +6,7 @@
 //!
 //! - BilerpTexture
 //! - Checkerboard2DTexture
+//! - Checkerboard3DTexture
 //! - ConstantTexture
 //! - DotsTexture
 //! - FBmTexture
@@ -2,18 +2,52 @@
 use core::geometry::{Point3f, Vector3f};
 use core::interaction::SurfaceInteraction;
 use core::pbrt::Float;
-use core::texture::fbm;
+use core::texture::{fbm, fbm_variance};
 use core::texture::{Texture, TextureMapping3D};
 
 // see windy.h
 
 pub struct WindyTexture {
     pub mapping: Box<TextureMapping3D + Send + Sync>,
+    /// Per instance offset (to decorrelate several water surfaces)
+    pub offset: Vector3f,
+    /// Minimum filter footprint, used if the ray differentials are
+    /// missing (e.g. for the **EnvironmentCamera**), which limits the
+    /// number of noise octaves (0, the default, disables the clamp)
+    pub min_lod: Float,
 }
 
 impl WindyTexture {
-    pub fn new(mapping: Box<TextureMapping3D + Send + Sync>) -> Self {
-        WindyTexture { mapping: mapping }
+    pub fn new(
+        mapping: Box<TextureMapping3D + Send + Sync>,
+        offset: Vector3f,
+        min_lod: Float,
+    ) -> Self {
+        WindyTexture {
+            mapping: mapping,
+            offset: offset,
+            min_lod: min_lod,
+        }
+    }
+    /// Maps *si* to texture space and returns the (offset) lookup
+    /// point plus the clamped filter footprint.
+    fn footprint(&self, si: &SurfaceInteraction) -> (Point3f, Vector3f, Vector3f) {
+        let mut dpdx: Vector3f = Vector3f::default();
+        let mut dpdy: Vector3f = Vector3f::default();
+        let p: Point3f = self.mapping.map(si, &mut dpdx, &mut dpdy) + self.offset;
+        self.clamp_footprint(&mut dpdx);
+        self.clamp_footprint(&mut dpdy);
+        (p, dpdx, dpdy)
+    }
+    fn clamp_footprint(&self, dpd: &mut Vector3f) {
+        // also catches NaN
+        if !(dpd.length_squared() >= self.min_lod * self.min_lod) {
+            *dpd = Vector3f {
+                x: self.min_lod,
+                y: 0.0 as Float,
+                z: 0.0 as Float,
+            };
+        }
     }
 }
 
@@ -22,9 +56,11 @@ where
     T: From<Float>,
 {
     fn evaluate(&self, si: &SurfaceInteraction) -> T {
-        let mut dpdx: Vector3f = Vector3f::default();
-        let mut dpdy: Vector3f = Vector3f::default();
-        let p: Point3f = self.mapping.map(si, &mut dpdx, &mut dpdy);
+        let (value, _variance) = self.evaluate_with_variance(si);
+        value
+    }
+    fn evaluate_with_variance(&self, si: &SurfaceInteraction) -> (T, Float) {
+        let (p, dpdx, dpdy) = self.footprint(si);
         let wind_strength: Float = fbm(
             &(p * 0.1 as Float),
             &(dpdx * 0.1 as Float),
@@ -33,6 +69,191 @@ where
             3_i32,
         );
         let wave_height: Float = fbm(&p, &dpdx, &dpdy, 0.5 as Float, 6_i32);
-        T::from(wind_strength.abs() * wave_height)
+        // variance of the product, ignoring the correlation of both terms
+        let wind_variance: Float = fbm_variance(
+            &(dpdx * 0.1 as Float),
+            &(dpdy * 0.1 as Float),
+            0.5 as Float,
+            3_i32,
+        );
+        let wave_variance: Float = fbm_variance(&dpdx, &dpdy, 0.5 as Float, 6_i32);
+        let variance: Float = wave_height * wave_height * wind_variance
+            + wind_strength * wind_strength * wave_variance;
+        (T::from(wind_strength.abs() * wave_height), variance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cameras::perspective::PerspectiveCamera;
+    use core::camera::{Camera, CameraSample};
+    use core::film::Film;
+    use core::geometry::{Bounds2f, Point2f, Point2i, Ray};
+    use core::shape::Shape;
+    use core::testutil::test_film;
+    use core::texture::{fbm_octaves, IdentityMapping3D};
+    use core::transform::{AnimatedTransform, Transform};
+    use shapes::disk::Disk;
+    use std::sync::Arc;
+    use textures::constant::ConstantTexture;
+
+    fn windy(min_lod: Float) -> WindyTexture {
+        windy_with_offset(Vector3f::default(), min_lod)
+    }
+
+    fn windy_with_offset(offset: Vector3f, min_lod: Float) -> WindyTexture {
+        WindyTexture::new(
+            Box::new(IdentityMapping3D::new(Transform::default())),
+            offset,
+            min_lod,
+        )
+    }
+
+    #[test]
+    fn offset_decorrelates_instances() {
+        let mut si: SurfaceInteraction = SurfaceInteraction::default();
+        si.p = Point3f {
+            x: 0.37 as Float,
+            y: 1.21 as Float,
+            z: -2.53 as Float,
+        };
+        let offset: Vector3f = Vector3f {
+            x: 17.3,
+            y: -4.1,
+            z: 8.9,
+        };
+        let plain: Float = windy(0.0 as Float).evaluate(&si);
+        let shifted: Float = windy_with_offset(offset, 0.0 as Float).evaluate(&si);
+        assert_ne!(plain, shifted);
+        // same as evaluating the plain texture at the shifted point
+        let mut moved: SurfaceInteraction = SurfaceInteraction::default();
+        moved.p = si.p + offset;
+        let expected: Float = windy(0.0 as Float).evaluate(&moved);
+        assert!(
+            (shifted - expected).abs() < 1e-5 as Float,
+            "{} {}",
+            shifted,
+            expected
+        );
+    }
+
+    #[test]
+    fn zero_footprint_uses_min_lod() {
+        // no ray differentials: dpdx and dpdy are zero
+        let mut si: SurfaceInteraction = SurfaceInteraction::default();
+        si.p = Point3f {
+            x: 0.37 as Float,
+            y: 1.21 as Float,
+            z: -2.53 as Float,
+        };
+        let unclamped: Float = windy(0.0 as Float).evaluate(&si);
+        assert!(unclamped.is_finite());
+        // a footprint of 1 leaves no octave of the wave noise
+        let clamped: Float = windy(1.0 as Float).evaluate(&si);
+        assert!(clamped.is_finite());
+        assert_eq!(clamped, 0.0 as Float);
+    }
+
+    #[test]
+    fn far_water_evaluates_fewer_octaves() {
+        // pinhole camera at the origin looking along +z
+        let film: Arc<Film> = Arc::new(test_film(
+            Point2i { x: 64, y: 64 },
+            0.5 as Float,
+            "water.png",
+        ));
+        let t: Transform = Transform::default();
+        let camera: PerspectiveCamera = PerspectiveCamera::new(
+            AnimatedTransform::new(&t, 0.0 as Float, &t, 1.0 as Float),
+            Bounds2f {
+                p_min: Point2f { x: -1.0, y: -1.0 },
+                p_max: Point2f { x: 1.0, y: 1.0 },
+            },
+            0.0 as Float,
+            1.0 as Float,
+            0.0 as Float,
+            1e6 as Float,
+            60.0 as Float,
+            film,
+            None,
+        );
+        // water plane at y = -1, receding into the distance
+        let object_to_world: Transform = Transform::translate(&Vector3f {
+            x: 0.0,
+            y: -1.0,
+            z: 0.0,
+        }) * Transform::rotate_x(90.0 as Float);
+        let water: Disk = Disk::new(
+            object_to_world,
+            Transform::inverse(&object_to_world),
+            false,
+            0.0 as Float,
+            1000.0 as Float,
+            0.0 as Float,
+            360.0 as Float,
+        );
+        let texture: WindyTexture = windy(0.0 as Float);
+        // camera -> ray differentials -> surface footprint -> octaves
+        let octaves = |raster_y: Float| -> Float {
+            let sample: CameraSample = CameraSample {
+                p_film: Point2f {
+                    x: 32.0,
+                    y: raster_y,
+                },
+                p_lens: Point2f { x: 0.5, y: 0.5 },
+                time: 0.0 as Float,
+            };
+            let mut ray: Ray = Ray::default();
+            camera.generate_ray_differential(&sample, &mut ray);
+            let (mut si, _t_hit) = water.intersect(&ray).expect("camera ray misses the water");
+            si.compute_differentials(&ray);
+            let (_p, dpdx, dpdy) = texture.footprint(&si);
+            assert!(dpdx.length_squared() > 0.0 as Float);
+            assert!(dpdy.length_squared() > 0.0 as Float);
+            fbm_octaves(&dpdx, &dpdy, 6_i32)
+        };
+        // the bottom of the image sees the water close by, just below
+        // the horizon it's far away
+        let near: Float = octaves(60.0 as Float);
+        let far: Float = octaves(34.0 as Float);
+        assert!(near > 0.0 as Float);
+        assert!(far < near, "far: {} octaves, near: {} octaves", far, near);
+    }
+
+    #[test]
+    fn windy_footprints_vary_and_constants_do_not() {
+        let mut si: SurfaceInteraction = SurfaceInteraction::default();
+        si.p = Point3f {
+            x: 0.37 as Float,
+            y: 1.21 as Float,
+            z: -2.53 as Float,
+        };
+        si.dpdx = Vector3f {
+            x: 0.05,
+            y: 0.0,
+            z: 0.0,
+        };
+        si.dpdy = Vector3f {
+            x: 0.0,
+            y: 0.0,
+            z: 0.05,
+        };
+        let constant: ConstantTexture<Float> = ConstantTexture::new(0.5 as Float);
+        assert_eq!(
+            constant.evaluate_with_variance(&si),
+            (0.5 as Float, 0.0 as Float)
+        );
+        let texture: WindyTexture = windy(0.0 as Float);
+        let (value, variance): (Float, Float) = texture.evaluate_with_variance(&si);
+        assert_eq!(value, texture.evaluate(&si));
+        assert!(variance > 0.0 as Float, "{}", variance);
+        // a footprint covering a whole noise period is filtered to a constant
+        let mut wide: SurfaceInteraction = SurfaceInteraction::default();
+        wide.p = si.p;
+        wide.dpdx = si.dpdx * 40.0 as Float;
+        wide.dpdy = si.dpdy * 40.0 as Float;
+        let (_value, variance): (Float, Float) = texture.evaluate_with_variance(&wide);
+        assert_eq!(variance, 0.0 as Float);
     }
 }